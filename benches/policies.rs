@@ -0,0 +1,33 @@
+//! `cargo bench` entry point: benches every built-in policy and prints the report
+//!
+//! Pass a saved report as `-- --baseline <file>` to exit with an error if any policy regressed against it.
+
+use simcache::bench::{Bench, Report};
+use simcache::eviction::PolicyKind;
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::process;
+
+fn main() -> io::Result<()> {
+    let args: Vec<String> = std::env::args().collect();
+    let baseline = args.iter().position(|arg| arg == "--baseline").and_then(|i| args.get(i + 1));
+
+    let kinds: Vec<PolicyKind> = PolicyKind::all().collect();
+    let report = Bench::new(1_000).run(&kinds);
+    report.write(io::stdout().lock())?;
+
+    if let Some(path) = baseline {
+        let baseline = Report::read(BufReader::new(File::open(path)?))?;
+        let regressions = report.regressions(&baseline, 0.005, 0.25);
+        for r in &regressions {
+            eprintln!(
+                "regression in {} on {}: {:.0} -> {:.0} ops/sec, hit ratio {:.4} -> {:.4}",
+                r.current.policy, r.current.trace, r.baseline.ops_per_sec, r.current.ops_per_sec, r.baseline.hit_ratio, r.current.hit_ratio,
+            );
+        }
+        if !regressions.is_empty() {
+            process::exit(1);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,176 @@
+//! The `#[memoize]` attribute of simcache, re-exported by it under the `macros` feature
+
+use proc_macro::{Delimiter, Group, Ident, Spacing, Span, TokenStream, TokenTree};
+
+/// cache the results of a function in a Simcache, keyed by its arguments
+///
+/// `capacity` is required; `ttl` is in seconds, and `policy` names a policy in
+/// `simcache::eviction`, LRU if it isn't given. The function can't be generic
+/// or a method, and its arguments have to be plain `name: Type` or `mut name: Type`.
+#[proc_macro_attribute]
+pub fn memoize(attr: TokenStream, item: TokenStream) -> TokenStream {
+    match expand(attr, item) {
+        Ok(tokens) => tokens,
+        Err((message, span)) => compile_error(&message, span),
+    }
+}
+
+type Error = (String, Span);
+
+struct Settings {
+    capacity: String,
+    ttl: Option<String>,
+    policy: String,
+}
+
+struct Arg {
+    name: Ident,
+    ty: TokenStream,
+}
+
+fn compile_error(message: &str, span: Span) -> TokenStream {
+    let tokens: TokenStream = format!("::core::compile_error!({message:?});").parse().expect("compile_error! should parse");
+    tokens
+        .into_iter()
+        .map(|mut token| {
+            token.set_span(span);
+            token
+        })
+        .collect()
+}
+
+fn parse(code: &str) -> TokenStream {
+    code.parse().expect("generated code should parse")
+}
+
+fn parse_settings(attr: TokenStream) -> Result<Settings, Error> {
+    let mut settings = Settings { capacity: String::new(), ttl: None, policy: "LRU".to_string() };
+    let tokens: Vec<TokenTree> = attr.into_iter().collect();
+    for setting in tokens.split(|token| matches!(token, TokenTree::Punct(p) if p.as_char() == ',')) {
+        match setting {
+            [] => {}
+            [TokenTree::Ident(name), TokenTree::Punct(eq), value] if eq.as_char() == '=' => {
+                let value = value.to_string();
+                match name.to_string().as_str() {
+                    "capacity" => settings.capacity = value,
+                    "ttl" => settings.ttl = Some(value),
+                    "policy" => settings.policy = value,
+                    other => return Err((format!("unknown memoize setting `{other}`"), name.span())),
+                }
+            }
+            [first, ..] => return Err(("expected `capacity = ..`, `ttl = ..`, or `policy = ..`".to_string(), first.span())),
+        }
+    }
+    if settings.capacity.is_empty() {
+        return Err(("memoize needs a `capacity = ..`".to_string(), Span::call_site()));
+    }
+    Ok(settings)
+}
+
+// split the argument list at its top level commas, which aren't in a group but may be inside <..>
+fn parse_args(args: &Group) -> Result<Vec<Arg>, Error> {
+    let mut parts = vec![Vec::new()];
+    let mut depth = 0usize;
+    let mut after_dash = false;
+    for token in args.stream() {
+        if let TokenTree::Punct(p) = &token {
+            match p.as_char() {
+                '<' => depth += 1,
+                '>' if !after_dash => depth = depth.saturating_sub(1),
+                ',' if depth == 0 => {
+                    parts.push(Vec::new());
+                    after_dash = false;
+                    continue;
+                }
+                _ => {}
+            }
+            after_dash = p.as_char() == '-' && p.spacing() == Spacing::Joint;
+        } else {
+            after_dash = false;
+        }
+        parts.last_mut().expect("there should be a part to add to").push(token);
+    }
+    parts
+        .into_iter()
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let start = usize::from(matches!(&part[0], TokenTree::Ident(i) if i.to_string() == "mut"));
+            match &part[start..] {
+                [TokenTree::Ident(name), TokenTree::Punct(colon), ty @ ..] if colon.as_char() == ':' && name.to_string() != "self" => {
+                    Ok(Arg { name: name.clone(), ty: ty.iter().cloned().collect() })
+                }
+                _ => Err(("memoized arguments should be `name: Type`".to_string(), part[0].span())),
+            }
+        })
+        .collect()
+}
+
+fn expand(attr: TokenStream, item: TokenStream) -> Result<TokenStream, Error> {
+    let settings = parse_settings(attr)?;
+    let tokens: Vec<TokenTree> = item.into_iter().collect();
+    let fn_at = tokens
+        .iter()
+        .position(|token| matches!(token, TokenTree::Ident(i) if i.to_string() == "fn"))
+        .ok_or_else(|| ("memoize should be put on a function".to_string(), Span::call_site()))?;
+    let (prefix, rest) = tokens.split_at(fn_at);
+    let (name, args, signature_end, body) = match rest {
+        [_, TokenTree::Ident(name), TokenTree::Group(args), signature_end @ .., TokenTree::Group(body)]
+            if args.delimiter() == Delimiter::Parenthesis && body.delimiter() == Delimiter::Brace =>
+        {
+            (name, args, signature_end, body)
+        }
+        [_, _, TokenTree::Punct(p), ..] if p.as_char() == '<' => {
+            return Err(("memoized functions can't be generic".to_string(), p.span()));
+        }
+        _ => return Err(("memoize should be put on a function with a body".to_string(), Span::call_site())),
+    };
+    let output = match signature_end {
+        [] => parse("()"),
+        [TokenTree::Punct(dash), TokenTree::Punct(arrow), ty @ ..] if dash.as_char() == '-' && arrow.as_char() == '>' => {
+            if let Some(bad) = ty.iter().find(|token| matches!(token, TokenTree::Ident(i) if i.to_string() == "where")) {
+                return Err(("memoized functions can't have a where clause".to_string(), bad.span()));
+            }
+            ty.iter().cloned().collect()
+        }
+        [first, ..] => return Err(("expected a return type".to_string(), first.span())),
+    };
+    let args_list = parse_args(args)?;
+
+    let key_type = format!("({})", args_list.iter().map(|arg| format!("{},", arg.ty)).collect::<String>());
+    let key_names = format!("({})", args_list.iter().map(|arg| format!("{},", arg.name)).collect::<String>());
+    let policy = format!("::simcache::eviction::{}<{key_type}>", settings.policy);
+    let ttl = settings.ttl.map_or(String::new(), |ttl| format!(".default_ttl(::std::time::Duration::from_secs({ttl}))"));
+
+    // the original body goes in an inner function, where calls to the memoized function itself go through the cache
+    let mut uncached = parse("fn __uncached");
+    uncached.extend([TokenTree::Group(args.clone())]);
+    uncached.extend(parse("->"));
+    uncached.extend(output.clone());
+    uncached.extend([TokenTree::Group(body.clone())]);
+
+    let mut inner = uncached;
+    inner.extend(parse(&format!(
+        "static MEMOIZED: ::std::sync::OnceLock<::simcache::memoize::Memoized<{key_type}, {output}, fn({key_type}) -> {output}, {policy}>> = \
+            ::std::sync::OnceLock::new();
+        MEMOIZED
+            .get_or_init(|| {{
+                let cache = ::simcache::Simcache::builder().max_capacity({capacity}){ttl}.build();
+                ::simcache::memoize::Memoized::with_cache(cache, |{key_names}: {key_type}| __uncached{key_names})
+            }})
+            .call({key_names})",
+        capacity = settings.capacity,
+    )));
+
+    let mut expanded: TokenStream = prefix.iter().cloned().collect();
+    expanded.extend([TokenTree::Ident(Ident::new("fn", Span::call_site())), TokenTree::Ident(name.clone())]);
+    // the outer function takes plain names, as mut bindings only matter to the body
+    let outer_args = args_list
+        .iter()
+        .map(|arg| format!("{}: {},", arg.name, arg.ty))
+        .collect::<String>();
+    expanded.extend([TokenTree::Group(Group::new(Delimiter::Parenthesis, parse(&outer_args)))]);
+    expanded.extend(parse("->"));
+    expanded.extend(output);
+    expanded.extend([TokenTree::Group(Group::new(Delimiter::Brace, inner))]);
+    Ok(expanded)
+}
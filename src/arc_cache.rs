@@ -0,0 +1,123 @@
+//! Cache of reference counted values
+//!
+//! Values are stored as `Arc<V>` so that large values can be handed out and
+//! shared across threads without being cloned.
+
+use std::borrow::Borrow;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hash};
+use std::ops::{Deref, DerefMut};
+use std::sync::Arc;
+use std::time::Duration;
+use crate::{Clock, EvictionPolicy, InsertOutcome, Simcache, SystemClock};
+
+/// a Simcache whose lookups return shared handles to the stored values
+/// every other Simcache method is available through deref
+pub struct ArcSimcache<K, V, E, S = RandomState, C = SystemClock>
+where
+    E: EvictionPolicy<K>
+{
+    inner: Simcache<K, Arc<V>, E, S, C>,
+}
+
+impl<K, V, E> ArcSimcache<K, V, E>
+where
+    K: Eq + Hash + Clone,
+    E: EvictionPolicy<K>,
+    {
+        /// return a new, empty cache
+        pub fn new(max_capacity: usize) -> Self {
+            ArcSimcache { inner: Simcache::new(max_capacity) }
+        }
+    }
+
+impl<K, V, E, S, C> ArcSimcache<K, V, E, S, C>
+where
+    K: Eq + Hash + Clone,
+    E: EvictionPolicy<K>,
+    S: BuildHasher,
+    C: Clock,
+    {
+        /// insert a key value pair into the cache
+        /// option to include a ttl for the item, otherwise the cache's default ttl applies
+        pub fn insert(&mut self, key: K, value: impl Into<Arc<V>>, ttl: Option<Duration>) -> InsertOutcome {
+            self.inner.insert(key, value.into(), ttl)
+        }
+
+        /// return a handle to the value of the given key if it is not expired
+        pub fn get<Q>(&mut self, key: &Q) -> Option<Arc<V>>
+        where
+            K: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+        {
+            self.inner.get(key).cloned()
+        }
+
+        /// return a handle to the value of the given key, computing and inserting it with f on a miss
+        pub fn get_or_insert_with<F>(&mut self, key: K, ttl: Option<Duration>, f: F) -> Arc<V>
+        where
+            F: FnOnce() -> V,
+        {
+            self.inner.get_or_insert_with(key, ttl, || Arc::new(f())).clone()
+        }
+
+        /// return the underlying cache
+        pub fn into_inner(self) -> Simcache<K, Arc<V>, E, S, C> {
+            self.inner
+        }
+    }
+
+impl<K, V, E, S, C> From<Simcache<K, Arc<V>, E, S, C>> for ArcSimcache<K, V, E, S, C>
+where
+    E: EvictionPolicy<K>
+{
+    fn from(inner: Simcache<K, Arc<V>, E, S, C>) -> Self {
+        ArcSimcache { inner }
+    }
+}
+
+impl<K, V, E, S, C> Deref for ArcSimcache<K, V, E, S, C>
+where
+    E: EvictionPolicy<K>
+{
+    type Target = Simcache<K, Arc<V>, E, S, C>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+impl<K, V, E, S, C> DerefMut for ArcSimcache<K, V, E, S, C>
+where
+    E: EvictionPolicy<K>
+{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.inner
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LRU;
+
+    // deliberately not Clone
+    #[derive(Debug, PartialEq)]
+    struct Buffer(Vec<u8>);
+
+    #[test]
+    fn arc_cache_test() {
+        let mut cache: ArcSimcache<&'static str, Buffer, LRU<&'static str>> = ArcSimcache::new(2);
+
+        cache.insert("a", Buffer(vec![0; 1024]), None);
+        let first = cache.get(&"a").expect("cache should have a value for key a");
+        let second = cache.get(&"a").expect("cache should have a value for key a");
+
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!(cache.len(), 1);
+
+        // handles outlive the entry
+        cache.remove(&"a");
+        assert_eq!(first.0.len(), 1024);
+    }
+}
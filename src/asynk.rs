@@ -0,0 +1,193 @@
+//! Async cache with per-key request coalescing
+//!
+//! Concurrent `get_or_insert_with` calls for the same missing key share a
+//! single loader future: the first caller runs it and every other caller
+//! waits for its result instead of hitting the backing service again.
+
+use std::future::Future;
+use std::hash::Hash;
+use std::sync::Mutex;
+use std::time::Duration;
+use crate::coalesce::{InFlight, Role, WaitLoad};
+use crate::{EvictionPolicy, Stats};
+
+/// a thread safe cache whose loads can be awaited
+/// values are cloned out of the cache since no lock is held across an await
+pub struct Simcache<K, V, E>
+where
+    E: EvictionPolicy<K>
+{
+    inner: Mutex<crate::Simcache<K, V, E>>,
+    in_flight: InFlight<K, V>,
+}
+
+impl<K, V, E> Simcache<K, V, E>
+where
+    K: Eq + Hash + Clone,
+    V: Clone,
+    E: EvictionPolicy<K>,
+    {
+        /// return a new, empty cache
+        pub fn new(max_capacity: usize) -> Self {
+            Simcache {
+                inner: Mutex::new(crate::Simcache::new(max_capacity)),
+                in_flight: InFlight::new(),
+            }
+        }
+
+        fn cache(&self) -> std::sync::MutexGuard<'_, crate::Simcache<K, V, E>> {
+            self.inner.lock().expect("cache mutex should not be poisoned")
+        }
+
+        /// insert a key value pair into the cache
+        /// option to include a ttl for the item
+        pub fn insert(&self, key: K, value: V, ttl: Option<Duration>) {
+            self.cache().insert(key, value, ttl);
+        }
+
+        /// return a clone of the value of the given key if it is present and not expired
+        pub fn get(&self, key: &K) -> Option<V> {
+            self.cache().get(key).cloned()
+        }
+
+        /// remove the key value pair with the given key from the cache
+        pub fn remove(&self, key: &K) -> Option<V> {
+            self.cache().remove(key)
+        }
+
+        /// return the current size of the cache
+        pub fn len(&self) -> usize {
+            self.cache().len()
+        }
+
+        /// return true if the cache holds no entries
+        pub fn is_empty(&self) -> bool {
+            self.cache().is_empty()
+        }
+
+        /// remove every expired entry, returning how many were removed
+        pub fn purge_expired(&self) -> usize {
+            self.cache().purge_expired()
+        }
+
+        /// return the hit, miss, eviction, expiry, and insert counters
+        pub fn stats(&self) -> Stats {
+            self.cache().stats()
+        }
+
+        /// set all stats counters back to zero
+        pub fn reset_stats(&self) {
+            self.cache().reset_stats();
+        }
+
+        /// return the cached value for the key, or await the loader and cache its result
+        /// concurrent callers missing on the same key share one loader future;
+        /// if that future is dropped before finishing, one of the waiters runs its own loader
+        pub async fn get_or_insert_with<F, Fut>(&self, key: K, ttl: Option<Duration>, f: F) -> V
+        where
+            F: FnOnce() -> Fut,
+            Fut: Future<Output = V>,
+        {
+            let mut loader = Some(f);
+            loop {
+                if let Some(v) = self.get(&key) {
+                    return v;
+                }
+
+                // a load may have just completed, which is looked for with peek as the lookup above counted the miss
+                match self.in_flight.join(&key, || self.cache().peek(&key).cloned()) {
+                    Role::Cached(v) => return v,
+                    Role::Wait(load) => {
+                        if let Some(v) = WaitLoad(load).await {
+                            return v;
+                        }
+                    }
+                    Role::Lead(guard) => {
+                        let f = loader.take().expect("loader should only be run by the leading caller");
+                        let value = f().await;
+
+                        self.insert(key.clone(), value.clone(), ttl);
+                        guard.finish(value.clone());
+                        return value;
+                    }
+                }
+            }
+        }
+    }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eviction::LRU;
+    use std::cell::Cell;
+    use std::pin::Pin;
+    use std::task::{Context, Poll, Waker};
+
+    /// yields to the caller once before completing
+    struct YieldOnce(bool);
+
+    impl Future for YieldOnce {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            if self.0 {
+                return Poll::Ready(());
+            }
+            self.0 = true;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+
+    fn poll_once<F: Future>(fut: Pin<&mut F>) -> Poll<F::Output> {
+        fut.poll(&mut Context::from_waker(Waker::noop()))
+    }
+
+    #[test]
+    fn test_coalesced_load() {
+        let cache: Simcache<&'static str, usize, LRU<&'static str>> = Simcache::new(3);
+        let calls = Cell::new(0);
+        let loader = || async {
+            calls.set(calls.get() + 1);
+            YieldOnce(false).await;
+            7
+        };
+
+        let mut first = std::pin::pin!(cache.get_or_insert_with("a", None, loader));
+        let mut second = std::pin::pin!(cache.get_or_insert_with("a", None, loader));
+
+        assert!(poll_once(first.as_mut()).is_pending());
+        assert!(poll_once(second.as_mut()).is_pending());
+        assert_eq!(poll_once(first.as_mut()), Poll::Ready(7));
+        assert_eq!(poll_once(second.as_mut()), Poll::Ready(7));
+
+        assert_eq!(calls.get(), 1);
+        // one miss for each caller, not two for the leader
+        assert_eq!((cache.stats().hits, cache.stats().misses, cache.stats().inserts), (0, 2, 1));
+        assert_eq!(cache.get(&"a"), Some(7));
+    }
+
+    #[test]
+    fn test_abandoned_load_is_taken_over() {
+        let cache: Simcache<&'static str, usize, LRU<&'static str>> = Simcache::new(3);
+        let calls = Cell::new(0);
+        let loader = || async {
+            calls.set(calls.get() + 1);
+            YieldOnce(false).await;
+            calls.get()
+        };
+
+        let mut waiter = Box::pin(cache.get_or_insert_with("a", None, loader));
+        {
+            let mut leader = Box::pin(cache.get_or_insert_with("a", None, loader));
+            assert!(poll_once(leader.as_mut()).is_pending());
+            assert!(poll_once(waiter.as_mut()).is_pending());
+        }
+
+        // the leader was dropped, so the waiter retries and leads its own load
+        assert!(poll_once(waiter.as_mut()).is_pending());
+        assert_eq!(poll_once(waiter.as_mut()), Poll::Ready(2));
+        assert_eq!(calls.get(), 2);
+    }
+}
@@ -0,0 +1,260 @@
+//! Cache in front of a backing store such as a database
+//!
+//! Misses are read through from the store. Writes either go to the store as
+//! they happen (`WriteMode::WriteThrough`), or are buffered in the cache as
+//! dirty entries and written when they are evicted, expire, or are flushed
+//! (`WriteMode::WriteBack`).
+
+use std::collections::hash_map::RandomState;
+use std::collections::HashSet;
+use std::hash::{BuildHasher, Hash};
+use std::time::Duration;
+use crate::{Clock, EvictionPolicy, InsertOutcome, Simcache, SystemClock};
+
+/// the system of record a cache sits in front of
+pub trait BackingStore<K, V> {
+    type Error;
+    /// return the stored value of the key, or None if it has none
+    fn load(&mut self, key: &K) -> Result<Option<V>, Self::Error>;
+    /// write the value of the key, replacing any stored value
+    fn store(&mut self, key: &K, value: &V) -> Result<(), Self::Error>;
+    /// delete the stored value of the key, if it has one
+    fn delete(&mut self, key: &K) -> Result<(), Self::Error>;
+}
+
+/// when writes to the cache reach the backing store
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WriteMode {
+    /// every insert is written to the store before it is cached
+    #[default]
+    WriteThrough,
+    /// inserts are only cached, and written to the store once they leave the cache or are flushed
+    WriteBack,
+}
+
+/// a cache that reads misses from a backing store and writes inserts to it
+/// in write-back mode, call flush before dropping the cache or buffered writes are lost
+pub struct BackedCache<K, V, E, B, S = RandomState, C = SystemClock>
+where
+    E: EvictionPolicy<K>
+{
+    cache: Simcache<K, V, E, S, C>,
+    store: B,
+    mode: WriteMode,
+    // keys whose cached value hasn't been written to the store yet
+    dirty: HashSet<K>,
+    // a loaded value the cache had no room for, kept so get can still return it
+    rejected: Option<V>,
+}
+
+impl<K, V, E, B, S, C> BackedCache<K, V, E, B, S, C>
+where
+    K: Eq + Hash + Clone,
+    E: EvictionPolicy<K>,
+    B: BackingStore<K, V>,
+    S: BuildHasher,
+    C: Clock,
+    {
+        /// return a cache over the given store, writing to it as the mode says
+        pub fn new(mut cache: Simcache<K, V, E, S, C>, store: B, mode: WriteMode) -> Self {
+            cache.collect_spilled();
+            BackedCache { cache, store, mode, dirty: HashSet::new(), rejected: None }
+        }
+
+        /// insert a key value pair into the cache
+        /// in write-through mode the store is written first, and nothing is cached if that fails;
+        /// in write-back mode the entry is only marked dirty, unless the cache has no room for it
+        pub fn insert(&mut self, key: K, value: V, ttl: Option<Duration>) -> Result<InsertOutcome, B::Error> {
+            match self.mode {
+                WriteMode::WriteThrough => {
+                    self.store.store(&key, &value)?;
+                    let outcome = self.cache.insert(key, value, ttl);
+                    self.write_spilled()?;
+                    Ok(outcome)
+                }
+                WriteMode::WriteBack => match self.cache.insert_or_return(key.clone(), value, ttl) {
+                    Ok(outcome) => {
+                        self.dirty.insert(key);
+                        self.write_spilled()?;
+                        Ok(outcome)
+                    }
+                    Err(value) => {
+                        self.dirty.remove(&key);
+                        self.write_spilled()?;
+                        self.store.store(&key, &value)?;
+                        Ok(InsertOutcome::Rejected)
+                    }
+                },
+            }
+        }
+
+        /// return the value of the given key, loading it from the store if it isn't cached
+        /// a loaded value the cache refuses to keep is returned this once and then dropped
+        pub fn get(&mut self, key: &K) -> Result<Option<&V>, B::Error> {
+            // get rather than contains_key, so an expired dirty entry is written before the load
+            if self.cache.get(key).is_some() {
+                return Ok(self.cache.peek(key));
+            }
+            self.write_spilled()?;
+            let Some(value) = self.store.load(key)? else {
+                return Ok(None);
+            };
+            match self.cache.insert_or_return(key.clone(), value, None) {
+                Ok(_) => {
+                    self.write_spilled()?;
+                    Ok(self.cache.peek(key))
+                }
+                Err(value) => Ok(Some(self.rejected.insert(value))),
+            }
+        }
+
+        /// remove the key from the cache and delete it from the store, in either mode
+        pub fn remove(&mut self, key: &K) -> Result<Option<V>, B::Error> {
+            self.store.delete(key)?;
+            self.dirty.remove(key);
+            Ok(self.cache.remove(key))
+        }
+
+        /// write every dirty entry to the store
+        /// entries that fail to write stay dirty, and the first error is returned
+        pub fn flush(&mut self) -> Result<(), B::Error> {
+            self.cache.purge_expired();
+            let mut result = self.write_spilled();
+            let dirty: Vec<K> = self.dirty.iter().cloned().collect();
+            for key in dirty {
+                let Some(value) = self.cache.peek(&key) else {
+                    self.dirty.remove(&key);
+                    continue;
+                };
+                match self.store.store(&key, value) {
+                    Ok(()) => {
+                        self.dirty.remove(&key);
+                    }
+                    Err(e) => {
+                        if result.is_ok() {
+                            result = Err(e);
+                        }
+                    }
+                }
+            }
+            result
+        }
+
+        /// return true if the key has a cached value that hasn't been written to the store
+        pub fn is_dirty(&self, key: &K) -> bool {
+            self.dirty.contains(key)
+        }
+
+        /// return the write mode
+        pub fn mode(&self) -> WriteMode {
+            self.mode
+        }
+
+        /// return the cache in front of the store
+        pub fn cache(&self) -> &Simcache<K, V, E, S, C> {
+            &self.cache
+        }
+
+        /// return the backing store
+        pub fn store(&self) -> &B {
+            &self.store
+        }
+
+        // write the dirty entries the cache has evicted or expired since the last call
+        // every entry is tried, and the first error is returned
+        fn write_spilled(&mut self) -> Result<(), B::Error> {
+            let mut result = Ok(());
+            for (key, value, _) in self.cache.take_spilled() {
+                if !self.dirty.remove(&key) {
+                    continue;
+                }
+                if let Err(e) = self.store.store(&key, &value) {
+                    if result.is_ok() {
+                        result = Err(e);
+                    }
+                }
+            }
+            result
+        }
+    }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eviction::LRU;
+    use std::collections::HashMap;
+
+    // a store that counts its calls
+    #[derive(Default)]
+    struct MapStore {
+        values: HashMap<&'static str, u64>,
+        loads: usize,
+        stores: usize,
+    }
+
+    impl BackingStore<&'static str, u64> for MapStore {
+        type Error = ();
+        fn load(&mut self, key: &&'static str) -> Result<Option<u64>, ()> {
+            self.loads += 1;
+            Ok(self.values.get(key).copied())
+        }
+        fn store(&mut self, key: &&'static str, value: &u64) -> Result<(), ()> {
+            self.stores += 1;
+            self.values.insert(key, *value);
+            Ok(())
+        }
+        fn delete(&mut self, key: &&'static str) -> Result<(), ()> {
+            self.values.remove(key);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn write_through_test() {
+        let cache: Simcache<&'static str, u64, LRU<&'static str>> = Simcache::new(2);
+        let mut store = MapStore::default();
+        store.values.insert("a", 1);
+        let mut cache = BackedCache::new(cache, store, WriteMode::WriteThrough);
+
+        // a miss is read through and cached
+        assert_eq!(cache.get(&"a"), Ok(Some(&1)));
+        assert_eq!(cache.get(&"a"), Ok(Some(&1)));
+        assert_eq!(cache.store().loads, 1);
+        assert_eq!(cache.get(&"z"), Ok(None));
+
+        cache.insert("b", 2, None).unwrap();
+        assert_eq!(cache.store().values.get("b"), Some(&2));
+        assert!(!cache.is_dirty(&"b"));
+
+        assert_eq!(cache.remove(&"a"), Ok(Some(1)));
+        assert_eq!(cache.store().values.get("a"), None);
+    }
+
+    #[test]
+    fn write_back_test() {
+        let cache: Simcache<&'static str, u64, LRU<&'static str>> = Simcache::new(2);
+        let mut cache = BackedCache::new(cache, MapStore::default(), WriteMode::WriteBack);
+
+        cache.insert("a", 1, None).unwrap();
+        cache.insert("b", 2, None).unwrap();
+        assert!(cache.is_dirty(&"a"));
+        assert_eq!(cache.store().stores, 0);
+
+        // evicting a writes it to the store
+        cache.insert("c", 3, None).unwrap();
+        assert!(!cache.is_dirty(&"a"));
+        assert_eq!(cache.store().values.get("a"), Some(&1));
+        assert_eq!(cache.store().stores, 1);
+
+        // and it is read back on the next miss, which evicts b
+        assert_eq!(cache.get(&"a"), Ok(Some(&1)));
+        assert_eq!(cache.store().values.get("b"), Some(&2));
+
+        cache.insert("a", 10, None).unwrap();
+        cache.flush().unwrap();
+        assert_eq!(cache.store().values.get("a"), Some(&10));
+        assert_eq!(cache.store().values.get("c"), Some(&3));
+        assert!(!cache.is_dirty(&"a") && !cache.is_dirty(&"c"));
+        assert_eq!(cache.store().stores, 4);
+    }
+}
@@ -0,0 +1,189 @@
+//! Throughput and hit ratio benchmarks for the built-in policies
+//!
+//! A `Bench` replays canned Zipfian traces against each policy, timing the
+//! replay and recording the hit ratio. Its `Report` is written as tab separated
+//! lines that can be saved as a baseline, read back, and compared against a
+//! later run to flag policies that got slower or started missing more.
+//! `cargo bench` runs the default bench and prints its report.
+//!
+//! ```
+//! use simcache::bench::Bench;
+//! use simcache::eviction::PolicyKind;
+//!
+//! let report = Bench::new(100).accesses(1_000).run(&[PolicyKind::Lru, PolicyKind::Lfu]);
+//! let baseline = report.clone();
+//! assert!(report.regressions(&baseline, 0.01, 0.5).is_empty());
+//! ```
+
+use std::io::{self, BufRead, Write};
+use std::time::Instant;
+use crate::eviction::{AnyPolicy, PolicyKind};
+use crate::simulation::workload::Zipf;
+use crate::simulation::{Access, Simulation};
+
+/// the Zipf exponents of the canned traces; higher is more skewed toward a few hot keys
+pub const ZIPF_EXPONENTS: [f64; 3] = [0.8, 1.0, 1.2];
+
+/// the result of replaying one trace against one policy
+#[derive(Debug, Clone, PartialEq)]
+pub struct Measurement {
+    pub trace: String,
+    pub policy: PolicyKind,
+    pub ops_per_sec: f64,
+    pub hit_ratio: f64,
+}
+
+/// every measurement of a bench run
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Report {
+    pub measurements: Vec<Measurement>,
+}
+
+/// a measurement that got worse than its baseline by more than the tolerance
+#[derive(Debug, Clone, PartialEq)]
+pub struct Regression {
+    pub baseline: Measurement,
+    pub current: Measurement,
+}
+
+/// the settings of a bench run
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bench {
+    capacity: usize,
+    keys: usize,
+    accesses: usize,
+    seed: u64,
+}
+
+impl Bench {
+    /// bench a cache of the given capacity on traces over 10 times as many keys
+    pub fn new(capacity: usize) -> Self {
+        Bench { capacity, keys: capacity.saturating_mul(10).max(1), accesses: 100_000, seed: 1 }
+    }
+
+    /// replay this many accesses per trace
+    pub fn accesses(mut self, accesses: usize) -> Self {
+        self.accesses = accesses;
+        self
+    }
+
+    /// generate the traces with the given seed, so that runs with the same seed see the same accesses
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// return the canned traces, named by their exponent
+    pub fn traces(&self) -> Vec<(String, Vec<Access<u64>>)> {
+        ZIPF_EXPONENTS
+            .iter()
+            .map(|&exponent| {
+                let trace = Zipf::with_seed(self.keys, exponent, self.seed).take(self.accesses).map(Access::new).collect();
+                (format!("zipf-{exponent}"), trace)
+            })
+            .collect()
+    }
+
+    /// replay every canned trace against each of the given policies
+    pub fn run(&self, kinds: &[PolicyKind]) -> Report {
+        let simulation = Simulation::new(self.capacity);
+        let mut measurements = Vec::new();
+        for (name, trace) in self.traces() {
+            for &kind in kinds {
+                let start = Instant::now();
+                let report = simulation.run(&trace, AnyPolicy::from_kind(kind));
+                let secs = start.elapsed().as_secs_f64().max(f64::MIN_POSITIVE);
+                measurements.push(Measurement {
+                    trace: name.clone(),
+                    policy: kind,
+                    ops_per_sec: report.accesses as f64 / secs,
+                    hit_ratio: report.hit_ratio(),
+                });
+            }
+        }
+        Report { measurements }
+    }
+}
+
+fn invalid_line(line: usize, message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {}", line, message))
+}
+
+impl Report {
+    /// write a `trace policy ops_per_sec hit_ratio` header and a tab separated line for each measurement
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writeln!(writer, "trace\tpolicy\tops_per_sec\thit_ratio")?;
+        for m in &self.measurements {
+            writeln!(writer, "{}\t{}\t{:.0}\t{:.6}", m.trace, m.policy, m.ops_per_sec, m.hit_ratio)?;
+        }
+        Ok(())
+    }
+
+    /// read a report written by write
+    pub fn read<R: BufRead>(reader: R) -> io::Result<Self> {
+        let mut measurements = Vec::new();
+        for (i, line) in reader.lines().enumerate().skip(1) {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let fields: Vec<&str> = line.split('\t').collect();
+            let [trace, policy, ops_per_sec, hit_ratio] = fields[..] else {
+                return Err(invalid_line(i + 1, "expected trace, policy, ops_per_sec, and hit_ratio"));
+            };
+            let (Ok(policy), Ok(ops_per_sec), Ok(hit_ratio)) = (policy.parse(), ops_per_sec.parse(), hit_ratio.parse()) else {
+                return Err(invalid_line(i + 1, "expected a policy name and two numbers"));
+            };
+            measurements.push(Measurement { trace: trace.to_string(), policy, ops_per_sec, hit_ratio });
+        }
+        Ok(Report { measurements })
+    }
+
+    /// return the measurements whose hit ratio fell by more than hit_tolerance, as a fraction of accesses,
+    /// or whose throughput fell by more than speed_tolerance, as a fraction of the baseline's
+    /// measurements without a baseline for the same trace and policy are skipped
+    pub fn regressions(&self, baseline: &Report, hit_tolerance: f64, speed_tolerance: f64) -> Vec<Regression> {
+        self.measurements
+            .iter()
+            .filter_map(|current| {
+                let baseline = baseline
+                    .measurements
+                    .iter()
+                    .find(|b| b.trace == current.trace && b.policy == current.policy)?;
+                let slower = current.ops_per_sec < baseline.ops_per_sec * (1.0 - speed_tolerance);
+                let missing = current.hit_ratio < baseline.hit_ratio - hit_tolerance;
+                (slower || missing).then(|| Regression { baseline: baseline.clone(), current: current.clone() })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bench_test() {
+        let bench = Bench::new(50).accesses(2_000);
+        let report = bench.run(&[PolicyKind::Lru, PolicyKind::Fifo]);
+        assert_eq!(report.measurements.len(), ZIPF_EXPONENTS.len() * 2);
+        assert!(report.measurements.iter().all(|m| m.ops_per_sec > 0.0 && m.hit_ratio > 0.0));
+        // the same seed gives the same hit ratios
+        let again = bench.run(&[PolicyKind::Lru, PolicyKind::Fifo]);
+        assert!(report.measurements.iter().zip(&again.measurements).all(|(a, b)| a.hit_ratio == b.hit_ratio));
+
+        let mut written = Vec::new();
+        report.write(&mut written).unwrap();
+        let read = Report::read(written.as_slice()).unwrap();
+        assert_eq!(read.measurements.len(), report.measurements.len());
+        assert_eq!(read.measurements[0].policy, PolicyKind::Lru);
+        assert!(Report::read("header\nzipf\tlru\tfast\t0.5\n".as_bytes()).is_err());
+
+        let mut worse = read.clone();
+        worse.measurements[1].hit_ratio -= 0.1;
+        worse.measurements[2].ops_per_sec /= 4.0;
+        let regressions = worse.regressions(&read, 0.01, 0.5);
+        assert_eq!(regressions.len(), 2);
+        assert_eq!(regressions[0].current, worse.measurements[1]);
+    }
+}
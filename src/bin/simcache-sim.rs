@@ -0,0 +1,119 @@
+//! Replays an access trace against eviction policies and prints how each did
+//!
+//! usage: simcache-sim --trace <file> --capacity <entries> [--format keys|csv|arc|lirs]
+//!                     [--policies lru,lfu,...] [--curve <capacity>,<capacity>,...]
+//!
+//! Every policy is run at the given capacity, then a miss-rate curve is printed
+//! with a miss ratio for each policy at each capacity of the curve, by default
+//! from an eighth of the capacity up to four times it.
+
+use simcache::eviction::PolicyKind;
+use simcache::simulation::trace::{read_trace, TraceFormat};
+use simcache::simulation::Simulation;
+use std::fs::File;
+use std::io::BufReader;
+use std::process;
+
+const USAGE: &str = "usage: simcache-sim --trace <file> --capacity <entries> [--format keys|csv|arc|lirs] \
+    [--policies lru,lfu,...] [--curve <capacity>,<capacity>,...]";
+
+struct Args {
+    trace: String,
+    format: TraceFormat,
+    capacity: usize,
+    policies: Vec<PolicyKind>,
+    curve: Option<Vec<usize>>,
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("simcache-sim: {message}\n{USAGE}");
+    process::exit(2);
+}
+
+fn parse_args() -> Args {
+    let mut trace = None;
+    let mut format = TraceFormat::Keys;
+    let mut capacity = None;
+    let mut policies: Vec<PolicyKind> = PolicyKind::all().collect();
+    let mut curve = None;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(flag) = args.next() {
+        let Some(value) = args.next() else { fail(&format!("{flag} needs a value")) };
+        match flag.as_str() {
+            "--trace" => trace = Some(value),
+            "--format" => {
+                format = match value.as_str() {
+                    "keys" => TraceFormat::Keys,
+                    "csv" => TraceFormat::Csv,
+                    "arc" => TraceFormat::Arc,
+                    "lirs" => TraceFormat::Lirs,
+                    other => fail(&format!("unknown trace format {other:?}")),
+                }
+            }
+            "--capacity" => capacity = Some(value.parse().unwrap_or_else(|_| fail("--capacity should be a number"))),
+            "--policies" => {
+                policies = value
+                    .split(',')
+                    .map(|name| name.parse().unwrap_or_else(|err| fail(&format!("{err}"))))
+                    .collect()
+            }
+            "--curve" => {
+                curve = Some(
+                    value
+                        .split(',')
+                        .map(|c| c.trim().parse().unwrap_or_else(|_| fail("--curve should be comma separated numbers")))
+                        .collect(),
+                )
+            }
+            other => fail(&format!("unknown flag {other}")),
+        }
+    }
+    let Some(trace) = trace else { fail("--trace is required") };
+    let Some(capacity) = capacity else { fail("--capacity is required") };
+    Args { trace, format, capacity, policies, curve }
+}
+
+fn main() {
+    let args = parse_args();
+    let file = File::open(&args.trace).unwrap_or_else(|err| {
+        eprintln!("simcache-sim: could not open {}: {err}", args.trace);
+        process::exit(1);
+    });
+    let trace = read_trace(BufReader::new(file), args.format).unwrap_or_else(|err| {
+        eprintln!("simcache-sim: could not read {}: {err}", args.trace);
+        process::exit(1);
+    });
+    println!("{} accesses, capacity {}", trace.len(), args.capacity);
+    println!();
+
+    let width = args.policies.iter().map(|kind| kind.name().len()).max().unwrap_or(0).max("policy".len());
+    println!("{:width$}  {:>9}  {:>14}  {:>11}", "policy", "hit ratio", "byte hit ratio", "evictions");
+    for (kind, report) in Simulation::new(args.capacity).compare_trace(&trace, &args.policies) {
+        println!(
+            "{:width$}  {:>9.4}  {:>14.4}  {:>11}",
+            kind.name(),
+            report.hit_ratio(),
+            report.byte_hit_ratio(),
+            report.evictions
+        );
+    }
+
+    let capacities = args.curve.unwrap_or_else(|| {
+        [8, 4, 2].iter().map(|d| (args.capacity / d).max(1)).chain([args.capacity, args.capacity * 2, args.capacity * 4]).collect()
+    });
+    println!();
+    println!("miss ratio by capacity");
+    print!("{:>10}", "capacity");
+    for kind in &args.policies {
+        print!("  {:>w$}", kind.name(), w = kind.name().len().max(6));
+    }
+    println!();
+    for capacity in capacities {
+        print!("{capacity:>10}");
+        for (kind, report) in Simulation::new(capacity).compare_trace(&trace, &args.policies) {
+            print!("  {:>w$.4}", 1.0 - report.hit_ratio(), w = kind.name().len().max(6));
+        }
+        println!();
+    }
+}
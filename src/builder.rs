@@ -0,0 +1,333 @@
+//! Builder for configuring a cache before it is created
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hash};
+use std::sync::Arc;
+use std::time::Duration;
+use crate::{CapacityLimit, Clock, EvictionPolicy, RemovalCause, Simcache, SystemClock};
+use crate::eviction::{AnyPolicy, PolicyKind};
+#[cfg(feature = "tracing")]
+use crate::instrument::{TraceEvent, Tracer};
+use crate::listener::Listener;
+use crate::mem_size::MemSize;
+use crate::ordered::OrderedKeys;
+
+/// computes the weight of an entry, used to bound the cache by something other than entry count
+/// kept in an Arc so that clones of the cache share it
+pub(crate) type Weigher<K, V> = Arc<dyn Fn(&K, &V) -> u64 + Send + Sync>;
+
+/// estimates the memory an entry takes up, used to bound the cache by bytes
+pub(crate) type Sizer<K, V> = fn(&K, &V) -> u64;
+
+/// collects the settings for a new Simcache
+/// any limit that isn't set is left unbounded
+///
+/// ```
+/// use simcache::{EvictionPolicy, Simcache, LRU};
+/// use std::time::Duration;
+///
+/// let cache: Simcache<u64, String, LRU<u64>> = Simcache::builder()
+///     .max_capacity(1000)
+///     .default_ttl(Duration::from_secs(60))
+///     .policy(LRU::new())
+///     .build();
+/// assert!(cache.is_empty());
+/// ```
+pub struct SimcacheBuilder<K, V, E, S = RandomState, C = SystemClock> {
+    pub(crate) hasher: S,
+    pub(crate) clock: C,
+    pub(crate) capacity: CapacityLimit,
+    pub(crate) max_weight: u64,
+    pub(crate) weigher: Option<Weigher<K, V>>,
+    pub(crate) max_bytes: u64,
+    pub(crate) sizer: Option<Sizer<K, V>>,
+    pub(crate) default_ttl: Option<Duration>,
+    pub(crate) time_to_idle: Option<Duration>,
+    pub(crate) refresh_after: Option<Duration>,
+    pub(crate) negative_ttl: Option<Duration>,
+    pub(crate) ttl_jitter: u8,
+    pub(crate) policy: Option<E>,
+    pub(crate) listener: Option<Listener<K, V>>,
+    #[cfg(feature = "tracing")]
+    pub(crate) tracer: Option<Tracer>,
+    pub(crate) shadow: bool,
+    pub(crate) doorkeeper: bool,
+    pub(crate) watermarks: Option<(u8, u8)>,
+    pub(crate) ordered: Option<OrderedKeys<K>>,
+    pub(crate) access_buffer: usize,
+}
+
+impl<K, V, E> SimcacheBuilder<K, V, E>
+where
+    K: Eq + Hash + Clone,
+    E: EvictionPolicy<K>,
+    {
+        /// return a builder with no limits set
+        pub fn new() -> Self {
+            SimcacheBuilder {
+                hasher: RandomState::new(),
+                clock: SystemClock,
+                capacity: CapacityLimit::Unbounded,
+                max_weight: u64::MAX,
+                weigher: None,
+                max_bytes: u64::MAX,
+                sizer: None,
+                default_ttl: None,
+                time_to_idle: None,
+                refresh_after: None,
+                negative_ttl: None,
+                ttl_jitter: 0,
+                policy: None,
+                listener: None,
+                #[cfg(feature = "tracing")]
+                tracer: None,
+                shadow: false,
+                doorkeeper: false,
+                watermarks: None,
+                ordered: None,
+                access_buffer: 0,
+            }
+        }
+    }
+
+impl<K, V, E, S, C> SimcacheBuilder<K, V, E, S, C>
+where
+    K: Eq + Hash + Clone,
+    E: EvictionPolicy<K>,
+    S: BuildHasher,
+    C: Clock,
+    {
+        /// hash keys with the given hasher instead of the default SipHash
+        pub fn hasher<S2: BuildHasher>(self, hasher: S2) -> SimcacheBuilder<K, V, E, S2, C> {
+            SimcacheBuilder {
+                hasher,
+                clock: self.clock,
+                capacity: self.capacity,
+                max_weight: self.max_weight,
+                weigher: self.weigher,
+                max_bytes: self.max_bytes,
+                sizer: self.sizer,
+                default_ttl: self.default_ttl,
+                time_to_idle: self.time_to_idle,
+                refresh_after: self.refresh_after,
+                negative_ttl: self.negative_ttl,
+                ttl_jitter: self.ttl_jitter,
+                policy: self.policy,
+                listener: self.listener,
+                #[cfg(feature = "tracing")]
+                tracer: self.tracer,
+                shadow: self.shadow,
+                doorkeeper: self.doorkeeper,
+                watermarks: self.watermarks,
+                ordered: self.ordered,
+                access_buffer: self.access_buffer,
+            }
+        }
+
+        /// read the time for expiry from the given clock instead of the system clock
+        pub fn clock<C2: Clock>(self, clock: C2) -> SimcacheBuilder<K, V, E, S, C2> {
+            SimcacheBuilder {
+                hasher: self.hasher,
+                clock,
+                capacity: self.capacity,
+                max_weight: self.max_weight,
+                weigher: self.weigher,
+                max_bytes: self.max_bytes,
+                sizer: self.sizer,
+                default_ttl: self.default_ttl,
+                time_to_idle: self.time_to_idle,
+                refresh_after: self.refresh_after,
+                negative_ttl: self.negative_ttl,
+                ttl_jitter: self.ttl_jitter,
+                policy: self.policy,
+                listener: self.listener,
+                #[cfg(feature = "tracing")]
+                tracer: self.tracer,
+                shadow: self.shadow,
+                doorkeeper: self.doorkeeper,
+                watermarks: self.watermarks,
+                ordered: self.ordered,
+                access_buffer: self.access_buffer,
+            }
+        }
+
+        /// limit the number of entries in the cache
+        pub fn max_capacity(self, max_capacity: usize) -> Self {
+            self.capacity(CapacityLimit::Bounded(max_capacity))
+        }
+
+        /// set the entry limit, or remove it with CapacityLimit::Unbounded
+        pub fn capacity(mut self, capacity: CapacityLimit) -> Self {
+            self.capacity = capacity;
+            self
+        }
+
+        /// limit the combined weight of the entries in the cache
+        pub fn max_weight(mut self, max_weight: u64) -> Self {
+            self.max_weight = max_weight;
+            self
+        }
+
+        /// weigh each entry with the given function instead of counting it as 1
+        pub fn weigher<F>(mut self, weigher: F) -> Self
+        where
+            F: Fn(&K, &V) -> u64 + Send + Sync + 'static,
+        {
+            self.weigher = Some(Arc::new(weigher));
+            self
+        }
+
+        /// limit the estimated memory taken up by the entries in the cache, counting
+        /// the MemSize of each key and value plus the cache's own bookkeeping per entry
+        /// the estimate is taken on insert, so values grown in place through get_mut aren't re-measured
+        pub fn max_bytes(mut self, max_bytes: u64) -> Self
+        where
+            K: MemSize,
+            V: MemSize,
+        {
+            self.max_bytes = max_bytes;
+            self.track_memory()
+        }
+
+        /// estimate the memory taken up by the entries, for current_memory, without limiting it
+        pub fn track_memory(mut self) -> Self
+        where
+            K: MemSize,
+            V: MemSize,
+        {
+            self.sizer = Some(crate::cache::entry_memory::<K, V>);
+            self
+        }
+
+        /// expire entries inserted without a ttl after the given duration
+        pub fn default_ttl(mut self, ttl: Duration) -> Self {
+            self.default_ttl = Some(ttl);
+            self
+        }
+
+        /// shorten each ttl by a random amount of up to the given percentage,
+        /// so that entries inserted together don't all expire together
+        pub fn ttl_jitter(mut self, percent: u8) -> Self {
+            self.ttl_jitter = percent.min(100);
+            self
+        }
+
+        /// expire entries once they have gone the given duration without being used,
+        /// in addition to any ttl they have
+        pub fn time_to_idle(mut self, time_to_idle: Duration) -> Self {
+            self.time_to_idle = Some(time_to_idle);
+            self
+        }
+
+        /// treat values as stale once they are older than the given duration,
+        /// so that needs_refresh asks for a reload while the stale value is still served
+        pub fn refresh_after(mut self, refresh_after: Duration) -> Self {
+            self.refresh_after = Some(refresh_after);
+            self
+        }
+
+        /// expire the negative entries cached by get_or_insert_optional_with after the given duration
+        pub fn negative_ttl(mut self, ttl: Duration) -> Self {
+            self.negative_ttl = Some(ttl);
+            self
+        }
+
+        /// use the given eviction policy instance instead of a new default one
+        pub fn policy(mut self, policy: E) -> Self {
+            self.policy = Some(policy);
+            self
+        }
+
+        /// call the given function with every entry removed from the cache,
+        /// whether by eviction, expiry, an explicit remove, or replacement
+        pub fn on_evict<F>(mut self, listener: F) -> Self
+        where
+            F: Fn(&K, &V, RemovalCause) + Send + Sync + 'static,
+        {
+            self.listener = Some(Arc::new(listener));
+            self
+        }
+
+        /// call the given function with a trace event for every lookup, insert, and removal
+        #[cfg(feature = "tracing")]
+        pub fn tracer<F>(mut self, tracer: F) -> Self
+        where
+            F: Fn(&TraceEvent) + Send + Sync + 'static,
+        {
+            self.tracer = Some(Arc::new(tracer));
+            self
+        }
+
+        /// evict in batches: once a new key would take the cache past high percent of its entry limit,
+        /// evict down to low percent instead of making room for just the one key
+        /// only the entry limit has watermarks, the weight limit still evicts one entry at a time
+        pub fn watermarks(mut self, high: u8, low: u8) -> Self {
+            let high = high.min(100);
+            self.watermarks = Some((high, low.min(high)));
+            self
+        }
+
+        /// keep the keys in order as well, so that invalidate_range and invalidate_prefix
+        /// only visit the keys they remove instead of every key in the cache
+        pub fn ordered_index(mut self) -> Self
+        where
+            K: Ord,
+        {
+            self.ordered = Some(OrderedKeys::new());
+            self
+        }
+
+        /// keep shadow caches of keys only at half and double the entry limit,
+        /// so that shadow_stats shows whether resizing the cache would change its hit ratio
+        /// does nothing for a cache without an entry limit
+        pub fn shadow_capacities(mut self) -> Self {
+            self.shadow = true;
+            self
+        }
+
+        /// keep a new key out of a full cache the first time it is inserted, and only put it to
+        /// the policy's admission check once it is inserted again, so that keys used only once
+        /// can't push out ones in use; the keys seen are kept in a bloom filter sized from the
+        /// entry limit, so now and then a key gets in on its first insert
+        pub fn doorkeeper(mut self) -> Self {
+            self.doorkeeper = true;
+            self
+        }
+
+        /// record the hits of get and get_many in a buffer of up to size keys instead of telling the
+        /// eviction policy of each one as it happens, so a lookup only touches the map; the buffer is
+        /// given to the policy in one batch when it fills, before anything is inserted or popped, and
+        /// by run_pending_tasks, which a sweeper thread can call in the background
+        /// until then the policy may evict a key whose last uses are still in the buffer
+        pub fn buffer_accesses(mut self, size: usize) -> Self {
+            self.access_buffer = size;
+            self
+        }
+
+        /// return a new, empty cache with these settings
+        pub fn build(self) -> Simcache<K, V, E, S, C> {
+            Simcache::from_builder(self)
+        }
+    }
+
+impl<K, V, S, C> SimcacheBuilder<K, V, AnyPolicy<K>, S, C>
+where
+    K: Eq + Hash + Clone,
+    S: BuildHasher,
+    C: Clock,
+    {
+        /// use a new policy of the given kind, for caches that pick their policy at runtime
+        pub fn policy_kind(self, kind: PolicyKind) -> Self {
+            self.policy(AnyPolicy::from_kind(kind))
+        }
+    }
+
+impl<K, V, E> Default for SimcacheBuilder<K, V, E>
+where
+    K: Eq + Hash + Clone,
+    E: EvictionPolicy<K>,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,150 +1,3575 @@
-use std::collections::{HashMap};
-use std::hash::Hash;
-use std::time::{Duration, Instant};
-use crate::EvictionPolicy;
+use std::borrow::Borrow;
+use std::collections::hash_map::RandomState;
+use std::collections::{hash_map, HashMap, HashSet};
+use std::hash::{BuildHasher, DefaultHasher, Hash, Hasher};
+use std::ops::{Bound, Deref, DerefMut, Index, RangeBounds};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::mem::size_of;
+use std::time::{Duration, Instant, SystemTime};
+use crate::{CapacityLimit, Clock, EvictionPolicy, RemovalCause, SimcacheBuilder, SimcacheError, Stats, SystemClock};
+use crate::builder::{Sizer, Weigher};
+use crate::entry::{Entry, OccupiedEntry, VacantEntry};
+use crate::doorkeeper::Doorkeeper;
+use crate::events::{CacheEvent, EventCause};
+use crate::expiry::ExpiryIndex;
+#[cfg(feature = "tracing")]
+use crate::instrument::{LookupOutcome, TraceEvent, Tracer};
+use crate::listener::Listener;
+use crate::mem_size::MemSize;
+use crate::ordered::OrderedKeys;
+use crate::priority::{PolicyLevels, Priority};
+use crate::rng::Rng;
+use crate::shadow::Shadows;
+use crate::stats::{LifetimeStats, ShadowStats};
+use crate::tags::{BoxedTag, TagIndex};
+
+#[cfg(feature = "persist")]
+mod snapshot;
 
 /// accessed objects are pushed onto the back of the access_order queue
 /// therefore the oldest items are at the front
-pub struct Simcache<K, V, E> 
+pub struct Simcache<K, V, E, S = RandomState, C = SystemClock> 
 where 
     E: EvictionPolicy<K>
 {
-    store: HashMap<K, (V, Option<Instant>)>,
-    eviction_policy: E,
-    max_capacity: usize,
+    store: HashMap<K, CacheEntry<V>, S>,
+    clock: C,
+    // one eviction policy per priority level
+    policies: PolicyLevels<E>,
+    capacity: CapacityLimit,
+    max_weight: u64,
+    weigher: Option<Weigher<K, V>>,
+    max_bytes: u64,
+    sizer: Option<Sizer<K, V>>,
+    default_ttl: Option<Duration>,
+    time_to_idle: Option<Duration>,
+    refresh_after: Option<Duration>,
+    negative_ttl: Option<Duration>,
+    ttl_jitter: u8,
+    // high and low percentages of the entry limit for batch eviction
+    watermarks: Option<(u8, u8)>,
+    rng: Rng,
+    listener: Option<Listener<K, V>>,
+    #[cfg(feature = "tracing")]
+    tracer: Option<Tracer>,
+    // the hash of the last key found expired, so the lookup that found it can be traced as expired
+    #[cfg(feature = "tracing")]
+    expired_lookup: Option<u64>,
+    // sum of the weights of every entry in the store
+    total_weight: u64,
+    // estimated memory taken up by every entry in the store, with memory tracking on
+    total_bytes: u64,
+    // number of pinned entries in the store, which the eviction policy doesn't track
+    pinned: usize,
+    // keys a loader found no value for, with when that answer expires
+    // these don't count towards len or the eviction policy, but are limited to the entry limit
+    negatives: HashMap<K, Option<Instant>>,
+    // a loaded value the cache had no room for, kept so the loaders can still return a reference to it
+    rejected: Option<V>,
+    // evicted and expired entries, with their expiry, collected for a second tier or store instead of being dropped
+    spilled: Option<Vec<(K, V, Option<Instant>)>>,
+    // a hash of the key of every lookup since recording started, for writing out as a trace
+    recorded: Option<Vec<u64>>,
+    // the tags attached by insert_with_tags
+    tags: TagIndex<K>,
+    // the keys in order, if the cache was built with an ordered index
+    ordered: Option<OrderedKeys<K>>,
+    // the keys of entries that expire, by when, so expired entries are found without a scan
+    expiry: ExpiryIndex<K>,
+    // channels to send every cache event to, dropped once their receiver is
+    subscribers: Vec<Sender<CacheEvent<K>>>,
+    // new keys offered to the cache while it was full, if it was built with a doorkeeper
+    doorkeeper: Option<Doorkeeper>,
+    // caches of keys only at other entry limits, for estimating the hit ratio there
+    shadows: Option<Shadows<K, E>>,
+    // the keys with hits not yet given to the policy, each once, when the cache was built to buffer
+    // up to access_buffer hits; the entries count their own hits, so a removed key's hits go with it
+    pending: Vec<K>,
+    buffered_reads: usize,
+    access_buffer: usize,
+    // new keys are given to the policy as cold while set, by insert_transient and scan_mode
+    transient: bool,
+    stats: Stats,
+    lifetimes: LifetimeStats,
+}
+
+/// what happened to a value passed to insert
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InsertOutcome {
+    /// the value was stored under a key that had no live entry
+    Inserted,
+    /// the value was stored in place of a live entry for the same key
+    Replaced,
+    /// the value was dropped by the policy's admission check or the doorkeeper, because the capacity is 0,
+    /// or because insert_until was given a time that had already passed
+    /// rejected values are never passed to the removal listener
+    Rejected,
+}
+
+/// everything insert_full did, handing back every value the insert displaced
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InsertResult<K, V> {
+    pub outcome: InsertOutcome,
+    /// the previous value of the key, if it had a live one
+    pub replaced: Option<V>,
+    /// the entries evicted to make room, in the order they went
+    pub evicted: Vec<(K, V)>,
+    /// the value itself, if it was rejected
+    pub rejected: Option<V>,
+}
+
+/// what get_or_insert_optional_with found for a key
+#[derive(Debug, PartialEq, Eq)]
+pub enum Lookup<'a, V> {
+    /// the key has a value, either cached or just loaded
+    Value(&'a V),
+    /// the key has a live negative entry, so the loader wasn't called
+    Negative,
+    /// the loader found no value, and a negative entry was cached for the key
+    Missing,
+}
+
+impl<'a, V> Lookup<'a, V> {
+    /// return the value, or None for a negative entry or a miss
+    pub fn value(self) -> Option<&'a V> {
+        match self {
+            Lookup::Value(value) => Some(value),
+            Lookup::Negative | Lookup::Missing => None,
+        }
+    }
+
+    /// return true if the lookup was answered by a cached negative entry
+    pub fn is_negative(&self) -> bool {
+        matches!(self, Lookup::Negative)
+    }
+}
+
+/// a cache in scan mode, see Simcache::scan_mode
+/// derefs to the cache, and leaves scan mode when dropped
+pub struct ScanMode<'a, K, V, E, S = RandomState, C = SystemClock>
+where
+    E: EvictionPolicy<K>
+{
+    cache: &'a mut Simcache<K, V, E, S, C>,
+    was_scanning: bool,
+}
+
+impl<K, V, E, S, C> Deref for ScanMode<'_, K, V, E, S, C>
+where
+    E: EvictionPolicy<K>
+{
+    type Target = Simcache<K, V, E, S, C>;
+
+    fn deref(&self) -> &Self::Target {
+        self.cache
+    }
+}
+
+impl<K, V, E, S, C> DerefMut for ScanMode<'_, K, V, E, S, C>
+where
+    E: EvictionPolicy<K>
+{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.cache
+    }
+}
+
+impl<K, V, E, S, C> Drop for ScanMode<'_, K, V, E, S, C>
+where
+    E: EvictionPolicy<K>
+{
+    fn drop(&mut self) {
+        self.cache.transient = self.was_scanning;
+    }
+}
+
+/// what entry_info found out about a live entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EntryInfo {
+    /// when the value was inserted, which a replacement resets
+    pub inserted_at: Instant,
+    /// when the entry was last used, or None if it hasn't been since it was inserted
+    pub last_accessed: Option<Instant>,
+    /// the number of times the entry has been used since it was inserted
+    pub accesses: u64,
+    /// the time left until the entry expires by ttl or idle time, or None if it never does
+    pub ttl_remaining: Option<Duration>,
+    pub weight: u64,
+    pub priority: Priority,
+    pub pinned: bool,
+    /// the eviction policy's score for the key, see EvictionPolicy::score; None for pinned entries
+    pub policy_score: Option<u64>,
+}
+
+/// a stored value and the metadata needed to expire and evict it
+#[derive(Clone)]
+struct CacheEntry<V> {
+    value: V,
+    inserted_at: Instant,
+    last_accessed: Option<Instant>,
+    accesses: u64,
+    expires_at: Option<Instant>,
+    // the ttl the entry was given, so touch can restart it
+    ttl: Option<Duration>,
+    // when the entry expires if it isn't used again, with time-to-idle enabled
+    idle_expires_at: Option<Instant>,
+    // when the value becomes stale and should be reloaded, with refresh_after enabled
+    refresh_at: Option<Instant>,
+    weight: u64,
+    // the memory estimate taken when the entry was inserted
+    bytes: u64,
+    pinned: bool,
+    priority: Priority,
+    // hits buffered for the policy, and the place of the last of them among the buffered hits
+    pending_reads: usize,
+    last_read: usize,
+}
+
+/// hash a key with a default hasher, which has fixed keys unlike the randomly seeded one
+/// the cache usually has, so the same key hashes the same in every cache and every run
+fn stable_hash<Q: Hash + ?Sized>(key: &Q) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// estimate the memory of an entry: the key and value, the rest of the entry around the value,
+/// a control byte in the store's hash table, and the copy of the key kept by the eviction policy
+pub(crate) fn entry_memory<K: MemSize, V: MemSize>(key: &K, value: &V) -> u64 {
+    let overhead = size_of::<CacheEntry<V>>() - size_of::<V>() + 1;
+    (2 * key.mem_size() + value.mem_size() + overhead) as u64
+}
+
+impl<V> CacheEntry<V> {
+    /// return the earlier of the ttl and idle expiry times
+    fn deadline(&self) -> Option<Instant> {
+        match (self.expires_at, self.idle_expires_at) {
+            (Some(ttl), Some(idle)) => Some(ttl.min(idle)),
+            (ttl, idle) => ttl.or(idle),
+        }
+    }
+
+    fn is_expired(&self, now: Instant) -> bool {
+        self.deadline().is_some_and(|expiry_time| now > expiry_time)
+    }
 }
 
 impl<K, V, E> Simcache<K, V, E> 
 where 
     K: Eq + Hash + Clone,
-    V: Clone,
     E: EvictionPolicy<K>,
     {
         /// return a new, empty cache
         pub fn new(max_capacity: usize) -> Self {
-            Simcache {
-                store: HashMap::new(),
-                eviction_policy: E::new(),
-                max_capacity,
-            }
+            Self::builder().max_capacity(max_capacity).build()
+        }
+
+        /// return a new, empty cache with no entry limit
+        pub fn unbounded() -> Self {
+            Self::builder().capacity(CapacityLimit::Unbounded).build()
+        }
+
+        /// return a builder for configuring a new cache
+        pub fn builder() -> SimcacheBuilder<K, V, E> {
+            SimcacheBuilder::new()
         }
 
         /// return a new, empty cache with the specified capacity
         pub fn new_with_capacity(capacity: usize, max_capacity: usize) -> Self {
+            let mut cache = Self::new(max_capacity);
+            cache.store.reserve(capacity);
+            cache
+        }
+    }
+
+impl<K, V, E, S, C> Simcache<K, V, E, S, C> 
+where 
+    K: Eq + Hash + Clone,
+    E: EvictionPolicy<K>,
+    S: BuildHasher,
+    C: Clock,
+    {
+        pub(crate) fn from_builder(builder: SimcacheBuilder<K, V, E, S, C>) -> Self {
+            let policy = builder.policy.unwrap_or_else(E::new);
+            let bounded = builder.capacity != CapacityLimit::Unbounded;
+            let shadows = (builder.shadow && bounded).then(|| Shadows::new(&policy, builder.capacity.max_entries()));
             Simcache {
-                store: HashMap::with_capacity(capacity),
-                eviction_policy: E::new(),
-                max_capacity,
+                store: HashMap::with_hasher(builder.hasher),
+                clock: builder.clock,
+                policies: PolicyLevels::new(policy, builder.capacity.max_entries()),
+                capacity: builder.capacity,
+                max_weight: builder.max_weight,
+                weigher: builder.weigher,
+                max_bytes: builder.max_bytes,
+                sizer: builder.sizer,
+                default_ttl: builder.default_ttl,
+                time_to_idle: builder.time_to_idle,
+                refresh_after: builder.refresh_after,
+                negative_ttl: builder.negative_ttl,
+                ttl_jitter: builder.ttl_jitter,
+                watermarks: builder.watermarks,
+                rng: Rng::from_entropy(),
+                listener: builder.listener,
+                #[cfg(feature = "tracing")]
+                tracer: builder.tracer,
+                #[cfg(feature = "tracing")]
+                expired_lookup: None,
+                total_weight: 0,
+                total_bytes: 0,
+                pinned: 0,
+                negatives: HashMap::new(),
+                rejected: None,
+                spilled: None,
+                recorded: None,
+                tags: TagIndex::new(),
+                ordered: builder.ordered,
+                expiry: ExpiryIndex::new(),
+                subscribers: Vec::new(),
+                doorkeeper: builder.doorkeeper.then(|| Doorkeeper::new(builder.capacity.max_entries())),
+                shadows,
+                pending: Vec::new(),
+                buffered_reads: 0,
+                access_buffer: builder.access_buffer,
+                transient: false,
+                stats: Stats::default(),
+                lifetimes: LifetimeStats::default(),
             }
         }
 
         /// insert a key value pair into the cache
-        /// option to include a ttl for the item
-        pub fn insert(&mut self, key: K, value: V, ttl: Option<Duration>) {
-            if self.store.len() > self.max_capacity - 1 && self.get(&key).is_none() {
-                println!("Evicting");
-                let key_to_evict = self.eviction_policy.evict_next();
-                self.remove(&key_to_evict);
+        /// option to include a ttl for the item, otherwise the cache's default ttl applies
+        /// either way the ttl is shortened by the configured jitter
+        /// entries are evicted until the entry count, the total weight, and the memory estimate all fit;
+        /// an entry heavier than max_weight or larger than max_bytes on its own is kept once everything else is gone,
+        /// while a cache with a capacity of 0 keeps nothing
+        /// a full cache first asks the policy whether to admit the key over the one it would evict
+        pub fn insert(&mut self, key: K, value: V, ttl: Option<Duration>) -> InsertOutcome {
+            let ttl = ttl.or(self.default_ttl).map(|ttl| self.jitter(ttl));
+            self.insert_exact(key, value, ttl)
+        }
+
+        /// insert a key value pair with exactly the given ttl, or none at all,
+        /// ignoring the cache's default ttl and jitter
+        pub fn insert_exact(&mut self, key: K, value: V, ttl: Option<Duration>) -> InsertOutcome {
+            self.try_store(key, value, ttl, None).unwrap_or(InsertOutcome::Rejected)
+        }
+
+        /// insert a key value pair that expires at the given wall clock time, such as one another
+        /// process got from expires_at, ignoring the cache's default ttl and jitter;
+        /// the time is turned into a ttl from now, so later jumps of the wall clock don't move it
+        pub fn insert_until(&mut self, key: K, value: V, expires_at: SystemTime) -> InsertOutcome {
+            match expires_at.duration_since(self.clock.wall_time()) {
+                Ok(ttl) => self.insert_exact(key, value, Some(ttl)),
+                Err(_) => InsertOutcome::Rejected,
             }
-            match ttl {
-                Some(x) => {self.store.insert(key.clone(), (value, Some(Instant::now() + x)));},
-                None => {self.store.insert(key.clone(), (value, None));}
+        }
+
+        /// insert like insert, returning the replaced value, the evicted entries, and the value itself if it
+        /// was rejected instead of dropping them, e.g. to write evicted values back to a slower store
+        /// the listener is still told of each removal
+        pub fn insert_full(&mut self, key: K, value: V, ttl: Option<Duration>) -> InsertResult<K, V> {
+            let ttl = ttl.or(self.default_ttl).map(|ttl| self.jitter(ttl));
+            let collecting = self.spilled.is_some();
+            let before = self.spilled.get_or_insert_with(Vec::new).len();
+            let result = self.store_full(key, value, ttl, None);
+            let spilled = if collecting {
+                self.spilled.as_mut().map(|spilled| spilled.split_off(before)).unwrap_or_default()
+            } else {
+                self.spilled.take().unwrap_or_default()
+            };
+            let evicted = spilled.into_iter().map(|(key, value, _)| (key, value)).collect();
+            match result {
+                Ok((outcome, replaced)) => InsertResult { outcome, replaced, evicted, rejected: None },
+                Err((value, replaced)) => InsertResult { outcome: InsertOutcome::Rejected, replaced, evicted, rejected: Some(value) },
             }
-            self.eviction_policy.key_used(&key);
         }
 
-        /// return the value of the given key from the cache if it is not expired
-        /// or None if it does not exist in the cache or has expired
-        pub fn get(&mut self, key: &K) -> Option<&V> {
-            // self.store.get() is an immutable borrow
-            // therefore, the mutable borrow self.store.remove(key) cannot be called using it
-            // so the expiration check and the removal are performed in 2 steps
-            let expired = if let Some((_, exp)) = self.store.get(key) {
-                if let Some(expiry_time) = exp {
-                    if Instant::now() > *expiry_time {
-                        true
-                    } else {
-                        false
+        /// insert like insert, but return an error saying why the value wasn't kept instead of InsertOutcome::Rejected
+        /// the rejected value is dropped; insert_full hands it back
+        pub fn try_insert(&mut self, key: K, value: V, ttl: Option<Duration>) -> Result<InsertOutcome, SimcacheError> {
+            if self.capacity.max_entries() == 0 {
+                return Err(SimcacheError::ZeroCapacity);
+            }
+            match self.insert(key, value, ttl) {
+                InsertOutcome::Rejected => Err(SimcacheError::Rejected),
+                outcome => Ok(outcome),
+            }
+        }
+
+        /// insert like insert, with the given priority instead of Normal
+        /// entries of a lower priority are all evicted before any of a higher one, so a key
+        /// is rejected if making room for it would mean evicting something of a higher priority
+        pub fn insert_with_priority(&mut self, key: K, value: V, ttl: Option<Duration>, priority: Priority) -> InsertOutcome {
+            let ttl = ttl.or(self.default_ttl).map(|ttl| self.jitter(ttl));
+            self.try_store(key, value, ttl, Some(priority)).unwrap_or(InsertOutcome::Rejected)
+        }
+
+        /// insert like insert, telling the policy the key is part of a one-time scan such as a batch job's
+        /// the entry is among the first to be evicted unless it is used again, so a long scan mostly
+        /// evicts its own entries instead of the working set; overwriting a live key is a normal insert
+        pub fn insert_transient(&mut self, key: K, value: V, ttl: Option<Duration>) -> InsertOutcome {
+            self.scan_mode().insert(key, value, ttl)
+        }
+
+        /// return a guard through which every insert is like insert_transient, until it is dropped
+        /// for handing the cache to code that inserts through the usual methods during a scan
+        pub fn scan_mode(&mut self) -> ScanMode<'_, K, V, E, S, C> {
+            let was_scanning = std::mem::replace(&mut self.transient, true);
+            ScanMode { cache: self, was_scanning }
+        }
+
+        /// store an entry, evicting as needed, or hand the value back if it isn't admitted
+        /// a replaced entry keeps its pin, and its priority unless a new one is given
+        fn try_store(&mut self, key: K, value: V, ttl: Option<Duration>, priority: Option<Priority>) -> Result<InsertOutcome, V> {
+            self.store_full(key, value, ttl, priority).map(|(outcome, _)| outcome).map_err(|(value, _)| value)
+        }
+
+        /// like try_store, also handing back the value that was replaced, which is gone even if
+        /// the new value is then rejected for want of room
+        fn store_full(&mut self, key: K, value: V, ttl: Option<Duration>, priority: Option<Priority>) -> Result<(InsertOutcome, Option<V>), (V, Option<V>)> {
+            #[cfg(feature = "tracing")]
+            let key_hash = self.tracer.is_some().then(|| stable_hash(&key));
+            // the policy catches up on buffered hits before it picks a victim
+            self.apply_pending();
+            let subscribed_key = (!self.subscribers.is_empty()).then(|| key.clone());
+            let result = self.admit_and_store(key, value, ttl, priority);
+            if let (Some(key), Ok(_)) = (subscribed_key, &result) {
+                self.publish(Some(key), EventCause::Insert);
+            }
+            #[cfg(feature = "tracing")]
+            if let (Some(tracer), Some(key_hash)) = (&self.tracer, key_hash) {
+                let outcome = result.as_ref().map_or(InsertOutcome::Rejected, |(outcome, _)| *outcome);
+                tracer(&TraceEvent::Insert { key_hash, outcome });
+            }
+            result
+        }
+
+        fn admit_and_store(&mut self, key: K, value: V, ttl: Option<Duration>, priority: Option<Priority>) -> Result<(InsertOutcome, Option<V>), (V, Option<V>)> {
+            if self.capacity.max_entries() == 0 {
+                return Err((value, None));
+            }
+            let weight = self.weigh(&key, &value);
+            let bytes = self.measure(&key, &value);
+            // the previous value is dropped first so that it isn't counted against the limits
+            let (outcome, pinned, previous_priority, replaced) = if self.contains_live(&key) {
+                let (_, previous) = self.take_entry(&key, RemovalCause::Replaced).expect("live entry should be in the store");
+                (InsertOutcome::Replaced, previous.pinned, previous.priority, Some(previous.value))
+            } else {
+                (InsertOutcome::Inserted, false, Priority::Normal, None)
+            };
+            let priority = priority.unwrap_or(previous_priority);
+            // take_entry only untracks the old level, and the key moving off it must not stay in its policy
+            if outcome == InsertOutcome::Replaced && !pinned && priority != previous_priority {
+                self.policies.get_mut(previous_priority).on_remove(&key);
+            }
+            // only a new key is put to the policy's admission check, and only once
+            let mut admitted = outcome == InsertOutcome::Replaced;
+            // a new key past the high watermark starts a batch that evicts down to the low one
+            let batch = match self.watermark_entries() {
+                Some((high, low)) if outcome == InsertOutcome::Inserted && self.store.len() >= high => Some(low),
+                _ => None,
+            };
+            // the doorkeeper only stands between a new key and a full cache
+            let full = self.doorkeeper.is_some() && outcome == InsertOutcome::Inserted && (batch.is_some() || self.needs_room(weight, bytes));
+            if let (Some(doorkeeper), true) = (&mut self.doorkeeper, full) {
+                if !doorkeeper.check_and_record(&key) {
+                    return Err((value, None));
+                }
+            }
+            loop {
+                let batching = self.store.len() > self.pinned && batch.is_some_and(|low| self.store.len() >= low);
+                let needs_room = self.needs_room(weight, bytes);
+                if !batching && !needs_room {
+                    break;
+                }
+                let level = self.policies.lowest().expect("an unpinned entry should be tracked by a level");
+                if level > priority {
+                    // a batch never evicts a higher priority, but a full cache still rejects the key
+                    if needs_room {
+                        return Err((value, replaced));
+                    }
+                    break;
+                }
+                let policy = self.policies.get_mut(level);
+                if !admitted && level == priority {
+                    admitted = true;
+                    let victim = policy.next_victim().filter(|victim| self.store.contains_key(*victim)).cloned();
+                    if let Some(victim) = victim {
+                        if !policy.admit(&key, &victim) {
+                            return Err((value, replaced));
+                        }
                     }
-                } else {
-                    false
                 }
+                self.evict_from(level);
+            }
+            let entry = CacheEntry { pinned, priority, ..self.new_entry(value, ttl, weight, bytes) };
+            // a scan overwriting a key keeps it where it was rather than marking it for eviction
+            let cold = self.transient && outcome == InsertOutcome::Inserted;
+            self.store_entry(key, entry, cold);
+            Ok((outcome, replaced))
+        }
+
+        /// ask the given level's policy for a victim and evict it, returning false if the policy
+        /// gave up a key that no longer has a current entry at that level
+        fn evict_from(&mut self, level: Priority) -> bool {
+            let key_to_evict = self.policies.get_mut(level).evict_next();
+            // the level's policy may still hold keys whose entries have since expired
+            let current = self.store.get(&key_to_evict).is_some_and(|entry| entry.priority == level && !entry.pinned);
+            if !current {
+                return false;
+            }
+            if let Some((key, entry)) = self.take_entry(&key_to_evict, RemovalCause::Evicted) {
+                self.stats.evictions += 1;
+                self.spill(key, entry);
+            }
+            true
+        }
+
+        /// return an unpinned, normal priority entry whose ttl and idle timer start now
+        fn new_entry(&self, value: V, ttl: Option<Duration>, weight: u64, bytes: u64) -> CacheEntry<V> {
+            let now = self.clock.now();
+            // a time too far off for an Instant to hold is never reached
+            let expires_at = ttl.and_then(|x| now.checked_add(x));
+            let idle_expires_at = self.time_to_idle.and_then(|x| now.checked_add(x));
+            let refresh_at = self.refresh_after.and_then(|x| now.checked_add(x));
+            CacheEntry {
+                value,
+                inserted_at: now,
+                last_accessed: None,
+                accesses: 0,
+                expires_at,
+                ttl,
+                idle_expires_at,
+                refresh_at,
+                weight,
+                bytes,
+                pinned: false,
+                priority: Priority::Normal,
+                pending_reads: 0,
+                last_read: 0,
+            }
+        }
+
+        /// add an entry that there is room for to the store and its level's policy
+        fn store_entry(&mut self, key: K, entry: CacheEntry<V>, cold: bool) {
+            let (weight, bytes, pinned, priority, deadline) = (entry.weight, entry.bytes, entry.pinned, entry.priority, entry.deadline());
+            if !self.negatives.is_empty() {
+                self.negatives.remove(&key);
+            }
+            if let Some(shadows) = &mut self.shadows {
+                shadows.insert(&key);
+            }
+            if let Some(ordered) = &mut self.ordered {
+                ordered.insert(&key);
+            }
+            if let Some(deadline) = deadline {
+                self.index_deadline(&key, deadline);
+            }
+            self.store.insert(key.clone(), entry);
+            self.total_weight += weight;
+            self.total_bytes += bytes;
+            if pinned {
+                self.pinned += 1;
             } else {
+                let policy = self.policies.get_mut(priority);
+                if cold {
+                    policy.on_insert_cold(&key, weight);
+                } else {
+                    policy.on_insert(&key, weight);
+                }
+                policy.on_deadline(&key, deadline);
+                self.policies.track(priority);
+            }
+            self.stats.inserts += 1;
+        }
+
+        /// bulk load entries into the free room of the cache, returning how many were stored
+        /// nothing is evicted to make room and live entries are not overwritten,
+        /// so warming never churns through what is already cached
+        /// ttls fall back to the default ttl and jitter as with insert
+        pub fn warm<I>(&mut self, entries: I) -> usize
+        where
+            I: IntoIterator<Item = (K, V, Option<Duration>)>,
+        {
+            self.warm_with(entries, false)
+        }
+
+        /// like warm, but the policy is told the entries are cold,
+        /// so that the ones which go unused are the first to be evicted
+        pub fn warm_cold<I>(&mut self, entries: I) -> usize
+        where
+            I: IntoIterator<Item = (K, V, Option<Duration>)>,
+        {
+            self.warm_with(entries, true)
+        }
+
+        fn warm_with<I>(&mut self, entries: I, cold: bool) -> usize
+        where
+            I: IntoIterator<Item = (K, V, Option<Duration>)>,
+        {
+            let entries = entries.into_iter();
+            let room = self.capacity.max_entries().saturating_sub(self.store.len());
+            self.store.reserve(entries.size_hint().0.min(room));
+            let mut warmed = 0;
+            for (key, value, ttl) in entries {
+                if self.capacity.is_full(self.store.len()) {
+                    break;
+                }
+                let weight = self.weigh(&key, &value);
+                let bytes = self.measure(&key, &value);
+                let over = self.total_weight.saturating_add(weight) > self.max_weight
+                    || self.total_bytes.saturating_add(bytes) > self.max_bytes;
+                if over || self.contains_live(&key) {
+                    continue;
+                }
+                let ttl = ttl.or(self.default_ttl).map(|ttl| self.jitter(ttl));
+                let entry = self.new_entry(value, ttl, weight, bytes);
+                self.store_entry(key, entry, cold);
+                warmed += 1;
+            }
+            warmed
+        }
+
+        /// return the high and low watermarks as entry counts, if batch eviction is on
+        fn watermark_entries(&self) -> Option<(usize, usize)> {
+            let (high, low) = self.watermarks?;
+            let CapacityLimit::Bounded(limit) = self.capacity else {
                 return None;
             };
+            let percent_of = |percent: u8| (limit as u128 * percent as u128 / 100) as usize;
+            Some((percent_of(high), percent_of(low)))
+        }
 
-            if expired {
-                self.store.remove(key);
+        /// return true if an entry of the given weight and size doesn't fit without evicting
+        /// and there is an unpinned entry that could be evicted; once every entry is pinned
+        /// the cache grows past its limits instead
+        fn needs_room(&self, weight: u64, bytes: u64) -> bool {
+            self.store.len() > self.pinned
+                && (self.capacity.is_full(self.store.len())
+                    || self.total_weight.saturating_add(weight) > self.max_weight
+                    || self.total_bytes.saturating_add(bytes) > self.max_bytes)
+        }
+
+        /// change the entry limit, evicting with the eviction policy until the cache fits it
+        /// return the number of entries evicted; pinned entries are kept even if that leaves the cache over the limit
+        pub fn set_capacity_limit(&mut self, capacity: CapacityLimit) -> usize {
+            self.capacity = capacity;
+            self.policies.set_capacity(capacity.max_entries());
+            if let Some(shadows) = &mut self.shadows {
+                shadows.set_capacity(capacity.max_entries());
+            }
+            self.shrink_to_limits()
+        }
+
+        /// like set_capacity_limit, with a limit of max_capacity entries
+        pub fn set_max_capacity(&mut self, max_capacity: usize) -> usize {
+            self.set_capacity_limit(CapacityLimit::Bounded(max_capacity))
+        }
+
+        /// change the limit on the combined weight of the entries, evicting until the cache fits it
+        /// return the number of entries evicted
+        pub fn set_max_weight(&mut self, max_weight: u64) -> usize {
+            self.max_weight = max_weight;
+            self.shrink_to_limits()
+        }
+
+        /// change the limit on the estimated memory of the entries, evicting until the cache fits it
+        /// return the number of entries evicted; has no effect unless the cache tracks memory
+        pub fn set_max_bytes(&mut self, max_bytes: u64) -> usize {
+            self.max_bytes = max_bytes;
+            self.shrink_to_limits()
+        }
+
+        // evict, lowest priority first, until no limit is exceeded or only pinned entries are left
+        fn shrink_to_limits(&mut self) -> usize {
+            // the policy catches up on buffered hits before it picks a victim
+            self.apply_pending();
+            let mut evicted = 0;
+            while self.store.len() > self.pinned
+                && (self.store.len() > self.capacity.max_entries()
+                    || self.total_weight > self.max_weight
+                    || self.total_bytes > self.max_bytes)
+            {
+                let level = self.policies.lowest().expect("an unpinned entry should be tracked by a level");
+                if self.evict_from(level) {
+                    evicted += 1;
+                }
+            }
+            evicted
+        }
+
+        /// insert like insert, attaching the given tags to the entry so that invalidate_tag can drop it
+        /// the tags stay with the entry until it is removed or replaced, including by a plain insert
+        pub fn insert_with_tags<T>(&mut self, key: K, value: V, ttl: Option<Duration>, tags: &[T]) -> InsertOutcome
+        where
+            T: Hash + Eq + Clone + Send + Sync + 'static,
+        {
+            let outcome = self.insert(key.clone(), value, ttl);
+            if outcome != InsertOutcome::Rejected {
+                self.tags.tag(&key, tags.iter().map(|tag| (stable_hash(tag), BoxedTag(Box::new(tag.clone())))).collect());
+            }
+            outcome
+        }
+
+        /// remove every entry carrying a tag equal to the given one, returning how many were removed
+        /// the tag should be of the same type the entries were tagged with, e.g. &str for tags given as &str
+        pub fn invalidate_tag<T: Hash + Eq + 'static>(&mut self, tag: &T) -> usize {
+            let keys = self.tags.keys(stable_hash(tag), tag);
+            keys.iter().filter(|key| self.forget(key, RemovalCause::Removed).is_some()).count()
+        }
+
+        /// remove every entry whose key is in the range, returning how many were removed
+        /// without an ordered index every key in the cache is checked
+        pub fn invalidate_range<R: RangeBounds<K>>(&mut self, range: R) -> usize
+        where
+            K: Ord,
+        {
+            let keys: Vec<K> = match &self.ordered {
+                Some(ordered) => ordered.keys().range(range).cloned().collect(),
+                None => self.store.keys().filter(|key| range.contains(key)).cloned().collect(),
+            };
+            keys.iter().filter(|key| self.forget(key, RemovalCause::Removed).is_some()).count()
+        }
+
+        /// remove every entry whose key starts with the prefix, returning how many were removed
+        /// without an ordered index every key in the cache is checked
+        pub fn invalidate_prefix(&mut self, prefix: &str) -> usize
+        where
+            K: Ord + Borrow<str>,
+        {
+            let starts_with = |key: &&K| Borrow::<str>::borrow(*key).starts_with(prefix);
+            let keys: Vec<K> = match &self.ordered {
+                // keys with the prefix sort together, starting at the prefix itself
+                Some(ordered) => ordered
+                    .keys()
+                    .range::<str, _>((Bound::Included(prefix), Bound::Unbounded))
+                    .take_while(starts_with)
+                    .cloned()
+                    .collect(),
+                None => self.store.keys().filter(starts_with).cloned().collect(),
+            };
+            keys.iter().filter(|key| self.forget(key, RemovalCause::Removed).is_some()).count()
+        }
+
+        /// insert like insert, also telling the eviction policy what the value costs to fetch again,
+        /// e.g. in microseconds of latency; policies that don't weigh costs ignore it
+        pub fn insert_with_cost(&mut self, key: K, value: V, ttl: Option<Duration>, cost: u64) -> InsertOutcome {
+            let outcome = self.insert(key.clone(), value, ttl);
+            if let Some((stored_key, entry)) = self.store.get_key_value(&key).filter(|(_, entry)| !entry.pinned) {
+                self.policies.get_mut(entry.priority).on_cost(stored_key, cost);
+            }
+            outcome
+        }
+
+        /// insert like insert, returning the value mutably even if the cache had no room to keep it
+        pub(crate) fn insert_and_get(&mut self, key: K, value: V, ttl: Option<Duration>) -> &mut V {
+            let ttl = ttl.or(self.default_ttl).map(|ttl| self.jitter(ttl));
+            match self.try_store(key.clone(), value, ttl, None) {
+                Ok(_) => self.value_mut(&key).expect("cache should have a value for a just inserted key"),
+                Err(value) => self.rejected.insert(value),
+            }
+        }
+
+        /// insert like insert, handing the value back if the cache has no room for it
+        pub(crate) fn insert_or_return(&mut self, key: K, value: V, ttl: Option<Duration>) -> Result<InsertOutcome, V> {
+            let ttl = ttl.or(self.default_ttl).map(|ttl| self.jitter(ttl));
+            self.try_store(key, value, ttl, None)
+        }
+
+        /// insert like insert_exact, handing the value back if the cache has no room for it
+        #[cfg(feature = "tiered")]
+        pub(crate) fn insert_exact_or_return(&mut self, key: K, value: V, ttl: Option<Duration>) -> Result<InsertOutcome, V> {
+            self.try_store(key, value, ttl, None)
+        }
+
+        /// keep evicted and expired entries for take_spilled instead of dropping them
+        pub(crate) fn collect_spilled(&mut self) {
+            self.spilled.get_or_insert_with(Vec::new);
+        }
+
+        /// return the entries evicted or expired since the last call, with the time each one expires at
+        pub(crate) fn take_spilled(&mut self) -> Vec<(K, V, Option<Instant>)> {
+            self.spilled.as_mut().map(std::mem::take).unwrap_or_default()
+        }
+
+        /// return the current time according to the cache's clock
+        #[cfg(feature = "tiered")]
+        pub(crate) fn now(&self) -> Instant {
+            self.clock.now()
+        }
+
+        /// return the value of the given key from the cache if it is not expired
+        /// or None if it does not exist in the cache or has expired
+        /// the key may be any borrowed form of the cache's key type
+        pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
+        where
+            K: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+        {
+            if !self.contains_live(key) {
+                self.stats.misses += 1;
+                self.observe(key, false, None);
                 return None;
             }
 
-            self.eviction_policy.key_used(&key);
-            return self.store.get(key).map(|(val, _)| val)
+            self.stats.hits += 1;
+            self.observe(key, true, None);
+            self.mark_read(key);
+            self.store.get(key).map(|entry| &entry.value)
         }
 
-        /// remove the key value pair with the given key from the cache
-        pub fn remove(&mut self, key: &K) -> Option<V> {
-            // self.eviction_policy.remove_key(key);
-            self.store.remove(key).map(|(value, _)| value)
+        /// return the value of each key, like get, in the order the keys were given
+        /// every hit is recorded with the eviction policy before any value is borrowed
+        pub fn get_many(&mut self, keys: &[K]) -> Vec<Option<&V>> {
+            for key in keys {
+                if self.contains_live(key) {
+                    self.stats.hits += 1;
+                    self.observe(key, true, Some(key));
+                    self.mark_read(key);
+                } else {
+                    self.stats.misses += 1;
+                    self.observe(key, false, Some(key));
+                }
+            }
+            keys.iter().map(|key| self.store.get(key).map(|entry| &entry.value)).collect()
         }
 
-        /// return the current size of the cache
-        pub fn len(&self) -> usize {
-            return self.store.len()
+        /// insert each key value pair, like insert, returning what happened to each one
+        pub fn insert_many<I>(&mut self, entries: I) -> Vec<InsertOutcome>
+        where
+            I: IntoIterator<Item = (K, V, Option<Duration>)>,
+        {
+            let entries = entries.into_iter();
+            let (lower, _) = entries.size_hint();
+            let room = self.capacity.max_entries().saturating_sub(self.store.len());
+            self.store.reserve(lower.min(room));
+            entries.map(|(key, value, ttl)| self.insert(key, value, ttl)).collect()
         }
-    }
 
+        /// remove each key, like remove, returning the values of the ones that were present
+        pub fn remove_many(&mut self, keys: &[K]) -> Vec<Option<V>> {
+            keys.iter().map(|key| self.remove(key)).collect()
+        }
 
-    #[cfg(test)]
-    mod common {
-        use super::*;
-        use super::super::eviction::*;
+        /// return a mutable reference to the value of the given key if it is not expired
+        /// counts as a single use; the entry's weight is not recalculated after the change
+        pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+        where
+            K: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+        {
+            if !self.contains_live(key) {
+                self.stats.misses += 1;
+                self.observe(key, false, None);
+                return None;
+            }
 
-        #[test]
-        fn test_cache_lru() {
-            let mut cache: Simcache::<&'static str, &'static str, LRU<&'static str>> = Simcache::new(3);
+            self.stats.hits += 1;
+            self.observe(key, true, None);
+            self.mark_used(key);
+            self.store.get_mut(key).map(|entry| &mut entry.value)
+        }
 
-            cache.insert("a", "1", None);
-            cache.insert("b", "2", None);
-            cache.insert("c", "3", None);
+        /// change the value of the given key in place if it is not expired, returning true if it was
+        /// counts as a single use, like get_mut
+        pub fn update<Q, F>(&mut self, key: &Q, f: F) -> bool
+        where
+            K: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+            F: FnOnce(&mut V),
+        {
+            match self.get_mut(key) {
+                Some(value) => {
+                    f(value);
+                    true
+                }
+                None => false,
+            }
+        }
 
-            assert_eq!(cache.len(), 3);
+        /// change the value of the given key in place, or insert the given value with the default ttl
+        /// if there is no live entry; either way the eviction policy sees a single use or insert
+        pub fn upsert<F>(&mut self, key: K, insert: V, update: F)
+        where
+            F: FnOnce(&mut V),
+        {
+            if !self.update(&key, update) {
+                self.insert(key, insert, None);
+            }
+        }
 
-            cache.insert("d", "4", None);
+        /// insert the key value pair only if the key has no live entry
+        /// return the live value that was kept instead, or None if the pair was inserted
+        /// or rejected; a kept value counts as a use
+        pub fn insert_if_absent(&mut self, key: K, value: V, ttl: Option<Duration>) -> Option<&V> {
+            if self.contains_live(&key) {
+                self.stats.hits += 1;
+                self.observe(&key, true, Some(&key));
+                self.mark_used(&key);
+                return self.store.get(&key).map(|entry| &entry.value);
+            }
+            self.insert(key, value, ttl);
+            None
+        }
 
-            assert_eq!(cache.get(&"a"), None);
+        /// replace the live value of the given key only if the predicate accepts it,
+        /// returning true if it was replaced
+        /// the new value is written like an insert with the ttl the entry was first given
+        pub fn replace_if<F>(&mut self, key: K, expected: F, value: V) -> bool
+        where
+            F: FnOnce(&V) -> bool,
+        {
+            let Some(entry) = self.live_entry(&key).filter(|entry| expected(&entry.value)) else {
+                return false;
+            };
+            let ttl = entry.ttl;
+            self.insert_exact(key, value, ttl) == InsertOutcome::Replaced
+        }
 
-            assert_eq!(*(cache.get(&"b").expect("cache should have a value for key b")), "2");
+        /// return the entry for the given key for in-place manipulation
+        /// a live entry counts as one use, a vacant one as a miss
+        pub fn entry(&mut self, key: K) -> Entry<'_, K, V, E, S, C> {
+            if self.contains_live(&key) {
+                self.stats.hits += 1;
+                self.observe(&key, true, Some(&key));
+                self.mark_used(&key);
+                Entry::Occupied(OccupiedEntry { cache: self, key })
+            } else {
+                self.stats.misses += 1;
+                self.observe(&key, false, Some(&key));
+                Entry::Vacant(VacantEntry { cache: self, key, ttl: None })
+            }
+        }
 
-            cache.insert("e","5", None);
+        /// return the stored value mutably without any expiry check or bookkeeping
+        pub(crate) fn value_mut(&mut self, key: &K) -> Option<&mut V> {
+            self.store.get_mut(key).map(|entry| &mut entry.value)
+        }
 
-            cache.remove(&"b");
+        /// return the value of the given key if it is not expired, without counting as a use
+        /// neither the eviction policy nor the stats are updated
+        pub fn peek<Q>(&self, key: &Q) -> Option<&V>
+        where
+            K: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+        {
+            self.live_entry(key).map(|entry| &entry.value)
+        }
 
-            assert_eq!(cache.len(), 2);
+        /// return the value of the given key even if it has expired, as long as it hasn't been removed yet
+        pub(crate) fn peek_stale<Q>(&self, key: &Q) -> Option<&V>
+        where
+            K: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+        {
+            self.store.get(key).map(|entry| &entry.value)
         }
 
-        #[test]
-        fn test_cache_lfu() {
-            let mut cache: Simcache::<&'static str, &'static str, LFU<&'static str>> = Simcache::new(3);
+        /// return up to n keys in the order they would be evicted, lowest priority first, without changing any state
+        /// pinned entries are never candidates, and policies that can't predict their order contribute none
+        pub fn peek_eviction_candidates(&self, n: usize) -> Vec<&K> {
+            let mut candidates = Vec::with_capacity(n.min(self.store.len()));
+            for (level, policy) in Priority::ALL.into_iter().filter_map(|level| Some((level, self.policies.get(level)?))) {
+                // a policy may still hold keys whose entries have since expired, so ask for more until enough are current
+                let mut asked = n - candidates.len();
+                loop {
+                    let keys = policy.eviction_candidates(asked);
+                    let exhausted = keys.len() < asked;
+                    let current: Vec<&K> = keys
+                        .into_iter()
+                        .filter(|key| self.store.get(*key).is_some_and(|entry| entry.priority == level && !entry.pinned))
+                        .collect();
+                    if exhausted || candidates.len() + current.len() >= n {
+                        candidates.extend(current.into_iter().take(n - candidates.len()));
+                        break;
+                    }
+                    asked *= 2;
+                }
+                if candidates.len() == n {
+                    break;
+                }
+            }
+            candidates
+        }
 
-            cache.insert("a", "1", None);
-            cache.insert("b", "2", None);
-            cache.insert("c", "3", None);
-            cache.get(&"a");
-            cache.get(&"c");
+        /// return the metadata of the given key's entry if it is not expired, without counting as a use
+        pub fn entry_info<Q>(&self, key: &Q) -> Option<EntryInfo>
+        where
+            K: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+        {
+            let now = self.clock.now();
+            let (stored_key, entry) = self.store.get_key_value(key).filter(|(_, entry)| !entry.is_expired(now))?;
+            let policy_score = match entry.pinned {
+                true => None,
+                false => self.policies.get(entry.priority).and_then(|policy| policy.score(stored_key)),
+            };
+            Some(EntryInfo {
+                inserted_at: entry.inserted_at,
+                last_accessed: entry.last_accessed,
+                accesses: entry.accesses,
+                ttl_remaining: entry.deadline().map(|deadline| deadline.saturating_duration_since(now)),
+                weight: entry.weight,
+                priority: entry.priority,
+                pinned: entry.pinned,
+                policy_score,
+            })
+        }
 
-            assert_eq!(cache.len(), 3);
+        /// return true if the key has an unexpired entry, without counting as a use
+        pub fn contains_key<Q>(&self, key: &Q) -> bool
+        where
+            K: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+        {
+            self.live_entry(key).is_some()
+        }
 
-            cache.insert("d", "4", None);
+        /// give a live entry a new ttl starting now, or no expiry at all if ttl is None
+        /// return false if there was no live entry to update
+        pub fn set_ttl<Q>(&mut self, key: &Q, ttl: Option<Duration>) -> bool
+        where
+            K: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+        {
+            if !self.contains_live(key) {
+                return false;
+            }
+            let entry = self.store.get_mut(key).expect("live entry should be in the store");
+            entry.ttl = ttl;
+            entry.expires_at = ttl.and_then(|x| self.clock.now().checked_add(x));
+            self.report_deadline(key);
+            true
+        }
 
-            assert_eq!(cache.get(&"b"), None);
+        /// return how long a live entry has left before it expires
+        /// or None if there is no live entry or it never expires
+        pub fn ttl_remaining<Q>(&self, key: &Q) -> Option<Duration>
+        where
+            K: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+        {
+            let expiry_time = self.live_entry(key)?.deadline()?;
+            Some(expiry_time.saturating_duration_since(self.clock.now()))
+        }
 
-            assert_eq!(*(cache.get(&"a").expect("cache should have a value for key a")), "1");
+        /// return the wall clock time a live entry expires at, which unlike an Instant
+        /// can be saved or sent to another process and passed to insert_until there
+        /// or None if there is no live entry or it never expires
+        pub fn expires_at<Q>(&self, key: &Q) -> Option<SystemTime>
+        where
+            K: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+        {
+            let expiry_time = self.live_entry(key)?.deadline()?;
+            Some(self.clock.wall_time() + expiry_time.saturating_duration_since(self.clock.now()))
+        }
 
-            cache.insert("e","5", None);
+        /// restart a live entry's ttl from now without replacing its value, counting as a use
+        /// return false if there was no live entry to touch
+        pub fn touch<Q>(&mut self, key: &Q) -> bool
+        where
+            K: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+        {
+            if !self.contains_live(key) {
+                return false;
+            }
+            self.mark_used(key);
+            let entry = self.store.get_mut(key).expect("live entry should be in the store");
+            entry.expires_at = entry.ttl.and_then(|x| self.clock.now().checked_add(x));
+            self.report_deadline(key);
+            true
+        }
 
-            cache.remove(&"a");
+        /// exclude a live entry from eviction until it is unpinned, though it still expires
+        /// if every entry is pinned the cache grows past its limits rather than evicting one
+        /// return false if there was no live entry to pin
+        pub fn pin<Q>(&mut self, key: &Q) -> bool
+        where
+            K: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+        {
+            if !self.contains_live(key) {
+                return false;
+            }
+            let (stored_key, entry) = self.store.get_key_value(key).expect("live entry should be in the store");
+            if !entry.pinned {
+                self.policies.get_mut(entry.priority).on_remove(stored_key);
+                self.policies.untrack(entry.priority);
+                self.pinned += 1;
+                self.store.get_mut(key).expect("live entry should be in the store").pinned = true;
+            }
+            true
+        }
 
-            assert_eq!(cache.len(), 2);
+        /// make a pinned entry evictable again, as if it had just been inserted, and evict down to the limits
+        /// if the pinned entries had pushed the cache past them
+        /// return false if there was no live entry to unpin
+        pub fn unpin<Q>(&mut self, key: &Q) -> bool
+        where
+            K: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+        {
+            if !self.contains_live(key) {
+                return false;
+            }
+            let entry = self.store.get_mut(key).expect("live entry should be in the store");
+            if entry.pinned {
+                entry.pinned = false;
+                self.pinned -= 1;
+                let (stored_key, entry) = self.store.get_key_value(key).expect("live entry should be in the store");
+                let policy = self.policies.get_mut(entry.priority);
+                policy.on_insert(stored_key, entry.weight);
+                policy.on_deadline(stored_key, entry.deadline());
+                self.policies.track(entry.priority);
+                self.shrink_to_limits();
+            }
+            true
+        }
+
+        /// return the priority of a live entry
+        pub fn priority<Q>(&self, key: &Q) -> Option<Priority>
+        where
+            K: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+        {
+            self.live_entry(key).map(|entry| entry.priority)
+        }
+
+        /// return true if the key has a live, pinned entry
+        pub fn is_pinned<Q>(&self, key: &Q) -> bool
+        where
+            K: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+        {
+            self.live_entry(key).is_some_and(|entry| entry.pinned)
         }
-    }
\ No newline at end of file
+
+        /// return true if the key has a live entry older than refresh_after
+        /// the stale value is still served, and only the first caller to ask is told to reload it,
+        /// by inserting the new value; if it never does, the next caller is told after another refresh_after
+        pub fn needs_refresh<Q>(&mut self, key: &Q) -> bool
+        where
+            K: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+        {
+            let (Some(refresh_after), true) = (self.refresh_after, self.contains_live(key)) else {
+                return false;
+            };
+            let now = self.clock.now();
+            let entry = self.store.get_mut(key).expect("live entry should be in the store");
+            match entry.refresh_at {
+                Some(refresh_at) if refresh_at <= now => {
+                    entry.refresh_at = now.checked_add(refresh_after);
+                    true
+                }
+                _ => false,
+            }
+        }
+
+        /// return the value of the given key, computing and inserting it with f on a miss
+        /// the eviction policy is updated exactly once either way
+        /// with a capacity of 0 the computed value is returned without being kept
+        pub fn get_or_insert_with<F>(&mut self, key: K, ttl: Option<Duration>, f: F) -> &V
+        where
+            F: FnOnce() -> V,
+        {
+            if self.contains_live(&key) {
+                self.stats.hits += 1;
+                self.observe(&key, true, Some(&key));
+                self.mark_used(&key);
+            } else {
+                self.stats.misses += 1;
+                self.observe(&key, false, Some(&key));
+                return self.insert_and_get(key, f(), ttl);
+            }
+            self.store.get(&key).map(|entry| &entry.value).expect("live entry should be in the store")
+        }
+
+        /// like get_or_insert_with, but the loader may fail
+        /// on error nothing is inserted and the error is returned
+        pub fn get_or_try_insert_with<F, Er>(&mut self, key: K, ttl: Option<Duration>, f: F) -> Result<&V, Er>
+        where
+            F: FnOnce() -> Result<V, Er>,
+        {
+            if self.contains_live(&key) {
+                self.stats.hits += 1;
+                self.observe(&key, true, Some(&key));
+                self.mark_used(&key);
+            } else {
+                self.stats.misses += 1;
+                self.observe(&key, false, Some(&key));
+                let value = f()?;
+                return Ok(self.insert_and_get(key, value, ttl));
+            }
+            Ok(self.store.get(&key).map(|entry| &entry.value).expect("live entry should be in the store"))
+        }
+
+        /// like get_or_insert_with, but the loader may find that the key has no value
+        /// that answer is cached as a negative entry, so the loader isn't called again until it expires
+        /// negative entries expire after negative_ttl, or the ttl a value would get if that isn't set,
+        /// and are dropped by insert, remove, and clear
+        pub fn get_or_insert_optional_with<F>(&mut self, key: K, ttl: Option<Duration>, f: F) -> Lookup<'_, V>
+        where
+            F: FnOnce() -> Option<V>,
+        {
+            if self.contains_live(&key) {
+                self.stats.hits += 1;
+                self.observe(&key, true, Some(&key));
+                self.mark_used(&key);
+                return Lookup::Value(&self.store.get(&key).expect("live entry should be in the store").value);
+            }
+            if let Some(&expires_at) = self.negatives.get(&key) {
+                if expires_at.is_none_or(|expires_at| expires_at > self.clock.now()) {
+                    self.stats.hits += 1;
+                    self.observe(&key, true, Some(&key));
+                    return Lookup::Negative;
+                }
+                self.negatives.remove(&key);
+            }
+            self.stats.misses += 1;
+            self.observe(&key, false, Some(&key));
+            match f() {
+                Some(value) => Lookup::Value(self.insert_and_get(key, value, ttl)),
+                None => {
+                    self.insert_negative(key, ttl);
+                    Lookup::Missing
+                }
+            }
+        }
+
+        // cache a negative entry, making room by dropping expired ones and then the one expiring soonest
+        fn insert_negative(&mut self, key: K, ttl: Option<Duration>) {
+            let limit = self.capacity.max_entries();
+            if limit == 0 {
+                return;
+            }
+            let now = self.clock.now();
+            if self.negatives.len() >= limit {
+                self.negatives.retain(|_, expires_at| expires_at.is_none_or(|expires_at| expires_at > now));
+            }
+            if self.negatives.len() >= limit {
+                let soonest = self.negatives
+                    .iter()
+                    .min_by_key(|(_, expires_at)| (expires_at.is_none(), **expires_at))
+                    .map(|(key, _)| key.clone());
+                if let Some(soonest) = soonest {
+                    self.negatives.remove(&soonest);
+                }
+            }
+            let ttl = self.negative_ttl.or(ttl).or(self.default_ttl).map(|ttl| self.jitter(ttl));
+            self.negatives.insert(key, ttl.and_then(|ttl| now.checked_add(ttl)));
+        }
+
+        /// record a use of a live entry with the eviction policy and restart its idle timer
+        fn mark_used<Q>(&mut self, key: &Q)
+        where
+            K: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+        {
+            // the policy is always given the stored key, so borrowed lookups never allocate
+            if let Some((stored_key, entry)) = self.store.get_key_value(key) {
+                if !entry.pinned {
+                    self.policies.get_mut(entry.priority).on_access(stored_key);
+                }
+            }
+            self.restart_idle(key);
+        }
+
+        /// record a use of a live entry by a lookup, buffering it for the policy if the cache buffers accesses
+        fn mark_read<Q>(&mut self, key: &Q)
+        where
+            K: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+        {
+            if self.access_buffer == 0 {
+                return self.mark_used(key);
+            }
+            self.buffered_reads += 1;
+            let read = self.buffered_reads;
+            // the key is only copied into the buffer for its first hit
+            let first = match self.store.get_mut(key) {
+                Some(entry) if !entry.pinned => {
+                    entry.pending_reads += 1;
+                    entry.last_read = read;
+                    entry.pending_reads == 1
+                }
+                _ => false,
+            };
+            if let (true, Some((stored_key, _))) = (first, self.store.get_key_value(key)) {
+                self.pending.push(stored_key.clone());
+            }
+            self.restart_idle(key);
+            if self.buffered_reads >= self.access_buffer {
+                self.apply_pending();
+            }
+        }
+
+        /// give the policy the buffered uses of every key that is still live and unpinned
+        /// keys are replayed in the order of their last hit, so recency comes out as if every hit had been given
+        /// straight away; a key removed or overwritten since has a new entry with no hits, and gets none
+        fn apply_pending(&mut self) {
+            if self.pending.is_empty() {
+                return;
+            }
+            let mut keys = std::mem::take(&mut self.pending);
+            self.buffered_reads = 0;
+            keys.sort_by_key(|key| self.store.get(key).map_or(0, |entry| entry.last_read));
+            for key in keys {
+                let Some(entry) = self.store.get_mut(&key) else {
+                    continue;
+                };
+                let reads = std::mem::take(&mut entry.pending_reads);
+                if entry.pinned {
+                    continue;
+                }
+                let policy = self.policies.get_mut(entry.priority);
+                for _ in 0..reads {
+                    policy.on_access(&key);
+                }
+            }
+        }
+
+        /// count a use of a live entry and restart its idle timer
+        fn restart_idle<Q>(&mut self, key: &Q)
+        where
+            K: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+        {
+            let now = self.clock.now();
+            if let Some(entry) = self.store.get_mut(key) {
+                entry.last_accessed = Some(now);
+                entry.accesses += 1;
+                if let Some(time_to_idle) = self.time_to_idle {
+                    entry.idle_expires_at = now.checked_add(time_to_idle);
+                }
+            }
+            if self.time_to_idle.is_some() {
+                self.report_deadline(key);
+            }
+        }
+
+        /// tell the eviction policy and the expiry index when a live entry is now due to expire
+        fn report_deadline<Q>(&mut self, key: &Q)
+        where
+            K: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+        {
+            let Some((stored_key, entry)) = self.store.get_key_value(key) else {
+                return;
+            };
+            let (stored_key, deadline, pinned, priority) = (stored_key.clone(), entry.deadline(), entry.pinned, entry.priority);
+            if let Some(deadline) = deadline {
+                self.index_deadline(&stored_key, deadline);
+            }
+            if !pinned {
+                self.policies.get_mut(priority).on_deadline(&stored_key, deadline);
+            }
+        }
+
+        /// file the key under its new deadline, rebuilding the index from the store
+        /// once more than half of what it holds is stale, so it stays within twice the entry count
+        fn index_deadline(&mut self, key: &K, deadline: Instant) {
+            if self.expiry.len() >= 2 * self.store.len() + 16 {
+                let store = &self.store;
+                self.expiry.rebuild(store.iter().filter_map(|(key, entry)| Some((key, entry.deadline()?))));
+            }
+            self.expiry.insert(key, deadline);
+        }
+
+        /// return when the next live entry expires, by the cache's clock, or None if none do
+        /// a program that purges expired entries itself can sleep until then
+        pub fn next_expiration(&self) -> Option<Instant> {
+            let now = self.clock.now();
+            self.expiry
+                .iter()
+                .find(|(deadline, key)| {
+                    self.store.get(*key).is_some_and(|entry| entry.deadline() == Some(*deadline) && !entry.is_expired(now))
+                })
+                .map(|(deadline, _)| deadline)
+        }
+
+        fn live_entry<Q>(&self, key: &Q) -> Option<&CacheEntry<V>>
+        where
+            K: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+        {
+            let now = self.clock.now();
+            self.store.get(key).filter(|entry| !entry.is_expired(now))
+        }
+
+        /// return true if the key has an unexpired entry, removing it from the cache and the eviction policy
+        /// first if it has expired; finding a live entry does not count as a use
+        fn contains_live<Q>(&mut self, key: &Q) -> bool
+        where
+            K: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+        {
+            // self.store.get() is an immutable borrow
+            // therefore, the mutable borrow self.store.remove(key) cannot be called using it
+            // so the expiration check and the removal are performed in 2 steps
+            let expired = if let Some(entry) = self.store.get(key) {
+                entry.is_expired(self.clock.now())
+            } else {
+                return false;
+            };
+
+            if expired {
+                #[cfg(feature = "tracing")]
+                if self.tracer.is_some() {
+                    self.expired_lookup = Some(stable_hash(key));
+                }
+                if let Some((key, entry)) = self.take_entry(key, RemovalCause::Expired) {
+                    // left in the policy, the key would come back with its old position or count when reinserted
+                    if !entry.pinned {
+                        self.policies.get_mut(entry.priority).on_remove(&key);
+                    }
+                    self.spill(key, entry);
+                }
+                self.stats.expired += 1;
+                return false;
+            }
+            true
+        }
+
+        /// remove every expired entry from the cache and the eviction policy
+        /// return the number of entries removed
+        pub fn purge_expired(&mut self) -> usize {
+            let expired = self.expired_keys();
+            for key in expired.iter().cloned() {
+                if let Some(entry) = self.forget(&key, RemovalCause::Expired) {
+                    self.spill(key, entry);
+                }
+            }
+            self.stats.expired += expired.len() as u64;
+            expired.len()
+        }
+
+        /// do the upkeep a cache leaves for later: give the eviction policy the hits buffered by
+        /// buffer_accesses and remove every expired entry, returning the number removed
+        pub fn run_pending_tasks(&mut self) -> usize {
+            self.apply_pending();
+            self.purge_expired()
+        }
+
+        /// remove every expired entry from the cache and the eviction policy, returning them
+        /// instead of dropping them, e.g. to archive them; the listener is still told of each,
+        /// but they are not collected for a second tier
+        pub fn drain_expired(&mut self) -> Vec<(K, V)> {
+            let expired = self.expired_keys();
+            self.stats.expired += expired.len() as u64;
+            expired
+                .into_iter()
+                .filter_map(|key| {
+                    let entry = self.forget(&key, RemovalCause::Expired)?;
+                    Some((key, entry.value))
+                })
+                .collect()
+        }
+
+        /// take the keys of the expired entries from the expiry index, leaving out stale ones,
+        /// so purging takes time in proportion to what has expired rather than to the whole store
+        fn expired_keys(&mut self) -> Vec<K> {
+            let now = self.clock.now();
+            let mut seen = HashSet::new();
+            let mut expired = self.expiry.take_due(now);
+            expired.retain(|key| self.store.get(key).is_some_and(|entry| entry.is_expired(now)) && seen.insert(key.clone()));
+            expired
+        }
+
+        /// shorten a ttl by a random fraction of up to ttl_jitter percent
+        fn jitter(&mut self, ttl: Duration) -> Duration {
+            if self.ttl_jitter == 0 {
+                return ttl;
+            }
+            let fraction = self.rng.next_f64() * self.ttl_jitter as f64 / 100.0;
+            // scaled through f64, Duration::MAX can round up past itself
+            Duration::try_from_secs_f64(ttl.as_secs_f64() * (1.0 - fraction)).unwrap_or(ttl)
+        }
+
+        /// remove every entry, notifying the listener of each as an explicit removal
+        pub fn clear(&mut self) {
+            if let Some(listener) = &self.listener {
+                for (key, entry) in self.store.iter() {
+                    listener(key, &entry.value, RemovalCause::Removed);
+                }
+            }
+            if !self.subscribers.is_empty() {
+                let keys: Vec<K> = self.store.keys().cloned().collect();
+                for key in keys {
+                    self.publish(Some(key), EventCause::Remove);
+                }
+            }
+            self.store.clear();
+            self.negatives.clear();
+            self.tags.clear();
+            self.expiry.clear();
+            if let Some(doorkeeper) = &mut self.doorkeeper {
+                doorkeeper.clear();
+            }
+            if let Some(ordered) = &mut self.ordered {
+                ordered.clear();
+            }
+            if let Some(shadows) = &mut self.shadows {
+                shadows.clear();
+            }
+            self.total_weight = 0;
+            self.total_bytes = 0;
+            self.pinned = 0;
+            self.pending.clear();
+            self.buffered_reads = 0;
+            self.policies.clear();
+        }
+
+        /// return the cache with a new default policy of another type, keeping every entry
+        /// the new policy is given the unpinned entries in the order the old one would have evicted them,
+        /// so it sees the oldest first; costs from insert_with_cost are not carried over
+        pub fn switch_policy<E2: EvictionPolicy<K>>(self) -> Simcache<K, V, E2, S, C> {
+            self.with_policy(E2::new())
+        }
+
+        /// like switch_policy, with the given policy instance
+        /// shadow caches start over empty with the new policy
+        pub fn with_policy<E2: EvictionPolicy<K>>(self, policy: E2) -> Simcache<K, V, E2, S, C> {
+            let shadows = self.shadows.as_ref().map(|_| Shadows::new(&policy, self.capacity.max_entries()));
+            let policies = self.rebuild_policies(policy);
+            Simcache {
+                store: self.store,
+                clock: self.clock,
+                policies,
+                capacity: self.capacity,
+                max_weight: self.max_weight,
+                weigher: self.weigher,
+                max_bytes: self.max_bytes,
+                sizer: self.sizer,
+                default_ttl: self.default_ttl,
+                time_to_idle: self.time_to_idle,
+                refresh_after: self.refresh_after,
+                negative_ttl: self.negative_ttl,
+                ttl_jitter: self.ttl_jitter,
+                watermarks: self.watermarks,
+                rng: self.rng,
+                listener: self.listener,
+                #[cfg(feature = "tracing")]
+                tracer: self.tracer,
+                #[cfg(feature = "tracing")]
+                expired_lookup: self.expired_lookup,
+                total_weight: self.total_weight,
+                total_bytes: self.total_bytes,
+                pinned: self.pinned,
+                negatives: self.negatives,
+                rejected: self.rejected,
+                spilled: self.spilled,
+                recorded: self.recorded,
+                tags: self.tags,
+                ordered: self.ordered,
+                expiry: self.expiry,
+                subscribers: self.subscribers,
+                doorkeeper: self.doorkeeper,
+                shadows,
+                pending: self.pending,
+                buffered_reads: self.buffered_reads,
+                access_buffer: self.access_buffer,
+                transient: self.transient,
+                stats: self.stats,
+                lifetimes: self.lifetimes,
+            }
+        }
+
+        /// replace the eviction policy with another of the same type, keeping every entry
+        /// useful with AnyPolicy to change the kind of policy at runtime
+        pub fn set_policy(&mut self, policy: E) {
+            if self.shadows.is_some() {
+                self.shadows = Some(Shadows::new(&policy, self.capacity.max_entries()));
+            }
+            self.policies = self.rebuild_policies(policy);
+        }
+
+        // give a new set of levels every unpinned entry, in the order the current levels would evict them
+        fn rebuild_policies<E2: EvictionPolicy<K>>(&self, policy: E2) -> PolicyLevels<E2> {
+            let mut policies = PolicyLevels::new(policy, self.capacity.max_entries());
+            let mut seen = HashSet::with_capacity(self.store.len());
+            let ordered = self.policies.iter().flat_map(|policy| policy.eviction_order());
+            for key in ordered.chain(self.store.keys()) {
+                let Some(entry) = self.store.get(key) else {
+                    continue;
+                };
+                if entry.pinned || !seen.insert(key) {
+                    continue;
+                }
+                let policy = policies.get_mut(entry.priority);
+                policy.on_insert(key, entry.weight);
+                policy.on_deadline(key, entry.deadline());
+                policies.track(entry.priority);
+            }
+            policies
+        }
+
+        /// keep only the entries for which the predicate returns true,
+        /// removing the rest from both the store and the eviction policy
+        pub fn retain<F>(&mut self, mut f: F)
+        where
+            F: FnMut(&K, &mut V) -> bool,
+        {
+            let rejected: Vec<K> = self.store
+                .iter_mut()
+                .filter_map(|(key, entry)| (!f(key, &mut entry.value)).then(|| key.clone()))
+                .collect();
+            for key in &rejected {
+                self.forget(key, RemovalCause::Removed);
+            }
+        }
+
+        /// return the weight of an entry, 1 unless a weigher was configured
+        fn weigh(&self, key: &K, value: &V) -> u64 {
+            self.weigher.as_ref().map_or(1, |weigher| weigher(key, value))
+        }
+
+        /// return the memory estimate of an entry, 0 unless memory tracking is on
+        fn measure(&self, key: &K, value: &V) -> u64 {
+            self.sizer.map_or(0, |sizer| sizer(key, value))
+        }
+
+        /// remove an entry from the store, keeping the total weight in step
+        /// and notifying the listener of the cause
+        fn take_entry<Q>(&mut self, key: &Q, cause: RemovalCause) -> Option<(K, CacheEntry<V>)>
+        where
+            K: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+        {
+            let (stored_key, entry) = self.store.remove_entry(key)?;
+            if let (Some(shadows), RemovalCause::Removed) = (&mut self.shadows, cause) {
+                shadows.remove(key);
+            }
+            if !self.tags.is_empty() {
+                self.tags.untag(key);
+            }
+            if let Some(ordered) = &mut self.ordered {
+                ordered.remove(&stored_key);
+            }
+            self.total_weight -= entry.weight;
+            self.total_bytes -= entry.bytes;
+            self.record_lifetime(&entry, cause);
+            if entry.pinned {
+                self.pinned -= 1;
+            } else {
+                self.policies.untrack(entry.priority);
+            }
+            if let Some(listener) = &self.listener {
+                listener(&stored_key, &entry.value, cause);
+            }
+            let event_cause = match cause {
+                RemovalCause::Evicted => Some(EventCause::Evict),
+                RemovalCause::Expired => Some(EventCause::Expire),
+                RemovalCause::Removed => Some(EventCause::Remove),
+                // the insert that replaced the entry is its own event
+                RemovalCause::Replaced => None,
+            };
+            if let (false, Some(event_cause)) = (self.subscribers.is_empty(), event_cause) {
+                self.publish(Some(stored_key.clone()), event_cause);
+            }
+            #[cfg(feature = "tracing")]
+            if let Some(tracer) = &self.tracer {
+                tracer(&TraceEvent::Removal { key_hash: stable_hash(key), cause });
+            }
+            Some((stored_key, entry))
+        }
+
+        /// remove an entry from both the store and its level's eviction policy
+        fn forget(&mut self, key: &K, cause: RemovalCause) -> Option<CacheEntry<V>> {
+            let (_, entry) = self.take_entry(key, cause)?;
+            if !entry.pinned {
+                self.policies.get_mut(entry.priority).on_remove(key);
+            }
+            Some(entry)
+        }
+
+        /// add an entry that was evicted or expired to the lifetime stats
+        fn record_lifetime(&mut self, entry: &CacheEntry<V>, cause: RemovalCause) {
+            let now = self.clock.now();
+            let deadline = entry.deadline();
+            match cause {
+                RemovalCause::Evicted => {
+                    self.lifetimes.lifetimes.record(now.saturating_duration_since(entry.inserted_at));
+                    match deadline {
+                        Some(deadline) => self.lifetimes.ttl_left_at_eviction.record(deadline.saturating_duration_since(now)),
+                        None => self.lifetimes.evicted_without_ttl += 1,
+                    }
+                }
+                RemovalCause::Expired => {
+                    // an entry lasted until it expired, however long after that it was found
+                    let ended = deadline.map_or(now, |deadline| deadline.min(now));
+                    self.lifetimes.lifetimes.record(ended.saturating_duration_since(entry.inserted_at));
+                    self.lifetimes.expired += 1;
+                }
+                RemovalCause::Removed | RemovalCause::Replaced => {}
+            }
+        }
+
+        /// keep a removed entry for take_spilled, if it is collecting them
+        fn spill(&mut self, key: K, entry: CacheEntry<V>) {
+            if let Some(spilled) = &mut self.spilled {
+                spilled.push((key, entry.value, entry.expires_at));
+            }
+        }
+
+        /// remove the key value pair with the given key from the cache
+        pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+        where
+            K: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+        {
+            self.pop_key(key).map(|(_, value)| value)
+        }
+
+        /// remove the key value pair with the given key, returning the stored key along with the value
+        pub fn pop_key<Q>(&mut self, key: &Q) -> Option<(K, V)>
+        where
+            K: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+        {
+            self.negatives.remove(key);
+            let (stored_key, entry) = self.take_entry(key, RemovalCause::Removed)?;
+            if !entry.pinned {
+                self.policies.get_mut(entry.priority).on_remove(&stored_key);
+            }
+            Some((stored_key, entry.value))
+        }
+
+        /// evict the entry the policy would evict next and return it, e.g. to drain the cache at shutdown
+        /// expired entries met on the way are dropped rather than returned, and pinned ones are never popped
+        pub fn pop_next(&mut self) -> Option<(K, V)> {
+            self.apply_pending();
+            let now = self.clock.now();
+            while let Some(level) = self.policies.lowest() {
+                let key = self.policies.get_mut(level).evict_next();
+                // the level's policy may still hold keys whose entries have since expired
+                let current = self.store.get(&key).is_some_and(|entry| entry.priority == level && !entry.pinned);
+                if !current {
+                    continue;
+                }
+                // evict_next has already dropped the key from the policy, so only the store is left to clean up
+                if self.store.get(&key).is_some_and(|entry| entry.is_expired(now)) {
+                    self.take_entry(&key, RemovalCause::Expired);
+                    self.stats.expired += 1;
+                    continue;
+                }
+                let (key, entry) = self.take_entry(&key, RemovalCause::Evicted).expect("current entry should be in the store");
+                self.stats.evictions += 1;
+                return Some((key, entry.value));
+            }
+            None
+        }
+
+        /// evict like pop_next, returning an error instead of None when there is nothing to evict
+        pub fn try_evict(&mut self) -> Result<(K, V), SimcacheError> {
+            self.pop_next().ok_or(SimcacheError::NothingToEvict)
+        }
+
+        /// return an iterator over the live entries, in no particular order
+        pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> + '_ {
+            let now = self.clock.now();
+            self.store
+                .iter()
+                .filter(move |(_, entry)| !entry.is_expired(now))
+                .map(|(key, entry)| (key, &entry.value))
+        }
+
+        /// return an iterator over the keys of the live entries
+        pub fn keys(&self) -> impl Iterator<Item = &K> + '_ {
+            self.iter().map(|(key, _)| key)
+        }
+
+        /// return an iterator over the values of the live entries
+        pub fn values(&self) -> impl Iterator<Item = &V> + '_ {
+            self.iter().map(|(_, value)| value)
+        }
+
+        /// return an iterator over the live entries in the order the policy would evict them
+        /// yields nothing if the policy can't predict its eviction order
+        pub fn iter_eviction_order(&self) -> impl Iterator<Item = (&K, &V)> + '_ {
+            let now = self.clock.now();
+            self.policies
+                .iter()
+                .flat_map(|policy| policy.eviction_order())
+                .filter_map(move |key| {
+                    let entry = self.store.get(key).filter(|entry| !entry.is_expired(now))?;
+                    Some((key, &entry.value))
+                })
+        }
+
+        /// return the keys of the live entries in eviction order, followed by the pinned entries
+        /// and any the policy couldn't predict the order of, so that inserting them in this order
+        /// recreates the recency of the cache
+        pub(crate) fn keys_in_insert_order(&self) -> Vec<&K> {
+            let ordered: Vec<&K> = self.iter_eviction_order().map(|(key, _)| key).collect();
+            let seen: HashSet<&K> = ordered.iter().copied().collect();
+            ordered.into_iter().chain(self.keys().filter(|key| !seen.contains(key))).collect()
+        }
+
+        /// consume the cache, yielding its live entries hottest first: pinned entries and any the policy
+        /// can't order, then the rest in the reverse of eviction order, so that a shutdown hook that runs
+        /// out of time has saved the entries worth the most; the listener isn't told of them
+        pub fn drain_ordered(self) -> impl Iterator<Item = (K, V)> {
+            let order: Vec<K> = self.keys_in_insert_order().into_iter().rev().cloned().collect();
+            let mut store = self.store;
+            order.into_iter().filter_map(move |key| store.remove_entry(&key).map(|(key, entry)| (key, entry.value)))
+        }
+
+        /// return a copy of every live entry with what is left of its ttl, in eviction order,
+        /// so that warming an empty cache with it recreates the recency of this one
+        /// the remaining ttl is the earlier of the ttl and idle expiry, or None if the entry never expires
+        pub fn snapshot(&self) -> Vec<(K, V, Option<Duration>)>
+        where
+            V: Clone,
+        {
+            let now = self.clock.now();
+            self.keys_in_insert_order()
+                .into_iter()
+                .map(|key| {
+                    let entry = &self.store[key];
+                    let ttl = entry.deadline().map(|expiry_time| expiry_time.saturating_duration_since(now));
+                    (key.clone(), entry.value.clone(), ttl)
+                })
+                .collect()
+        }
+
+        /// return the current size of the cache
+        pub fn len(&self) -> usize {
+            self.store.len()
+        }
+
+        /// return a receiver that is sent an event for every insert, lookup, and removal from now on
+        /// events are sent as they happen, so a receiver that is never drained keeps growing;
+        /// dropping it unsubscribes
+        pub fn subscribe(&mut self) -> Receiver<CacheEvent<K>> {
+            let (sender, receiver) = mpsc::channel();
+            self.subscribers.push(sender);
+            receiver
+        }
+
+        // send an event to every subscriber, forgetting the ones whose receiver is gone
+        fn publish(&mut self, key: Option<K>, cause: EventCause) {
+            let event = CacheEvent { key, cause, timestamp: self.clock.now() };
+            self.subscribers.retain(|subscriber| subscriber.send(event.clone()).is_ok());
+        }
+
+        /// return the entry limit the cache was built with
+        pub fn capacity_limit(&self) -> CapacityLimit {
+            self.capacity
+        }
+
+        /// return true if the cache holds no entries
+        pub fn is_empty(&self) -> bool {
+            self.store.is_empty()
+        }
+
+        /// return how many entries the store has room for without reallocating,
+        /// which is unrelated to the entry limit
+        pub fn capacity(&self) -> usize {
+            self.store.capacity()
+        }
+
+        /// reserve room in the store for at least additional more entries, without changing the entry limit
+        pub fn reserve(&mut self, additional: usize) {
+            self.store.reserve(additional);
+        }
+
+        /// return the combined weight of every entry in the cache
+        pub fn weight(&self) -> u64 {
+            self.total_weight
+        }
+
+        /// return the weight limit, as set on the builder or by set_max_weight
+        pub fn max_weight(&self) -> u64 {
+            self.max_weight
+        }
+
+        /// return the estimated memory taken up by the entries in bytes,
+        /// or 0 unless the cache was built with max_bytes or track_memory
+        pub fn current_memory(&self) -> u64 {
+            self.total_bytes
+        }
+
+        /// return the hit, miss, eviction, expiry, and insert counters
+        pub fn stats(&self) -> Stats {
+            self.stats
+        }
+
+        /// set all stats counters back to zero
+        pub fn reset_stats(&mut self) {
+            self.stats = Stats::default();
+            self.lifetimes = LifetimeStats::default();
+            if let Some(shadows) = &mut self.shadows {
+                shadows.reset_stats();
+            }
+        }
+
+        /// return how long evicted and expired entries lasted, and how much time evicted ones had left,
+        /// since the cache was created or its stats were reset
+        pub fn lifetime_stats(&self) -> LifetimeStats {
+            self.lifetimes
+        }
+
+        /// return the hit counters of the shadow caches, if shadow_capacities was set on the builder
+        /// shadows see the same lookups, inserts, and removals as the cache, but ignore weights and expiry
+        pub fn shadow_stats(&self) -> Option<ShadowStats> {
+            self.shadows.as_ref().map(Shadows::stats)
+        }
+
+        /// start keeping a record of every lookup that counts as a hit or a miss
+        /// keys are recorded as a hash that is stable across runs, so the record can be
+        /// written out with simulation::trace and replayed without exposing the keys
+        pub fn record_accesses(&mut self) {
+            self.recorded.get_or_insert_with(Vec::new);
+        }
+
+        /// return the lookups recorded since the last call, oldest first
+        pub fn take_recorded(&mut self) -> Vec<u64> {
+            self.recorded.as_mut().map(std::mem::take).unwrap_or_default()
+        }
+
+        /// stop recording lookups, dropping any that haven't been taken
+        pub fn stop_recording(&mut self) {
+            self.recorded = None;
+        }
+
+        // add a lookup of the key to the recording, the shadow caches, the tracer, and the subscribers,
+        // if there are any; owned is the key as a K when the caller has one, so that a miss can carry it
+        fn observe<Q>(&mut self, key: &Q, hit: bool, owned: Option<&K>)
+        where
+            K: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+        {
+            if !self.subscribers.is_empty() {
+                let stored = self.store.get_key_value(key).map(|(stored_key, _)| stored_key);
+                let subscribed_key = owned.or(stored).cloned();
+                self.publish(subscribed_key, if hit { EventCause::Hit } else { EventCause::Miss });
+            }
+            if let Some(shadows) = &mut self.shadows {
+                shadows.lookup(key, self.store.get_key_value(key).map(|(stored_key, _)| stored_key));
+            }
+            if let Some(recorded) = &mut self.recorded {
+                recorded.push(stable_hash(key));
+            }
+            #[cfg(feature = "tracing")]
+            if let Some(tracer) = &self.tracer {
+                let key_hash = stable_hash(key);
+                let outcome = match (hit, self.expired_lookup.take()) {
+                    (true, _) => LookupOutcome::Hit,
+                    (false, Some(expired)) if expired == key_hash => LookupOutcome::Expired,
+                    (false, _) => LookupOutcome::Miss,
+                };
+                tracer(&TraceEvent::Lookup { key_hash, outcome });
+            }
+        }
+    }
+
+
+/// consuming iterator over the live entries of a cache
+pub struct IntoIter<K, V> {
+    inner: hash_map::IntoIter<K, CacheEntry<V>>,
+    now: Instant,
+}
+
+impl<K, V> Iterator for IntoIter<K, V> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (key, entry) = self.inner.next()?;
+            if !entry.is_expired(self.now) {
+                return Some((key, entry.value));
+            }
+        }
+    }
+}
+
+impl<K, V, E, S, C> IntoIterator for Simcache<K, V, E, S, C>
+where
+    E: EvictionPolicy<K>,
+    C: Clock,
+{
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V>;
+
+    /// consume the cache, yielding its live entries in no particular order
+    fn into_iter(self) -> IntoIter<K, V> {
+        IntoIter { now: self.clock.now(), inner: self.store.into_iter() }
+    }
+}
+
+/// insert each key value pair like insert, so the default ttl applies and a bounded cache evicts
+impl<K, V, E, S, C> Extend<(K, V)> for Simcache<K, V, E, S, C>
+where
+    K: Eq + Hash + Clone,
+    E: EvictionPolicy<K>,
+    S: BuildHasher,
+    C: Clock,
+{
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, entries: I) {
+        self.insert_many(entries.into_iter().map(|(key, value)| (key, value, None)));
+    }
+}
+
+/// collect into a cache with no entry limit, so that nothing collected is evicted
+impl<K, V, E> FromIterator<(K, V)> for Simcache<K, V, E>
+where
+    K: Eq + Hash + Clone,
+    E: EvictionPolicy<K>,
+{
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(entries: I) -> Self {
+        let mut cache = Self::unbounded();
+        cache.extend(entries);
+        cache
+    }
+}
+
+/// return the value of a live entry like peek, without counting a use
+/// panics if there is no live entry for the key
+impl<K, V, E, S, C, Q> Index<&Q> for Simcache<K, V, E, S, C>
+where
+    K: Eq + Hash + Clone + Borrow<Q>,
+    Q: Hash + Eq + ?Sized,
+    E: EvictionPolicy<K>,
+    S: BuildHasher,
+    C: Clock,
+{
+    type Output = V;
+
+    fn index(&self, key: &Q) -> &V {
+        self.peek(key).expect("indexed key should have a live entry")
+    }
+}
+
+/// a clone has the same entries, settings, eviction policy state, and stats as the original,
+/// and shares its weigher, removal listener, and tracer, which it calls for its own entries
+/// subscribers of the original are not sent the clone's events
+impl<K, V, E, S, C> Clone for Simcache<K, V, E, S, C>
+where
+    K: Clone,
+    V: Clone,
+    E: EvictionPolicy<K> + Clone,
+    S: Clone,
+    C: Clone,
+{
+    fn clone(&self) -> Self {
+        Simcache {
+            store: self.store.clone(),
+            clock: self.clock.clone(),
+            policies: self.policies.clone(),
+            capacity: self.capacity,
+            max_weight: self.max_weight,
+            weigher: self.weigher.clone(),
+            max_bytes: self.max_bytes,
+            sizer: self.sizer,
+            default_ttl: self.default_ttl,
+            time_to_idle: self.time_to_idle,
+            refresh_after: self.refresh_after,
+            negative_ttl: self.negative_ttl,
+            ttl_jitter: self.ttl_jitter,
+            watermarks: self.watermarks,
+            rng: self.rng.clone(),
+            listener: self.listener.clone(),
+            #[cfg(feature = "tracing")]
+            tracer: self.tracer.clone(),
+            #[cfg(feature = "tracing")]
+            expired_lookup: None,
+            total_weight: self.total_weight,
+            total_bytes: self.total_bytes,
+            pinned: self.pinned,
+            negatives: self.negatives.clone(),
+            rejected: None,
+            // spilled entries are collected for whatever wraps the original, not for the clone
+            spilled: None,
+            recorded: self.recorded.clone(),
+            tags: self.tags.clone(),
+            ordered: self.ordered.clone(),
+            expiry: self.expiry.clone(),
+            subscribers: Vec::new(),
+            doorkeeper: self.doorkeeper.clone(),
+            shadows: self.shadows.clone(),
+            pending: self.pending.clone(),
+            buffered_reads: self.buffered_reads,
+            access_buffer: self.access_buffer,
+            transient: false,
+            stats: self.stats,
+            lifetimes: self.lifetimes,
+        }
+    }
+}
+
+
+    #[cfg(test)]
+    mod common {
+        use super::*;
+        use super::super::eviction::*;
+        use crate::MockClock;
+        use crate::stats::ShadowCounts;
+
+        type MockCache<E> = Simcache<&'static str, &'static str, E, RandomState, MockClock>;
+
+        fn mock_builder<E: EvictionPolicy<&'static str>>(clock: &MockClock) -> SimcacheBuilder<&'static str, &'static str, E, RandomState, MockClock> {
+            Simcache::builder().clock(clock.clone())
+        }
+
+        #[test]
+        fn test_cache_lru() {
+            let mut cache: Simcache::<&'static str, &'static str, LRU<&'static str>> = Simcache::new(3);
+
+            cache.insert("a", "1", None);
+            cache.insert("b", "2", None);
+            cache.insert("c", "3", None);
+
+            assert_eq!(cache.len(), 3);
+
+            cache.insert("d", "4", None);
+
+            assert_eq!(cache.get(&"a"), None);
+
+            assert_eq!(*(cache.get(&"b").expect("cache should have a value for key b")), "2");
+
+            cache.insert("e","5", None);
+
+            cache.remove(&"b");
+
+            assert_eq!(cache.len(), 2);
+        }
+
+        #[test]
+        fn test_cache_lfu() {
+            let mut cache: Simcache::<&'static str, &'static str, LFU<&'static str>> = Simcache::new(3);
+
+            cache.insert("a", "1", None);
+            cache.insert("b", "2", None);
+            cache.insert("c", "3", None);
+            cache.get(&"a");
+            cache.get(&"c");
+
+            assert_eq!(cache.len(), 3);
+
+            cache.insert("d", "4", None);
+
+            assert_eq!(cache.get(&"b"), None);
+
+            assert_eq!(*(cache.get(&"a").expect("cache should have a value for key a")), "1");
+
+            cache.insert("e","5", None);
+
+            cache.remove(&"a");
+
+            assert_eq!(cache.len(), 2);
+        }
+
+        #[test]
+        fn test_get_or_insert_with() {
+            let mut cache: Simcache::<&'static str, usize, LFU<&'static str>> = Simcache::new(2);
+            let mut calls = 0;
+
+            assert_eq!(*cache.get_or_insert_with("a", None, || { calls += 1; 1 }), 1);
+            assert_eq!(*cache.get_or_insert_with("a", None, || { calls += 1; 2 }), 1);
+            assert_eq!(calls, 1);
+
+            // "a" has been used twice, so "b" is the coldest entry when "c" arrives
+            cache.insert("b", 2, None);
+            cache.insert("c", 3, None);
+            assert_eq!(cache.get(&"b"), None);
+
+            let failed: Result<&usize, &str> = cache.get_or_try_insert_with("d", None, || Err("unavailable"));
+            assert_eq!(failed, Err("unavailable"));
+            assert_eq!(cache.get(&"d"), None);
+            assert_eq!(cache.get_or_try_insert_with::<_, &str>("a", None, || Ok(9)), Ok(&1));
+        }
+
+        #[test]
+        fn test_cache_fifo() {
+            let mut cache: Simcache::<&'static str, &'static str, FIFO<&'static str>> = Simcache::new(2);
+
+            cache.insert("a", "1", None);
+            cache.insert("b", "2", None);
+            cache.get(&"a");
+            cache.insert("c", "3", None);
+
+            assert_eq!(cache.get(&"a"), None);
+            assert_eq!(*(cache.get(&"b").expect("cache should have a value for key b")), "2");
+        }
+
+        #[test]
+        fn test_stats() {
+            let mut cache: Simcache::<&'static str, &'static str, LRU<&'static str>> = Simcache::new(2);
+
+            cache.insert("a", "1", None);
+            cache.insert("b", "2", None);
+            cache.insert("c", "3", None);
+            cache.get(&"a");
+            cache.get(&"c");
+            cache.get(&"b");
+            cache.get(&"d");
+
+            let stats = cache.stats();
+            assert_eq!(stats.inserts, 3);
+            assert_eq!(stats.evictions, 1);
+            assert_eq!(stats.hits, 2);
+            assert_eq!(stats.misses, 2);
+            assert_eq!(stats.hit_ratio(), 0.5);
+
+            cache.reset_stats();
+            assert_eq!(cache.stats(), Stats::default());
+        }
+
+        #[test]
+        fn test_weighted_capacity() {
+            let mut cache: Simcache::<&'static str, Vec<u8>, LRU<&'static str>> = Simcache::builder()
+                .max_weight(10)
+                .weigher(|_, v: &Vec<u8>| v.len() as u64)
+                .build();
+
+            cache.insert("a", vec![0; 4], None);
+            cache.insert("b", vec![0; 4], None);
+            assert_eq!(cache.weight(), 8);
+
+            // both older entries have to go to fit 9 more
+            cache.insert("c", vec![0; 9], None);
+            assert_eq!(cache.len(), 1);
+            assert_eq!(cache.weight(), 9);
+
+            // replacing an entry only counts its new weight
+            cache.insert("c", vec![0; 10], None);
+            assert_eq!(cache.weight(), 10);
+
+            cache.insert("d", vec![0; 12], None);
+            assert_eq!(cache.get(&"c"), None);
+            assert_eq!(cache.weight(), 12);
+            assert_eq!(cache.stats().evictions, 3);
+        }
+
+        #[test]
+        fn test_builder() {
+            let mut cache: Simcache::<&'static str, &'static str, FIFO<&'static str>> = Simcache::builder()
+                .max_capacity(1)
+                .default_ttl(Duration::from_secs(60))
+                .policy(FIFO::new())
+                .build();
+
+            cache.insert("a", "1", None);
+            cache.insert("b", "2", None);
+
+            assert_eq!(cache.len(), 1);
+            assert!(cache.ttl_remaining(&"b").is_some());
+        }
+
+        #[test]
+        fn test_purge_expired() {
+            let clock = MockClock::new();
+            let mut cache: MockCache<LRU<&'static str>> = mock_builder(&clock).max_capacity(3).build();
+
+            cache.insert("a", "1", Some(Duration::from_secs(1)));
+            cache.insert("b", "2", Some(Duration::from_secs(2)));
+            cache.insert("c", "3", None);
+
+            assert_eq!(cache.purge_expired(), 0);
+            clock.advance(Duration::from_secs(3));
+
+            assert_eq!(cache.purge_expired(), 2);
+            assert_eq!(cache.len(), 1);
+            assert_eq!(cache.stats().expired, 2);
+        }
+
+        #[test]
+        fn test_removal_listener() {
+            use std::sync::{Arc, Mutex};
+
+            let removed = Arc::new(Mutex::new(Vec::new()));
+            let log = removed.clone();
+            let clock = MockClock::new();
+            let mut cache: MockCache<LRU<&'static str>> = mock_builder(&clock)
+                .max_capacity(2)
+                .on_evict(move |k, v, cause| log.lock().unwrap().push((*k, *v, cause)))
+                .build();
+
+            cache.insert("a", "1", None);
+            cache.insert("a", "2", None);
+            cache.insert("b", "3", None);
+            cache.insert("c", "4", None);
+            cache.insert("d", "5", Some(Duration::from_secs(1)));
+            clock.advance(Duration::from_secs(2));
+            cache.purge_expired();
+            cache.remove(&"c");
+
+            assert_eq!(*removed.lock().unwrap(), vec![
+                ("a", "1", RemovalCause::Replaced),
+                ("a", "2", RemovalCause::Evicted),
+                ("b", "3", RemovalCause::Evicted),
+                ("d", "5", RemovalCause::Expired),
+                ("c", "4", RemovalCause::Removed),
+            ]);
+        }
+
+        #[test]
+        fn test_cache_tinylfu() {
+            let mut cache: Simcache::<u32, u32, TinyLFU<u32>> = Simcache::new(10);
+
+            for key in 0..5 {
+                cache.insert(key, key, None);
+            }
+            for _ in 0..5 {
+                for key in 0..5 {
+                    cache.get(&key);
+                }
+            }
+            // a scan of keys seen only once shouldn't displace a hot set that is still in use
+            for key in 100..200 {
+                cache.insert(key, key, None);
+                if key % 10 == 0 {
+                    for hot in 0..5 {
+                        cache.get(&hot);
+                    }
+                }
+            }
+
+            assert!((0..5).all(|key| cache.get(&key).is_some()));
+        }
+
+        #[test]
+        fn test_peek() {
+            let clock = MockClock::new();
+            let mut cache: MockCache<LRU<&'static str>> = mock_builder(&clock).max_capacity(2).build();
+
+            cache.insert("a", "1", None);
+            cache.insert("b", "2", Some(Duration::from_secs(1)));
+            clock.advance(Duration::from_secs(2));
+
+            assert_eq!(cache.peek(&"a"), Some(&"1"));
+            assert!(cache.contains_key(&"a"));
+            assert!(!cache.contains_key(&"b"));
+            assert_eq!(cache.stats().hits, 0);
+
+            // peeking at a doesn't make it more recent than c
+            cache.insert("c", "3", None);
+            cache.insert("d", "4", None);
+            assert_eq!(cache.peek(&"a"), None);
+        }
+
+        #[test]
+        fn test_get_mut() {
+            let mut cache: Simcache::<&'static str, Vec<u32>, LFU<&'static str>> = Simcache::new(2);
+
+            cache.insert("a", vec![1], None);
+            cache.insert("b", vec![2], None);
+            cache.get_mut(&"a").expect("cache should have a value for key a").push(3);
+
+            assert_eq!(cache.peek(&"a"), Some(&vec![1, 3]));
+            assert_eq!(cache.get_mut(&"c"), None);
+
+            cache.insert("c", vec![4], None);
+            assert!(cache.contains_key(&"a"));
+            assert!(!cache.contains_key(&"b"));
+        }
+
+        #[test]
+        fn test_borrowed_keys() {
+            let mut cache: Simcache::<String, u32, LRU<String>> = Simcache::new(2);
+
+            cache.insert("a".to_string(), 1, None);
+            cache.insert("b".to_string(), 2, None);
+
+            *cache.get_mut("b").expect("cache should have a value for key b") += 1;
+            assert_eq!(cache.peek("b"), Some(&3));
+            assert_eq!(cache.get("a"), Some(&1));
+            assert!(cache.contains_key("b"));
+
+            // the lookup of a by &str still refreshed it, so b goes first
+            cache.insert("c".to_string(), 3, None);
+            assert_eq!(cache.peek("a"), Some(&1));
+            assert_eq!(cache.remove("c"), Some(3));
+        }
+
+        #[test]
+        fn test_custom_hasher() {
+            use std::hash::{BuildHasherDefault, Hasher};
+
+            // passes small integer keys straight through
+            #[derive(Default)]
+            struct IdentityHasher(u64);
+
+            impl Hasher for IdentityHasher {
+                fn finish(&self) -> u64 {
+                    self.0
+                }
+                fn write(&mut self, bytes: &[u8]) {
+                    for byte in bytes {
+                        self.0 = self.0 << 8 | *byte as u64;
+                    }
+                }
+                fn write_u64(&mut self, n: u64) {
+                    self.0 = n;
+                }
+            }
+
+            let mut cache: Simcache::<u64, u64, LRU<u64>, BuildHasherDefault<IdentityHasher>> = Simcache::builder()
+                .max_capacity(2)
+                .hasher(BuildHasherDefault::default())
+                .build();
+
+            cache.insert(1, 10, None);
+            cache.insert(2, 20, None);
+            cache.insert(3, 30, None);
+
+            assert_eq!(cache.get(&1), None);
+            assert_eq!(cache.get(&3), Some(&30));
+        }
+
+        #[test]
+        fn test_ttl_updates() {
+            let clock = MockClock::new();
+            let mut cache: MockCache<LRU<&'static str>> = mock_builder(&clock).max_capacity(2).build();
+            let minute = Duration::from_secs(60);
+
+            cache.insert("a", "1", Some(minute));
+            cache.insert("b", "2", None);
+
+            clock.advance(Duration::from_secs(15));
+            assert_eq!(cache.ttl_remaining(&"a"), Some(Duration::from_secs(45)));
+            assert_eq!(cache.ttl_remaining(&"b"), None);
+
+            assert!(cache.set_ttl(&"b", Some(minute * 2)));
+            assert_eq!(cache.ttl_remaining(&"b"), Some(minute * 2));
+            assert!(cache.touch(&"a"));
+            assert_eq!(cache.ttl_remaining(&"a"), Some(minute));
+            assert!(cache.set_ttl(&"a", None));
+            assert_eq!(cache.ttl_remaining(&"a"), None);
+
+            cache.set_ttl(&"a", Some(Duration::from_secs(1)));
+            clock.advance(Duration::from_secs(2));
+            assert!(!cache.touch(&"a"));
+            assert!(!cache.set_ttl(&"c", None));
+        }
+
+        #[test]
+        fn test_time_to_idle() {
+            let clock = MockClock::new();
+            let mut cache: MockCache<LRU<&'static str>> = mock_builder(&clock)
+                .time_to_idle(Duration::from_secs(5))
+                .build();
+
+            cache.insert("a", "1", None);
+            cache.insert("b", "2", None);
+            for _ in 0..8 {
+                clock.advance(Duration::from_secs(1));
+                assert!(cache.get(&"a").is_some());
+            }
+
+            // b has been idle for 8s, a only for 0
+            assert!(!cache.contains_key(&"b"));
+            assert!(cache.ttl_remaining(&"a").is_some());
+        }
+
+        #[test]
+        fn test_ttl_jitter() {
+            let mut cache: Simcache::<u32, u32, LRU<u32>> = Simcache::builder()
+                .default_ttl(Duration::from_secs(100))
+                .ttl_jitter(50)
+                .build();
+
+            for key in 0..20 {
+                cache.insert(key, key, None);
+            }
+            cache.insert_exact(20, 20, None);
+
+            let remaining: Vec<Duration> = (0..20).filter_map(|key| cache.ttl_remaining(&key)).collect();
+            assert!(remaining.iter().all(|ttl| *ttl <= Duration::from_secs(100) && *ttl >= Duration::from_secs(49)));
+            assert!(remaining.windows(2).any(|pair| pair[0] != pair[1]));
+            assert_eq!(cache.ttl_remaining(&20), None);
+        }
+
+        #[test]
+        fn test_iteration() {
+            let clock = MockClock::new();
+            let mut cache: MockCache<LRU<&'static str>> = mock_builder(&clock).build();
+
+            cache.insert("a", "1", None);
+            cache.insert("b", "2", Some(Duration::from_secs(1)));
+            cache.insert("c", "3", None);
+            cache.get(&"a");
+            clock.advance(Duration::from_secs(2));
+
+            let mut keys: Vec<_> = cache.keys().copied().collect();
+            keys.sort();
+            assert_eq!(keys, vec!["a", "c"]);
+            assert_eq!(cache.values().count(), 2);
+            assert_eq!(cache.iter_eviction_order().collect::<Vec<_>>(), vec![(&"c", &"3"), (&"a", &"1")]);
+
+            let mut entries: Vec<_> = cache.into_iter().collect();
+            entries.sort();
+            assert_eq!(entries, vec![("a", "1"), ("c", "3")]);
+        }
+
+        #[test]
+        fn test_clear_and_retain() {
+            let mut cache: Simcache::<u32, u32, LFU<u32>> = Simcache::builder()
+                .max_capacity(4)
+                .policy(LFU::with_aging(100))
+                .build();
+
+            for key in 0..4 {
+                cache.insert(key, key * 10, None);
+            }
+            cache.get(&0);
+            cache.retain(|key, _| key % 2 == 0);
+
+            assert_eq!(cache.len(), 2);
+            assert!(cache.contains_key(&0) && cache.contains_key(&2));
+
+            // the policy no longer tracks 1 and 3, so the next eviction is a real entry
+            cache.insert(4, 40, None);
+            cache.insert(5, 50, None);
+            cache.insert(6, 60, None);
+            assert_eq!(cache.len(), 4);
+            assert!(cache.contains_key(&0));
+
+            cache.clear();
+            assert!(cache.is_empty());
+            assert_eq!(cache.weight(), 0);
+            assert!(cache.iter_eviction_order().next().is_none());
+        }
+
+        #[test]
+        fn test_capacity_limits() {
+            let mut cache: Simcache<u32, u32, LRU<u32>> = Simcache::new(0);
+            cache.insert(1, 10, None);
+            assert!(cache.is_empty());
+            assert_eq!(cache.get(&1), None);
+            assert_eq!(*cache.get_or_insert_with(2, None, || 20), 20);
+            assert_eq!(*cache.entry(3).or_insert(30), 30);
+            assert!(cache.is_empty());
+            assert_eq!(cache.stats().inserts, 0);
+            assert_eq!(cache.stats().evictions, 0);
+
+            let mut cache: Simcache<u32, u32, LRU<u32>> = Simcache::new(1);
+            cache.insert(1, 10, None);
+            cache.insert(1, 11, None);
+            assert_eq!(cache.stats().evictions, 0);
+            for key in 2..5 {
+                cache.insert(key, key * 10, None);
+                assert_eq!(cache.len(), 1);
+            }
+            assert_eq!(cache.peek(&4), Some(&40));
+            assert_eq!(cache.stats().evictions, 3);
+
+            let mut cache: Simcache<u32, u32, LRU<u32>> = Simcache::unbounded();
+            for key in 0..1000 {
+                cache.insert(key, key, None);
+            }
+            assert_eq!(cache.len(), 1000);
+            assert_eq!(cache.capacity_limit(), CapacityLimit::Unbounded);
+            assert_eq!(cache.stats().evictions, 0);
+        }
+
+        #[test]
+        fn test_admission() {
+            let mut cache: Simcache::<&'static str, u32, TinyLFU<&'static str>> = Simcache::new(2);
+
+            assert_eq!(cache.insert("a", 1, None), InsertOutcome::Inserted);
+            assert_eq!(cache.insert("b", 2, None), InsertOutcome::Inserted);
+            for _ in 0..3 {
+                cache.get(&"a");
+                cache.get(&"b");
+            }
+
+            // c is colder than the victim b, so it is turned away until it has been seen as often
+            assert_eq!(cache.insert("c", 3, None), InsertOutcome::Rejected);
+            assert_eq!(cache.len(), 2);
+            assert!(cache.contains_key(&"a") && cache.contains_key(&"b"));
+
+            let mut rejections = 1;
+            while cache.insert("c", 3, None) == InsertOutcome::Rejected {
+                rejections += 1;
+            }
+            assert_eq!(rejections, 3);
+            assert!(cache.contains_key(&"c") && !cache.contains_key(&"b"));
+            assert_eq!(cache.insert("c", 4, None), InsertOutcome::Replaced);
+        }
+
+        #[test]
+        fn test_cache_slru() {
+            let mut cache: Simcache::<u32, u32, SLRU<u32>> = Simcache::new(10);
+
+            for key in 0..5 {
+                cache.insert(key, key, None);
+                cache.get(&key);
+            }
+            // the scan only ever passes through probation
+            for key in 100..200 {
+                cache.insert(key, key, None);
+            }
+
+            assert!((0..5).all(|key| cache.contains_key(&key)));
+        }
+
+        #[test]
+        fn test_cache_clock() {
+            // named in full, as the glob imports bring in both the policy and the time source trait
+            let mut cache: Simcache::<&'static str, &'static str, crate::eviction::Clock<&'static str>> = Simcache::new(2);
+
+            cache.insert("a", "1", None);
+            cache.insert("b", "2", None);
+            cache.get(&"a");
+            cache.insert("c", "3", None);
+
+            assert_eq!(cache.get(&"b"), None);
+            assert_eq!(*(cache.get(&"a").expect("cache should have a value for key a")), "1");
+        }
+
+        #[test]
+        fn test_cache_sieve() {
+            let mut cache: Simcache::<&'static str, &'static str, Sieve<&'static str>> = Simcache::new(2);
+
+            cache.insert("a", "1", None);
+            cache.insert("b", "2", None);
+            cache.get(&"a");
+            cache.insert("c", "3", None);
+            cache.get(&"a");
+            cache.insert("d", "4", None);
+
+            // a has been visited each time the hand reaches it, so the newer keys go instead
+            assert!(cache.contains_key(&"a") && cache.contains_key(&"d"));
+            assert_eq!(cache.len(), 2);
+        }
+
+        #[test]
+        fn test_cache_s3fifo() {
+            let mut cache: Simcache::<u32, u32, S3Fifo<u32>> = Simcache::new(10);
+
+            for key in 0..5 {
+                cache.insert(key, key, None);
+                cache.get(&key);
+            }
+            // keys seen once never leave the small queue
+            for key in 100..200 {
+                cache.insert(key, key, None);
+            }
+
+            assert!((0..5).all(|key| cache.contains_key(&key)));
+        }
+
+        #[test]
+        fn test_cache_mru() {
+            let mut cache: Simcache::<u32, u32, MRU<u32>> = Simcache::new(4);
+
+            // a cyclic scan over 5 keys misses every time with LRU, but mostly hits with MRU
+            for _ in 0..10 {
+                for key in 0..5 {
+                    cache.get_or_insert_with(key, None, || key);
+                }
+            }
+
+            assert!(cache.stats().hit_ratio() > 0.5);
+        }
+
+        #[test]
+        fn test_cache_random() {
+            let mut cache: Simcache::<u32, u32, RandomEviction<u32>> = Simcache::builder()
+                .max_capacity(4)
+                .policy(RandomEviction::with_seed(7))
+                .build();
+
+            for key in 0..100 {
+                cache.insert(key, key, None);
+            }
+
+            assert_eq!(cache.len(), 4);
+            assert_eq!(cache.stats().evictions, 96);
+        }
+
+        #[test]
+        fn test_cache_expiry_first() {
+            let clock = MockClock::new();
+            let mut cache: MockCache<ExpiryFirst<&'static str>> = mock_builder(&clock).max_capacity(3).build();
+
+            cache.insert("a", "1", None);
+            cache.insert("b", "2", Some(Duration::from_secs(10)));
+            cache.insert("c", "3", Some(Duration::from_secs(5)));
+            cache.insert("d", "4", None);
+            assert!(!cache.contains_key(&"c"));
+
+            // touching b pushes its expiry back, but it still expires before a and d do
+            clock.advance(Duration::from_secs(1));
+            cache.touch(&"b");
+            cache.insert("e", "5", None);
+            assert!(!cache.contains_key(&"b"));
+
+            cache.insert("f", "6", None);
+            assert!(!cache.contains_key(&"a"));
+            assert!(cache.contains_key(&"d") && cache.contains_key(&"e") && cache.contains_key(&"f"));
+        }
+
+        #[test]
+        fn test_cache_gdsf() {
+            let mut cache: Simcache::<&'static str, &'static str, GDSF<&'static str>> = Simcache::new(2);
+
+            cache.insert_with_cost("a", "1", None, 100);
+            cache.insert("b", "2", None);
+            cache.get(&"b");
+            cache.insert("c", "3", None);
+
+            // b was used more recently, but a is far more expensive to fetch again
+            assert!(cache.contains_key(&"a"));
+            assert!(!cache.contains_key(&"b"));
+        }
+
+        #[test]
+        fn test_pinning() {
+            let clock = MockClock::new();
+            let mut cache: MockCache<LRU<&'static str>> = mock_builder(&clock).max_capacity(2).build();
+
+            cache.insert("a", "1", Some(Duration::from_secs(10)));
+            assert!(cache.pin(&"a"));
+            cache.insert("b", "2", None);
+            cache.insert("a", "3", Some(Duration::from_secs(10)));
+            cache.insert("c", "4", None);
+
+            // a is never the victim, and stays pinned when its value is replaced
+            assert!(cache.is_pinned(&"a") && !cache.contains_key(&"b"));
+
+            // with everything pinned the cache grows instead
+            cache.pin(&"c");
+            cache.insert("d", "5", None);
+            assert_eq!(cache.len(), 3);
+
+            // unpinning makes the limit apply again straight away
+            cache.unpin(&"c");
+            assert_eq!(cache.len(), 2);
+            assert!(!cache.contains_key(&"d"));
+            cache.insert("e", "6", None);
+            assert_eq!(cache.len(), 2);
+            assert!(cache.contains_key(&"a") && cache.contains_key(&"e"));
+
+            // pinned entries still expire
+            clock.advance(Duration::from_secs(11));
+            assert!(!cache.contains_key(&"a"));
+            assert!(!cache.pin(&"a"));
+
+            // with no room at all, an unpinned entry goes as soon as it is unpinned
+            let mut cache: MockCache<LRU<&'static str>> = mock_builder(&clock).max_capacity(1).build();
+            cache.insert("a", "1", None);
+            cache.pin(&"a");
+            cache.set_max_capacity(0);
+            assert_eq!(cache.len(), 1);
+            assert!(cache.unpin(&"a"));
+            assert!(cache.is_empty());
+        }
+
+        #[test]
+        fn test_priorities() {
+            let mut cache: Simcache::<&'static str, &'static str, LRU<&'static str>> = Simcache::new(3);
+
+            cache.insert_with_priority("a", "1", None, Priority::High);
+            cache.insert_with_priority("b", "2", None, Priority::Low);
+            cache.insert("c", "3", None);
+            cache.get(&"b");
+
+            // b is the most recently used, but every low entry goes before a normal one
+            cache.insert("d", "4", None);
+            assert!(!cache.contains_key(&"b"));
+            cache.insert("e", "5", None);
+            assert!(!cache.contains_key(&"c"));
+
+            // a low entry can't displace anything of a higher priority
+            assert_eq!(cache.insert_with_priority("f", "6", None, Priority::Low), InsertOutcome::Rejected);
+
+            // replacing a value keeps its priority
+            cache.insert("a", "7", None);
+            assert_eq!(cache.priority(&"a"), Some(Priority::High));
+            let order: Vec<_> = cache.iter_eviction_order().map(|(key, _)| *key).collect();
+            assert_eq!(order, vec!["d", "e", "a"]);
+
+            // giving it a new priority moves it to the new level's policy and out of the old one
+            cache.insert_with_priority("a", "8", None, Priority::Low);
+            let order: Vec<_> = cache.iter_eviction_order().map(|(key, _)| *key).collect();
+            assert_eq!(order, vec!["a", "d", "e"]);
+        }
+
+        #[test]
+        fn test_warm() {
+            let mut cache: Simcache::<u32, u32, LRU<u32>> = Simcache::new(4);
+            cache.insert(0, 100, None);
+
+            // only the free room is filled, and the live entry isn't overwritten
+            assert_eq!(cache.warm((0..10).map(|key| (key, key, None))), 3);
+            assert_eq!(cache.peek(&0), Some(&100));
+            assert_eq!(cache.stats().evictions, 0);
+
+            cache.clear();
+            cache.insert(0, 0, None);
+            cache.warm_cold([(1, 1, None), (2, 2, None)]);
+            cache.get(&1);
+
+            // cold entries go before anything inserted normally, unless they have been used
+            let order: Vec<u32> = cache.iter_eviction_order().map(|(key, _)| *key).collect();
+            assert_eq!(order, vec![2, 0, 1]);
+        }
+
+        #[test]
+        fn test_refresh_after() {
+            let clock = MockClock::new();
+            let mut cache: MockCache<LRU<&'static str>> = mock_builder(&clock)
+                .refresh_after(Duration::from_secs(5))
+                .build();
+
+            cache.insert("a", "1", Some(Duration::from_secs(60)));
+            clock.advance(Duration::from_secs(4));
+            assert!(!cache.needs_refresh(&"a"));
+            assert!(!cache.needs_refresh(&"b"));
+
+            // the stale value is still served, and only one caller is asked to reload it
+            clock.advance(Duration::from_secs(1));
+            assert_eq!(cache.get(&"a"), Some(&"1"));
+            assert!(cache.needs_refresh(&"a"));
+            assert!(!cache.needs_refresh(&"a"));
+
+            // a reload that never happens is asked for again later
+            clock.advance(Duration::from_secs(5));
+            assert!(cache.needs_refresh(&"a"));
+            cache.insert("a", "2", Some(Duration::from_secs(60)));
+            assert!(!cache.needs_refresh(&"a"));
+            assert_eq!(cache.get(&"a"), Some(&"2"));
+        }
+
+        #[test]
+        fn test_negative_caching() {
+            let clock = MockClock::new();
+            let mut cache: MockCache<LRU<&'static str>> = mock_builder(&clock)
+                .max_capacity(2)
+                .negative_ttl(Duration::from_secs(5))
+                .build();
+            let mut loads = 0;
+
+            let found = cache.get_or_insert_optional_with("a", None, || { loads += 1; None });
+            assert_eq!(found, Lookup::Missing);
+            let found = cache.get_or_insert_optional_with("a", None, || { loads += 1; Some("1") });
+            assert!(found.is_negative());
+            assert_eq!(loads, 1);
+            assert_eq!(cache.len(), 0);
+
+            // once the negative entry expires the loader runs again
+            clock.advance(Duration::from_secs(5));
+            let found = cache.get_or_insert_optional_with("a", None, || { loads += 1; Some("1") });
+            assert_eq!(found.value(), Some(&"1"));
+            assert_eq!(loads, 2);
+
+            // an insert replaces a negative entry
+            cache.get_or_insert_optional_with("b", None, || None);
+            cache.insert("b", "2", None);
+            assert_eq!(cache.get_or_insert_optional_with("b", None, || None), Lookup::Value(&"2"));
+
+            // negative entries are limited to the entry limit
+            for key in ["c", "d", "e"] {
+                clock.advance(Duration::from_secs(1));
+                cache.get_or_insert_optional_with(key, None, || None);
+            }
+            assert!(!cache.get_or_insert_optional_with("c", None, || None).is_negative());
+            assert!(cache.get_or_insert_optional_with("e", None, || None).is_negative());
+            assert_eq!(cache.stats().hits, 3);
+        }
+
+        #[test]
+        fn test_batch_operations() {
+            let mut cache: Simcache::<&'static str, u32, LRU<&'static str>> = Simcache::new(3);
+
+            let outcomes = cache.insert_many([("a", 1, None), ("b", 2, None), ("a", 3, None)]);
+            assert_eq!(outcomes, vec![InsertOutcome::Inserted, InsertOutcome::Inserted, InsertOutcome::Replaced]);
+
+            assert_eq!(cache.get_many(&["b", "z", "a"]), vec![Some(&2), None, Some(&3)]);
+            assert_eq!(cache.stats().hits, 2);
+            assert_eq!(cache.stats().misses, 1);
+
+            // a was used after b, so b makes room for d
+            cache.insert_many([("c", 4, None), ("d", 5, None)]);
+            assert!(!cache.contains_key(&"b"));
+
+            assert_eq!(cache.remove_many(&["a", "b", "d"]), vec![Some(3), None, Some(5)]);
+            assert_eq!(cache.len(), 1);
+        }
+
+        #[test]
+        fn test_update() {
+            let mut cache: Simcache::<&'static str, u32, LRU<&'static str>> = Simcache::new(2);
+
+            assert!(!cache.update(&"a", |count| *count += 1));
+            cache.upsert("a", 1, |count| *count += 1);
+            cache.upsert("a", 1, |count| *count += 1);
+            cache.upsert("b", 1, |count| *count += 1);
+            assert!(cache.update(&"a", |count| *count += 1));
+            assert_eq!(cache.peek(&"a"), Some(&3));
+
+            // a was updated after b, so b is evicted first
+            cache.insert("c", 1, None);
+            assert!(!cache.contains_key(&"b"));
+            assert_eq!(cache.stats().inserts, 3);
+        }
+
+        #[test]
+        fn test_conditional_insert() {
+            let clock = MockClock::new();
+            let mut cache: MockCache<LRU<&'static str>> = mock_builder(&clock).build();
+
+            assert_eq!(cache.insert_if_absent("a", "1", Some(Duration::from_secs(10))), None);
+            assert_eq!(cache.insert_if_absent("a", "2", None), Some(&"1"));
+            assert_eq!(cache.peek(&"a"), Some(&"1"));
+
+            assert!(!cache.replace_if("a", |value| *value == "2", "3"));
+            assert!(cache.replace_if("a", |value| *value == "1", "3"));
+            assert!(!cache.replace_if("b", |_| true, "3"));
+            assert_eq!(cache.peek(&"a"), Some(&"3"));
+
+            // the replacement restarts the ttl the entry was given
+            clock.advance(Duration::from_secs(5));
+            assert!(cache.replace_if("a", |_| true, "4"));
+            assert_eq!(cache.ttl_remaining(&"a"), Some(Duration::from_secs(10)));
+
+            clock.advance(Duration::from_secs(11));
+            assert_eq!(cache.insert_if_absent("a", "5", None), None);
+        }
+
+        #[test]
+        fn test_runtime_policy() {
+            let kind: PolicyKind = "fifo".parse().unwrap();
+            let mut cache: Simcache::<&'static str, &'static str, AnyPolicy<&'static str>> = Simcache::builder()
+                .max_capacity(2)
+                .policy_kind(kind)
+                .build();
+
+            cache.insert("a", "1", None);
+            cache.insert("b", "2", None);
+            cache.get(&"a");
+            cache.insert("c", "3", None);
+
+            // fifo ignores the use of a
+            assert!(!cache.contains_key(&"a"));
+
+            // and so does the policy of another priority level
+            cache.clear();
+            cache.insert_with_priority("d", "4", None, Priority::Low);
+            cache.insert_with_priority("e", "5", None, Priority::Low);
+            cache.get(&"d");
+            cache.insert_with_priority("f", "6", None, Priority::Low);
+            assert!(!cache.contains_key(&"d"));
+        }
+
+        #[test]
+        fn test_switch_policy() {
+            let mut cache: Simcache::<&'static str, &'static str, LRU<&'static str>> = Simcache::new(3);
+
+            cache.insert("a", "1", None);
+            cache.insert("b", "2", None);
+            cache.insert("c", "3", None);
+            cache.get(&"a");
+            cache.pin(&"c");
+
+            // the fifo policy starts from the lru order, and the pinned entry stays out of it
+            let mut cache: Simcache::<&'static str, &'static str, FIFO<&'static str>> = cache.switch_policy();
+            assert_eq!(cache.len(), 3);
+            assert_eq!(cache.iter_eviction_order().map(|(key, _)| *key).collect::<Vec<_>>(), vec!["b", "a"]);
+            cache.get(&"b");
+            cache.insert("d", "4", None);
+            assert!(!cache.contains_key(&"b"));
+            assert!(cache.is_pinned(&"c"));
+
+            let mut cache: Simcache::<&'static str, &'static str, AnyPolicy<&'static str>> = cache.with_policy(AnyPolicy::from_kind(PolicyKind::Fifo));
+            cache.set_policy(AnyPolicy::from_kind(PolicyKind::Lru));
+            cache.get(&"a");
+            cache.insert("e", "5", None);
+            assert!(!cache.contains_key(&"d"));
+            assert_eq!(cache.len(), 3);
+        }
+
+        #[test]
+        fn test_shadow_stats() {
+            let mut cache: Simcache::<u32, u32, LRU<u32>> = Simcache::builder()
+                .max_capacity(4)
+                .shadow_capacities()
+                .build();
+
+            // three keys in a loop fit in 4 or 8 entries, but lru at 2 entries misses every time
+            for _ in 0..10 {
+                for key in 0..3 {
+                    if cache.get(&key).is_none() {
+                        cache.insert(key, key, None);
+                    }
+                }
+            }
+            let shadow = cache.shadow_stats().unwrap();
+            assert_eq!(shadow.half, ShadowCounts { capacity: 2, hits: 0, misses: 30 });
+            assert_eq!(shadow.double, ShadowCounts { capacity: 8, hits: 27, misses: 3 });
+            assert_eq!(cache.stats().hits, 27);
+
+            cache.remove(&0);
+            cache.reset_stats();
+            cache.get(&0);
+            assert_eq!(cache.shadow_stats().unwrap().double.misses, 1);
+            assert!(Simcache::<u32, u32, LRU<u32>>::new(4).shadow_stats().is_none());
+        }
+
+        #[test]
+        fn test_resize() {
+            let mut cache: Simcache::<&'static str, u64, LRU<&'static str>> = Simcache::builder()
+                .max_capacity(4)
+                .weigher(|_, value| *value)
+                .shadow_capacities()
+                .build();
+
+            cache.insert("a", 1, None);
+            cache.insert("b", 2, None);
+            cache.insert("c", 3, None);
+            cache.insert("d", 4, None);
+            cache.get(&"a");
+            cache.pin(&"d");
+
+            // shrinking evicts in lru order, skipping the pinned entry
+            assert_eq!(cache.set_max_capacity(2), 2);
+            assert_eq!(cache.keys().count(), 2);
+            assert!(cache.contains_key(&"a") && cache.contains_key(&"d"));
+            assert_eq!(cache.shadow_stats().unwrap().double.capacity, 4);
+
+            assert_eq!(cache.set_max_weight(4), 1);
+            assert!(!cache.contains_key(&"a"));
+            assert_eq!(cache.set_max_weight(0), 0);
+            assert_eq!(cache.len(), 1);
+
+            cache.set_capacity_limit(CapacityLimit::Unbounded);
+            cache.set_max_weight(u64::MAX);
+            for (key, value) in [("e", 5), ("f", 6), ("g", 7)] {
+                cache.insert(key, value, None);
+            }
+            assert_eq!(cache.len(), 4);
+        }
+
+        #[test]
+        fn test_watermarks() {
+            let mut cache: Simcache::<u32, u32, LRU<u32>> = Simcache::builder()
+                .max_capacity(10)
+                .watermarks(80, 50)
+                .build();
+
+            for key in 0..8 {
+                cache.insert(key, key, None);
+            }
+            assert_eq!(cache.stats().evictions, 0);
+
+            // the ninth key would pass 8 entries, so the cache drops to 5 including it
+            cache.insert(8, 8, None);
+            assert_eq!(cache.len(), 5);
+            assert_eq!(cache.stats().evictions, 4);
+            assert!((4..9).all(|key| cache.contains_key(&key)));
+
+            // replacing a key never starts a batch
+            cache.insert(8, 9, None);
+            assert_eq!(cache.len(), 5);
+        }
+
+        #[test]
+        fn test_max_bytes() {
+            let value = |len: usize| "x".repeat(len);
+            let small = entry_memory(&0u64, &value(10));
+            let mut cache: Simcache<u64, String, LRU<u64>> = Simcache::builder()
+                .max_bytes(3 * small)
+                .build();
+
+            for key in 0..3 {
+                cache.insert(key, value(10), None);
+            }
+            assert_eq!(cache.current_memory(), 3 * small);
+            cache.insert(3, value(10), None);
+            assert_eq!(cache.len(), 3);
+            assert!(!cache.contains_key(&0));
+
+            // a value twice the size needs room for both of its halves
+            let large = entry_memory(&0u64, &value(10 + small as usize));
+            assert_eq!(large, 2 * small);
+            cache.insert(4, value(10 + small as usize), None);
+            assert_eq!(cache.len(), 2);
+            assert_eq!(cache.current_memory(), 3 * small);
+
+            cache.remove(&4);
+            assert_eq!(cache.current_memory(), small);
+            cache.insert(5, value(10), None);
+            assert_eq!(cache.set_max_bytes(small), 1);
+            assert_eq!(cache.current_memory(), small);
+
+            // tracking without a limit only keeps count
+            let mut tracked: Simcache<u64, String, LRU<u64>> = Simcache::builder().track_memory().build();
+            tracked.insert(1, value(10), None);
+            assert_eq!(tracked.current_memory(), small);
+            tracked.clear();
+            assert_eq!(tracked.current_memory(), 0);
+            let untracked: Simcache<u64, String, LRU<u64>> = Simcache::new(1);
+            assert_eq!(untracked.current_memory(), 0);
+        }
+
+        #[test]
+        fn test_tags() {
+            let mut cache: Simcache<&str, u64, LRU<&str>> = Simcache::new(3);
+            cache.insert_with_tags("page-1", 1, None, &["product-7", "product-8"]);
+            cache.insert_with_tags("page-2", 2, None, &["product-7"]);
+            cache.insert_with_tags("page-3", 3, None, &["product-8"]);
+
+            assert_eq!(cache.invalidate_tag(&"product-7"), 2);
+            assert_eq!(cache.keys().collect::<Vec<_>>(), [&"page-3"]);
+            assert_eq!(cache.invalidate_tag(&"product-7"), 0);
+
+            // a replaced entry loses its old tags, and an evicted one leaves its tags behind
+            cache.insert("page-3", 30, None);
+            assert_eq!(cache.invalidate_tag(&"product-8"), 0);
+            cache.insert_with_tags("page-4", 4, None, &[9u32]);
+            cache.insert("page-5", 5, None);
+            cache.insert("page-6", 6, None);
+            assert!(!cache.contains_key(&"page-3"));
+            assert_eq!(cache.invalidate_tag(&9u32), 1);
+            assert_eq!(cache.len(), 2);
+
+            // tags are compared, not just their hashes, so one of another type or value that collides leaves the entry
+            cache.insert_with_tags("page-7", 7, None, &[1u64]);
+            assert_eq!(cache.invalidate_tag(&1u32), 0);
+            let hash = stable_hash(&1u64);
+            assert_eq!(cache.tags.keys(hash, &2u64), Vec::<&str>::new());
+            assert_eq!(cache.tags.keys(hash, &1u64), ["page-7"]);
+        }
+
+        #[test]
+        fn test_invalidate_range() {
+            for indexed in [false, true] {
+                let builder = Simcache::<String, u64, LRU<String>>::builder().max_capacity(10);
+                let mut cache = if indexed { builder.ordered_index().build() } else { builder.build() };
+                for key in ["user:1", "user:2", "user:10", "users", "post:1", "post:2"] {
+                    cache.insert(key.to_string(), 1, None);
+                }
+
+                assert_eq!(cache.invalidate_prefix("user:"), 3);
+                assert!(cache.contains_key("users"));
+                assert_eq!(cache.invalidate_prefix("user:"), 0);
+
+                assert_eq!(cache.invalidate_range("post:1".to_string().."post:2".to_string()), 1);
+                assert!(cache.contains_key("post:2"));
+                cache.remove("post:2");
+                cache.insert("post:3".to_string(), 1, None);
+                assert_eq!(cache.invalidate_range(.."q".to_string()), 1);
+                assert_eq!(cache.keys().collect::<Vec<_>>(), [&"users".to_string()]);
+            }
+        }
+
+        #[test]
+        fn test_entry_info() {
+            let clock = MockClock::new();
+            let mut cache: MockCache<LFU<&'static str>> = Simcache::builder()
+                .max_capacity(2)
+                .clock(clock.clone())
+                .build();
+            let inserted_at = crate::Clock::now(&clock);
+            cache.insert("a", "1", Some(Duration::from_secs(10)));
+            cache.insert("b", "2", None);
+
+            let info = cache.entry_info(&"a").unwrap();
+            assert_eq!(info.inserted_at, inserted_at);
+            assert_eq!((info.last_accessed, info.accesses), (None, 0));
+            assert_eq!(info.ttl_remaining, Some(Duration::from_secs(10)));
+            assert_eq!((info.weight, info.priority, info.pinned), (1, Priority::Normal, false));
+
+            clock.advance(Duration::from_secs(4));
+            cache.get(&"a");
+            cache.get(&"a");
+            let info = cache.entry_info(&"a").unwrap();
+            assert_eq!((info.last_accessed, info.accesses), (Some(crate::Clock::now(&clock)), 2));
+            assert_eq!(info.ttl_remaining, Some(Duration::from_secs(6)));
+            assert!(info.policy_score > cache.entry_info(&"b").unwrap().policy_score);
+            assert_eq!(cache.entry_info(&"b").unwrap().ttl_remaining, None);
+
+            cache.pin(&"b");
+            assert_eq!(cache.entry_info(&"b").unwrap().policy_score, None);
+            clock.advance(Duration::from_secs(7));
+            assert_eq!(cache.entry_info(&"a"), None);
+        }
+
+        #[test]
+        fn test_peek_eviction_candidates() {
+            let mut cache: Simcache<&str, u64, LRU<&str>> = Simcache::new(10);
+            for key in ["a", "b", "c", "d"] {
+                cache.insert(key, 1, None);
+            }
+            cache.insert_with_priority("low", 1, None, Priority::Low);
+            cache.get(&"a");
+            cache.pin(&"b");
+
+            assert_eq!(cache.peek_eviction_candidates(3), [&"low", &"c", &"d"]);
+            assert_eq!(cache.peek_eviction_candidates(10), [&"low", &"c", &"d", &"a"]);
+            assert!(cache.peek_eviction_candidates(0).is_empty());
+            // peeking changes nothing
+            cache.insert("e", 1, None);
+            assert_eq!(cache.peek_eviction_candidates(2), [&"low", &"c"]);
+        }
+
+        #[test]
+        fn test_snapshot() {
+            let clock = MockClock::new();
+            let mut cache: MockCache<LRU<&'static str>> = mock_builder(&clock).max_capacity(3).build();
+            cache.insert("a", "1", Some(Duration::from_secs(10)));
+            cache.insert("b", "2", None);
+            cache.insert("c", "3", Some(Duration::from_secs(1)));
+            cache.get(&"a");
+            clock.advance(Duration::from_secs(2));
+
+            // expired entries are left out, and the rest come in eviction order with what is left of their ttl
+            assert_eq!(cache.snapshot(), [("b", "2", None), ("a", "1", Some(Duration::from_secs(8)))]);
+
+            let mut standby: MockCache<LRU<&'static str>> = mock_builder(&clock).max_capacity(3).build();
+            assert_eq!(standby.warm(cache.snapshot()), 2);
+            standby.insert("d", "4", None);
+            standby.insert("e", "5", None);
+            assert_eq!(standby.peek(&"b"), None);
+
+            // a clone keeps the policy state and stats, and changes to it leave the original alone
+            let mut copy = cache.clone();
+            assert_eq!(copy.stats(), cache.stats());
+            copy.insert("d", "4", None);
+            copy.insert("e", "5", None);
+            assert_eq!(copy.peek(&"b"), None);
+            assert_eq!(copy.len(), 3);
+            assert_eq!(cache.peek(&"b"), Some(&"2"));
+            assert_eq!(cache.len(), 3);
+        }
+
+        #[test]
+        fn test_insert_until() {
+            let clock = MockClock::new();
+            let mut writer: MockCache<LRU<&'static str>> = mock_builder(&clock).max_capacity(3).build();
+            let mut reader: MockCache<LRU<&'static str>> = mock_builder(&clock).max_capacity(3).build();
+            writer.insert("a", "1", Some(Duration::from_secs(10)));
+            writer.insert("b", "2", None);
+            assert_eq!(writer.expires_at(&"b"), None);
+            assert_eq!(writer.expires_at(&"c"), None);
+
+            let expires_at = writer.expires_at(&"a").unwrap();
+            assert_eq!(expires_at, crate::Clock::wall_time(&clock) + Duration::from_secs(10));
+            clock.advance(Duration::from_secs(4));
+            assert_eq!(reader.insert_until("a", "1", expires_at), InsertOutcome::Inserted);
+            assert_eq!(reader.ttl_remaining(&"a"), Some(Duration::from_secs(6)));
+
+            clock.advance(Duration::from_secs(7));
+            assert_eq!(reader.get(&"a"), None);
+            assert_eq!(reader.insert_until("a", "1", expires_at), InsertOutcome::Rejected);
+            assert!(reader.is_empty());
+        }
+
+        #[test]
+        fn test_drain_expired() {
+            let clock = MockClock::new();
+            let mut cache: MockCache<LRU<&'static str>> = mock_builder(&clock).max_capacity(3).build();
+            cache.insert("a", "1", Some(Duration::from_secs(1)));
+            cache.insert("b", "2", Some(Duration::from_secs(1)));
+            cache.insert("c", "3", None);
+            assert!(cache.drain_expired().is_empty());
+
+            clock.advance(Duration::from_secs(2));
+            let mut drained = cache.drain_expired();
+            drained.sort();
+            assert_eq!(drained, [("a", "1"), ("b", "2")]);
+            assert_eq!(cache.len(), 1);
+            assert_eq!(cache.stats().expired, 2);
+            // the policy no longer tracks them, so the next eviction takes the live entry
+            assert_eq!(cache.peek_eviction_candidates(3), [&"c"]);
+        }
+
+        #[test]
+        fn test_next_expiration() {
+            let clock = MockClock::new();
+            let start = crate::Clock::now(&clock);
+            let mut cache: MockCache<LRU<&'static str>> = mock_builder(&clock).max_capacity(10).build();
+            assert_eq!(cache.next_expiration(), None);
+            cache.insert("a", "1", Some(Duration::from_secs(5)));
+            cache.insert("b", "2", Some(Duration::from_secs(2)));
+            cache.insert("c", "3", None);
+            assert_eq!(cache.next_expiration(), Some(start + Duration::from_secs(2)));
+
+            // moving or removing an entry leaves its old deadline behind in the index, which is skipped
+            cache.set_ttl(&"b", Some(Duration::from_secs(8)));
+            assert_eq!(cache.next_expiration(), Some(start + Duration::from_secs(5)));
+            cache.remove(&"a");
+            assert_eq!(cache.next_expiration(), Some(start + Duration::from_secs(8)));
+
+            clock.advance(Duration::from_secs(9));
+            assert_eq!(cache.next_expiration(), None);
+            assert_eq!(cache.purge_expired(), 1);
+            assert_eq!(cache.len(), 1);
+
+            // touching an entry over and over doesn't grow the index without bound
+            cache.insert("d", "4", Some(Duration::from_secs(1)));
+            for _ in 0..100 {
+                cache.touch(&"d");
+            }
+            assert!(cache.expiry.len() <= 2 * cache.len() + 16);
+            clock.advance(Duration::from_secs(2));
+            assert_eq!(cache.purge_expired(), 1);
+        }
+
+        #[test]
+        fn test_collection_traits() {
+            let mut cache: Simcache<u64, u64, LRU<u64>> = (0..5).map(|key| (key, key * 10)).collect();
+            assert_eq!(cache.capacity_limit(), CapacityLimit::Unbounded);
+            assert_eq!(cache.len(), 5);
+            assert_eq!(cache[&3], 30);
+            // indexing is a peek, so it isn't counted as a hit
+            assert_eq!(cache.stats().hits, 0);
+
+            let mut bounded: Simcache<u64, u64, LRU<u64>> = Simcache::new(3);
+            bounded.reserve(10);
+            assert!(bounded.capacity() >= 10);
+            bounded.extend([(11, 1), (12, 2), (13, 3), (14, 4)]);
+            assert_eq!(bounded.len(), 3);
+            assert!(!bounded.contains_key(&11));
+
+            cache.extend(bounded);
+            assert_eq!(cache.len(), 8);
+        }
+
+        #[test]
+        #[should_panic(expected = "indexed key should have a live entry")]
+        fn test_index_missing_key() {
+            let cache: Simcache<u64, u64, LRU<u64>> = Simcache::new(3);
+            let _ = cache[&1];
+        }
+
+        #[test]
+        fn test_doorkeeper() {
+            let mut cache: Simcache<&str, u64, LRU<&str>> = Simcache::builder().max_capacity(2).doorkeeper().build();
+            assert_eq!(cache.insert("a", 1, None), InsertOutcome::Inserted);
+            assert_eq!(cache.insert("b", 2, None), InsertOutcome::Inserted);
+            assert_eq!(cache.insert("a", 3, None), InsertOutcome::Replaced);
+
+            // a new key is turned away from a full cache once, then let in
+            assert_eq!(cache.insert("c", 3, None), InsertOutcome::Rejected);
+            assert_eq!(cache.len(), 2);
+            assert_eq!(cache.insert("c", 3, None), InsertOutcome::Inserted);
+            assert!(!cache.contains_key(&"b"));
+            assert_eq!(cache.stats().evictions, 1);
+        }
+
+        #[test]
+        fn test_insert_full() {
+            let clock = MockClock::new();
+            let mut cache: MockCache<LRU<&'static str>> = mock_builder(&clock).max_capacity(2).build();
+            let result = cache.insert_full("a", "1", None);
+            assert_eq!(result, InsertResult { outcome: InsertOutcome::Inserted, replaced: None, evicted: vec![], rejected: None });
+            cache.insert("b", "2", None);
+            assert_eq!(cache.insert_full("a", "3", None).replaced, Some("1"));
+
+            let result = cache.insert_full("c", "4", None);
+            assert_eq!(result.outcome, InsertOutcome::Inserted);
+            assert_eq!(result.evicted, [("b", "2")]);
+            assert_eq!(cache.stats().evictions, 1);
+
+            let mut empty: MockCache<LRU<&'static str>> = mock_builder(&clock).max_capacity(0).build();
+            let result = empty.insert_full("a", "1", None);
+            assert_eq!((result.outcome, result.rejected), (InsertOutcome::Rejected, Some("1")));
+        }
+
+        #[test]
+        fn test_pop_next() {
+            let clock = MockClock::new();
+            let mut cache: MockCache<LRU<&'static str>> = mock_builder(&clock).max_capacity(5).build();
+            cache.insert("a", "1", None);
+            cache.insert("b", "2", Some(Duration::from_secs(1)));
+            cache.insert("c", "3", None);
+            cache.insert("d", "4", None);
+            cache.get(&"a");
+
+            // a removed key is forgotten by the policy too, so it can't come up for eviction later
+            assert_eq!(cache.pop_key(&"c"), Some(("c", "3")));
+            assert_eq!(cache.peek_eviction_candidates(5), [&"b", &"d", &"a"]);
+
+            clock.advance(Duration::from_secs(2));
+            assert_eq!(cache.pop_next(), Some(("d", "4")));
+            assert_eq!(cache.pop_next(), Some(("a", "1")));
+            assert_eq!(cache.pop_next(), None);
+            assert!(cache.is_empty());
+            assert_eq!((cache.stats().evictions, cache.stats().expired), (2, 1));
+        }
+
+        #[test]
+        fn test_drain_ordered() {
+            let clock = MockClock::new();
+            let mut cache: MockCache<LRU<&'static str>> = mock_builder(&clock).max_capacity(5).build();
+            cache.insert("a", "1", None);
+            cache.insert("b", "2", None);
+            cache.insert("c", "3", Some(Duration::from_secs(1)));
+            cache.insert("d", "4", None);
+            cache.pin(&"b");
+            cache.get(&"a");
+            clock.advance(Duration::from_secs(2));
+
+            let drained: Vec<_> = cache.drain_ordered().collect();
+            assert_eq!(drained, [("b", "2"), ("a", "1"), ("d", "4")]);
+        }
+
+        #[test]
+        fn test_expired_lookup_leaves_policy() {
+            let clock = MockClock::new();
+            let mut cache: MockCache<FIFO<&'static str>> = mock_builder(&clock).max_capacity(2).build();
+            cache.insert("a", "1", Some(Duration::from_secs(1)));
+            cache.insert("b", "2", None);
+            clock.advance(Duration::from_secs(2));
+            assert_eq!(cache.get(&"a"), None);
+
+            // a comes back at the end of the queue rather than in its old place, so b is evicted first
+            cache.insert("a", "3", None);
+            assert_eq!(cache.peek_eviction_candidates(2), [&"b", &"a"]);
+            cache.insert("c", "4", None);
+            assert_eq!(cache.get(&"a"), Some(&"3"));
+            assert!(!cache.contains_key(&"b"));
+
+            // a reinserted key starts over rather than keeping the uses from before it expired
+            let mut cache: MockCache<LFU<&'static str>> = mock_builder(&clock).max_capacity(2).build();
+            cache.insert("hot", "1", Some(Duration::from_secs(1)));
+            for _ in 0..10 {
+                cache.get(&"hot");
+            }
+            clock.advance(Duration::from_secs(2));
+            assert_eq!(cache.get(&"hot"), None);
+            cache.insert("warm", "2", None);
+            for _ in 0..3 {
+                cache.get(&"warm");
+            }
+            cache.insert("hot", "3", None);
+            cache.insert("new", "4", None);
+            assert!(cache.contains_key(&"warm"));
+            assert!(!cache.contains_key(&"hot"));
+        }
+
+        #[test]
+        fn test_lifetime_stats() {
+            let clock = MockClock::new();
+            let mut cache: MockCache<LRU<&'static str>> = mock_builder(&clock).max_capacity(2).build();
+            cache.insert("a", "1", Some(Duration::from_secs(10)));
+            cache.insert("b", "2", None);
+            clock.advance(Duration::from_secs(3));
+
+            // a is evicted with 7 seconds to go, then b with none ever
+            cache.insert("c", "3", Some(Duration::from_secs(1)));
+            cache.insert("d", "4", None);
+            clock.advance(Duration::from_secs(5));
+            assert_eq!(cache.purge_expired(), 1);
+
+            let lifetimes = cache.lifetime_stats();
+            assert_eq!((lifetimes.evicted_without_ttl, lifetimes.expired), (1, 1));
+            assert_eq!(lifetimes.ttl_left_at_eviction.quantile(0.5), Some(Duration::from_millis(8192)));
+            // c lasted the second of its ttl, not the five until it was purged
+            assert_eq!(lifetimes.lifetimes.count(), 3);
+            assert_eq!(lifetimes.lifetimes.quantile(0.0), Some(Duration::from_millis(1024)));
+            assert_eq!(lifetimes.lifetimes.quantile(1.0), Some(Duration::from_millis(4096)));
+            assert_eq!(lifetimes.evicted_before_expiry(), 0.5);
+
+            cache.reset_stats();
+            assert_eq!(cache.lifetime_stats(), LifetimeStats::default());
+        }
+
+        #[test]
+        fn test_try_insert() {
+            let clock = MockClock::new();
+            let mut cache: MockCache<LRU<&'static str>> = mock_builder(&clock).max_capacity(1).doorkeeper().build();
+            assert_eq!(cache.try_evict(), Err(SimcacheError::NothingToEvict));
+            assert_eq!(cache.try_insert("a", "1", None), Ok(InsertOutcome::Inserted));
+            assert_eq!(cache.try_insert("a", "2", None), Ok(InsertOutcome::Replaced));
+
+            // the doorkeeper turns b away the first time the full cache sees it
+            assert_eq!(cache.try_insert("b", "3", None), Err(SimcacheError::Rejected));
+            assert_eq!(cache.try_evict(), Ok(("a", "2")));
+
+            let mut empty: MockCache<LRU<&'static str>> = mock_builder(&clock).max_capacity(0).build();
+            assert_eq!(empty.try_insert("a", "1", None), Err(SimcacheError::ZeroCapacity));
+
+            // a ttl too long to add to the clock means the entry never expires
+            let mut cache: MockCache<LRU<&'static str>> = mock_builder(&clock).max_capacity(2).time_to_idle(Duration::MAX).build();
+            assert_eq!(cache.try_insert("a", "1", Some(Duration::MAX)), Ok(InsertOutcome::Inserted));
+            assert!(cache.set_ttl(&"a", Some(Duration::MAX)));
+            assert!(cache.touch(&"a"));
+            clock.advance(Duration::from_secs(1 << 40));
+            assert_eq!(cache.get(&"a"), Some(&"1"));
+            let mut cache: Simcache<u32, u32, LRU<u32>> = Simcache::builder().max_capacity(1).ttl_jitter(50).build();
+            assert_eq!(cache.try_insert(1, 1, Some(Duration::MAX)), Ok(InsertOutcome::Inserted));
+        }
+
+        #[test]
+        fn test_scan_mode() {
+            let clock = MockClock::new();
+            let mut cache: MockCache<LRU<&'static str>> = mock_builder(&clock).max_capacity(4).build();
+            cache.insert("a", "1", None);
+            cache.insert("b", "2", None);
+            cache.insert("c", "3", None);
+
+            // each scanned key makes room by evicting the one before it, and an overwrite of a key in use stays hot
+            {
+                let mut scan = cache.scan_mode();
+                for key in ["s1", "s2", "s3"] {
+                    scan.insert(key, "s", None);
+                }
+                scan.insert("a", "4", None);
+            }
+            assert_eq!(cache.peek_eviction_candidates(4), [&"s3", &"b", &"c", &"a"]);
+
+            cache.insert("d", "5", None);
+            assert_eq!(cache.insert_transient("t", "6", None), InsertOutcome::Inserted);
+            assert_eq!(cache.peek_eviction_candidates(4), [&"t", &"c", &"a", &"d"]);
+        }
+
+        #[test]
+        fn test_buffer_accesses() {
+            let clock = MockClock::new();
+            let mut cache: MockCache<LRU<&'static str>> = mock_builder(&clock).max_capacity(3).buffer_accesses(4).build();
+            cache.insert("a", "1", None);
+            cache.insert("b", "2", None);
+            cache.insert("c", "3", None);
+            cache.get(&"a");
+            cache.get(&"b");
+
+            // the hits wait in the buffer until the pending tasks run
+            assert_eq!(cache.peek_eviction_candidates(3), [&"a", &"b", &"c"]);
+            assert_eq!(cache.run_pending_tasks(), 0);
+            assert_eq!(cache.peek_eviction_candidates(3), [&"c", &"a", &"b"]);
+
+            // a buffered hit on a key removed since is dropped, and an insert catches the policy up first
+            cache.get(&"c");
+            cache.remove(&"c");
+            cache.insert("d", "4", None);
+            cache.get(&"a");
+            cache.insert("e", "5", None);
+            assert!(!cache.contains_key(&"b"));
+            assert_eq!(cache.peek_eviction_candidates(3), [&"d", &"a", &"e"]);
+
+            cache.insert("f", "6", Some(Duration::from_secs(1)));
+            clock.advance(Duration::from_secs(2));
+            assert_eq!(cache.run_pending_tasks(), 1);
+            assert_eq!(cache.len(), 2);
+
+            // every hit on a key counts, though the key is only buffered once, and removing it drops its hits
+            let mut cache: MockCache<LFU<&'static str>> = mock_builder(&clock).max_capacity(3).buffer_accesses(100).build();
+            cache.insert("a", "1", None);
+            cache.insert("b", "2", None);
+            cache.insert("c", "3", None);
+            for _ in 0..3 {
+                cache.get(&"a");
+            }
+            cache.get(&"b");
+            cache.get(&"c");
+            cache.remove(&"c");
+            assert_eq!(cache.pending, ["a", "b", "c"]);
+            cache.run_pending_tasks();
+            assert_eq!(cache.entry_info(&"a").and_then(|info| info.policy_score), Some(4));
+            assert_eq!(cache.entry_info(&"b").and_then(|info| info.policy_score), Some(2));
+
+            // shrinking catches the policy up before it picks what to evict
+            cache.insert("c", "3", None);
+            cache.get(&"c");
+            cache.get(&"c");
+            assert_eq!(cache.set_max_capacity(2), 1);
+            assert!(cache.contains_key(&"c") && !cache.contains_key(&"b"));
+        }
+    }
@@ -0,0 +1,265 @@
+//! Saving a cache's contents to a snapshot and loading them back
+
+use std::hash::{BuildHasher, Hash};
+use std::io::{self, Read, Write};
+use std::time::{Duration, UNIX_EPOCH};
+#[cfg(feature = "encryption")]
+use crate::chacha::{self, NONCE_LEN};
+use crate::persist::{invalid, Persist};
+use crate::{Clock, EvictionPolicy, Priority, Simcache};
+
+// a snapshot starts with this and a format version, then the entry count and the entries
+// in the order they should be reinserted, which is the order the policy would evict them in
+// version 2 added each entry's policy score; version 1 snapshots still load, with their scores starting over
+const MAGIC: &[u8; 8] = b"SIMCACHE";
+const VERSION: u8 = 2;
+// an encrypted snapshot is this, a random nonce, then a whole snapshot sealed with the caller's key,
+// with the magic as associated data
+#[cfg(feature = "encryption")]
+const ENCRYPTED_MAGIC: &[u8; 8] = b"SIMCRYPT";
+
+// a nonce from the operating system's random source, so that no two snapshots share one
+#[cfg(all(feature = "encryption", unix))]
+fn random_nonce() -> io::Result<[u8; NONCE_LEN]> {
+    let mut nonce = [0; NONCE_LEN];
+    std::fs::File::open("/dev/urandom")?.read_exact(&mut nonce)?;
+    Ok(nonce)
+}
+
+#[cfg(all(feature = "encryption", not(unix)))]
+fn random_nonce() -> io::Result<[u8; NONCE_LEN]> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "encrypted snapshots need a random source, which is only read on unix"))
+}
+
+fn write_duration<W: Write>(w: &mut W, duration: Option<Duration>) -> io::Result<()> {
+    duration.map(|d| (d.as_secs(), d.subsec_nanos())).write_to(w)
+}
+
+fn read_duration<R: Read>(r: &mut R) -> io::Result<Option<Duration>> {
+    let duration: Option<(u64, u32)> = Persist::read_from(r)?;
+    duration
+        .map(|(secs, nanos)| {
+            if nanos >= 1_000_000_000 {
+                return Err(invalid("nanoseconds should be less than a second"));
+            }
+            Ok(Duration::new(secs, nanos))
+        })
+        .transpose()
+}
+
+fn read_priority<R: Read>(r: &mut R) -> io::Result<Priority> {
+    match u8::read_from(r)? {
+        0 => Ok(Priority::Low),
+        1 => Ok(Priority::Normal),
+        2 => Ok(Priority::High),
+        _ => Err(invalid("priority should be 0, 1, or 2")),
+    }
+}
+
+impl<K, V, E, S, C> Simcache<K, V, E, S, C>
+where
+    K: Eq + Hash + Clone + Persist,
+    V: Persist,
+    E: EvictionPolicy<K>,
+    S: BuildHasher,
+    C: Clock,
+    {
+        /// write every live entry to w, with its ttl, priority, pin, and eviction policy score
+        /// expiry times are written as wall clock times so that they survive a restart,
+        /// and entries are written in eviction order so that loading them recreates it
+        pub fn save_to_writer<W: Write>(&self, mut w: W) -> io::Result<()> {
+            let now = self.clock.now();
+            let wall_now = self.clock.wall_time();
+            let keys = self.keys_in_insert_order();
+
+            w.write_all(MAGIC)?;
+            VERSION.write_to(&mut w)?;
+            keys.len().write_to(&mut w)?;
+            for key in keys {
+                let entry = &self.store[key];
+                key.write_to(&mut w)?;
+                entry.value.write_to(&mut w)?;
+                write_duration(&mut w, entry.ttl)?;
+                let expires_at = entry.expires_at.map(|at| wall_now + at.saturating_duration_since(now));
+                write_duration(&mut w, expires_at.map(|at| at.duration_since(UNIX_EPOCH).unwrap_or_default()))?;
+                (entry.priority as u8).write_to(&mut w)?;
+                entry.pinned.write_to(&mut w)?;
+                let score = if entry.pinned { None } else { self.policies.get(entry.priority).and_then(|policy| policy.score(key)) };
+                score.write_to(&mut w)?;
+            }
+            w.flush()
+        }
+
+        /// insert the entries of a snapshot written by save_to_writer, returning how many were loaded
+        /// entries that expired since the snapshot was taken are skipped, and the others keep
+        /// what was left of their ttl; eviction policy state is rebuilt by inserting in eviction order,
+        /// which restores recency, and by handing each key's saved score to the policy's on_restore,
+        /// which restores use counts for LFU; a snapshot that is corrupt returns an error rather than panicking
+        pub fn load_from_reader<R: Read>(&mut self, mut r: R) -> io::Result<usize> {
+            let mut magic = [0; 8];
+            r.read_exact(&mut magic)?;
+            if &magic != MAGIC {
+                return Err(invalid("not a simcache snapshot"));
+            }
+            let version = u8::read_from(&mut r)?;
+            if !(1..=VERSION).contains(&version) {
+                return Err(invalid("unsupported snapshot version"));
+            }
+            let wall_now = self.clock.wall_time();
+            let mut loaded = 0;
+            for _ in 0..usize::read_from(&mut r)? {
+                let key = K::read_from(&mut r)?;
+                let value = V::read_from(&mut r)?;
+                let ttl = read_duration(&mut r)?;
+                let expires_at = read_duration(&mut r)?
+                    .map(|since_epoch| UNIX_EPOCH.checked_add(since_epoch).ok_or_else(|| invalid("expiry out of range")))
+                    .transpose()?;
+                let priority = read_priority(&mut r)?;
+                let pinned = bool::read_from(&mut r)?;
+                let score: Option<u64> = if version >= 2 { Persist::read_from(&mut r)? } else { None };
+
+                let remaining = match expires_at.map(|at| at.duration_since(wall_now)) {
+                    Some(Err(_)) => continue,
+                    Some(Ok(remaining)) => Some(remaining),
+                    None => None,
+                };
+                if self.try_store(key.clone(), value, remaining, Some(priority)).is_err() {
+                    continue;
+                }
+                // touch restarts the original ttl, not what was left of it
+                if let Some(entry) = self.store.get_mut(&key) {
+                    entry.ttl = ttl;
+                    if let (Some(score), false) = (score, entry.pinned) {
+                        self.policies.get_mut(entry.priority).on_restore(&key, score);
+                    }
+                }
+                if pinned {
+                    self.pin(&key);
+                }
+                loaded += 1;
+            }
+            Ok(loaded)
+        }
+
+        /// like save_to_writer, but encrypt and authenticate the snapshot with ChaCha20-Poly1305 under the key,
+        /// so entries written to disk can't be read or altered without it
+        #[cfg(feature = "encryption")]
+        pub fn save_encrypted_to_writer<W: Write>(&self, key: &[u8; 32], mut w: W) -> io::Result<()> {
+            let mut plaintext = Vec::new();
+            self.save_to_writer(&mut plaintext)?;
+            let nonce = random_nonce()?;
+            w.write_all(ENCRYPTED_MAGIC)?;
+            w.write_all(&nonce)?;
+            w.write_all(&chacha::seal(key, &nonce, ENCRYPTED_MAGIC, &plaintext))?;
+            w.flush()
+        }
+
+        /// insert the entries of a snapshot written by save_encrypted_to_writer, returning how many were loaded
+        /// nothing is loaded from a snapshot that doesn't authenticate under the key
+        #[cfg(feature = "encryption")]
+        pub fn load_encrypted_from_reader<R: Read>(&mut self, key: &[u8; 32], mut r: R) -> io::Result<usize> {
+            let mut header = [0; 8 + NONCE_LEN];
+            r.read_exact(&mut header)?;
+            let (magic, nonce) = header.split_at(8);
+            if magic != ENCRYPTED_MAGIC {
+                return Err(invalid("not an encrypted simcache snapshot"));
+            }
+            let nonce: [u8; NONCE_LEN] = nonce.try_into().expect("the header should hold a whole nonce");
+            let mut sealed = Vec::new();
+            r.read_to_end(&mut sealed)?;
+            let plaintext = chacha::open(key, &nonce, ENCRYPTED_MAGIC, &sealed)
+                .ok_or_else(|| invalid("snapshot failed authentication; the key is wrong or the snapshot was altered"))?;
+            self.load_from_reader(plaintext.as_slice())
+        }
+    }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eviction::{LFU, LRU};
+
+    #[test]
+    fn snapshot_test() {
+        let mut cache: Simcache<String, u32, LRU<String>> = Simcache::new(3);
+        cache.insert("a".to_string(), 1, Some(Duration::from_secs(60)));
+        cache.insert("b".to_string(), 2, None);
+        cache.insert_with_priority("c".to_string(), 3, None, Priority::High);
+        cache.get("a");
+        cache.pin("b");
+
+        let mut bytes = Vec::new();
+        cache.save_to_writer(&mut bytes).expect("writing to a Vec should not fail");
+
+        let mut restored: Simcache<String, u32, LRU<String>> = Simcache::new(3);
+        assert_eq!(restored.load_from_reader(bytes.as_slice()).expect("snapshot should load"), 3);
+        assert_eq!(restored.peek("a"), Some(&1));
+        assert!(restored.ttl_remaining("a").is_some_and(|ttl| ttl <= Duration::from_secs(60)));
+        assert!(restored.is_pinned("b"));
+        assert_eq!(restored.priority("c"), Some(Priority::High));
+
+        // recency survives the round trip, so a is still the last normal key to go
+        let order: Vec<&str> = restored.iter_eviction_order().map(|(key, _)| key.as_str()).collect();
+        assert_eq!(order, vec!["a", "c"]);
+
+        assert!(restored.load_from_reader(&b"not a snapshot"[..]).is_err());
+
+        // use counts come back, so the key used most is still the last to go
+        let mut cache: Simcache<String, u32, LFU<String>> = Simcache::new(3);
+        cache.insert("a".to_string(), 1, None);
+        cache.insert("b".to_string(), 2, None);
+        for _ in 0..5 {
+            cache.get("a");
+        }
+        let mut bytes = Vec::new();
+        cache.save_to_writer(&mut bytes).expect("writing to a Vec should not fail");
+        let mut restored: Simcache<String, u32, LFU<String>> = Simcache::new(2);
+        restored.load_from_reader(bytes.as_slice()).expect("snapshot should load");
+        assert_eq!(restored.entry_info("a").and_then(|info| info.policy_score), Some(6));
+        restored.insert("c".to_string(), 3, None);
+        restored.get("c");
+        restored.insert("d".to_string(), 4, None);
+        assert!(restored.contains_key("a"));
+        assert!(!restored.contains_key("b"));
+    }
+
+    #[test]
+    fn corrupt_snapshot_test() {
+        // one entry whose expiry is u64::MAX seconds after the epoch
+        let mut bytes = MAGIC.to_vec();
+        VERSION.write_to(&mut bytes).unwrap();
+        1usize.write_to(&mut bytes).unwrap();
+        "a".to_string().write_to(&mut bytes).unwrap();
+        1u32.write_to(&mut bytes).unwrap();
+        write_duration(&mut bytes, None).unwrap();
+        write_duration(&mut bytes, Some(Duration::new(u64::MAX, 0))).unwrap();
+        1u8.write_to(&mut bytes).unwrap();
+        false.write_to(&mut bytes).unwrap();
+        None::<u64>.write_to(&mut bytes).unwrap();
+
+        let mut cache: Simcache<String, u32, LRU<String>> = Simcache::new(3);
+        let err = cache.load_from_reader(bytes.as_slice()).expect_err("an expiry past what SystemTime holds should not load");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(cache.is_empty());
+    }
+
+    #[cfg(feature = "encryption")]
+    #[test]
+    fn encrypted_snapshot_test() {
+        let mut cache: Simcache<String, String, LRU<String>> = Simcache::new(3);
+        cache.insert("email".to_string(), "someone@example.com".to_string(), None);
+        let key = [7; 32];
+
+        let mut bytes = Vec::new();
+        cache.save_encrypted_to_writer(&key, &mut bytes).expect("writing to a Vec should not fail");
+        assert!(!bytes.windows(7).any(|window| window == b"example"));
+
+        let mut restored: Simcache<String, String, LRU<String>> = Simcache::new(3);
+        assert!(restored.load_encrypted_from_reader(&[8; 32], bytes.as_slice()).is_err());
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+        assert!(restored.load_encrypted_from_reader(&key, bytes.as_slice()).is_err());
+        bytes[last] ^= 1;
+        assert_eq!(restored.load_encrypted_from_reader(&key, bytes.as_slice()).expect("snapshot should load"), 1);
+        assert_eq!(restored.peek("email").map(String::as_str), Some("someone@example.com"));
+    }
+}
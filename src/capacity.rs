@@ -0,0 +1,35 @@
+//! Limits on the number of entries a cache holds
+
+/// the most entries a cache may hold at once
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum CapacityLimit {
+    /// never hold more than this many entries, so a limit of 0 caches nothing
+    Bounded(usize),
+    /// never evict to make room for an entry, only the weight limit and expiry remove them
+    #[default]
+    Unbounded,
+}
+
+impl CapacityLimit {
+    /// return the most entries allowed, usize::MAX when unbounded
+    pub fn max_entries(&self) -> usize {
+        match self {
+            CapacityLimit::Bounded(max) => *max,
+            CapacityLimit::Unbounded => usize::MAX,
+        }
+    }
+
+    /// return true if a cache holding len entries has no room for another
+    pub fn is_full(&self, len: usize) -> bool {
+        match self {
+            CapacityLimit::Bounded(max) => len >= *max,
+            CapacityLimit::Unbounded => false,
+        }
+    }
+}
+
+impl From<usize> for CapacityLimit {
+    fn from(max: usize) -> Self {
+        CapacityLimit::Bounded(max)
+    }
+}
@@ -0,0 +1,215 @@
+//! ChaCha20-Poly1305 authenticated encryption as in RFC 8439, for encrypted snapshots
+
+// a sealed message is the ciphertext followed by a tag of this many bytes
+pub(crate) const TAG_LEN: usize = 16;
+pub(crate) const NONCE_LEN: usize = 12;
+
+fn quarter_round(state: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize) {
+    state[a] = state[a].wrapping_add(state[b]);
+    state[d] = (state[d] ^ state[a]).rotate_left(16);
+    state[c] = state[c].wrapping_add(state[d]);
+    state[b] = (state[b] ^ state[c]).rotate_left(12);
+    state[a] = state[a].wrapping_add(state[b]);
+    state[d] = (state[d] ^ state[a]).rotate_left(8);
+    state[c] = state[c].wrapping_add(state[d]);
+    state[b] = (state[b] ^ state[c]).rotate_left(7);
+}
+
+fn le32(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+fn block(key: &[u8; 32], counter: u32, nonce: &[u8; NONCE_LEN]) -> [u8; 64] {
+    let mut initial = [0u32; 16];
+    initial[..4].copy_from_slice(&[0x61707865, 0x3320646e, 0x79622d32, 0x6b206574]);
+    for i in 0..8 {
+        initial[4 + i] = le32(&key[i * 4..]);
+    }
+    initial[12] = counter;
+    for i in 0..3 {
+        initial[13 + i] = le32(&nonce[i * 4..]);
+    }
+    let mut state = initial;
+    for _ in 0..10 {
+        quarter_round(&mut state, 0, 4, 8, 12);
+        quarter_round(&mut state, 1, 5, 9, 13);
+        quarter_round(&mut state, 2, 6, 10, 14);
+        quarter_round(&mut state, 3, 7, 11, 15);
+        quarter_round(&mut state, 0, 5, 10, 15);
+        quarter_round(&mut state, 1, 6, 11, 12);
+        quarter_round(&mut state, 2, 7, 8, 13);
+        quarter_round(&mut state, 3, 4, 9, 14);
+    }
+    let mut out = [0; 64];
+    for i in 0..16 {
+        out[i * 4..i * 4 + 4].copy_from_slice(&state[i].wrapping_add(initial[i]).to_le_bytes());
+    }
+    out
+}
+
+// xor data with the key stream, starting from block 1 as block 0 keys the tag
+fn apply_key_stream(key: &[u8; 32], nonce: &[u8; NONCE_LEN], data: &mut [u8]) {
+    for (i, chunk) in data.chunks_mut(64).enumerate() {
+        let stream = block(key, 1 + i as u32, nonce);
+        for (byte, k) in chunk.iter_mut().zip(stream) {
+            *byte ^= k;
+        }
+    }
+}
+
+// poly1305 in 26 bit limbs, after poly1305-donna
+fn poly1305(key: &[u8; 32], message: &[u8]) -> [u8; TAG_LEN] {
+    const MASK: u32 = 0x3ffffff;
+    let r = [
+        le32(&key[0..]) & 0x3ffffff,
+        (le32(&key[3..]) >> 2) & 0x3ffff03,
+        (le32(&key[6..]) >> 4) & 0x3ffc0ff,
+        (le32(&key[9..]) >> 6) & 0x3f03fff,
+        (le32(&key[12..]) >> 8) & 0x00fffff,
+    ];
+    let s = [r[1] * 5, r[2] * 5, r[3] * 5, r[4] * 5];
+    let mut h = [0u32; 5];
+
+    for chunk in message.chunks(16) {
+        let mut m = [0u8; 17];
+        m[..chunk.len()].copy_from_slice(chunk);
+        m[chunk.len()] = 1;
+        let hibit = if chunk.len() == 16 { 1 << 24 } else { 0 };
+        h[0] += le32(&m[0..]) & MASK;
+        h[1] += (le32(&m[3..]) >> 2) & MASK;
+        h[2] += (le32(&m[6..]) >> 4) & MASK;
+        h[3] += (le32(&m[9..]) >> 6) & MASK;
+        h[4] += (le32(&m[12..]) >> 8) | hibit;
+
+        let [h0, h1, h2, h3, h4] = h.map(u64::from);
+        let [r0, r1, r2, r3, r4] = r.map(u64::from);
+        let [s1, s2, s3, s4] = s.map(u64::from);
+        let mut d = [
+            h0 * r0 + h1 * s4 + h2 * s3 + h3 * s2 + h4 * s1,
+            h0 * r1 + h1 * r0 + h2 * s4 + h3 * s3 + h4 * s2,
+            h0 * r2 + h1 * r1 + h2 * r0 + h3 * s4 + h4 * s3,
+            h0 * r3 + h1 * r2 + h2 * r1 + h3 * r0 + h4 * s4,
+            h0 * r4 + h1 * r3 + h2 * r2 + h3 * r1 + h4 * r0,
+        ];
+        for i in 0..4 {
+            d[i + 1] += d[i] >> 26;
+            h[i] = d[i] as u32 & MASK;
+        }
+        h[4] = d[4] as u32 & MASK;
+        h[0] += (d[4] >> 26) as u32 * 5;
+        h[1] += h[0] >> 26;
+        h[0] &= MASK;
+    }
+
+    // carry fully, then subtract p = 2^130 - 5 if h is at least p
+    let mut carry = 0;
+    for limb in h.iter_mut().skip(1) {
+        *limb += carry;
+        carry = *limb >> 26;
+        *limb &= MASK;
+    }
+    h[0] += carry * 5;
+    carry = h[0] >> 26;
+    h[0] &= MASK;
+    h[1] += carry;
+
+    let mut g = [0u32; 5];
+    carry = 5;
+    for i in 0..5 {
+        g[i] = h[i].wrapping_add(carry);
+        carry = g[i] >> 26;
+        g[i] &= MASK;
+    }
+    g[4] = g[4].wrapping_add(carry << 26).wrapping_sub(1 << 26);
+    let use_g = (g[4] >> 31).wrapping_sub(1);
+    for i in 0..5 {
+        h[i] = (h[i] & !use_g) | (g[i] & use_g);
+    }
+
+    let words = [
+        h[0] | (h[1] << 26),
+        (h[1] >> 6) | (h[2] << 20),
+        (h[2] >> 12) | (h[3] << 14),
+        (h[3] >> 18) | (h[4] << 8),
+    ];
+    let mut tag = [0; TAG_LEN];
+    let mut carry = 0u64;
+    for i in 0..4 {
+        let sum = words[i] as u64 + le32(&key[16 + i * 4..]) as u64 + carry;
+        tag[i * 4..i * 4 + 4].copy_from_slice(&(sum as u32).to_le_bytes());
+        carry = sum >> 32;
+    }
+    tag
+}
+
+fn tag(key: &[u8; 32], nonce: &[u8; NONCE_LEN], aad: &[u8], ciphertext: &[u8]) -> [u8; TAG_LEN] {
+    let mut one_time_key = [0; 32];
+    one_time_key.copy_from_slice(&block(key, 0, nonce)[..32]);
+    let mut mac_data = Vec::with_capacity(aad.len() + ciphertext.len() + 48);
+    for part in [aad, ciphertext] {
+        mac_data.extend_from_slice(part);
+        mac_data.resize(mac_data.len().next_multiple_of(16), 0);
+    }
+    mac_data.extend_from_slice(&(aad.len() as u64).to_le_bytes());
+    mac_data.extend_from_slice(&(ciphertext.len() as u64).to_le_bytes());
+    poly1305(&one_time_key, &mac_data)
+}
+
+/// encrypt plaintext and return it with a tag covering it and aad
+/// a nonce must never be used twice with the same key
+pub(crate) fn seal(key: &[u8; 32], nonce: &[u8; NONCE_LEN], aad: &[u8], plaintext: &[u8]) -> Vec<u8> {
+    let mut sealed = plaintext.to_vec();
+    apply_key_stream(key, nonce, &mut sealed);
+    let tag = tag(key, nonce, aad, &sealed);
+    sealed.extend_from_slice(&tag);
+    sealed
+}
+
+/// check the tag of a sealed message and return its plaintext, or None if it or aad was altered or the key is wrong
+pub(crate) fn open(key: &[u8; 32], nonce: &[u8; NONCE_LEN], aad: &[u8], sealed: &[u8]) -> Option<Vec<u8>> {
+    let ciphertext_len = sealed.len().checked_sub(TAG_LEN)?;
+    let (ciphertext, received) = sealed.split_at(ciphertext_len);
+    let expected = tag(key, nonce, aad, ciphertext);
+    // compared without an early exit, so the time taken says nothing about where the tags differ
+    if expected.iter().zip(received).fold(0, |diff, (a, b)| diff | (a ^ b)) != 0 {
+        return None;
+    }
+    let mut plaintext = ciphertext.to_vec();
+    apply_key_stream(key, nonce, &mut plaintext);
+    Some(plaintext)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(s: &str) -> Vec<u8> {
+        (0..s.len()).step_by(2).map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap()).collect()
+    }
+
+    #[test]
+    fn chacha_test() {
+        // the poly1305 and aead test vectors of RFC 8439, sections 2.5.2 and 2.8.2
+        let poly_key: [u8; 32] = hex("85d6be7857556d337f4452fe42d506a80103808afb0db2fd4abff6af4149f51b").try_into().unwrap();
+        assert_eq!(poly1305(&poly_key, b"Cryptographic Forum Research Group").to_vec(), hex("a8061dc1305136c6c22b8baf0c0127a9"));
+
+        let key: [u8; 32] = (0x80..0xa0).collect::<Vec<u8>>().try_into().unwrap();
+        let nonce: [u8; NONCE_LEN] = hex("070000004041424344454647").try_into().unwrap();
+        let aad = hex("50515253c0c1c2c3c4c5c6c7");
+        let plaintext = b"Ladies and Gentlemen of the class of '99: If I could offer you only one tip for the future, sunscreen would be it.";
+        let sealed = seal(&key, &nonce, &aad, plaintext);
+        assert_eq!(
+            sealed,
+            hex("d31a8d34648e60db7b86afbc53ef7ec2a4aded51296e08fea9e2b5a736ee62d63dbea45e8ca9671282fafb69da92728b\
+                 1a71de0a9e060b2905d6a5b67ecd3b3692ddbd7f2d778b8c9803aee328091b58fab324e4fad675945585808b4831d7bc\
+                 3ff4def08e4b7a9de576d26586cec64b61161ae10b594f09e26a7e902ecbd0600691")
+        );
+        assert_eq!(open(&key, &nonce, &aad, &sealed).as_deref(), Some(&plaintext[..]));
+
+        let mut tampered = sealed.clone();
+        tampered[3] ^= 1;
+        assert_eq!(open(&key, &nonce, &aad, &tampered), None);
+        assert_eq!(open(&key, &nonce, b"other", &sealed), None);
+        assert_eq!(open(&key, &nonce, &aad, &sealed[..10]), None);
+    }
+}
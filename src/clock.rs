@@ -0,0 +1,93 @@
+//! Time sources for expiry
+//!
+//! Every expiry check asks the cache's clock for the current time, so swapping
+//! in a MockClock lets tests move time forward without sleeping. Expiry is
+//! always decided on the monotonic time; the wall clock time is only used to
+//! turn expiry times into `SystemTime`s that mean the same in another process
+//! or after a restart, and back.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+/// a source of the current time, which must never go backwards
+pub trait Clock {
+    fn now(&self) -> Instant;
+
+    /// the current wall clock time, which can jump unlike now
+    fn wall_time(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// the real monotonic clock
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// a clock that only moves when told to
+/// clones share the same time, so a test can keep one and hand another to the cache
+#[derive(Debug, Clone)]
+pub struct MockClock {
+    start: Instant,
+    wall_start: SystemTime,
+    elapsed_nanos: Arc<AtomicU64>,
+}
+
+impl MockClock {
+    /// return a clock stopped at the current time
+    pub fn new() -> Self {
+        MockClock { start: Instant::now(), wall_start: SystemTime::now(), elapsed_nanos: Arc::new(AtomicU64::new(0)) }
+    }
+
+    fn elapsed(&self) -> Duration {
+        Duration::from_nanos(self.elapsed_nanos.load(Ordering::SeqCst))
+    }
+
+    /// move the clock forward
+    pub fn advance(&self, duration: Duration) {
+        let nanos = u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX);
+        self.elapsed_nanos.fetch_add(nanos, Ordering::SeqCst);
+    }
+}
+
+impl Default for MockClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        self.start + self.elapsed()
+    }
+
+    fn wall_time(&self) -> SystemTime {
+        self.wall_start + self.elapsed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mock_clock_test() {
+        let clock = MockClock::new();
+        let shared = clock.clone();
+        let start = clock.now();
+        let wall_start = clock.wall_time();
+
+        assert_eq!(clock.now(), start);
+
+        shared.advance(Duration::from_secs(5));
+
+        assert_eq!(clock.now() - start, Duration::from_secs(5));
+        assert_eq!(clock.wall_time(), wall_start + Duration::from_secs(5));
+    }
+}
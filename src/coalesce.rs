@@ -0,0 +1,151 @@
+//! Sharing one load between every caller missing on the same key
+//!
+//! The first caller to miss on a key leads its load, and callers missing on it
+//! while that runs wait for the result, blocking with `Load::wait` or awaiting
+//! `WaitLoad`. If the leader gives up, by panicking or by its future being
+//! dropped, the waiters are told the load was abandoned and one of them leads
+//! its own. Used by both the sharded and the async cache.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::hash::Hash;
+use std::pin::Pin;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::task::{Context, Poll, Waker};
+use std::thread::{self, ThreadId};
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().expect("in-flight load mutex should not be poisoned")
+}
+
+/// the result of a load in progress, which the callers missing on the same key wait for
+pub(crate) struct Load<V> {
+    leader: ThreadId,
+    state: Mutex<LoadState<V>>,
+    done: Condvar,
+}
+
+struct LoadState<V> {
+    // None while loading, then Some(None) if the load was abandoned
+    result: Option<Option<V>>,
+    wakers: Vec<Waker>,
+}
+
+impl<V: Clone> Load<V> {
+    fn new() -> Self {
+        Load {
+            leader: thread::current().id(),
+            state: Mutex::new(LoadState { result: None, wakers: Vec::new() }),
+            done: Condvar::new(),
+        }
+    }
+
+    /// return the thread that started the load
+    pub(crate) fn leader(&self) -> ThreadId {
+        self.leader
+    }
+
+    fn finish(&self, value: Option<V>) {
+        let wakers = {
+            let mut state = lock(&self.state);
+            state.result = Some(value);
+            std::mem::take(&mut state.wakers)
+        };
+        self.done.notify_all();
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+
+    /// block until the load finishes, returning None if it was abandoned
+    pub(crate) fn wait(&self) -> Option<V> {
+        let state = self.done.wait_while(lock(&self.state), |state| state.result.is_none());
+        state.expect("in-flight load mutex should not be poisoned").result.clone().flatten()
+    }
+}
+
+/// resolves to the loaded value, or None if the load was abandoned
+pub(crate) struct WaitLoad<V>(pub(crate) Arc<Load<V>>);
+
+impl<V: Clone> Future for WaitLoad<V> {
+    type Output = Option<V>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = lock(&self.0.state);
+        if let Some(result) = &state.result {
+            return Poll::Ready(result.clone());
+        }
+        if !state.wakers.iter().any(|w| w.will_wake(cx.waker())) {
+            state.wakers.push(cx.waker().clone());
+        }
+        Poll::Pending
+    }
+}
+
+/// the loads that have started and not yet finished, by key
+pub(crate) struct InFlight<K, V> {
+    loads: Mutex<HashMap<K, Arc<Load<V>>>>,
+}
+
+/// what a caller that missed should do
+pub(crate) enum Role<'a, K: Eq + Hash, V: Clone> {
+    /// the value was cached after all
+    Cached(V),
+    /// run the loader, then finish the guard with its value
+    Lead(LeadGuard<'a, K, V>),
+    /// wait for another caller's load
+    Wait(Arc<Load<V>>),
+}
+
+impl<K, V> InFlight<K, V> {
+    pub(crate) fn new() -> Self {
+        InFlight { loads: Mutex::new(HashMap::new()) }
+    }
+}
+
+impl<K: Eq + Hash + Clone, V: Clone> InFlight<K, V> {
+    /// join the load of the key if one is running, or else start one
+    /// cached is asked for the value under the lock first, as a load may have finished since the caller missed,
+    /// and should look without counting a miss, which the caller already has
+    pub(crate) fn join<'a>(&'a self, key: &'a K, cached: impl FnOnce() -> Option<V>) -> Role<'a, K, V> {
+        let mut loads = lock(&self.loads);
+        if let Some(load) = loads.get(key) {
+            return Role::Wait(load.clone());
+        }
+        if let Some(value) = cached() {
+            return Role::Cached(value);
+        }
+        let load = Arc::new(Load::new());
+        loads.insert(key.clone(), load.clone());
+        Role::Lead(LeadGuard { in_flight: self, key, load, completed: false })
+    }
+}
+
+/// the lead of a load, which abandons it if dropped before finishing so that a waiting caller takes over
+pub(crate) struct LeadGuard<'a, K: Eq + Hash, V: Clone> {
+    in_flight: &'a InFlight<K, V>,
+    key: &'a K,
+    load: Arc<Load<V>>,
+    completed: bool,
+}
+
+impl<K: Eq + Hash, V: Clone> LeadGuard<'_, K, V> {
+    /// hand the loaded value to the waiters, once it has been cached so that later callers find it
+    pub(crate) fn finish(mut self, value: V) {
+        lock(&self.in_flight.loads).remove(self.key);
+        self.load.finish(Some(value));
+        self.completed = true;
+    }
+}
+
+impl<K: Eq + Hash, V: Clone> Drop for LeadGuard<'_, K, V> {
+    fn drop(&mut self) {
+        if self.completed {
+            return;
+        }
+        if let Ok(mut loads) = self.in_flight.loads.lock() {
+            loads.remove(self.key);
+        }
+        self.load.finish(None);
+    }
+}
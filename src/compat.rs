@@ -0,0 +1,10 @@
+//! Adapters with the api of other caching crates, for migrating to simcache
+//!
+//! `compat::lru::LruCache` has the method names and signatures of the `lru`
+//! crate's `LruCache`, and `compat::moka::Cache` those of a subset of
+//! `moka::sync::Cache`, so code written against either can switch by changing
+//! its imports. Both are thin wrappers, and `into_inner` hands over the
+//! underlying cache once the code is ready to use the rest of the simcache api.
+
+pub mod lru;
+pub mod moka;
@@ -0,0 +1,181 @@
+//! The api of the `lru` crate's `LruCache`
+
+use std::borrow::Borrow;
+use std::hash::Hash;
+use std::mem;
+use std::num::NonZeroUsize;
+use crate::{CapacityLimit, Simcache, LRU};
+
+/// a least recently used cache with the methods of `lru::LruCache`
+pub struct LruCache<K, V>
+where
+    K: Eq + Hash + Clone,
+{
+    inner: Simcache<K, V, LRU<K>>,
+}
+
+impl<K, V> LruCache<K, V>
+where
+    K: Eq + Hash + Clone,
+    {
+        /// return a new, empty cache holding up to cap entries
+        pub fn new(cap: NonZeroUsize) -> Self {
+            LruCache { inner: Simcache::new(cap.get()) }
+        }
+
+        /// return a new, empty cache with no entry limit
+        pub fn unbounded() -> Self {
+            LruCache { inner: Simcache::unbounded() }
+        }
+
+        /// insert a key value pair, returning the value it replaced
+        /// the key becomes the most recently used, and a full cache evicts its least recently used key
+        pub fn put(&mut self, key: K, value: V) -> Option<V> {
+            if let Some(current) = self.inner.get_mut(&key) {
+                return Some(mem::replace(current, value));
+            }
+            self.inner.insert(key, value, None);
+            None
+        }
+
+        /// like put, but return the key and value that made room for the new one,
+        /// either the replaced value under the same key or the evicted least recently used entry
+        pub fn push(&mut self, key: K, value: V) -> Option<(K, V)> {
+            if let Some(current) = self.inner.get_mut(&key) {
+                return Some((key, mem::replace(current, value)));
+            }
+            let evicted = if self.len() >= self.cap().get() { self.pop_lru() } else { None };
+            self.inner.insert(key, value, None);
+            evicted
+        }
+
+        /// return the value of the key, making it the most recently used
+        pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
+        where
+            K: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+        {
+            self.inner.get(key)
+        }
+
+        /// return a mutable reference to the value of the key, making it the most recently used
+        pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+        where
+            K: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+        {
+            self.inner.get_mut(key)
+        }
+
+        /// return the value of the key without changing how recently it was used
+        pub fn peek<Q>(&self, key: &Q) -> Option<&V>
+        where
+            K: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+        {
+            self.inner.peek(key)
+        }
+
+        /// return the least recently used entry without changing anything
+        pub fn peek_lru(&self) -> Option<(&K, &V)> {
+            self.inner.iter_eviction_order().next()
+        }
+
+        /// return true if the cache holds the key, without changing how recently it was used
+        pub fn contains<Q>(&self, key: &Q) -> bool
+        where
+            K: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+        {
+            self.inner.contains_key(key)
+        }
+
+        /// remove the key, returning its value
+        pub fn pop<Q>(&mut self, key: &Q) -> Option<V>
+        where
+            K: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+        {
+            self.inner.remove(key)
+        }
+
+        /// remove and return the least recently used entry
+        pub fn pop_lru(&mut self) -> Option<(K, V)> {
+            let key = self.peek_lru()?.0.clone();
+            let value = self.inner.remove(&key)?;
+            Some((key, value))
+        }
+
+        /// return the number of entries
+        pub fn len(&self) -> usize {
+            self.inner.len()
+        }
+
+        /// return true if the cache holds no entries
+        pub fn is_empty(&self) -> bool {
+            self.inner.is_empty()
+        }
+
+        /// return the entry limit, which is usize::MAX for an unbounded cache
+        pub fn cap(&self) -> NonZeroUsize {
+            match self.inner.capacity_limit() {
+                CapacityLimit::Bounded(cap) => NonZeroUsize::new(cap).expect("an lru cache should have a non-zero capacity"),
+                CapacityLimit::Unbounded => NonZeroUsize::MAX,
+            }
+        }
+
+        /// change the entry limit, evicting the least recently used entries that no longer fit
+        pub fn resize(&mut self, cap: NonZeroUsize) {
+            self.inner.set_max_capacity(cap.get());
+        }
+
+        /// remove every entry
+        pub fn clear(&mut self) {
+            self.inner.clear();
+        }
+
+        /// return an iterator over the entries, most recently used first
+        pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> + '_ {
+            let entries: Vec<(&K, &V)> = self.inner.iter_eviction_order().collect();
+            entries.into_iter().rev()
+        }
+
+        /// return the Simcache underneath
+        pub fn into_inner(self) -> Simcache<K, V, LRU<K>> {
+            self.inner
+        }
+    }
+
+impl<K: Eq + Hash + Clone, V> From<Simcache<K, V, LRU<K>>> for LruCache<K, V> {
+    fn from(inner: Simcache<K, V, LRU<K>>) -> Self {
+        LruCache { inner }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lru_test() {
+        let mut cache = LruCache::new(NonZeroUsize::new(2).unwrap());
+        assert_eq!(cache.put("a", 1), None);
+        assert_eq!(cache.put("b", 2), None);
+        assert_eq!(cache.put("a", 3), Some(1));
+        assert_eq!(cache.peek_lru(), Some((&"b", &2)));
+        assert_eq!(cache.get(&"b"), Some(&2));
+
+        assert_eq!(cache.push("c", 4), Some(("a", 3)));
+        assert_eq!(cache.push("c", 5), Some(("c", 4)));
+        assert_eq!(cache.iter().collect::<Vec<_>>(), [(&"c", &5), (&"b", &2)]);
+        assert_eq!(cache.pop_lru(), Some(("b", 2)));
+        assert_eq!(cache.pop(&"c"), Some(5));
+        assert!(cache.is_empty());
+
+        cache.put("d", 6);
+        cache.resize(NonZeroUsize::new(1).unwrap());
+        assert_eq!(cache.cap().get(), 1);
+        assert!(cache.contains(&"d"));
+        assert_eq!(LruCache::<u64, u64>::unbounded().cap(), NonZeroUsize::MAX);
+    }
+}
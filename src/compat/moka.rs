@@ -0,0 +1,224 @@
+//! A subset of the api of `moka::sync::Cache`
+
+use std::borrow::Borrow;
+use std::hash::Hash;
+use std::sync::Arc;
+use std::time::Duration;
+use crate::eviction::TinyLFU;
+use crate::{ShardedSimcache, Simcache, SimcacheBuilder};
+
+type Weigher<K, V> = Arc<dyn Fn(&K, &V) -> u32 + Send + Sync>;
+
+/// a thread safe cache with the methods of `moka::sync::Cache`
+/// clones share the same entries, like moka's
+pub struct Cache<K, V>
+where
+    K: Eq + Hash + Clone,
+{
+    inner: Arc<ShardedSimcache<K, V, TinyLFU<K>>>,
+}
+
+impl<K: Eq + Hash + Clone, V> Clone for Cache<K, V> {
+    fn clone(&self) -> Self {
+        Cache { inner: self.inner.clone() }
+    }
+}
+
+/// collects the settings for a new Cache, like `moka::sync::CacheBuilder`
+pub struct CacheBuilder<K, V> {
+    max_capacity: Option<u64>,
+    time_to_live: Option<Duration>,
+    time_to_idle: Option<Duration>,
+    weigher: Option<Weigher<K, V>>,
+}
+
+impl<K, V> CacheBuilder<K, V>
+where
+    K: Eq + Hash + Clone,
+    {
+        /// limit the number of entries, or their combined weight if a weigher is set
+        pub fn max_capacity(mut self, max_capacity: u64) -> Self {
+            self.max_capacity = Some(max_capacity);
+            self
+        }
+
+        /// expire every entry this long after it was inserted
+        pub fn time_to_live(mut self, ttl: Duration) -> Self {
+            self.time_to_live = Some(ttl);
+            self
+        }
+
+        /// expire every entry this long after it was last read or written
+        pub fn time_to_idle(mut self, tti: Duration) -> Self {
+            self.time_to_idle = Some(tti);
+            self
+        }
+
+        /// weigh each entry with the given function, making max_capacity a limit on the total weight
+        pub fn weigher<F>(mut self, weigher: F) -> Self
+        where
+            F: Fn(&K, &V) -> u32 + Send + Sync + 'static,
+        {
+            self.weigher = Some(Arc::new(weigher));
+            self
+        }
+
+        /// return a new, empty cache with these settings
+        /// small caches get a single shard so that their limit is exact; larger ones up to 16
+        pub fn build(self) -> Cache<K, V>
+        where
+            K: 'static,
+            V: 'static,
+        {
+            let shards = self.max_capacity.map_or(16, |max| (max / 1024).clamp(1, 16)) as usize;
+            let per_shard = self.max_capacity.map(|max| max.div_ceil(shards as u64));
+            let caches = (0..shards).map(|_| self.shard_builder(per_shard).build()).collect();
+            Cache { inner: Arc::new(ShardedSimcache::from_shards(caches)) }
+        }
+
+        fn shard_builder(&self, limit: Option<u64>) -> SimcacheBuilder<K, V, TinyLFU<K>>
+        where
+            K: 'static,
+            V: 'static,
+        {
+            let mut builder = Simcache::builder();
+            if let Some(ttl) = self.time_to_live {
+                builder = builder.default_ttl(ttl);
+            }
+            if let Some(tti) = self.time_to_idle {
+                builder = builder.time_to_idle(tti);
+            }
+            match (&self.weigher, limit) {
+                (Some(weigher), limit) => {
+                    let weigher = weigher.clone();
+                    builder = builder.weigher(move |key, value| weigher(key, value) as u64);
+                    if let Some(limit) = limit {
+                        builder = builder.max_weight(limit);
+                    }
+                }
+                (None, Some(limit)) => builder = builder.max_capacity(usize::try_from(limit).unwrap_or(usize::MAX)),
+                (None, None) => {}
+            }
+            builder
+        }
+    }
+
+impl<K, V> Cache<K, V>
+where
+    K: Eq + Hash + Clone + 'static,
+    V: Clone + 'static,
+    {
+        /// return a new, empty cache holding up to max_capacity entries
+        pub fn new(max_capacity: u64) -> Self {
+            Self::builder().max_capacity(max_capacity).build()
+        }
+
+        /// return a builder for configuring a new cache
+        pub fn builder() -> CacheBuilder<K, V> {
+            CacheBuilder { max_capacity: None, time_to_live: None, time_to_idle: None, weigher: None }
+        }
+
+        /// return a clone of the value of the key
+        pub fn get<Q>(&self, key: &Q) -> Option<V>
+        where
+            K: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+        {
+            self.inner.get(key)
+        }
+
+        /// insert a key value pair, replacing any value the key had
+        pub fn insert(&self, key: K, value: V) {
+            self.inner.insert(key, value, None);
+        }
+
+        /// return the value of the key, or run init and cache its result
+        /// concurrent callers missing on the same key wait for one init instead of each running their own
+        pub fn get_with<F>(&self, key: K, init: F) -> V
+        where
+            F: FnOnce() -> V,
+        {
+            self.inner.get_or_insert_with(key, None, init)
+        }
+
+        /// return true if the cache holds the key
+        pub fn contains_key<Q>(&self, key: &Q) -> bool
+        where
+            K: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+        {
+            self.inner.contains_key(key)
+        }
+
+        /// remove the key, returning its value
+        pub fn remove<Q>(&self, key: &Q) -> Option<V>
+        where
+            K: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+        {
+            self.inner.remove(key)
+        }
+
+        /// remove the key
+        pub fn invalidate<Q>(&self, key: &Q)
+        where
+            K: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+        {
+            self.inner.remove(key);
+        }
+
+        /// remove every entry
+        pub fn invalidate_all(&self) {
+            self.inner.clear();
+        }
+
+        /// return the number of entries
+        pub fn entry_count(&self) -> u64 {
+            self.inner.len() as u64
+        }
+
+        /// remove expired entries, which moka leaves to its maintenance tasks
+        pub fn run_pending_tasks(&self) {
+            self.inner.purge_expired();
+        }
+
+        /// return the sharded cache underneath
+        pub fn into_inner(self) -> Arc<ShardedSimcache<K, V, TinyLFU<K>>> {
+            self.inner
+        }
+    }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn moka_test() {
+        let cache: Cache<u64, String> = Cache::new(100);
+        cache.insert(1, "one".to_string());
+        let shared = cache.clone();
+        thread::spawn(move || shared.insert(2, "two".to_string())).join().unwrap();
+        assert_eq!(cache.get(&2).as_deref(), Some("two"));
+        assert_eq!(cache.get_with(3, || "three".to_string()), "three");
+        assert_eq!(cache.get_with(3, || unreachable!()), "three");
+        assert_eq!(cache.entry_count(), 3);
+
+        cache.invalidate(&1);
+        assert!(!cache.contains_key(&1));
+        assert_eq!(cache.remove(&2).as_deref(), Some("two"));
+        cache.invalidate_all();
+        assert_eq!(cache.entry_count(), 0);
+
+        let weighted: Cache<u64, String> = Cache::builder()
+            .max_capacity(10)
+            .weigher(|_, value: &String| value.len() as u32)
+            .time_to_live(Duration::from_secs(60))
+            .build();
+        weighted.insert(1, "abcdef".to_string());
+        weighted.insert(2, "ghijkl".to_string());
+        weighted.run_pending_tasks();
+        assert_eq!(weighted.entry_count(), 1);
+    }
+}
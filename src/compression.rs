@@ -0,0 +1,337 @@
+//! Cache of byte strings that compresses the large ones
+//!
+//! A `CompressedCache` runs every value at least as long as its threshold
+//! through a `ValueCodec` on insert and back on get, keeping whichever of the
+//! compressed or raw bytes is shorter. Weighing entries by `Stored::len` makes
+//! a max_weight a limit on the bytes actually held, so values that compress
+//! well let more of them fit. The built-in codec is `Lz4`, which writes the
+//! LZ4 block format; other codecs, such as zstd bindings, can be plugged in
+//! by implementing the trait.
+
+use std::borrow::Borrow;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hash};
+use std::io;
+use std::time::Duration;
+use crate::{Clock, EvictionPolicy, InsertOutcome, Simcache, SystemClock};
+
+/// turns values into fewer bytes and back
+pub trait ValueCodec {
+    /// return the compressed form of data
+    fn compress(&self, data: &[u8]) -> Vec<u8>;
+    /// return the original bytes of data, which compress turned the len bytes long value into
+    fn decompress(&self, data: &[u8], len: usize) -> io::Result<Vec<u8>>;
+}
+
+/// a value as the cache holds it, compressed or not
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stored {
+    bytes: Vec<u8>,
+    // the length of the original value if bytes is its compressed form
+    compressed_from: Option<usize>,
+}
+
+impl Stored {
+    /// return the number of bytes held, for weighing entries
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    /// return true if no bytes are held
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// return true if the value was stored compressed
+    pub fn is_compressed(&self) -> bool {
+        self.compressed_from.is_some()
+    }
+}
+
+/// how much compression has saved
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CompressionStats {
+    /// values stored compressed
+    pub compressed: u64,
+    /// values stored raw, being under the threshold or not getting any shorter
+    pub raw: u64,
+    /// the combined length of the values inserted
+    pub bytes_in: u64,
+    /// the combined length of what was stored for them
+    pub bytes_stored: u64,
+}
+
+impl CompressionStats {
+    /// return how many fewer bytes were stored than were inserted
+    pub fn bytes_saved(&self) -> u64 {
+        self.bytes_in.saturating_sub(self.bytes_stored)
+    }
+}
+
+/// a Simcache of byte strings that compresses values of at least threshold bytes
+pub struct CompressedCache<K, E, D = Lz4, S = RandomState, C = SystemClock>
+where
+    E: EvictionPolicy<K>,
+{
+    inner: Simcache<K, Stored, E, S, C>,
+    codec: D,
+    threshold: usize,
+    stats: CompressionStats,
+}
+
+impl<K, E, D, S, C> CompressedCache<K, E, D, S, C>
+where
+    K: Eq + Hash + Clone,
+    E: EvictionPolicy<K>,
+    D: ValueCodec,
+    S: BuildHasher,
+    C: Clock,
+    {
+        /// return a cache over inner that compresses values of at least threshold bytes with the codec
+        pub fn new(inner: Simcache<K, Stored, E, S, C>, codec: D, threshold: usize) -> Self {
+            CompressedCache { inner, codec, threshold, stats: CompressionStats::default() }
+        }
+
+        /// insert a key value pair, compressing the value if it is long enough and gets shorter
+        pub fn insert(&mut self, key: K, value: Vec<u8>, ttl: Option<Duration>) -> InsertOutcome {
+            let len = value.len();
+            let compressed = (len >= self.threshold).then(|| self.codec.compress(&value)).filter(|c| c.len() < len);
+            let stored = match compressed {
+                Some(bytes) => {
+                    self.stats.compressed += 1;
+                    Stored { bytes, compressed_from: Some(len) }
+                }
+                None => {
+                    self.stats.raw += 1;
+                    Stored { bytes: value, compressed_from: None }
+                }
+            };
+            self.stats.bytes_in += len as u64;
+            self.stats.bytes_stored += stored.len() as u64;
+            self.inner.insert(key, stored, ttl)
+        }
+
+        /// return the value of the key, decompressing it if it was stored compressed
+        pub fn get<Q>(&mut self, key: &Q) -> Option<Vec<u8>>
+        where
+            K: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+        {
+            let stored = self.inner.get(key)?;
+            Some(decode(&self.codec, stored))
+        }
+
+        /// remove the key, returning its value
+        pub fn remove<Q>(&mut self, key: &Q) -> Option<Vec<u8>>
+        where
+            K: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+        {
+            let stored = self.inner.remove(key)?;
+            Some(decode(&self.codec, &stored))
+        }
+
+        /// return true if the cache holds the key
+        pub fn contains_key<Q>(&self, key: &Q) -> bool
+        where
+            K: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+        {
+            self.inner.contains_key(key)
+        }
+
+        /// return the number of entries
+        pub fn len(&self) -> usize {
+            self.inner.len()
+        }
+
+        /// return true if the cache holds no entries
+        pub fn is_empty(&self) -> bool {
+            self.inner.is_empty()
+        }
+
+        /// return how much compression has saved so far
+        pub fn compression_stats(&self) -> CompressionStats {
+            self.stats
+        }
+
+        /// return the Simcache underneath, e.g. for its hit and eviction stats
+        pub fn inner(&self) -> &Simcache<K, Stored, E, S, C> {
+            &self.inner
+        }
+    }
+
+fn decode<D: ValueCodec>(codec: &D, stored: &Stored) -> Vec<u8> {
+    match stored.compressed_from {
+        Some(len) => codec.decompress(&stored.bytes, len).expect("a value the codec compressed should decompress"),
+        None => stored.bytes.clone(),
+    }
+}
+
+// the LZ4 block format's limits: matches are at least 4 bytes long, the last 5 bytes of a block are
+// literals, and the last match starts at least 12 bytes before the end
+const MIN_MATCH: usize = 4;
+const LAST_LITERALS: usize = 5;
+const MATCH_LIMIT: usize = 12;
+const MAX_OFFSET: usize = 65535;
+const HASH_BITS: u32 = 12;
+
+/// the LZ4 block format, fast to compress and decompress and readable by other LZ4 implementations
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Lz4;
+
+fn read_u32(data: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]])
+}
+
+fn hash(sequence: u32) -> usize {
+    (sequence.wrapping_mul(2654435761) >> (32 - HASH_BITS)) as usize
+}
+
+// a length of 15 or more spills into bytes of 255 and a final remainder
+fn write_length(out: &mut Vec<u8>, mut len: usize) {
+    while len >= 255 {
+        out.push(255);
+        len -= 255;
+    }
+    out.push(len as u8);
+}
+
+fn write_sequence(out: &mut Vec<u8>, literals: &[u8], matched: Option<(usize, usize)>) {
+    let match_code = matched.map_or(0, |(_, len)| len - MIN_MATCH);
+    out.push(((literals.len().min(15) as u8) << 4) | match_code.min(15) as u8);
+    if literals.len() >= 15 {
+        write_length(out, literals.len() - 15);
+    }
+    out.extend_from_slice(literals);
+    if let Some((offset, _)) = matched {
+        out.extend_from_slice(&(offset as u16).to_le_bytes());
+        if match_code >= 15 {
+            write_length(out, match_code - 15);
+        }
+    }
+}
+
+fn corrupt(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("corrupt lz4 block: {}", message))
+}
+
+fn read_length(data: &[u8], at: &mut usize, mut len: usize) -> io::Result<usize> {
+    if len == 15 {
+        loop {
+            let byte = *data.get(*at).ok_or_else(|| corrupt("length runs past the end"))?;
+            *at += 1;
+            len += byte as usize;
+            if byte != 255 {
+                break;
+            }
+        }
+    }
+    Ok(len)
+}
+
+impl ValueCodec for Lz4 {
+    fn compress(&self, data: &[u8]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(data.len() / 2 + 16);
+        let mut table = vec![usize::MAX; 1 << HASH_BITS];
+        let mut anchor = 0;
+        let mut i = 0;
+        while data.len() > MATCH_LIMIT && i + MATCH_LIMIT <= data.len() {
+            let sequence = read_u32(data, i);
+            let slot = hash(sequence);
+            let candidate = table[slot];
+            table[slot] = i;
+            if candidate == usize::MAX || i - candidate > MAX_OFFSET || read_u32(data, candidate) != sequence {
+                i += 1;
+                continue;
+            }
+            let max_len = data.len() - LAST_LITERALS - i;
+            let mut len = MIN_MATCH;
+            while len < max_len && data[candidate + len] == data[i + len] {
+                len += 1;
+            }
+            write_sequence(&mut out, &data[anchor..i], Some((i - candidate, len)));
+            i += len;
+            anchor = i;
+        }
+        write_sequence(&mut out, &data[anchor..], None);
+        out
+    }
+
+    fn decompress(&self, data: &[u8], len: usize) -> io::Result<Vec<u8>> {
+        let mut out = Vec::with_capacity(len);
+        let mut at = 0;
+        loop {
+            let token = *data.get(at).ok_or_else(|| corrupt("missing token"))?;
+            at += 1;
+            let literals = read_length(data, &mut at, (token >> 4) as usize)?;
+            let end = at.checked_add(literals).filter(|&end| end <= data.len()).ok_or_else(|| corrupt("literals run past the end"))?;
+            out.extend_from_slice(&data[at..end]);
+            at = end;
+            if at == data.len() {
+                break;
+            }
+            let offset = match data.get(at..at + 2) {
+                Some(bytes) => u16::from_le_bytes([bytes[0], bytes[1]]) as usize,
+                None => return Err(corrupt("offset runs past the end")),
+            };
+            at += 2;
+            if offset == 0 || offset > out.len() {
+                return Err(corrupt("match offset is out of range"));
+            }
+            let match_len = read_length(data, &mut at, (token & 15) as usize)? + MIN_MATCH;
+            if out.len() + match_len > len {
+                return Err(corrupt("output is longer than expected"));
+            }
+            // the match may overlap what it is copying, so it is copied a byte at a time
+            let start = out.len() - offset;
+            for k in 0..match_len {
+                out.push(out[start + k]);
+            }
+        }
+        if out.len() != len {
+            return Err(corrupt("output is shorter than expected"));
+        }
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LRU;
+
+    #[test]
+    fn lz4_test() {
+        let json: Vec<u8> = (0..200).flat_map(|i| format!("{{\"id\":{i},\"name\":\"user\",\"active\":true}},").into_bytes()).collect();
+        let compressed = Lz4.compress(&json);
+        assert!(compressed.len() * 4 < json.len());
+        assert_eq!(Lz4.decompress(&compressed, json.len()).unwrap(), json);
+
+        for data in [&b""[..], b"short", &[7; 1000], b"abcdefghijklmnopqrstuvwxyz0123456789"] {
+            assert_eq!(Lz4.decompress(&Lz4.compress(data), data.len()).unwrap(), data);
+        }
+        // a hand written block: 4 literals, a match of 10 at offset 4, then 5 more literals
+        assert_eq!(Lz4.decompress(&[0x46, b'a', b'b', b'c', b'd', 4, 0, 0x50, b'a', b'b', b'c', b'd', b'e'], 19).unwrap(), b"abcdabcdabcdababcde");
+        assert!(Lz4.decompress(&[0x46, b'a', b'b', b'c', b'd', 9, 0], 14).is_err());
+    }
+
+    #[test]
+    fn compressed_cache_test() {
+        let inner: Simcache<&str, Stored, LRU<&str>> = Simcache::builder().weigher(|_, stored: &Stored| stored.len() as u64).max_weight(1000).build();
+        let mut cache = CompressedCache::new(inner, Lz4, 64);
+        let blob = b"{\"status\":\"ok\",\"items\":[]}".repeat(100);
+        cache.insert("big", blob.clone(), None);
+        cache.insert("small", b"tiny".to_vec(), None);
+        assert_eq!(cache.get("big"), Some(blob.clone()));
+        assert_eq!(cache.get("small").as_deref(), Some(&b"tiny"[..]));
+
+        let stats = cache.compression_stats();
+        assert_eq!((stats.compressed, stats.raw), (1, 1));
+        assert!(stats.bytes_saved() > 2000);
+        // the limit is on the compressed bytes, so a value longer than max_weight fits
+        assert!(cache.inner().weight() < 1000);
+        assert_eq!(cache.remove("big"), Some(blob));
+        assert_eq!(cache.len(), 1);
+    }
+}
@@ -0,0 +1,83 @@
+//! Bloom filter that keeps keys seen only once out of a full cache
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+const HASHES: usize = 4;
+// about a 1% false positive rate at the most keys the filter holds between resets
+const BITS_PER_KEY: usize = 10;
+
+// remembers which keys have been offered to a full cache before; the first offer of a key only
+// records it, and a second offer finds it and is let through to the policy
+// the filter is emptied after every `keys` keys recorded, so it never fills up, at the cost of
+// forgetting keys whose second offer comes after the reset
+#[derive(Clone)]
+pub(crate) struct Doorkeeper {
+    bits: Vec<u64>,
+    keys: usize,
+    recorded: usize,
+}
+
+impl Doorkeeper {
+    // return a filter sized for keys distinct keys between resets
+    pub(crate) fn new(keys: usize) -> Self {
+        let keys = keys.clamp(64, 1 << 24);
+        let words = (keys * BITS_PER_KEY).div_ceil(64).next_power_of_two();
+        Doorkeeper { bits: vec![0; words], keys, recorded: 0 }
+    }
+
+    fn positions<K: Hash + ?Sized>(&self, key: &K) -> [usize; HASHES] {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        let hash = hasher.finish();
+        let (h1, h2) = (hash as usize, (hash >> 32) as usize | 1);
+        let mask = self.bits.len() * 64 - 1;
+        let mut positions = [0; HASHES];
+        for (i, position) in positions.iter_mut().enumerate() {
+            *position = h1.wrapping_add(i.wrapping_mul(h2)) & mask;
+        }
+        positions
+    }
+
+    // return true if the key was seen before, recording it if not
+    pub(crate) fn check_and_record<K: Hash + ?Sized>(&mut self, key: &K) -> bool {
+        let positions = self.positions(key);
+        if positions.iter().all(|&bit| self.bits[bit / 64] & (1 << (bit % 64)) != 0) {
+            return true;
+        }
+        if self.recorded >= self.keys {
+            self.clear();
+        }
+        for bit in positions {
+            self.bits[bit / 64] |= 1 << (bit % 64);
+        }
+        self.recorded += 1;
+        false
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.bits.fill(0);
+        self.recorded = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn doorkeeper_test() {
+        let mut doorkeeper = Doorkeeper::new(100);
+        assert!(!doorkeeper.check_and_record("a"));
+        assert!(doorkeeper.check_and_record("a"));
+        assert!(!doorkeeper.check_and_record("b"));
+
+        // a filter holding its quota of keys starts over before recording another
+        for key in 0..100 {
+            doorkeeper.check_and_record(&key);
+        }
+        assert!(!doorkeeper.check_and_record("a"));
+        doorkeeper.clear();
+        assert!(!doorkeeper.check_and_record("a"));
+    }
+}
@@ -0,0 +1,182 @@
+//! Entry API for in-place manipulation of a single cache entry
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hash};
+use std::time::Duration;
+use crate::{Clock, EvictionPolicy, Simcache, SystemClock};
+
+/// a view into a single key of the cache, which is either live or vacant
+/// looking up an entry counts as one use of the key if it is live
+pub enum Entry<'a, K, V, E, S = RandomState, C = SystemClock>
+where
+    E: EvictionPolicy<K>
+{
+    Occupied(OccupiedEntry<'a, K, V, E, S, C>),
+    Vacant(VacantEntry<'a, K, V, E, S, C>),
+}
+
+/// a key with a live value in the cache
+pub struct OccupiedEntry<'a, K, V, E, S = RandomState, C = SystemClock>
+where
+    E: EvictionPolicy<K>
+{
+    pub(crate) cache: &'a mut Simcache<K, V, E, S, C>,
+    pub(crate) key: K,
+}
+
+/// a key with no live value in the cache
+pub struct VacantEntry<'a, K, V, E, S = RandomState, C = SystemClock>
+where
+    E: EvictionPolicy<K>
+{
+    pub(crate) cache: &'a mut Simcache<K, V, E, S, C>,
+    pub(crate) key: K,
+    pub(crate) ttl: Option<Duration>,
+}
+
+impl<'a, K, V, E, S, C> Entry<'a, K, V, E, S, C>
+where
+    K: Eq + Hash + Clone,
+    E: EvictionPolicy<K>,
+    S: BuildHasher,
+    C: Clock,
+    {
+        /// return the key of this entry
+        pub fn key(&self) -> &K {
+            match self {
+                Entry::Occupied(entry) => entry.key(),
+                Entry::Vacant(entry) => entry.key(),
+            }
+        }
+
+        /// set the ttl used if a value ends up being inserted into a vacant entry
+        pub fn with_ttl(self, ttl: Duration) -> Self {
+            match self {
+                Entry::Occupied(entry) => Entry::Occupied(entry),
+                Entry::Vacant(entry) => Entry::Vacant(entry.with_ttl(ttl)),
+            }
+        }
+
+        /// return the live value, inserting the given one if the entry is vacant
+        pub fn or_insert(self, value: V) -> &'a mut V {
+            self.or_insert_with(|| value)
+        }
+
+        /// return the live value, inserting the result of f if the entry is vacant
+        pub fn or_insert_with<F: FnOnce() -> V>(self, f: F) -> &'a mut V {
+            match self {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => entry.insert(f()),
+            }
+        }
+
+        /// return the live value, inserting the default value if the entry is vacant
+        pub fn or_default(self) -> &'a mut V
+        where
+            V: Default,
+        {
+            self.or_insert_with(V::default)
+        }
+
+        /// update the value in place if the entry is live
+        pub fn and_modify<F: FnOnce(&mut V)>(self, f: F) -> Self {
+            match self {
+                Entry::Occupied(mut entry) => {
+                    f(entry.get_mut());
+                    Entry::Occupied(entry)
+                }
+                Entry::Vacant(entry) => Entry::Vacant(entry),
+            }
+        }
+    }
+
+impl<'a, K, V, E, S, C> OccupiedEntry<'a, K, V, E, S, C>
+where
+    K: Eq + Hash + Clone,
+    E: EvictionPolicy<K>,
+    S: BuildHasher,
+    C: Clock,
+    {
+        /// return the key of this entry
+        pub fn key(&self) -> &K {
+            &self.key
+        }
+
+        /// return the live value
+        pub fn get(&self) -> &V {
+            self.cache.peek(&self.key).expect("occupied entry should have a value")
+        }
+
+        /// return the live value mutably
+        pub fn get_mut(&mut self) -> &mut V {
+            self.cache.value_mut(&self.key).expect("occupied entry should have a value")
+        }
+
+        /// return the live value mutably, for as long as the cache is borrowed
+        pub fn into_mut(self) -> &'a mut V {
+            self.cache.value_mut(&self.key).expect("occupied entry should have a value")
+        }
+
+        /// remove the entry from the cache, returning its value
+        pub fn remove(self) -> V {
+            self.cache.remove(&self.key).expect("occupied entry should have a value")
+        }
+    }
+
+impl<'a, K, V, E, S, C> VacantEntry<'a, K, V, E, S, C>
+where
+    K: Eq + Hash + Clone,
+    E: EvictionPolicy<K>,
+    S: BuildHasher,
+    C: Clock,
+    {
+        /// return the key of this entry
+        pub fn key(&self) -> &K {
+            &self.key
+        }
+
+        /// set the ttl used when a value is inserted, instead of the cache's default
+        pub fn with_ttl(mut self, ttl: Duration) -> Self {
+            self.ttl = Some(ttl);
+            self
+        }
+
+        /// insert the value, evicting as needed, and return it mutably
+        /// a cache with a capacity of 0 returns the value without keeping it
+        pub fn insert(self, value: V) -> &'a mut V {
+            self.cache.insert_and_get(self.key, value, self.ttl)
+        }
+    }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eviction::LFU;
+
+    #[test]
+    fn entry_test() {
+        let mut cache: Simcache<&'static str, u32, LFU<&'static str>> = Simcache::new(2);
+
+        *cache.entry("a").or_insert(0) += 1;
+        *cache.entry("a").or_insert(0) += 1;
+        cache.entry("b").and_modify(|v| *v += 10).or_insert_with(|| 5);
+        cache.entry("b").and_modify(|v| *v += 10).or_insert_with(|| 5);
+
+        assert_eq!(cache.peek(&"a"), Some(&2));
+        assert_eq!(cache.peek(&"b"), Some(&15));
+        assert_eq!(cache.stats().hits, 2);
+        assert_eq!(cache.stats().misses, 2);
+
+        // a has been used three times to b's twice, so b is evicted
+        cache.entry("a").or_default();
+        let ttl = Duration::from_secs(60);
+        assert_eq!(*cache.entry("c").with_ttl(ttl).or_default(), 0);
+        assert!(!cache.contains_key(&"b"));
+
+        match cache.entry("a") {
+            Entry::Occupied(entry) => assert_eq!(entry.remove(), 2),
+            Entry::Vacant(_) => panic!("a should be occupied"),
+        }
+        assert!(cache.contains_key(&"c"));
+    }
+}
@@ -0,0 +1,27 @@
+//! Errors returned by the fallible cache operations
+
+use std::fmt;
+
+/// why try_insert or try_evict couldn't do what was asked
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SimcacheError {
+    /// the cache has an entry limit of 0, so it can't hold anything
+    ZeroCapacity,
+    /// the value wasn't kept, because the policy or the doorkeeper turned the key away
+    /// or making room for it would have meant evicting a higher priority
+    Rejected,
+    /// there was no unpinned entry to evict
+    NothingToEvict,
+}
+
+impl fmt::Display for SimcacheError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SimcacheError::ZeroCapacity => f.write_str("the cache has a capacity of 0"),
+            SimcacheError::Rejected => f.write_str("the value was not admitted to the cache"),
+            SimcacheError::NothingToEvict => f.write_str("the cache has no entry to evict"),
+        }
+    }
+}
+
+impl std::error::Error for SimcacheError {}
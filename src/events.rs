@@ -0,0 +1,87 @@
+//! A stream of cache events for observers outside the cache
+//!
+//! `Simcache::subscribe` returns a channel receiver that is sent a
+//! `CacheEvent` for every insert, hit, miss, eviction, expiry, and removal,
+//! so that something like an invalidation bus or an audit log can follow
+//! the cache without wrapping every call site. Each subscriber gets its own
+//! copy of every event, and a subscriber whose receiver has been dropped is
+//! forgotten on the next event.
+
+use std::time::Instant;
+
+/// what happened to the key of a cache event
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EventCause {
+    /// a value was stored, whether or not the key already had one
+    Insert,
+    /// a lookup found a live entry, or a cached negative answer
+    Hit,
+    /// a lookup found no live entry
+    Miss,
+    /// an entry was removed to make room under a limit
+    Evict,
+    /// an entry was removed because its ttl or idle time had passed
+    Expire,
+    /// an entry was removed by remove, retain, or clear
+    Remove,
+}
+
+/// one event sent to the subscribers of a cache
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheEvent<K> {
+    /// the key the event happened to
+    /// None only for a miss by get or get_mut, which are given a borrowed form of the key
+    pub key: Option<K>,
+    pub cause: EventCause,
+    /// when the event happened, according to the cache's clock
+    pub timestamp: Instant,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Clock, MockClock, Simcache, LRU};
+    use std::collections::hash_map::RandomState;
+    use std::time::Duration;
+
+    #[test]
+    fn subscribe_test() {
+        let clock = MockClock::new();
+        let mut cache: Simcache<&str, u64, LRU<&str>, RandomState, MockClock> = Simcache::builder()
+            .max_capacity(1)
+            .clock(clock.clone())
+            .build();
+        let events = cache.subscribe();
+
+        cache.insert("a", 1, Some(Duration::from_secs(1)));
+        cache.get(&"a");
+        cache.insert("b", 2, None);
+        cache.get(&"a");
+        cache.entry("a");
+        cache.remove(&"b");
+        let received: Vec<(Option<&str>, EventCause)> = events.try_iter().map(|event| (event.key, event.cause)).collect();
+        assert_eq!(received, [
+            (Some("a"), EventCause::Insert),
+            (Some("a"), EventCause::Hit),
+            (Some("a"), EventCause::Evict),
+            (Some("b"), EventCause::Insert),
+            (None, EventCause::Miss),
+            (Some("a"), EventCause::Miss),
+            (Some("b"), EventCause::Remove),
+        ]);
+
+        // events are stamped with the cache's clock, and expiry is reported when it is noticed
+        cache.insert("c", 3, Some(Duration::from_secs(1)));
+        clock.advance(Duration::from_secs(2));
+        cache.purge_expired();
+        let expired = events.try_iter().last().unwrap();
+        assert_eq!(expired.cause, EventCause::Expire);
+        assert_eq!(expired.timestamp, clock.now());
+
+        // a dropped receiver stops getting events without affecting the others
+        let other = cache.subscribe();
+        drop(events);
+        cache.insert("d", 4, None);
+        assert_eq!(other.try_iter().count(), 1);
+    }
+}
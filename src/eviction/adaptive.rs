@@ -0,0 +1,191 @@
+use crate::EvictionPolicy;
+use super::{LFU, LRU};
+use std::collections::VecDeque;
+use std::hash::Hash;
+
+// keeps every key in both an LRU and an LFU, and evicts with whichever has been making fewer mistakes
+// each side remembers the keys it evicted in a ghost list; a ghost that is inserted again was a miss
+// the other side might have kept, so it counts against the side that evicted it
+// the counts are halved after every capacity inserts, so the choice follows the recent workload,
+// e.g. moving to LFU while scans churn through the cache and back to LRU for steady traffic
+#[derive(Clone)]
+pub struct Adaptive<K> {
+    lru: LRU<K>,
+    lfu: LFU<K>,
+    recency_ghosts: VecDeque<K>,
+    frequency_ghosts: VecDeque<K>,
+    recency_misses: u64,
+    frequency_misses: u64,
+    inserts_since_decay: usize,
+    resident: usize,
+    capacity: usize,
+}
+
+fn take<K: PartialEq>(list: &mut VecDeque<K>, key: &K) -> bool {
+    if let Some(pos) = list.iter().position(|k| k == key) {
+        list.remove(pos);
+        true
+    } else {
+        false
+    }
+}
+
+impl<K: Eq + Hash + Clone> Adaptive<K> {
+    /// return true if evictions currently go by use count rather than recency
+    pub fn prefers_frequency(&self) -> bool {
+        self.frequency_misses < self.recency_misses
+    }
+
+    // the ghost lists never hold more keys than are resident, so an unbounded cache can't grow them forever
+    fn trim_ghosts(&mut self) {
+        let limit = self.capacity.min(self.resident + 1);
+        while self.recency_ghosts.len() > limit {
+            self.recency_ghosts.pop_front();
+        }
+        while self.frequency_ghosts.len() > limit {
+            self.frequency_ghosts.pop_front();
+        }
+    }
+
+    fn record_insert(&mut self, key: &K) {
+        if self.inserts_since_decay >= self.capacity.min(self.resident + 1) {
+            self.recency_misses /= 2;
+            self.frequency_misses /= 2;
+            self.inserts_since_decay = 0;
+        }
+        self.inserts_since_decay += 1;
+        if take(&mut self.recency_ghosts, key) {
+            self.recency_misses += 1;
+        } else if take(&mut self.frequency_ghosts, key) {
+            self.frequency_misses += 1;
+        }
+    }
+}
+
+impl<K: Eq + Hash + Clone> EvictionPolicy<K> for Adaptive<K> {
+    fn evict_next(&mut self) -> K {
+        let key = if self.prefers_frequency() {
+            let key = self.lfu.evict_next();
+            self.lru.on_remove(&key);
+            self.frequency_ghosts.push_back(key.clone());
+            key
+        } else {
+            let key = self.lru.evict_next();
+            self.lfu.on_remove(&key);
+            self.recency_ghosts.push_back(key.clone());
+            key
+        };
+        self.resident -= 1;
+        self.trim_ghosts();
+        key
+    }
+    fn try_evict_next(&mut self) -> Option<K> {
+        if self.resident == 0 {
+            return None;
+        }
+        Some(self.evict_next())
+    }
+    fn on_insert(&mut self, key: &K, weight: u64) {
+        if self.lfu.score(key).is_none() {
+            self.record_insert(key);
+            self.resident += 1;
+        }
+        self.lru.on_insert(key, weight);
+        self.lfu.on_insert(key, weight);
+    }
+    fn on_insert_cold(&mut self, key: &K, weight: u64) {
+        if self.lfu.score(key).is_none() {
+            self.record_insert(key);
+            self.resident += 1;
+        }
+        self.lru.on_insert_cold(key, weight);
+        self.lfu.on_insert_cold(key, weight);
+    }
+    fn on_access(&mut self, key: &K) {
+        self.lru.on_access(key);
+        self.lfu.on_access(key);
+    }
+    fn on_remove(&mut self, key: &K) {
+        if self.lfu.score(key).is_some() {
+            self.resident -= 1;
+        }
+        self.lru.on_remove(key);
+        self.lfu.on_remove(key);
+    }
+    fn next_victim(&self) -> Option<&K> {
+        if self.prefers_frequency() {
+            self.lfu.next_victim()
+        } else {
+            self.lru.next_victim()
+        }
+    }
+    fn eviction_order(&self) -> Vec<&K> {
+        if self.prefers_frequency() {
+            self.lfu.eviction_order()
+        } else {
+            self.lru.eviction_order()
+        }
+    }
+    fn score(&self, key: &K) -> Option<u64> {
+        if self.prefers_frequency() {
+            self.lfu.score(key)
+        } else {
+            self.lru.score(key)
+        }
+    }
+    fn clear(&mut self) {
+        *self = Adaptive { capacity: self.capacity, ..Self::new() };
+    }
+    fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.trim_ghosts();
+    }
+    fn new() -> Self {
+        Adaptive {
+            lru: LRU::new(),
+            lfu: LFU::new(),
+            recency_ghosts: VecDeque::new(),
+            frequency_ghosts: VecDeque::new(),
+            recency_misses: 0,
+            frequency_misses: 0,
+            inserts_since_decay: 0,
+            resident: 0,
+            capacity: usize::MAX,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn adaptive_test() {
+        let mut policy = Adaptive::new();
+        policy.set_capacity(3);
+        for key in ["a", "b", "c"] {
+            policy.on_insert(&key, 1);
+        }
+        for key in ["a", "a", "a", "b", "c", "c"] {
+            policy.on_access(&key);
+        }
+
+        // recency is preferred to start with, so the least recently used key goes, popular as it is
+        assert!(!policy.prefers_frequency());
+        assert!(policy.evict_next() == "a");
+        // bringing it straight back shows recency was wrong to evict it
+        policy.on_insert(&"a", 1);
+        for _ in 0..3 {
+            policy.on_access(&"a");
+        }
+        assert!(policy.prefers_frequency());
+        assert_eq!(policy.next_victim(), Some(&"b"));
+        assert!(policy.evict_next() == "b");
+
+        // b coming back counts against frequency in turn, evening things out
+        policy.on_insert(&"b", 1);
+        assert!(!policy.prefers_frequency());
+        policy.on_remove(&"b");
+        assert_eq!(policy.eviction_order(), vec![&"c", &"a"]);
+    }
+}
@@ -0,0 +1,275 @@
+use crate::EvictionPolicy;
+use super::{Adaptive, ApproxLFU, Clock, ExpiryFirst, RandomEviction, S3Fifo, Sieve, ARC, FIFO, GDSF, LFU, LRU, MRU, SLRU, TinyLFU};
+use std::fmt;
+use std::hash::Hash;
+use std::str::FromStr;
+use std::time::Instant;
+
+/// names one of the built-in eviction policies, for choosing a policy at runtime
+/// parses from the policy's name in lowercase, such as "lru", "tinylfu", or "expiry_first"
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum PolicyKind {
+    #[default]
+    Lru,
+    Lfu,
+    Fifo,
+    Arc,
+    TinyLfu,
+    Slru,
+    Clock,
+    Sieve,
+    S3Fifo,
+    Mru,
+    Random,
+    ExpiryFirst,
+    Gdsf,
+    Adaptive,
+    ApproxLfu,
+}
+
+impl PolicyKind {
+    const ALL: [(PolicyKind, &'static str); 15] = [
+        (PolicyKind::Lru, "lru"),
+        (PolicyKind::Lfu, "lfu"),
+        (PolicyKind::Fifo, "fifo"),
+        (PolicyKind::Arc, "arc"),
+        (PolicyKind::TinyLfu, "tinylfu"),
+        (PolicyKind::Slru, "slru"),
+        (PolicyKind::Clock, "clock"),
+        (PolicyKind::Sieve, "sieve"),
+        (PolicyKind::S3Fifo, "s3fifo"),
+        (PolicyKind::Mru, "mru"),
+        (PolicyKind::Random, "random"),
+        (PolicyKind::ExpiryFirst, "expiry_first"),
+        (PolicyKind::Gdsf, "gdsf"),
+        (PolicyKind::Adaptive, "adaptive"),
+        (PolicyKind::ApproxLfu, "approx_lfu"),
+    ];
+
+    /// return every built-in kind, in declaration order
+    pub fn all() -> impl Iterator<Item = PolicyKind> {
+        PolicyKind::ALL.iter().map(|(kind, _)| *kind)
+    }
+
+    /// return the name the kind parses from
+    pub fn name(&self) -> &'static str {
+        PolicyKind::ALL.iter().find(|(kind, _)| kind == self).map(|(_, name)| *name).expect("every kind should have a name")
+    }
+}
+
+impl fmt::Display for PolicyKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// the error returned when parsing a name that isn't a built-in policy
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownPolicy(pub String);
+
+impl fmt::Display for UnknownPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown eviction policy {:?}", self.0)
+    }
+}
+
+impl std::error::Error for UnknownPolicy {}
+
+impl FromStr for PolicyKind {
+    type Err = UnknownPolicy;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.trim().to_ascii_lowercase().replace('-', "_");
+        PolicyKind::ALL
+            .iter()
+            .find(|(_, known)| *known == name)
+            .map(|(kind, _)| *kind)
+            .ok_or_else(|| UnknownPolicy(s.to_string()))
+    }
+}
+
+// one variant per built-in policy, so the policy can be picked from a config file
+// instead of as a type parameter; every call is forwarded to the chosen policy
+#[derive(Clone)]
+pub enum AnyPolicy<K> {
+    Lru(LRU<K>),
+    Lfu(LFU<K>),
+    Fifo(FIFO<K>),
+    Arc(ARC<K>),
+    TinyLfu(TinyLFU<K>),
+    Slru(SLRU<K>),
+    Clock(Clock<K>),
+    Sieve(Sieve<K>),
+    S3Fifo(S3Fifo<K>),
+    Mru(MRU<K>),
+    Random(RandomEviction<K>),
+    ExpiryFirst(ExpiryFirst<K>),
+    Gdsf(GDSF<K>),
+    Adaptive(Adaptive<K>),
+    ApproxLfu(ApproxLFU<K>),
+}
+
+macro_rules! dispatch {
+    ($self:expr, $policy:ident => $call:expr) => {
+        match $self {
+            AnyPolicy::Lru($policy) => $call,
+            AnyPolicy::Lfu($policy) => $call,
+            AnyPolicy::Fifo($policy) => $call,
+            AnyPolicy::Arc($policy) => $call,
+            AnyPolicy::TinyLfu($policy) => $call,
+            AnyPolicy::Slru($policy) => $call,
+            AnyPolicy::Clock($policy) => $call,
+            AnyPolicy::Sieve($policy) => $call,
+            AnyPolicy::S3Fifo($policy) => $call,
+            AnyPolicy::Mru($policy) => $call,
+            AnyPolicy::Random($policy) => $call,
+            AnyPolicy::ExpiryFirst($policy) => $call,
+            AnyPolicy::Gdsf($policy) => $call,
+            AnyPolicy::Adaptive($policy) => $call,
+            AnyPolicy::ApproxLfu($policy) => $call,
+        }
+    };
+}
+
+impl<K: Eq + Hash + Clone> AnyPolicy<K> {
+    /// return a new policy of the given kind
+    pub fn from_kind(kind: PolicyKind) -> Self {
+        match kind {
+            PolicyKind::Lru => AnyPolicy::Lru(LRU::new()),
+            PolicyKind::Lfu => AnyPolicy::Lfu(LFU::new()),
+            PolicyKind::Fifo => AnyPolicy::Fifo(FIFO::new()),
+            PolicyKind::Arc => AnyPolicy::Arc(ARC::new()),
+            PolicyKind::TinyLfu => AnyPolicy::TinyLfu(TinyLFU::new()),
+            PolicyKind::Slru => AnyPolicy::Slru(SLRU::new()),
+            PolicyKind::Clock => AnyPolicy::Clock(Clock::new()),
+            PolicyKind::Sieve => AnyPolicy::Sieve(Sieve::new()),
+            PolicyKind::S3Fifo => AnyPolicy::S3Fifo(S3Fifo::new()),
+            PolicyKind::Mru => AnyPolicy::Mru(MRU::new()),
+            PolicyKind::Random => AnyPolicy::Random(RandomEviction::new()),
+            PolicyKind::ExpiryFirst => AnyPolicy::ExpiryFirst(ExpiryFirst::new()),
+            PolicyKind::Gdsf => AnyPolicy::Gdsf(GDSF::new()),
+            PolicyKind::Adaptive => AnyPolicy::Adaptive(Adaptive::new()),
+            PolicyKind::ApproxLfu => AnyPolicy::ApproxLfu(ApproxLFU::new()),
+        }
+    }
+
+    /// return which built-in policy this is
+    pub fn kind(&self) -> PolicyKind {
+        match self {
+            AnyPolicy::Lru(_) => PolicyKind::Lru,
+            AnyPolicy::Lfu(_) => PolicyKind::Lfu,
+            AnyPolicy::Fifo(_) => PolicyKind::Fifo,
+            AnyPolicy::Arc(_) => PolicyKind::Arc,
+            AnyPolicy::TinyLfu(_) => PolicyKind::TinyLfu,
+            AnyPolicy::Slru(_) => PolicyKind::Slru,
+            AnyPolicy::Clock(_) => PolicyKind::Clock,
+            AnyPolicy::Sieve(_) => PolicyKind::Sieve,
+            AnyPolicy::S3Fifo(_) => PolicyKind::S3Fifo,
+            AnyPolicy::Mru(_) => PolicyKind::Mru,
+            AnyPolicy::Random(_) => PolicyKind::Random,
+            AnyPolicy::ExpiryFirst(_) => PolicyKind::ExpiryFirst,
+            AnyPolicy::Gdsf(_) => PolicyKind::Gdsf,
+            AnyPolicy::Adaptive(_) => PolicyKind::Adaptive,
+            AnyPolicy::ApproxLfu(_) => PolicyKind::ApproxLfu,
+        }
+    }
+}
+
+impl<K: Eq + Hash + Clone> From<PolicyKind> for AnyPolicy<K> {
+    fn from(kind: PolicyKind) -> Self {
+        AnyPolicy::from_kind(kind)
+    }
+}
+
+impl<K: Eq + Hash + Clone> EvictionPolicy<K> for AnyPolicy<K> {
+    fn evict_next(&mut self) -> K {
+        dispatch!(self, policy => policy.evict_next())
+    }
+    fn try_evict_next(&mut self) -> Option<K> {
+        dispatch!(self, policy => policy.try_evict_next())
+    }
+    fn on_insert(&mut self, key: &K, weight: u64) {
+        dispatch!(self, policy => policy.on_insert(key, weight))
+    }
+    fn on_insert_cold(&mut self, key: &K, weight: u64) {
+        dispatch!(self, policy => policy.on_insert_cold(key, weight))
+    }
+    fn on_access(&mut self, key: &K) {
+        dispatch!(self, policy => policy.on_access(key))
+    }
+    fn on_remove(&mut self, key: &K) {
+        dispatch!(self, policy => policy.on_remove(key))
+    }
+    fn on_deadline(&mut self, key: &K, deadline: Option<Instant>) {
+        dispatch!(self, policy => policy.on_deadline(key, deadline))
+    }
+    fn on_cost(&mut self, key: &K, cost: u64) {
+        dispatch!(self, policy => policy.on_cost(key, cost))
+    }
+    fn on_restore(&mut self, key: &K, score: u64) {
+        dispatch!(self, policy => policy.on_restore(key, score))
+    }
+    fn next_victim(&self) -> Option<&K> {
+        dispatch!(self, policy => policy.next_victim())
+    }
+    fn admit(&mut self, candidate: &K, victim: &K) -> bool {
+        dispatch!(self, policy => policy.admit(candidate, victim))
+    }
+    fn eviction_order(&self) -> Vec<&K> {
+        dispatch!(self, policy => policy.eviction_order())
+    }
+    fn eviction_candidates(&self, n: usize) -> Vec<&K> {
+        dispatch!(self, policy => policy.eviction_candidates(n))
+    }
+    fn score(&self, key: &K) -> Option<u64> {
+        dispatch!(self, policy => policy.score(key))
+    }
+    fn clear(&mut self) {
+        dispatch!(self, policy => policy.clear())
+    }
+    fn set_capacity(&mut self, capacity: usize) {
+        dispatch!(self, policy => policy.set_capacity(capacity))
+    }
+    fn new_like(&self) -> Self {
+        AnyPolicy::from_kind(self.kind())
+    }
+    fn new() -> Self {
+        AnyPolicy::from_kind(PolicyKind::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn any_policy_test() {
+        assert_eq!("LRU".parse::<PolicyKind>(), Ok(PolicyKind::Lru));
+        assert_eq!("expiry-first".parse::<PolicyKind>(), Ok(PolicyKind::ExpiryFirst));
+        assert_eq!("lrv".parse::<PolicyKind>(), Err(UnknownPolicy("lrv".to_string())));
+        assert_eq!(PolicyKind::TinyLfu.to_string(), "tinylfu");
+
+        let mut policy = AnyPolicy::from_kind("fifo".parse().unwrap());
+        assert_eq!(policy.kind(), PolicyKind::Fifo);
+        policy.on_insert(&"a", 1);
+        policy.on_insert(&"b", 1);
+        policy.on_access(&"a");
+        assert_eq!(policy.eviction_order(), vec![&"a", &"b"]);
+        assert!(policy.evict_next() == "a");
+
+        let mut policy = AnyPolicy::from_kind(PolicyKind::Lru);
+        policy.on_insert(&"a", 1);
+        policy.on_insert(&"b", 1);
+        policy.on_access(&"a");
+        assert!(policy.evict_next() == "b");
+        assert_eq!(policy.new_like().kind(), PolicyKind::Lru);
+
+        // an empty policy has nothing to evict, whatever its kind
+        for kind in PolicyKind::all() {
+            let mut policy = AnyPolicy::from_kind(kind);
+            policy.on_insert(&"a", 1);
+            policy.on_remove(&"a");
+            assert_eq!(policy.try_evict_next(), None, "{kind}");
+        }
+    }
+}
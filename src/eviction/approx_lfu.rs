@@ -0,0 +1,163 @@
+use crate::EvictionPolicy;
+use super::sketch::CountMinSketch;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+
+// LFU whose frequencies come from a count-min sketch rather than a counter per key ever seen
+// only resident keys are tracked exactly, each under the sketch's estimate when it was last used,
+// so memory stays proportional to the capacity however many distinct keys pass through
+// the sketch halves its counters periodically, and the estimates of resident keys are
+// refreshed when it does so keys that were popular once become evictable
+// keys with the same estimate are evicted least recently used first
+#[derive(Clone)]
+pub struct ApproxLFU<K> {
+    sketch: CountMinSketch,
+    // key to its (estimate, sequence) in order
+    resident: HashMap<K, (u8, u64)>,
+    order: BTreeMap<(u8, u64), K>,
+    next_seq: u64,
+    // sketch resets already reflected in the estimates
+    seen_resets: usize,
+}
+
+impl<K: Eq + Hash + Clone> ApproxLFU<K> {
+    // move the key to the back of the keys with this estimate
+    fn place(&mut self, key: &K, frequency: u8) {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        if let Some(old) = self.resident.insert(key.clone(), (frequency, seq)) {
+            self.order.remove(&old);
+        }
+        self.order.insert((frequency, seq), key.clone());
+    }
+
+    // re-rank every resident key by the sketch if it has halved its counters since the last time
+    fn refresh(&mut self) {
+        if self.sketch.resets() == self.seen_resets {
+            return;
+        }
+        self.seen_resets = self.sketch.resets();
+        self.order.clear();
+        for (key, (frequency, seq)) in self.resident.iter_mut() {
+            *frequency = self.sketch.frequency(key);
+            self.order.insert((*frequency, *seq), key.clone());
+        }
+    }
+
+    fn touch(&mut self, key: &K) {
+        self.sketch.increment(key);
+        let frequency = self.sketch.frequency(key);
+        self.place(key, frequency);
+        self.refresh();
+    }
+}
+
+impl<K: Eq + Hash + Clone> EvictionPolicy<K> for ApproxLFU<K> {
+    fn evict_next(&mut self) -> K {
+        let (_, key) = self.order.pop_first().expect("policy should track at least one key");
+        self.resident.remove(&key);
+        key
+    }
+    fn try_evict_next(&mut self) -> Option<K> {
+        if self.order.is_empty() {
+            return None;
+        }
+        Some(self.evict_next())
+    }
+    fn on_insert(&mut self, key: &K, _weight: u64) {
+        self.touch(key);
+    }
+    fn on_insert_cold(&mut self, key: &K, _weight: u64) {
+        // counted as never used, so it goes before every key that has been
+        self.place(key, 0);
+    }
+    fn on_access(&mut self, key: &K) {
+        if self.resident.contains_key(key) {
+            self.touch(key);
+        }
+    }
+    fn on_remove(&mut self, key: &K) {
+        if let Some(rank) = self.resident.remove(key) {
+            self.order.remove(&rank);
+        }
+    }
+    fn next_victim(&self) -> Option<&K> {
+        self.order.values().next()
+    }
+    fn eviction_order(&self) -> Vec<&K> {
+        self.order.values().collect()
+    }
+    fn eviction_candidates(&self, n: usize) -> Vec<&K> {
+        self.order.values().take(n).collect()
+    }
+    fn score(&self, key: &K) -> Option<u64> {
+        self.resident.get(key).map(|&(frequency, _)| frequency as u64)
+    }
+    fn clear(&mut self) {
+        self.resident.clear();
+        self.order.clear();
+        self.sketch.clear();
+    }
+    fn set_capacity(&mut self, capacity: usize) {
+        self.sketch = CountMinSketch::new(capacity);
+        self.seen_resets = usize::MAX;
+        self.refresh();
+    }
+    fn new() -> Self {
+        // the sketch starts small and is sized properly once the cache sets a capacity
+        ApproxLFU {
+            sketch: CountMinSketch::new(0),
+            resident: HashMap::new(),
+            order: BTreeMap::new(),
+            next_seq: 0,
+            seen_resets: 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn approx_lfu_test() {
+        let mut policy = ApproxLFU::new();
+        policy.set_capacity(3);
+        policy.on_insert(&"a", 1);
+        policy.on_insert(&"b", 1);
+        policy.on_insert(&"c", 1);
+        policy.on_access(&"a");
+        policy.on_access(&"a");
+        policy.on_access(&"b");
+
+        assert_eq!(policy.eviction_order(), vec![&"c", &"b", &"a"]);
+        assert!(policy.evict_next() == "c");
+
+        // a removed key stops being tracked, but the sketch still counts its earlier uses when it comes back
+        policy.on_remove(&"b");
+        assert_eq!(policy.eviction_order(), vec![&"a"]);
+        policy.on_insert(&"b", 1);
+        policy.on_insert_cold(&"d", 1);
+        assert_eq!(policy.next_victim(), Some(&"d"));
+        assert_eq!(policy.score(&"b"), Some(3));
+
+        // enough uses of other keys age the counts, and a hot key falls back
+        let mut policy = ApproxLFU::new();
+        policy.set_capacity(16);
+        policy.on_insert(&0, 1);
+        for _ in 0..10 {
+            policy.on_access(&0);
+        }
+        for key in 1..16 {
+            policy.on_insert(&key, 1);
+        }
+        let hot = policy.score(&0).unwrap();
+        for _ in 0..20 {
+            for key in 1..16 {
+                policy.on_access(&key);
+            }
+        }
+        assert!(policy.score(&0).unwrap() < hot);
+        assert!(policy.evict_next() == 0);
+    }
+}
@@ -0,0 +1,155 @@
+use crate::EvictionPolicy;
+use std::collections::VecDeque;
+
+// Adaptive Replacement Cache
+// t1 holds keys seen once recently and t2 keys seen at least twice,
+// b1 and b2 are ghost lists remembering keys recently evicted from t1 and t2
+// a hit in a ghost list shifts the target size p of t1 towards the list that would have kept it
+// the front of each list is its least recently used key
+#[derive(Clone)]
+pub struct ARC<K> {
+    t1: VecDeque<K>,
+    t2: VecDeque<K>,
+    b1: VecDeque<K>,
+    b2: VecDeque<K>,
+    p: usize,
+    capacity: usize,
+}
+
+fn take<K: PartialEq>(list: &mut VecDeque<K>, key: &K) -> bool {
+    if let Some(pos) = list.iter().position(|k| k == key) {
+        list.remove(pos);
+        true
+    } else {
+        false
+    }
+}
+
+impl<K: PartialEq + Clone> ARC<K> {
+    /// the capacity used to bound p and the ghost lists,
+    /// which never exceeds what is actually resident so an unbounded cache can't grow the ghosts forever
+    fn effective_capacity(&self) -> usize {
+        self.capacity.min(self.t1.len() + self.t2.len() + 1)
+    }
+
+    fn trim_ghosts(&mut self) {
+        let c = self.effective_capacity();
+        while self.b1.len() > c {
+            self.b1.pop_front();
+        }
+        while self.b2.len() > c {
+            self.b2.pop_front();
+        }
+    }
+}
+
+impl<K: PartialEq + Clone> EvictionPolicy<K> for ARC<K> {
+    fn evict_next(&mut self) -> K {
+        let key = if !self.t1.is_empty() && (self.t1.len() > self.p || self.t2.is_empty()) {
+            let key = self.t1.pop_front().expect("t1 should not be empty");
+            self.b1.push_back(key.clone());
+            key
+        } else {
+            let key = self.t2.pop_front().expect("there should be at least one element in the eviction queue");
+            self.b2.push_back(key.clone());
+            key
+        };
+        self.trim_ghosts();
+        key
+    }
+    fn try_evict_next(&mut self) -> Option<K> {
+        if self.t1.is_empty() && self.t2.is_empty() {
+            return None;
+        }
+        Some(self.evict_next())
+    }
+    fn on_access(&mut self, key: &K) {
+        if take(&mut self.t1, key) || take(&mut self.t2, key) {
+            self.t2.push_back(key.clone());
+        }
+    }
+    fn on_insert(&mut self, key: &K, _weight: u64) {
+        let c = self.effective_capacity();
+        if take(&mut self.t1, key) || take(&mut self.t2, key) {
+            // an overwrite counts as a second use
+            self.t2.push_back(key.clone());
+        } else if take(&mut self.b1, key) {
+            // recency would have kept this key, so grow t1
+            let delta = (self.b2.len() / (self.b1.len() + 1)).max(1);
+            self.p = (self.p + delta).min(c);
+            self.t2.push_back(key.clone());
+        } else if take(&mut self.b2, key) {
+            // frequency would have kept this key, so shrink t1
+            let delta = (self.b1.len() / (self.b2.len() + 1)).max(1);
+            self.p = self.p.saturating_sub(delta);
+            self.t2.push_back(key.clone());
+        } else {
+            self.t1.push_back(key.clone());
+        }
+    }
+    fn on_remove(&mut self, key: &K) {
+        let _ = take(&mut self.t1, key) || take(&mut self.t2, key);
+    }
+    fn eviction_order(&self) -> Vec<&K> {
+        // replays evict_next, which never changes p, so only the list positions move
+        let (mut i1, mut i2) = (0, 0);
+        let mut order = Vec::with_capacity(self.t1.len() + self.t2.len());
+        while i1 < self.t1.len() || i2 < self.t2.len() {
+            let t1_left = self.t1.len() - i1;
+            if t1_left > 0 && (t1_left > self.p || i2 == self.t2.len()) {
+                order.push(&self.t1[i1]);
+                i1 += 1;
+            } else {
+                order.push(&self.t2[i2]);
+                i2 += 1;
+            }
+        }
+        order
+    }
+    fn clear(&mut self) {
+        self.t1.clear();
+        self.t2.clear();
+        self.b1.clear();
+        self.b2.clear();
+        self.p = 0;
+    }
+    fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.p = self.p.min(capacity);
+        self.trim_ghosts();
+    }
+    fn new() -> Self {
+        ARC{t1: VecDeque::new(), t2: VecDeque::new(), b1: VecDeque::new(), b2: VecDeque::new(), p: 0, capacity: usize::MAX}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arc_test() {
+        let mut policy = ARC::new();
+        policy.set_capacity(3);
+
+        policy.on_insert(&"a", 1);
+        policy.on_insert(&"b", 1);
+        policy.on_access(&"a");
+        policy.on_insert(&"c", 1);
+
+        // keys seen once go first
+        assert!(policy.evict_next() == "b");
+        policy.on_insert(&"d", 1);
+        assert!(policy.evict_next() == "c");
+
+        // b is remembered as a ghost, so its return grows the recency side
+        policy.on_insert(&"b", 1);
+        assert_eq!(policy.p, 1);
+        assert_eq!(policy.eviction_order(), vec![&"a", &"b", &"d"]);
+        assert!(policy.evict_next() == "a");
+
+        policy.on_remove(&"d");
+
+        assert!(policy.evict_next() == "b");
+    }
+}
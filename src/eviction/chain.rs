@@ -0,0 +1,156 @@
+use crate::EvictionPolicy;
+use std::collections::HashSet;
+use std::hash::Hash;
+use std::time::Instant;
+
+// two policies tracking the same keys, where the first picks each victim unless it declines
+// and the second picks when it does; a key evicted by one is removed from the other
+// the first should be one that can decline, such as Filtered, since otherwise the second is never asked
+// e.g. Chain<Filtered<K, LRU<K>>, LFU<K>> evicts the keys the filter accepts in LRU order, then the rest by LFU
+#[derive(Clone)]
+pub struct Chain<A, B> {
+    first: A,
+    then: B,
+}
+
+impl<A, B> Chain<A, B> {
+    /// return a policy that asks first for each victim and falls back to then
+    pub fn of(first: A, then: B) -> Self {
+        Chain { first, then }
+    }
+}
+
+impl<K, A, B> EvictionPolicy<K> for Chain<A, B>
+where
+    K: Eq + Hash + Clone,
+    A: EvictionPolicy<K>,
+    B: EvictionPolicy<K>,
+{
+    fn evict_next(&mut self) -> K {
+        match self.first.try_evict_next() {
+            Some(key) => {
+                self.then.on_remove(&key);
+                key
+            }
+            None => {
+                let key = self.then.evict_next();
+                self.first.on_remove(&key);
+                key
+            }
+        }
+    }
+    fn try_evict_next(&mut self) -> Option<K> {
+        if let Some(key) = self.first.try_evict_next() {
+            self.then.on_remove(&key);
+            return Some(key);
+        }
+        let key = self.then.try_evict_next()?;
+        self.first.on_remove(&key);
+        Some(key)
+    }
+    fn on_insert(&mut self, key: &K, weight: u64) {
+        self.first.on_insert(key, weight);
+        self.then.on_insert(key, weight);
+    }
+    fn on_insert_cold(&mut self, key: &K, weight: u64) {
+        self.first.on_insert_cold(key, weight);
+        self.then.on_insert_cold(key, weight);
+    }
+    fn on_access(&mut self, key: &K) {
+        self.first.on_access(key);
+        self.then.on_access(key);
+    }
+    fn on_remove(&mut self, key: &K) {
+        self.first.on_remove(key);
+        self.then.on_remove(key);
+    }
+    fn on_deadline(&mut self, key: &K, deadline: Option<Instant>) {
+        self.first.on_deadline(key, deadline);
+        self.then.on_deadline(key, deadline);
+    }
+    fn on_cost(&mut self, key: &K, cost: u64) {
+        self.first.on_cost(key, cost);
+        self.then.on_cost(key, cost);
+    }
+    fn on_restore(&mut self, key: &K, score: u64) {
+        self.first.on_restore(key, score);
+        self.then.on_restore(key, score);
+    }
+    fn next_victim(&self) -> Option<&K> {
+        self.first.next_victim().or_else(|| self.then.next_victim())
+    }
+    // only the policy that would pick the victim decides
+    fn admit(&mut self, candidate: &K, victim: &K) -> bool {
+        if self.first.next_victim().is_some() {
+            self.first.admit(candidate, victim)
+        } else {
+            self.then.admit(candidate, victim)
+        }
+    }
+    // the first policy's order, then the keys it doesn't track in the second's
+    fn eviction_order(&self) -> Vec<&K> {
+        let mut order = self.first.eviction_order();
+        let seen: HashSet<&K> = order.iter().copied().collect();
+        order.extend(self.then.eviction_order().into_iter().filter(|key| !seen.contains(key)));
+        order
+    }
+    fn score(&self, key: &K) -> Option<u64> {
+        self.first.score(key).or_else(|| self.then.score(key))
+    }
+    fn clear(&mut self) {
+        self.first.clear();
+        self.then.clear();
+    }
+    fn set_capacity(&mut self, capacity: usize) {
+        self.first.set_capacity(capacity);
+        self.then.set_capacity(capacity);
+    }
+    fn new_like(&self) -> Self {
+        Chain { first: self.first.new_like(), then: self.then.new_like() }
+    }
+    fn new() -> Self {
+        Chain { first: A::new(), then: B::new() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eviction::{Filtered, LFU};
+    use crate::{Simcache, LRU};
+
+    type TempFirst = Chain<Filtered<&'static str, LRU<&'static str>>, LFU<&'static str>>;
+
+    fn temp_first() -> TempFirst {
+        Chain::of(Filtered::with_filter(LRU::new(), |key| key.starts_with("tmp")), LFU::new())
+    }
+
+    #[test]
+    fn chain_test() {
+        let mut policy = temp_first();
+        for key in ["a", "tmp1", "b", "tmp2"] {
+            policy.on_insert(&key, 1);
+        }
+        policy.on_access(&"a");
+        policy.on_access(&"tmp1");
+
+        assert_eq!(policy.eviction_order(), vec![&"tmp2", &"tmp1", &"b", &"a"]);
+        assert_eq!(policy.next_victim(), Some(&"tmp2"));
+        assert!(policy.evict_next() == "tmp2");
+        assert!(policy.evict_next() == "tmp1");
+        // the filtered keys are gone, so the rest go by use count
+        assert!(policy.evict_next() == "b");
+        assert!(policy.evict_next() == "a");
+
+        // the filter survives being handed to a cache
+        let mut cache: Simcache<&str, u64, TempFirst> = Simcache::builder()
+            .max_capacity(2)
+            .policy(temp_first())
+            .build();
+        cache.insert("a", 1, None);
+        cache.insert("tmp", 1, None);
+        cache.insert("b", 1, None);
+        assert!(cache.contains_key(&"a"));
+        assert!(!cache.contains_key(&"tmp"));
+    }
+}
@@ -0,0 +1,134 @@
+use crate::EvictionPolicy;
+use std::collections::HashMap;
+use std::hash::Hash;
+
+// CLOCK, or second chance
+// keys sit in a ring of slots, each with a reference bit that is set when the key is used
+// the hand sweeps the ring clearing set bits, and evicts the first key whose bit was already clear
+// a use only sets a bit, found through the index, so reads never reorder anything
+// slots freed by removals are reused by later inserts
+#[derive(Clone)]
+pub struct Clock<K> {
+    slots: Vec<Option<(K, bool)>>,
+    index: HashMap<K, usize>,
+    free: Vec<usize>,
+    hand: usize,
+}
+
+impl<K: Eq + Hash + Clone> Clock<K> {
+    /// iterate over the occupied slots starting from the hand
+    fn sweep(&self) -> impl Iterator<Item = &(K, bool)> + '_ {
+        let (before, after) = self.slots.split_at(self.hand.min(self.slots.len()));
+        after.iter().chain(before.iter()).flatten()
+    }
+}
+
+impl<K: Eq + Hash + Clone> EvictionPolicy<K> for Clock<K> {
+    fn evict_next(&mut self) -> K {
+        assert!(!self.index.is_empty(), "there should be at least one element in the eviction queue");
+        loop {
+            if self.hand >= self.slots.len() {
+                self.hand = 0;
+            }
+            let pos = self.hand;
+            self.hand += 1;
+            match &mut self.slots[pos] {
+                Some((_, referenced)) if *referenced => *referenced = false,
+                Some(_) => {
+                    let (key, _) = self.slots[pos].take().expect("slot should be occupied");
+                    self.index.remove(&key);
+                    self.free.push(pos);
+                    return key;
+                }
+                None => {}
+            }
+        }
+    }
+    fn try_evict_next(&mut self) -> Option<K> {
+        if self.index.is_empty() {
+            return None;
+        }
+        Some(self.evict_next())
+    }
+    fn on_insert(&mut self, key: &K, _weight: u64) {
+        if self.index.contains_key(key) {
+            self.on_access(key);
+            return;
+        }
+        let slot = Some((key.clone(), false));
+        let pos = match self.free.pop() {
+            Some(pos) => {
+                self.slots[pos] = slot;
+                pos
+            }
+            None => {
+                self.slots.push(slot);
+                self.slots.len() - 1
+            }
+        };
+        self.index.insert(key.clone(), pos);
+    }
+    fn on_access(&mut self, key: &K) {
+        if let Some(&pos) = self.index.get(key) {
+            if let Some((_, referenced)) = &mut self.slots[pos] {
+                *referenced = true;
+            }
+        }
+    }
+    fn on_remove(&mut self, key: &K) {
+        if let Some(pos) = self.index.remove(key) {
+            self.slots[pos] = None;
+            self.free.push(pos);
+        }
+    }
+    fn next_victim(&self) -> Option<&K> {
+        self.sweep()
+            .find(|(_, referenced)| !referenced)
+            .or_else(|| self.sweep().next())
+            .map(|(key, _)| key)
+    }
+    fn eviction_order(&self) -> Vec<&K> {
+        // the first sweep takes every clear key and clears the rest, which the second sweep then takes
+        let clear = self.sweep().filter(|(_, referenced)| !referenced);
+        let referenced = self.sweep().filter(|(_, referenced)| *referenced);
+        clear.chain(referenced).map(|(key, _)| key).collect()
+    }
+    fn clear(&mut self) {
+        self.slots.clear();
+        self.index.clear();
+        self.free.clear();
+        self.hand = 0;
+    }
+    fn new() -> Self {
+        Clock{slots: Vec::new(), index: HashMap::new(), free: Vec::new(), hand: 0}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clock_test() {
+        let mut policy = Clock::new();
+
+        policy.on_insert(&"a", 1);
+        policy.on_insert(&"b", 1);
+        policy.on_insert(&"c", 1);
+        policy.on_access(&"a");
+        policy.on_access(&"c");
+
+        // a and c get a second chance, b doesn't
+        assert_eq!(policy.eviction_order(), vec![&"b", &"a", &"c"]);
+        assert!(policy.evict_next() == "b");
+
+        // d reuses b's slot, and c's bit still buys it another sweep
+        policy.on_insert(&"d", 1);
+        assert_eq!(policy.eviction_order(), vec![&"a", &"d", &"c"]);
+
+        policy.on_remove(&"a");
+
+        assert!(policy.evict_next() == "d");
+        assert!(policy.evict_next() == "c");
+    }
+}
@@ -0,0 +1,134 @@
+use crate::EvictionPolicy;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::hash::Hash;
+use std::time::Instant;
+
+// evicts the key closest to expiring, so the cache gives up entries that would soon be gone anyway
+// keys with a deadline are bucketed by it in the BTreeMap, oldest insert first within a bucket
+// keys that never expire are only evicted once no key has a deadline, in LRU order
+#[derive(Clone)]
+pub struct ExpiryFirst<K> {
+    by_deadline: BTreeMap<Instant, VecDeque<K>>,
+    deadlines: HashMap<K, Instant>,
+    access_order: VecDeque<K>,
+}
+
+fn take<K: PartialEq>(list: &mut VecDeque<K>, key: &K) -> bool {
+    if let Some(pos) = list.iter().position(|k| k == key) {
+        list.remove(pos);
+        true
+    } else {
+        false
+    }
+}
+
+impl<K: Eq + Hash + Clone> ExpiryFirst<K> {
+    // drop the key from whichever structure holds it, returning false if it wasn't tracked
+    fn untrack(&mut self, key: &K) -> bool {
+        match self.deadlines.remove(key) {
+            Some(deadline) => {
+                let bucket = self.by_deadline.get_mut(&deadline).expect("deadline should have a bucket");
+                take(bucket, key);
+                if bucket.is_empty() {
+                    self.by_deadline.remove(&deadline);
+                }
+                true
+            }
+            None => take(&mut self.access_order, key),
+        }
+    }
+}
+
+impl<K: Eq + Hash + Clone> EvictionPolicy<K> for ExpiryFirst<K> {
+    fn evict_next(&mut self) -> K {
+        if let Some(mut entry) = self.by_deadline.first_entry() {
+            let key = entry.get_mut().pop_front().expect("buckets should not be empty");
+            if entry.get().is_empty() {
+                entry.remove();
+            }
+            self.deadlines.remove(&key);
+            return key;
+        }
+        self.access_order.pop_front().expect("there should be at least one element in the eviction queue")
+    }
+    fn try_evict_next(&mut self) -> Option<K> {
+        if self.by_deadline.is_empty() && self.access_order.is_empty() {
+            return None;
+        }
+        Some(self.evict_next())
+    }
+    fn on_insert(&mut self, key: &K, _weight: u64) {
+        // the cache reports the deadline straight after, so a new key starts out never expiring
+        if self.deadlines.contains_key(key) || self.access_order.contains(key) {
+            self.on_access(key);
+        } else {
+            self.access_order.push_back(key.clone());
+        }
+    }
+    fn on_access(&mut self, key: &K) {
+        if take(&mut self.access_order, key) {
+            self.access_order.push_back(key.clone());
+        }
+    }
+    fn on_remove(&mut self, key: &K) {
+        self.untrack(key);
+    }
+    fn on_deadline(&mut self, key: &K, deadline: Option<Instant>) {
+        if !self.untrack(key) {
+            return;
+        }
+        match deadline {
+            Some(deadline) => {
+                self.by_deadline.entry(deadline).or_default().push_back(key.clone());
+                self.deadlines.insert(key.clone(), deadline);
+            }
+            None => self.access_order.push_back(key.clone()),
+        }
+    }
+    fn next_victim(&self) -> Option<&K> {
+        self.by_deadline.values().next().and_then(|keys| keys.front()).or(self.access_order.front())
+    }
+    fn eviction_order(&self) -> Vec<&K> {
+        self.by_deadline.values().flatten().chain(self.access_order.iter()).collect()
+    }
+    fn clear(&mut self) {
+        self.by_deadline.clear();
+        self.deadlines.clear();
+        self.access_order.clear();
+    }
+    fn new() -> Self {
+        ExpiryFirst{by_deadline: BTreeMap::new(), deadlines: HashMap::new(), access_order: VecDeque::new()}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn expiry_first_test() {
+        let mut policy = ExpiryFirst::new();
+        let now = Instant::now();
+
+        for key in ["a", "b", "c", "d"] {
+            policy.on_insert(&key, 1);
+        }
+        policy.on_deadline(&"b", Some(now + Duration::from_secs(10)));
+        policy.on_deadline(&"c", Some(now + Duration::from_secs(5)));
+        policy.on_access(&"a");
+
+        // expiring keys go first, soonest first, then the rest in LRU order
+        assert_eq!(policy.eviction_order(), vec![&"c", &"b", &"d", &"a"]);
+        assert!(policy.evict_next() == "c");
+
+        // b no longer expires, so it joins the LRU order as most recently used
+        policy.on_deadline(&"b", None);
+        assert_eq!(policy.next_victim(), Some(&"d"));
+
+        policy.on_remove(&"d");
+
+        assert!(policy.evict_next() == "a");
+        assert!(policy.evict_next() == "b");
+    }
+}
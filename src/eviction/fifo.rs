@@ -0,0 +1,69 @@
+use crate::EvictionPolicy;
+use std::collections::VecDeque;
+
+// keys are queued in the order they were first inserted
+// reads and overwrites of a queued key do not change its position
+#[derive(Clone)]
+pub struct FIFO<K> {insert_order: VecDeque<K>}
+
+impl<K: PartialEq + Clone> EvictionPolicy<K> for FIFO<K> {
+    fn evict_next(&mut self) -> K {
+        self.insert_order.pop_front().expect("there should be at least one element in the eviction queue")
+    }
+    fn try_evict_next(&mut self) -> Option<K> {
+        self.insert_order.pop_front()
+    }
+    fn on_access(&mut self, _key: &K) {}
+    fn on_insert(&mut self, key: &K, _weight: u64) {
+        if !self.insert_order.contains(key) {
+            self.insert_order.push_back(key.clone());
+        }
+    }
+    fn on_insert_cold(&mut self, key: &K, _weight: u64) {
+        if !self.insert_order.contains(key) {
+            self.insert_order.push_front(key.clone());
+        }
+    }
+    fn on_remove(&mut self, key: &K) {
+        if let Some(pos) = self.insert_order.iter().position(|k| k == key) {
+            self.insert_order.remove(pos);
+        }
+    }
+    fn next_victim(&self) -> Option<&K> {
+        self.insert_order.front()
+    }
+    fn eviction_order(&self) -> Vec<&K> {
+        self.insert_order.iter().collect()
+    }
+    fn eviction_candidates(&self, n: usize) -> Vec<&K> {
+        self.insert_order.iter().take(n).collect()
+    }
+    fn clear(&mut self) {
+        self.insert_order.clear();
+    }
+    fn new() -> Self {
+        FIFO{insert_order: VecDeque::new()}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fifo_test() {
+        let mut policy = FIFO::new();
+
+        policy.on_insert(&"a", 1);
+        policy.on_insert(&"b", 1);
+        policy.on_insert(&"c", 1);
+        policy.on_access(&"a");
+        policy.on_insert(&"a", 1);
+
+        assert!(policy.evict_next() == "a");
+
+        policy.on_remove(&"b");
+
+        assert!(policy.evict_next() == "c");
+    }
+}
@@ -0,0 +1,134 @@
+use crate::EvictionPolicy;
+use std::collections::HashSet;
+use std::hash::Hash;
+use std::time::Instant;
+
+// passes only the keys its filter accepts on to the inner policy, and declines to evict once it has none
+// meant as the first policy of a Chain, where the next policy covers the keys it leaves out;
+// on its own it panics when asked to evict while the cache only holds keys it ignored
+// new accepts every key, so the filter is given with with_filter and the builder's policy method
+#[derive(Clone)]
+pub struct Filtered<K, P> {
+    inner: P,
+    filter: fn(&K) -> bool,
+    tracked: HashSet<K>,
+}
+
+impl<K, P> Filtered<K, P> {
+    /// return a policy that hands the keys the filter accepts to inner and ignores the rest
+    pub fn with_filter(inner: P, filter: fn(&K) -> bool) -> Self {
+        Filtered { inner, filter, tracked: HashSet::new() }
+    }
+}
+
+impl<K: Eq + Hash + Clone, P: EvictionPolicy<K>> EvictionPolicy<K> for Filtered<K, P> {
+    fn evict_next(&mut self) -> K {
+        self.try_evict_next().expect("a filtered policy should track a key to evict, so it should come first in a Chain")
+    }
+    fn try_evict_next(&mut self) -> Option<K> {
+        if self.tracked.is_empty() {
+            return None;
+        }
+        let key = self.inner.evict_next();
+        self.tracked.remove(&key);
+        Some(key)
+    }
+    fn on_insert(&mut self, key: &K, weight: u64) {
+        if (self.filter)(key) {
+            self.tracked.insert(key.clone());
+            self.inner.on_insert(key, weight);
+        }
+    }
+    fn on_insert_cold(&mut self, key: &K, weight: u64) {
+        if (self.filter)(key) {
+            self.tracked.insert(key.clone());
+            self.inner.on_insert_cold(key, weight);
+        }
+    }
+    fn on_access(&mut self, key: &K) {
+        if self.tracked.contains(key) {
+            self.inner.on_access(key);
+        }
+    }
+    fn on_remove(&mut self, key: &K) {
+        if self.tracked.remove(key) {
+            self.inner.on_remove(key);
+        }
+    }
+    fn on_deadline(&mut self, key: &K, deadline: Option<Instant>) {
+        if self.tracked.contains(key) {
+            self.inner.on_deadline(key, deadline);
+        }
+    }
+    fn on_cost(&mut self, key: &K, cost: u64) {
+        if self.tracked.contains(key) {
+            self.inner.on_cost(key, cost);
+        }
+    }
+    fn on_restore(&mut self, key: &K, score: u64) {
+        if self.tracked.contains(key) {
+            self.inner.on_restore(key, score);
+        }
+    }
+    fn next_victim(&self) -> Option<&K> {
+        if self.tracked.is_empty() {
+            return None;
+        }
+        self.inner.next_victim()
+    }
+    fn admit(&mut self, candidate: &K, victim: &K) -> bool {
+        self.inner.admit(candidate, victim)
+    }
+    fn eviction_order(&self) -> Vec<&K> {
+        self.inner.eviction_order()
+    }
+    fn eviction_candidates(&self, n: usize) -> Vec<&K> {
+        self.inner.eviction_candidates(n)
+    }
+    fn score(&self, key: &K) -> Option<u64> {
+        self.tracked.contains(key).then(|| self.inner.score(key)).flatten()
+    }
+    fn clear(&mut self) {
+        self.tracked.clear();
+        self.inner.clear();
+    }
+    fn set_capacity(&mut self, capacity: usize) {
+        self.inner.set_capacity(capacity);
+    }
+    fn new_like(&self) -> Self {
+        Filtered { inner: self.inner.new_like(), filter: self.filter, tracked: HashSet::new() }
+    }
+    fn new() -> Self {
+        Filtered { inner: P::new(), filter: |_| true, tracked: HashSet::new() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LRU;
+
+    #[test]
+    fn filtered_test() {
+        let mut policy = Filtered::with_filter(LRU::new(), |key: &&str| key.starts_with("tmp"));
+
+        for key in ["tmp1", "a", "tmp2", "b"] {
+            policy.on_insert(&key, 1);
+        }
+        policy.on_access(&"tmp1");
+        policy.on_access(&"a");
+
+        assert_eq!(policy.eviction_order(), vec![&"tmp2", &"tmp1"]);
+        assert_eq!(policy.score(&"a"), None);
+        policy.on_remove(&"tmp2");
+        assert_eq!(policy.try_evict_next(), Some("tmp1"));
+        // nothing it tracks is left, so it declines
+        assert_eq!(policy.next_victim(), None);
+        assert_eq!(policy.try_evict_next(), None);
+
+        // new_like keeps the filter
+        let mut other = policy.new_like();
+        other.on_insert(&"c", 1);
+        assert_eq!(other.try_evict_next(), None);
+    }
+}
@@ -0,0 +1,128 @@
+use crate::EvictionPolicy;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+
+// GreedyDual-Size-Frequency
+// each key's priority is inflation + uses * cost / weight, and the lowest priority is evicted first
+// inflation is raised to each evicted key's priority, so keys that stop being used
+// fall behind newer ones instead of keeping the place their old uses earned them
+// cost comes from insert_with_cost and defaults to 1; weight comes from the cache's weigher
+// the queue is ordered on the bits of the priority, which sort like the non-negative floats they are,
+// then on a sequence number so that ties go to the key whose priority was set first
+#[derive(Clone)]
+pub struct GDSF<K> {
+    queue: BTreeMap<(u64, u64), K>,
+    entries: HashMap<K, Meta>,
+    inflation: f64,
+    next_seq: u64,
+}
+
+#[derive(Clone)]
+struct Meta {
+    uses: u64,
+    weight: u64,
+    cost: u64,
+    slot: (u64, u64),
+}
+
+impl<K: Eq + Hash + Clone> GDSF<K> {
+    // recompute the key's priority from its metadata and move it to its new place in the queue
+    fn requeue(&mut self, key: &K) {
+        let Some(meta) = self.entries.get_mut(key) else {
+            return;
+        };
+        self.queue.remove(&meta.slot);
+        let priority = self.inflation + meta.uses as f64 * meta.cost as f64 / meta.weight.max(1) as f64;
+        meta.slot = (priority.to_bits(), self.next_seq);
+        self.next_seq += 1;
+        self.queue.insert(meta.slot, key.clone());
+    }
+}
+
+impl<K: Eq + Hash + Clone> EvictionPolicy<K> for GDSF<K> {
+    fn evict_next(&mut self) -> K {
+        let ((bits, _), key) = self.queue.pop_first().expect("there should be at least one element in the eviction queue");
+        self.entries.remove(&key);
+        self.inflation = f64::from_bits(bits);
+        key
+    }
+    fn try_evict_next(&mut self) -> Option<K> {
+        if self.queue.is_empty() {
+            return None;
+        }
+        Some(self.evict_next())
+    }
+    fn on_insert(&mut self, key: &K, weight: u64) {
+        match self.entries.get_mut(key) {
+            Some(meta) => {
+                meta.weight = weight;
+                meta.uses += 1;
+            }
+            None => {
+                self.entries.insert(key.clone(), Meta { uses: 1, weight, cost: 1, slot: (0, 0) });
+            }
+        }
+        self.requeue(key);
+    }
+    fn on_access(&mut self, key: &K) {
+        if let Some(meta) = self.entries.get_mut(key) {
+            meta.uses += 1;
+            self.requeue(key);
+        }
+    }
+    fn on_remove(&mut self, key: &K) {
+        if let Some(meta) = self.entries.remove(key) {
+            self.queue.remove(&meta.slot);
+        }
+    }
+    fn on_cost(&mut self, key: &K, cost: u64) {
+        if let Some(meta) = self.entries.get_mut(key) {
+            meta.cost = cost;
+            self.requeue(key);
+        }
+    }
+    fn next_victim(&self) -> Option<&K> {
+        self.queue.values().next()
+    }
+    fn eviction_order(&self) -> Vec<&K> {
+        // an eviction only raises inflation, which doesn't move any queued key
+        self.queue.values().collect()
+    }
+    fn clear(&mut self) {
+        self.queue.clear();
+        self.entries.clear();
+        self.inflation = 0.0;
+    }
+    fn new() -> Self {
+        GDSF{queue: BTreeMap::new(), entries: HashMap::new(), inflation: 0.0, next_seq: 0}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gdsf_test() {
+        let mut policy = GDSF::new();
+
+        policy.on_insert(&"a", 1);
+        policy.on_insert(&"b", 10);
+        policy.on_insert(&"c", 1);
+        policy.on_cost(&"c", 100);
+
+        // b is heavy and c is expensive to fetch again
+        assert_eq!(policy.eviction_order(), vec![&"b", &"a", &"c"]);
+        assert!(policy.evict_next() == "b");
+
+        // inflation is now b's priority, so d starts ahead of where a did
+        policy.on_access(&"a");
+        policy.on_insert(&"d", 1);
+        assert_eq!(policy.eviction_order(), vec![&"d", &"a", &"c"]);
+
+        policy.on_remove(&"d");
+
+        assert!(policy.evict_next() == "a");
+        assert!(policy.evict_next() == "c");
+    }
+}
@@ -1,15 +1,53 @@
 use crate::EvictionPolicy;
-use std::collections::{BTreeMap, HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap};
 
 // Dual data structure allows for all necessary operations to be handled efficiently
 // The HashMap stores Key:Count 
 // The BTree stores Count:{set of Keys}
+// With aging enabled, every count is halved after each aging_period uses
+// so that keys which are no longer popular eventually become evictable
+// Counts stop at max_count, so no key can build up a lead that takes forever to lose
+// An evicted or removed key is forgotten and starts over at 1 if it comes back;
+// with reset_on_insert a key overwritten while cached starts over too
+#[derive(Clone)]
 pub struct LFU<K>{
     usage_counter: HashMap<K, usize>,
     count_to_key: BTreeMap<usize, std::collections::HashSet<K>>,
+    aging_period: Option<usize>,
+    uses_since_aging: usize,
+    max_count: usize,
+    reset_on_insert: bool,
 } 
 
 impl<K: Clone + Eq + std::hash::Hash> LFU<K> {
+    /// return an LFU policy that halves every count after each `period` uses
+    pub fn with_aging(period: usize) -> Self {
+        LFU{aging_period: Some(period.max(1)), ..LFU::new()}
+    }
+
+    /// stop counting a key's uses at max_count, so a key used heavily long ago is overtaken
+    /// by one in use now as soon as that reaches the same count
+    pub fn max_count(mut self, max_count: usize) -> Self {
+        self.max_count = max_count.max(1);
+        self
+    }
+
+    /// start a key's count over at 1 when it is overwritten, rather than counting the write as a use
+    pub fn reset_on_insert(mut self) -> Self {
+        self.reset_on_insert = true;
+        self
+    }
+
+    fn age(&mut self) {
+        self.count_to_key.clear();
+        for (key, count) in self.usage_counter.iter_mut() {
+            // counts never drop below 1, which is reserved for tracked keys
+            *count = (*count / 2).max(1);
+            self.count_to_key.entry(*count).or_default().insert(key.clone());
+        }
+        self.uses_since_aging = 0;
+    }
+
     fn update_count_mapping(&mut self, key: &K, old_count: usize, new_count: usize) {
         // Remove from old bucket
         if old_count > 0 {
@@ -24,7 +62,7 @@ impl<K: Clone + Eq + std::hash::Hash> LFU<K> {
         // Add to new bucket
         self.count_to_key
             .entry(new_count)
-            .or_insert_with(HashSet::new)
+            .or_default()
             .insert(key.clone());
     }
 }
@@ -59,14 +97,20 @@ impl<K: Clone + Eq + std::hash::Hash>EvictionPolicy<K> for LFU<K> {
         
         key_to_evict
     }
+    fn try_evict_next(&mut self) -> Option<K> {
+        if self.count_to_key.is_empty() {
+            return None;
+        }
+        Some(self.evict_next())
+    }
     
-    fn key_used(&mut self, key: &K) {
+    fn on_access(&mut self, key: &K) {
         use std::collections::hash_map::Entry;
         
         let (old_count, new_count) = match self.usage_counter.entry(key.clone()) {
             Entry::Occupied(mut e) => {
                 let old = *e.get();
-                let new = old.saturating_add(1);
+                let new = old.saturating_add(1).min(self.max_count);
                 e.insert(new);
                 (old, new)
             }
@@ -78,9 +122,34 @@ impl<K: Clone + Eq + std::hash::Hash>EvictionPolicy<K> for LFU<K> {
         
         // Handle count_to_key updates...
         self.update_count_mapping(key, old_count, new_count);
+
+        if let Some(period) = self.aging_period {
+            self.uses_since_aging += 1;
+            if self.uses_since_aging >= period {
+                self.age();
+            }
+        }
+    }
+
+    fn on_insert(&mut self, key: &K, _weight: u64) {
+        match self.usage_counter.get_mut(key) {
+            Some(count) if self.reset_on_insert => {
+                let old_count = std::mem::replace(count, 1);
+                self.update_count_mapping(key, old_count, 1);
+            }
+            _ => self.on_access(key),
+        }
+    }
+
+    fn on_restore(&mut self, key: &K, score: u64) {
+        if let Some(count) = self.usage_counter.get_mut(key) {
+            let restored = usize::try_from(score).unwrap_or(usize::MAX).clamp(1, self.max_count);
+            let old_count = std::mem::replace(count, restored);
+            self.update_count_mapping(key, old_count, restored);
+        }
     }
 
-    fn remove_key(&mut self, key: &K) {
+    fn on_remove(&mut self, key: &K) {
         let res = self.usage_counter.remove_entry(key);
         if res.is_none() {
             return
@@ -95,8 +164,34 @@ impl<K: Clone + Eq + std::hash::Hash>EvictionPolicy<K> for LFU<K> {
         }
     }
 
+    fn next_victim(&self) -> Option<&K> {
+        self.count_to_key.first_key_value().and_then(|(_, keys)| keys.iter().next())
+    }
+    fn eviction_order(&self) -> Vec<&K> {
+        // evict_next takes the first key of the lowest count's set, which is the set's iteration order
+        self.count_to_key.values().flat_map(|keys| keys.iter()).collect()
+    }
+    fn eviction_candidates(&self, n: usize) -> Vec<&K> {
+        self.count_to_key.values().flat_map(|keys| keys.iter()).take(n).collect()
+    }
+    fn score(&self, key: &K) -> Option<u64> {
+        self.usage_counter.get(key).map(|&count| count as u64)
+    }
+
+    fn clear(&mut self) {
+        self.usage_counter.clear();
+        self.count_to_key.clear();
+        self.uses_since_aging = 0;
+    }
     fn new() -> Self {
-        return LFU{usage_counter: HashMap::new(), count_to_key: BTreeMap::new()}
+        LFU{
+            usage_counter: HashMap::new(),
+            count_to_key: BTreeMap::new(),
+            aging_period: None,
+            uses_since_aging: 0,
+            max_count: usize::MAX,
+            reset_on_insert: false,
+        }
     }
 }
 
@@ -109,16 +204,55 @@ mod tests {
     fn lfu_test() {
         let mut policy = LFU::new();
 
-        policy.key_used(&"key1");
-        policy.key_used(&"key2");
-        policy.key_used(&"key2");
-        policy.key_used(&"key3");
-        policy.key_used(&"key3");
+        policy.on_access(&"key1");
+        policy.on_access(&"key2");
+        policy.on_access(&"key2");
+        policy.on_access(&"key3");
+        policy.on_access(&"key3");
 
+        assert_eq!(policy.eviction_order()[0], &"key1");
         assert!(policy.evict_next() == "key1");
 
-        policy.remove_key(&"key2");
+        policy.on_remove(&"key2");
 
         assert!(policy.evict_next() == "key3");
     }
+
+    #[test]
+    fn lfu_aging_test() {
+        let mut policy = LFU::with_aging(8);
+
+        for _ in 0..6 {
+            policy.on_access(&"old");
+        }
+        // the 8th use halves everything, old drops to 3 and new to 1
+        policy.on_access(&"new");
+        policy.on_access(&"new");
+        assert_eq!(policy.usage_counter[&"old"], 3);
+
+        for _ in 0..3 {
+            policy.on_access(&"new");
+        }
+
+        assert!(policy.evict_next() == "old");
+    }
+
+    #[test]
+    fn lfu_max_count_test() {
+        let mut policy = LFU::new().max_count(3).reset_on_insert();
+
+        for _ in 0..10 {
+            policy.on_access(&"old");
+        }
+        policy.on_access(&"mid");
+        policy.on_access(&"mid");
+        assert_eq!(policy.usage_counter[&"old"], 3);
+        assert!(policy.evict_next() == "mid");
+
+        // overwriting old starts it over, behind a key used twice since
+        policy.on_insert(&"new", 1);
+        policy.on_access(&"new");
+        policy.on_insert(&"old", 1);
+        assert_eq!(policy.eviction_order(), vec![&"old", &"new"]);
+    }
 }
@@ -1,23 +1,47 @@
 use crate::EvictionPolicy;
 use std::collections::VecDeque;
 
+#[derive(Clone)]
 pub struct LRU<K> {access_order: VecDeque<K>}
 
 impl<K: PartialEq + Clone> EvictionPolicy<K> for LRU<K> {
     fn evict_next(&mut self) -> K {
-        return self.access_order.pop_front().expect("there should be at least one element in the eviction queue")
+        self.access_order.pop_front().expect("there should be at least one element in the eviction queue")
     }
-    fn key_used(&mut self, key: &K) {
-        self.remove_key(key);
+    fn try_evict_next(&mut self) -> Option<K> {
+        self.access_order.pop_front()
+    }
+    fn on_insert_cold(&mut self, key: &K, _weight: u64) {
+        self.on_remove(key);
+        self.access_order.push_front(key.clone());
+    }
+    fn on_access(&mut self, key: &K) {
+        self.on_remove(key);
         self.access_order.push_back(key.clone());
     }
-    fn remove_key(&mut self, key: &K) {
+    fn on_remove(&mut self, key: &K) {
         if let Some(pos) = self.access_order.iter().position(|k| k == key) {
             self.access_order.remove(pos);
         }
     }
+    fn next_victim(&self) -> Option<&K> {
+        self.access_order.front()
+    }
+    fn eviction_order(&self) -> Vec<&K> {
+        self.access_order.iter().collect()
+    }
+    fn eviction_candidates(&self, n: usize) -> Vec<&K> {
+        self.access_order.iter().take(n).collect()
+    }
+    // the number of keys used less recently
+    fn score(&self, key: &K) -> Option<u64> {
+        self.access_order.iter().position(|k| k == key).map(|rank| rank as u64)
+    }
+    fn clear(&mut self) {
+        self.access_order.clear();
+    }
     fn new() -> Self {
-        return LRU{access_order: VecDeque::new()}
+        LRU{access_order: VecDeque::new()}
     }
 }
 
@@ -29,14 +53,16 @@ mod tests {
     fn lru_test() {
         let mut policy = LRU::new();
 
-        policy.key_used(&"a");
-        policy.key_used(&"b");
-        policy.key_used(&"c");
-        policy.key_used(&"a");
+        policy.on_access(&"a");
+        policy.on_access(&"b");
+        policy.on_access(&"c");
+        policy.on_access(&"a");
 
+        assert_eq!(policy.eviction_order(), vec![&"b", &"c", &"a"]);
+        assert_eq!(policy.score(&"a"), Some(2));
         assert!(policy.evict_next() == "b");
 
-        policy.remove_key(&"c");
+        policy.on_remove(&"c");
 
         assert!(policy.evict_next() == "a");
     }
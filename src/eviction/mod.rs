@@ -3,7 +3,40 @@
 mod policy;
 mod lru;
 mod lfu;
+mod approx_lfu;
+mod fifo;
+mod arc;
+mod sketch;
+mod tinylfu;
+mod slru;
+mod clock;
+mod sieve;
+mod s3fifo;
+mod mru;
+mod random;
+mod expiry_first;
+mod gdsf;
+mod adaptive;
+mod chain;
+mod filtered;
+mod any;
 
 pub use policy::EvictionPolicy;
 pub use lru::LRU;
-pub use lfu::LFU;
\ No newline at end of file
+pub use lfu::LFU;
+pub use approx_lfu::ApproxLFU;
+pub use fifo::FIFO;
+pub use arc::ARC;
+pub use tinylfu::TinyLFU;
+pub use slru::SLRU;
+pub use clock::Clock;
+pub use sieve::Sieve;
+pub use s3fifo::S3Fifo;
+pub use mru::MRU;
+pub use random::RandomEviction;
+pub use expiry_first::ExpiryFirst;
+pub use gdsf::GDSF;
+pub use adaptive::Adaptive;
+pub use chain::Chain;
+pub use filtered::Filtered;
+pub use any::{AnyPolicy, PolicyKind, UnknownPolicy};
\ No newline at end of file
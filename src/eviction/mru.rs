@@ -0,0 +1,60 @@
+use crate::EvictionPolicy;
+use std::collections::VecDeque;
+
+// accessed keys are pushed onto the back of the access_order queue, as in LRU,
+// but keys are evicted from the back, so the most recently used key goes first
+// this keeps most of a cyclic scan that is larger than the cache resident
+#[derive(Clone)]
+pub struct MRU<K> {access_order: VecDeque<K>}
+
+impl<K: PartialEq + Clone> EvictionPolicy<K> for MRU<K> {
+    fn evict_next(&mut self) -> K {
+        self.access_order.pop_back().expect("there should be at least one element in the eviction queue")
+    }
+    fn try_evict_next(&mut self) -> Option<K> {
+        self.access_order.pop_back()
+    }
+    fn on_access(&mut self, key: &K) {
+        self.on_remove(key);
+        self.access_order.push_back(key.clone());
+    }
+    fn on_remove(&mut self, key: &K) {
+        if let Some(pos) = self.access_order.iter().position(|k| k == key) {
+            self.access_order.remove(pos);
+        }
+    }
+    fn next_victim(&self) -> Option<&K> {
+        self.access_order.back()
+    }
+    fn eviction_order(&self) -> Vec<&K> {
+        self.access_order.iter().rev().collect()
+    }
+    fn clear(&mut self) {
+        self.access_order.clear();
+    }
+    fn new() -> Self {
+        MRU{access_order: VecDeque::new()}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mru_test() {
+        let mut policy = MRU::new();
+
+        policy.on_access(&"a");
+        policy.on_access(&"b");
+        policy.on_access(&"c");
+        policy.on_access(&"a");
+
+        assert_eq!(policy.eviction_order(), vec![&"a", &"c", &"b"]);
+        assert!(policy.evict_next() == "a");
+
+        policy.on_remove(&"c");
+
+        assert!(policy.evict_next() == "b");
+    }
+}
@@ -1,7 +1,141 @@
+use std::time::Instant;
+
 pub trait EvictionPolicy<K> {
+    /// stop tracking the key the policy would evict next and return it
+    /// the cache only calls it while the policy tracks a key, and it may panic if there is none
     fn evict_next(&mut self) -> K;
-    fn key_used(&mut self, key: &K);
-    fn remove_key(&mut self, key: &K);
+    /// like evict_next, but may return None to decline choosing, so that a Chain asks its next policy
+    /// a policy that declines should also return None from next_victim whenever it would
+    /// the built-in policies decline when they track no keys, rather than panicking
+    /// defaults to evict_next, which never declines
+    fn try_evict_next(&mut self) -> Option<K> {
+        Some(self.evict_next())
+    }
+    /// called when a key is written to the cache, with the weight of the new entry
+    /// this covers both new keys and overwrites of a live key
+    fn on_insert(&mut self, key: &K, weight: u64) {
+        #[allow(deprecated)]
+        self.key_inserted(key, weight);
+    }
+    /// called instead of on_insert for keys bulk loaded as cold,
+    /// which should be the first to go unless they are used
+    /// defaults to on_insert
+    fn on_insert_cold(&mut self, key: &K, weight: u64) {
+        self.on_insert(key, weight);
+    }
+    /// called when a live key is read or otherwise used
+    fn on_access(&mut self, key: &K);
+    /// called when a key leaves the cache for any reason other than evict_next
+    fn on_remove(&mut self, key: &K);
+    /// called after on_insert, and whenever a live entry's expiry time changes,
+    /// with the earliest time the entry expires at, or None if it never does
+    fn on_deadline(&mut self, _key: &K, _deadline: Option<Instant>) {}
+    /// called after on_insert when the entry was inserted with insert_with_cost,
+    /// with the cost of fetching its value again
+    fn on_cost(&mut self, _key: &K, _cost: u64) {}
+    /// called after on_insert for a key loaded from a snapshot, with the score it had when the snapshot was saved,
+    /// so that state such as use counts survives a restart
+    /// defaults to ignoring it, for policies whose state is recreated by the order keys are loaded in
+    fn on_restore(&mut self, _key: &K, _score: u64) {}
+    /// older name for on_insert, only called by policies that don't implement on_insert
+    /// defaults to treating the write as a use
+    #[deprecated(note = "implement on_insert instead")]
+    fn key_inserted(&mut self, key: &K, _weight: u64) {
+        self.on_access(key);
+    }
+    /// older name for on_access, kept for callers; forwards to on_access
+    #[deprecated(note = "call on_access instead")]
+    fn key_used(&mut self, key: &K) {
+        self.on_access(key);
+    }
+    /// older name for on_remove, kept for callers; forwards to on_remove
+    #[deprecated(note = "call on_remove instead")]
+    fn remove_key(&mut self, key: &K) {
+        self.on_remove(key);
+    }
+    /// return the key that evict_next would return, without changing any state
+    /// policies that can't tell return None, and are never asked to admit a key
+    fn next_victim(&self) -> Option<&K> {
+        None
+    }
+    /// called before a new key is inserted into a full cache, with the key it would displace
+    /// return false to keep the victim and drop the candidate instead
+    /// defaults to admitting every key
+    fn admit(&mut self, _candidate: &K, _victim: &K) -> bool {
+        true
+    }
+    /// return the tracked keys in the order they would be evicted, without changing any state
+    /// policies that can't predict their order return nothing
+    fn eviction_order(&self) -> Vec<&K> {
+        Vec::new()
+    }
+    /// return the first n keys of eviction_order, without changing any state
+    /// defaults to cutting eviction_order short, policies that can stop early should
+    fn eviction_candidates(&self, n: usize) -> Vec<&K> {
+        let mut order = self.eviction_order();
+        order.truncate(n);
+        order
+    }
+    /// return the policy's own measure of how worth keeping the key is, for inspecting entries,
+    /// such as its use count for LFU or its recency rank for LRU; higher is kept longer
+    /// policies without a per-key score return None
+    fn score(&self, _key: &K) -> Option<u64> {
+        None
+    }
+    /// forget every tracked key
+    /// defaults to replacing the policy with a new one, which loses any configuration
+    fn clear(&mut self) where Self: Sized {
+        *self = Self::new();
+    }
+    /// called by the cache with its entry limit when it is created or cleared
+    fn set_capacity(&mut self, _capacity: usize) {}
+    /// return a new, empty policy configured like this one, for the cache's other priority levels
+    /// defaults to new
+    fn new_like(&self) -> Self where Self: Sized {
+        Self::new()
+    }
     fn new() -> Self;
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a policy implementing only the required methods
+    struct Legacy {
+        used: Vec<&'static str>,
+    }
+
+    impl EvictionPolicy<&'static str> for Legacy {
+        fn evict_next(&mut self) -> &'static str {
+            self.used.remove(0)
+        }
+        fn on_access(&mut self, key: &&'static str) {
+            self.used.retain(|k| k != key);
+            self.used.push(key);
+        }
+        fn on_remove(&mut self, key: &&'static str) {
+            self.used.retain(|k| k != key);
+        }
+        fn new() -> Self {
+            Legacy { used: Vec::new() }
+        }
+    }
+
+    #[test]
+    fn legacy_policy_test() {
+        let mut policy = Legacy::new();
+
+        // an insert defaults to a use, and the old names forward to the new ones
+        policy.on_insert(&"a", 1);
+        policy.on_insert(&"b", 1);
+        #[allow(deprecated)]
+        policy.key_used(&"a");
+        policy.on_insert(&"c", 1);
+        #[allow(deprecated)]
+        policy.remove_key(&"c");
+
+        assert!(policy.evict_next() == "b");
+        assert!(policy.evict_next() == "a");
+    }
+}
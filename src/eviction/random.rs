@@ -0,0 +1,107 @@
+use crate::EvictionPolicy;
+use crate::rng::Rng;
+use std::collections::HashMap;
+use std::hash::Hash;
+
+// evicts a uniformly random key, ignoring how keys are used
+// keys are kept in a Vec with an index from key to position, so that removal is a swap_remove
+// the generator can be seeded to make evictions reproducible
+#[derive(Clone)]
+pub struct RandomEviction<K> {
+    keys: Vec<K>,
+    index: HashMap<K, usize>,
+    rng: Rng,
+}
+
+impl<K: Eq + Hash + Clone> RandomEviction<K> {
+    /// return a random policy that makes the same choices for the same seed and sequence of calls
+    pub fn with_seed(seed: u64) -> Self {
+        RandomEviction{keys: Vec::new(), index: HashMap::new(), rng: Rng::new(seed)}
+    }
+
+    fn take_at(&mut self, pos: usize) -> K {
+        let key = self.keys.swap_remove(pos);
+        self.index.remove(&key);
+        if let Some(moved) = self.keys.get(pos) {
+            self.index.insert(moved.clone(), pos);
+        }
+        key
+    }
+}
+
+impl<K: Eq + Hash + Clone> EvictionPolicy<K> for RandomEviction<K> {
+    fn evict_next(&mut self) -> K {
+        assert!(!self.keys.is_empty(), "there should be at least one element in the eviction queue");
+        let pos = self.rng.below(self.keys.len());
+        self.take_at(pos)
+    }
+    fn try_evict_next(&mut self) -> Option<K> {
+        if self.keys.is_empty() {
+            return None;
+        }
+        Some(self.evict_next())
+    }
+    fn on_insert(&mut self, key: &K, _weight: u64) {
+        if !self.index.contains_key(key) {
+            self.index.insert(key.clone(), self.keys.len());
+            self.keys.push(key.clone());
+        }
+    }
+    fn on_access(&mut self, _key: &K) {}
+    fn on_remove(&mut self, key: &K) {
+        if let Some(&pos) = self.index.get(key) {
+            self.take_at(pos);
+        }
+    }
+    fn next_victim(&self) -> Option<&K> {
+        // the generator is copied, so this is the choice evict_next will make
+        if self.keys.is_empty() {
+            return None;
+        }
+        self.keys.get(self.rng.clone().below(self.keys.len()))
+    }
+    fn eviction_order(&self) -> Vec<&K> {
+        // replays evict_next with a copy of the generator
+        let mut rng = self.rng.clone();
+        let mut keys: Vec<&K> = self.keys.iter().collect();
+        let mut order = Vec::with_capacity(keys.len());
+        while !keys.is_empty() {
+            order.push(keys.swap_remove(rng.below(keys.len())));
+        }
+        order
+    }
+    fn clear(&mut self) {
+        self.keys.clear();
+        self.index.clear();
+    }
+    fn new() -> Self {
+        RandomEviction{keys: Vec::new(), index: HashMap::new(), rng: Rng::from_entropy()}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn random_test() {
+        let mut policy = RandomEviction::with_seed(42);
+        let mut twin = RandomEviction::with_seed(42);
+
+        for key in ["a", "b", "c", "d"] {
+            policy.on_insert(&key, 1);
+            twin.on_insert(&key, 1);
+        }
+        policy.on_remove(&"b");
+        twin.on_remove(&"b");
+
+        let order: Vec<&str> = policy.eviction_order().into_iter().copied().collect();
+        assert_eq!(order.len(), 3);
+        assert_eq!(policy.next_victim(), Some(&order[0]));
+
+        // the same seed makes the same choices
+        let evicted: Vec<&str> = (0..3).map(|_| policy.evict_next()).collect();
+        assert_eq!(evicted, order);
+        assert_eq!(evicted, (0..3).map(|_| twin.evict_next()).collect::<Vec<_>>());
+    }
+}
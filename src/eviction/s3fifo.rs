@@ -0,0 +1,182 @@
+use crate::EvictionPolicy;
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+
+// S3-FIFO
+// new keys enter a small FIFO queue sized at 10% of the capacity, the rest is the main FIFO queue
+// each key has a use count capped at 3
+// a key leaving small moves to main if it was used while there, otherwise it is evicted
+// and remembered in the ghost queue, so that it goes straight to main if it comes back
+// a key leaving main with uses left gets one of them taken and rejoins the back of main
+// one-hit wonders are therefore dropped after a short stay in small
+// the front of each queue is the next key to leave it
+#[derive(Clone)]
+pub struct S3Fifo<K> {
+    small: VecDeque<K>,
+    main: VecDeque<K>,
+    ghost: VecDeque<K>,
+    uses: HashMap<K, u8>,
+    small_capacity: usize,
+    main_capacity: usize,
+}
+
+// the most uses a key can bank
+const MAX_USES: u8 = 3;
+
+fn take<K: PartialEq>(list: &mut VecDeque<K>, key: &K) -> bool {
+    if let Some(pos) = list.iter().position(|k| k == key) {
+        list.remove(pos);
+        true
+    } else {
+        false
+    }
+}
+
+impl<K: Eq + Hash + Clone> S3Fifo<K> {
+    fn evict_from_small(&self) -> bool {
+        !self.small.is_empty() && (self.small.len() >= self.small_capacity || self.main.is_empty())
+    }
+
+    // the ghost queue remembers as many keys as main holds, and never more than are resident
+    fn trim_ghost(&mut self) {
+        let limit = self.main_capacity.min(self.uses.len() + 1);
+        while self.ghost.len() > limit {
+            self.ghost.pop_front();
+        }
+    }
+}
+
+impl<K: Eq + Hash + Clone> EvictionPolicy<K> for S3Fifo<K> {
+    fn evict_next(&mut self) -> K {
+        loop {
+            if self.evict_from_small() {
+                let key = self.small.pop_front().expect("small should not be empty");
+                if self.uses[&key] > 0 {
+                    self.main.push_back(key);
+                    continue;
+                }
+                self.uses.remove(&key);
+                self.ghost.push_back(key.clone());
+                self.trim_ghost();
+                return key;
+            }
+            let key = self.main.pop_front().expect("there should be at least one element in the eviction queue");
+            let uses = self.uses.get_mut(&key).expect("queued key should have a use count");
+            if *uses > 0 {
+                *uses -= 1;
+                self.main.push_back(key);
+                continue;
+            }
+            self.uses.remove(&key);
+            return key;
+        }
+    }
+    fn try_evict_next(&mut self) -> Option<K> {
+        if self.small.is_empty() && self.main.is_empty() {
+            return None;
+        }
+        Some(self.evict_next())
+    }
+    fn on_insert(&mut self, key: &K, _weight: u64) {
+        if self.uses.contains_key(key) {
+            self.on_access(key);
+            return;
+        }
+        if take(&mut self.ghost, key) {
+            self.main.push_back(key.clone());
+        } else {
+            self.small.push_back(key.clone());
+        }
+        self.uses.insert(key.clone(), 0);
+    }
+    fn on_access(&mut self, key: &K) {
+        if let Some(uses) = self.uses.get_mut(key) {
+            *uses = (*uses + 1).min(MAX_USES);
+        }
+    }
+    fn on_remove(&mut self, key: &K) {
+        if self.uses.remove(key).is_some() {
+            let _ = take(&mut self.small, key) || take(&mut self.main, key);
+        }
+    }
+    fn eviction_order(&self) -> Vec<&K> {
+        // replays evict_next on borrowed copies of the queues and use counts
+        let mut small: VecDeque<&K> = self.small.iter().collect();
+        let mut main: VecDeque<&K> = self.main.iter().collect();
+        let mut uses: HashMap<&K, u8> = self.uses.iter().map(|(key, uses)| (key, *uses)).collect();
+        let mut order = Vec::with_capacity(uses.len());
+        while !small.is_empty() || !main.is_empty() {
+            if !small.is_empty() && (small.len() >= self.small_capacity || main.is_empty()) {
+                let key = small.pop_front().expect("small should not be empty");
+                if uses[key] > 0 {
+                    main.push_back(key);
+                } else {
+                    order.push(key);
+                }
+                continue;
+            }
+            let key = main.pop_front().expect("main should not be empty");
+            let left = uses.get_mut(key).expect("queued key should have a use count");
+            if *left > 0 {
+                *left -= 1;
+                main.push_back(key);
+            } else {
+                order.push(key);
+            }
+        }
+        order
+    }
+    fn clear(&mut self) {
+        self.small.clear();
+        self.main.clear();
+        self.ghost.clear();
+        self.uses.clear();
+    }
+    fn set_capacity(&mut self, capacity: usize) {
+        self.small_capacity = (capacity / 10).max(1);
+        self.main_capacity = capacity.saturating_sub(self.small_capacity);
+        self.trim_ghost();
+    }
+    fn new() -> Self {
+        let mut policy = S3Fifo{
+            small: VecDeque::new(),
+            main: VecDeque::new(),
+            ghost: VecDeque::new(),
+            uses: HashMap::new(),
+            small_capacity: 0,
+            main_capacity: 0,
+        };
+        policy.set_capacity(usize::MAX);
+        policy
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn s3fifo_test() {
+        let mut policy = S3Fifo::new();
+        policy.set_capacity(10);
+
+        policy.on_insert(&"a", 1);
+        policy.on_insert(&"b", 1);
+
+        // a was never used in small, so it goes straight away
+        assert!(policy.evict_next() == "a");
+
+        // b was, so it moves to main and c goes instead
+        policy.on_access(&"b");
+        policy.on_insert(&"c", 1);
+        assert!(policy.evict_next() == "c");
+
+        // a is remembered as a ghost and returns straight to main
+        policy.on_insert(&"a", 1);
+        assert_eq!(policy.eviction_order(), vec![&"a", &"b"]);
+
+        policy.on_remove(&"a");
+
+        assert!(policy.evict_next() == "b");
+    }
+}
@@ -0,0 +1,194 @@
+use crate::EvictionPolicy;
+use std::collections::HashMap;
+use std::hash::Hash;
+
+// SIEVE
+// keys are queued from oldest to newest, each with a visited bit set when the key is used
+// the hand walks from the oldest key towards the newest, clearing visited bits,
+// and evicts the first key it finds unvisited; it then carries on from the next newer key,
+// wrapping back to the oldest once it passes the newest
+// unlike CLOCK, surviving keys keep their place and new keys always join at the newest end
+// the queue is a doubly linked list in a slab, so a use is a lookup and a bit set
+#[derive(Clone)]
+pub struct Sieve<K> {
+    nodes: Vec<Option<Node<K>>>,
+    index: HashMap<K, usize>,
+    free: Vec<usize>,
+    oldest: Option<usize>,
+    newest: Option<usize>,
+    hand: Option<usize>,
+}
+
+#[derive(Clone)]
+struct Node<K> {
+    key: K,
+    visited: bool,
+    older: Option<usize>,
+    newer: Option<usize>,
+}
+
+impl<K: Eq + Hash + Clone> Sieve<K> {
+    fn node(&self, pos: usize) -> &Node<K> {
+        self.nodes[pos].as_ref().expect("linked slot should be occupied")
+    }
+
+    fn node_mut(&mut self, pos: usize) -> &mut Node<K> {
+        self.nodes[pos].as_mut().expect("linked slot should be occupied")
+    }
+
+    // the key after pos in the hand's direction
+    fn next_from(&self, pos: usize) -> Option<usize> {
+        self.node(pos).newer.or(self.oldest)
+    }
+
+    fn unlink(&mut self, pos: usize) -> K {
+        let node = self.nodes[pos].take().expect("linked slot should be occupied");
+        match node.older {
+            Some(older) => self.node_mut(older).newer = node.newer,
+            None => self.oldest = node.newer,
+        }
+        match node.newer {
+            Some(newer) => self.node_mut(newer).older = node.older,
+            None => self.newest = node.older,
+        }
+        if self.hand == Some(pos) {
+            self.hand = node.newer;
+        }
+        self.index.remove(&node.key);
+        self.free.push(pos);
+        node.key
+    }
+}
+
+impl<K: Eq + Hash + Clone> EvictionPolicy<K> for Sieve<K> {
+    fn evict_next(&mut self) -> K {
+        let mut pos = self.hand.or(self.oldest).expect("there should be at least one element in the eviction queue");
+        while self.node(pos).visited {
+            self.node_mut(pos).visited = false;
+            pos = self.next_from(pos).expect("queue should not be empty");
+        }
+        self.hand = self.node(pos).newer;
+        self.unlink(pos)
+    }
+    fn try_evict_next(&mut self) -> Option<K> {
+        if self.index.is_empty() {
+            return None;
+        }
+        Some(self.evict_next())
+    }
+    fn on_insert(&mut self, key: &K, _weight: u64) {
+        if self.index.contains_key(key) {
+            self.on_access(key);
+            return;
+        }
+        let node = Node { key: key.clone(), visited: false, older: self.newest, newer: None };
+        let pos = match self.free.pop() {
+            Some(pos) => {
+                self.nodes[pos] = Some(node);
+                pos
+            }
+            None => {
+                self.nodes.push(Some(node));
+                self.nodes.len() - 1
+            }
+        };
+        match self.newest {
+            Some(newest) => self.node_mut(newest).newer = Some(pos),
+            None => self.oldest = Some(pos),
+        }
+        self.newest = Some(pos);
+        self.index.insert(key.clone(), pos);
+    }
+    fn on_access(&mut self, key: &K) {
+        if let Some(&pos) = self.index.get(key) {
+            self.node_mut(pos).visited = true;
+        }
+    }
+    fn on_remove(&mut self, key: &K) {
+        if let Some(&pos) = self.index.get(key) {
+            self.unlink(pos);
+        }
+    }
+    fn next_victim(&self) -> Option<&K> {
+        // a full lap without finding an unvisited key clears every bit, so the start is taken
+        let start = self.hand.or(self.oldest)?;
+        let mut pos = start;
+        loop {
+            if !self.node(pos).visited {
+                return Some(&self.node(pos).key);
+            }
+            pos = self.next_from(pos).expect("queue should not be empty");
+            if pos == start {
+                return Some(&self.node(start).key);
+            }
+        }
+    }
+    fn eviction_order(&self) -> Vec<&K> {
+        // replays evict_next on a copy of the queue, from oldest to newest
+        let mut queue = Vec::with_capacity(self.index.len());
+        let mut hand = 0;
+        let mut next = self.oldest;
+        while let Some(pos) = next {
+            if self.hand == Some(pos) {
+                hand = queue.len();
+            }
+            let node = self.node(pos);
+            queue.push((&node.key, node.visited));
+            next = node.newer;
+        }
+        let mut order = Vec::with_capacity(queue.len());
+        while !queue.is_empty() {
+            if hand >= queue.len() {
+                hand = 0;
+            }
+            if queue[hand].1 {
+                queue[hand].1 = false;
+                hand += 1;
+            } else {
+                order.push(queue.remove(hand).0);
+            }
+        }
+        order
+    }
+    fn clear(&mut self) {
+        self.nodes.clear();
+        self.index.clear();
+        self.free.clear();
+        self.oldest = None;
+        self.newest = None;
+        self.hand = None;
+    }
+    fn new() -> Self {
+        Sieve{nodes: Vec::new(), index: HashMap::new(), free: Vec::new(), oldest: None, newest: None, hand: None}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sieve_test() {
+        let mut policy = Sieve::new();
+
+        policy.on_insert(&"a", 1);
+        policy.on_insert(&"b", 1);
+        policy.on_insert(&"c", 1);
+        policy.on_access(&"a");
+
+        assert_eq!(policy.eviction_order(), vec![&"b", &"c", &"a"]);
+        assert!(policy.evict_next() == "b");
+
+        // the hand now points at c, so a isn't looked at again until the hand wraps around
+        policy.on_insert(&"d", 1);
+        policy.on_access(&"a");
+        policy.on_access(&"c");
+        assert_eq!(policy.next_victim(), Some(&"d"));
+        assert_eq!(policy.eviction_order(), vec![&"d", &"c", &"a"]);
+
+        policy.on_remove(&"d");
+
+        assert!(policy.evict_next() == "c");
+        assert!(policy.evict_next() == "a");
+    }
+}
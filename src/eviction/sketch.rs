@@ -0,0 +1,105 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+const DEPTH: usize = 4;
+const MAX_COUNT: u8 = 15;
+
+// Count-min sketch estimating how often keys have been seen in a fixed amount of memory
+// each key maps to one counter per row and its estimate is the smallest of them,
+// so collisions can only overestimate
+// once sample_size increments have been recorded every counter is halved,
+// which ages out popularity that is no longer current
+#[derive(Clone)]
+pub(crate) struct CountMinSketch {
+    table: Vec<u8>,
+    width: usize,
+    additions: usize,
+    sample_size: usize,
+    resets: usize,
+}
+
+impl CountMinSketch {
+    /// return a sketch sized for tracking roughly `capacity` distinct hot keys
+    /// counters are aged after 10 increments per key of capacity
+    pub(crate) fn new(capacity: usize) -> Self {
+        let width = capacity.saturating_mul(4).clamp(64, 1 << 20).next_power_of_two();
+        CountMinSketch {
+            table: vec![0; width * DEPTH],
+            width,
+            additions: 0,
+            sample_size: capacity.clamp(16, 1 << 20) * 10,
+            resets: 0,
+        }
+    }
+
+    fn indexes<K: Hash + ?Sized>(&self, key: &K) -> [usize; DEPTH] {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        let hash = hasher.finish();
+        let (h1, h2) = (hash as usize, (hash >> 32) as usize | 1);
+        let mut indexes = [0; DEPTH];
+        for (row, index) in indexes.iter_mut().enumerate() {
+            *index = row * self.width + (h1.wrapping_add(row.wrapping_mul(h2)) & (self.width - 1));
+        }
+        indexes
+    }
+
+    /// record one occurrence of the key
+    pub(crate) fn increment<K: Hash + ?Sized>(&mut self, key: &K) {
+        for index in self.indexes(key) {
+            self.table[index] = self.table[index].saturating_add(1).min(MAX_COUNT);
+        }
+        self.additions += 1;
+        if self.additions >= self.sample_size {
+            self.reset();
+        }
+    }
+
+    /// return the estimated number of recent occurrences of the key
+    pub(crate) fn frequency<K: Hash + ?Sized>(&self, key: &K) -> u8 {
+        self.indexes(key).iter().map(|&index| self.table[index]).min().unwrap_or(0)
+    }
+
+    /// zero every counter
+    pub(crate) fn clear(&mut self) {
+        self.table.fill(0);
+        self.additions = 0;
+    }
+
+    /// halve every counter
+    pub(crate) fn reset(&mut self) {
+        for counter in self.table.iter_mut() {
+            *counter /= 2;
+        }
+        self.additions /= 2;
+        self.resets += 1;
+    }
+
+    /// return how many times the counters have been halved, so callers caching estimates can tell when they went stale
+    pub(crate) fn resets(&self) -> usize {
+        self.resets
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sketch_test() {
+        let mut sketch = CountMinSketch::new(16);
+
+        for _ in 0..6 {
+            sketch.increment(&"hot");
+        }
+        sketch.increment(&"cold");
+
+        assert!(sketch.frequency(&"hot") >= 6);
+        assert!(sketch.frequency(&"hot") > sketch.frequency(&"cold"));
+
+        sketch.reset();
+
+        assert!(sketch.frequency(&"hot") >= 3);
+        assert!(sketch.frequency(&"hot") < 6);
+    }
+}
@@ -0,0 +1,123 @@
+use crate::EvictionPolicy;
+use std::collections::VecDeque;
+
+// Segmented LRU
+// new keys enter the probation segment and are promoted to the protected segment
+// when they are used again; protected overflow is demoted back to the end of probation
+// evictions take from probation first, so a scan of keys used once can't flush protected
+// the front of each segment is its least recently used key
+#[derive(Clone)]
+pub struct SLRU<K> {
+    probation: VecDeque<K>,
+    protected: VecDeque<K>,
+    protected_percent: u8,
+    protected_capacity: usize,
+}
+
+fn take<K: PartialEq>(list: &mut VecDeque<K>, key: &K) -> bool {
+    if let Some(pos) = list.iter().position(|k| k == key) {
+        list.remove(pos);
+        true
+    } else {
+        false
+    }
+}
+
+impl<K: PartialEq + Clone> SLRU<K> {
+    /// return an SLRU policy that gives the given percentage of the capacity to the protected segment
+    pub fn with_protected_percent(percent: u8) -> Self {
+        let mut policy = Self::new();
+        policy.protected_percent = percent.min(100);
+        policy.set_capacity(usize::MAX);
+        policy
+    }
+
+    fn demote_overflow(&mut self) {
+        while self.protected.len() > self.protected_capacity {
+            let demoted = self.protected.pop_front().expect("protected should not be empty");
+            self.probation.push_back(demoted);
+        }
+    }
+}
+
+impl<K: PartialEq + Clone> EvictionPolicy<K> for SLRU<K> {
+    fn evict_next(&mut self) -> K {
+        self.probation.pop_front()
+            .or_else(|| self.protected.pop_front())
+            .expect("there should be at least one element in the eviction queue")
+    }
+    fn try_evict_next(&mut self) -> Option<K> {
+        if self.probation.is_empty() && self.protected.is_empty() {
+            return None;
+        }
+        Some(self.evict_next())
+    }
+    fn on_insert(&mut self, key: &K, _weight: u64) {
+        if self.probation.contains(key) || self.protected.contains(key) {
+            self.on_access(key);
+        } else {
+            self.probation.push_back(key.clone());
+        }
+    }
+    fn on_insert_cold(&mut self, key: &K, _weight: u64) {
+        if !self.probation.contains(key) && !self.protected.contains(key) {
+            self.probation.push_front(key.clone());
+        }
+    }
+    fn on_access(&mut self, key: &K) {
+        if take(&mut self.probation, key) || take(&mut self.protected, key) {
+            self.protected.push_back(key.clone());
+            self.demote_overflow();
+        }
+    }
+    fn on_remove(&mut self, key: &K) {
+        let _ = take(&mut self.probation, key) || take(&mut self.protected, key);
+    }
+    fn next_victim(&self) -> Option<&K> {
+        self.probation.front().or(self.protected.front())
+    }
+    fn eviction_order(&self) -> Vec<&K> {
+        self.probation.iter().chain(self.protected.iter()).collect()
+    }
+    fn clear(&mut self) {
+        self.probation.clear();
+        self.protected.clear();
+    }
+    fn set_capacity(&mut self, capacity: usize) {
+        self.protected_capacity = (capacity as u128 * self.protected_percent as u128 / 100) as usize;
+        self.demote_overflow();
+    }
+    fn new() -> Self {
+        // 80% protected, as in Caffeine's main space
+        SLRU{probation: VecDeque::new(), protected: VecDeque::new(), protected_percent: 80, protected_capacity: usize::MAX}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slru_test() {
+        let mut policy = SLRU::with_protected_percent(50);
+        policy.set_capacity(4);
+
+        policy.on_insert(&"a", 1);
+        policy.on_insert(&"b", 1);
+        policy.on_insert(&"c", 1);
+        policy.on_access(&"a");
+        policy.on_access(&"b");
+
+        // keys used only once are evicted before any protected key
+        assert_eq!(policy.eviction_order(), vec![&"c", &"a", &"b"]);
+
+        // protected holds 2, so promoting c demotes a to the end of probation
+        policy.on_access(&"c");
+        assert_eq!(policy.eviction_order(), vec![&"a", &"b", &"c"]);
+        assert!(policy.evict_next() == "a");
+
+        policy.on_remove(&"b");
+
+        assert!(policy.evict_next() == "c");
+    }
+}
@@ -0,0 +1,212 @@
+use crate::EvictionPolicy;
+use super::sketch::CountMinSketch;
+use std::collections::VecDeque;
+use std::hash::Hash;
+
+// Window TinyLFU, as used by Caffeine
+// new keys enter a small LRU window, the rest of the capacity is a segmented LRU
+// split into probation and protected; keys reach protected on their second access in main
+// a key leaving the window only gets into main if the sketch says it is
+// more popular than the key main would evict to make room for it
+// the front of each list is its least recently used key
+#[derive(Clone)]
+pub struct TinyLFU<K> {
+    window: VecDeque<K>,
+    probation: VecDeque<K>,
+    protected: VecDeque<K>,
+    sketch: CountMinSketch,
+    window_capacity: usize,
+    main_capacity: usize,
+    protected_capacity: usize,
+}
+
+fn take<K: PartialEq>(list: &mut VecDeque<K>, key: &K) -> bool {
+    if let Some(pos) = list.iter().position(|k| k == key) {
+        list.remove(pos);
+        true
+    } else {
+        false
+    }
+}
+
+impl<K: PartialEq + Clone + Hash> TinyLFU<K> {
+    fn main_len(&self) -> usize {
+        self.probation.len() + self.protected.len()
+    }
+
+    // move keys from the window into main while main has room to spare
+    fn drain_window(&mut self) {
+        while self.window.len() > self.window_capacity && self.main_len() < self.main_capacity {
+            let key = self.window.pop_front().expect("window should not be empty");
+            self.probation.push_back(key);
+        }
+    }
+
+    fn resize(&mut self, capacity: usize) {
+        // 1% window, and 80% of main protected
+        self.window_capacity = (capacity / 100).max(1);
+        self.main_capacity = capacity.saturating_sub(self.window_capacity);
+        self.protected_capacity = self.main_capacity - self.main_capacity / 5;
+    }
+
+    fn promote(&mut self, key: K) {
+        self.protected.push_back(key);
+        while self.protected.len() > self.protected_capacity {
+            let demoted = self.protected.pop_front().expect("protected should not be empty");
+            self.probation.push_back(demoted);
+        }
+    }
+}
+
+impl<K: PartialEq + Clone + Hash> EvictionPolicy<K> for TinyLFU<K> {
+    fn evict_next(&mut self) -> K {
+        // the incoming key is about to enter a full window and push its oldest key out
+        let main_victim = self.probation.front().or(self.protected.front()).cloned();
+        if (self.window.len() >= self.window_capacity && !self.window.is_empty()) || main_victim.is_none() {
+            let candidate = self.window.pop_front().expect("there should be at least one element in the eviction queue");
+            let Some(victim) = main_victim else {
+                return candidate;
+            };
+            // admission: the window's victim displaces main's victim only if it is seen more often
+            if self.sketch.frequency(&candidate) > self.sketch.frequency(&victim) {
+                let _ = take(&mut self.probation, &victim) || take(&mut self.protected, &victim);
+                self.probation.push_back(candidate);
+                return victim;
+            }
+            return candidate;
+        }
+        self.probation.pop_front()
+            .or_else(|| self.protected.pop_front())
+            .expect("there should be at least one element in the eviction queue")
+    }
+    fn try_evict_next(&mut self) -> Option<K> {
+        if self.window.is_empty() && self.probation.is_empty() && self.protected.is_empty() {
+            return None;
+        }
+        Some(self.evict_next())
+    }
+    fn on_access(&mut self, key: &K) {
+        self.sketch.increment(key);
+        if take(&mut self.window, key) {
+            self.window.push_back(key.clone());
+        } else if take(&mut self.probation, key) || take(&mut self.protected, key) {
+            self.promote(key.clone());
+        }
+    }
+    fn on_insert(&mut self, key: &K, _weight: u64) {
+        if self.window.contains(key) || self.probation.contains(key) || self.protected.contains(key) {
+            self.on_access(key);
+            return;
+        }
+        self.sketch.increment(key);
+        self.window.push_back(key.clone());
+        self.drain_window();
+    }
+    fn on_remove(&mut self, key: &K) {
+        let _ = take(&mut self.window, key) || take(&mut self.probation, key) || take(&mut self.protected, key);
+    }
+    fn next_victim(&self) -> Option<&K> {
+        let main_victim = self.probation.front().or(self.protected.front());
+        if (self.window.len() >= self.window_capacity && !self.window.is_empty()) || main_victim.is_none() {
+            let candidate = self.window.front()?;
+            return match main_victim {
+                Some(victim) if self.sketch.frequency(candidate) > self.sketch.frequency(victim) => Some(victim),
+                _ => Some(candidate),
+            };
+        }
+        main_victim
+    }
+    fn admit(&mut self, candidate: &K, victim: &K) -> bool {
+        // a newcomer only displaces a key that has been seen no more often than it has,
+        // and a rejected key is still counted so that it is admitted once it proves popular
+        let admitted = self.sketch.frequency(candidate) + 1 >= self.sketch.frequency(victim);
+        if !admitted {
+            self.sketch.increment(candidate);
+        }
+        admitted
+    }
+    fn eviction_order(&self) -> Vec<&K> {
+        // replays evict_next, admission duels included, on borrowed copies of the lists
+        let mut window: VecDeque<&K> = self.window.iter().collect();
+        let mut probation: VecDeque<&K> = self.probation.iter().collect();
+        let mut protected: VecDeque<&K> = self.protected.iter().collect();
+        let mut order = Vec::with_capacity(window.len() + probation.len() + protected.len());
+        loop {
+            let main_victim = probation.front().or(protected.front()).copied();
+            if (window.len() >= self.window_capacity && !window.is_empty()) || main_victim.is_none() {
+                let Some(candidate) = window.pop_front() else {
+                    return order;
+                };
+                match main_victim {
+                    Some(victim) if self.sketch.frequency(candidate) > self.sketch.frequency(victim) => {
+                        if probation.pop_front().is_none() {
+                            protected.pop_front();
+                        }
+                        probation.push_back(candidate);
+                        order.push(victim);
+                    }
+                    _ => order.push(candidate),
+                }
+            } else {
+                order.push(probation.pop_front().or_else(|| protected.pop_front()).expect("main should not be empty"));
+            }
+        }
+    }
+    fn clear(&mut self) {
+        self.window.clear();
+        self.probation.clear();
+        self.protected.clear();
+        self.sketch.clear();
+    }
+    fn set_capacity(&mut self, capacity: usize) {
+        self.resize(capacity);
+        self.sketch = CountMinSketch::new(capacity);
+        self.drain_window();
+    }
+    fn new() -> Self {
+        // the sketch starts small and is sized properly once the cache sets a capacity
+        let mut policy = TinyLFU{
+            window: VecDeque::new(),
+            probation: VecDeque::new(),
+            protected: VecDeque::new(),
+            sketch: CountMinSketch::new(0),
+            window_capacity: 0,
+            main_capacity: 0,
+            protected_capacity: 0,
+        };
+        policy.resize(usize::MAX);
+        policy
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tinylfu_test() {
+        let mut policy = TinyLFU::new();
+        policy.set_capacity(3);
+
+        policy.on_insert(&"a", 1);
+        policy.on_insert(&"b", 1);
+        policy.on_insert(&"c", 1);
+        policy.on_access(&"a");
+        policy.on_access(&"a");
+
+        // c leaves the window but is no more popular than b, so it isn't admitted
+        assert!(policy.evict_next() == "c");
+        policy.on_insert(&"d", 1);
+        policy.on_access(&"d");
+        policy.on_access(&"d");
+
+        // d has been seen more often than b, so it takes b's place
+        assert_eq!(policy.eviction_order(), vec![&"b", &"d", &"a"]);
+        assert!(policy.evict_next() == "b");
+        policy.on_insert(&"e", 1);
+
+        policy.on_remove(&"a");
+
+        assert!(policy.evict_next() == "e");
+    }
+}
@@ -0,0 +1,86 @@
+//! Index of cached entries by expiry time, for finding expired entries without a scan
+
+use std::collections::BTreeMap;
+use std::time::Instant;
+
+// keys are filed under each deadline they are given, and moving a key's deadline doesn't take it out
+// of its old slot, so the index can hold stale keys: ones that were removed or now expire at another
+// time; callers check against the store, and compact rebuilds the index once the stale keys pile up
+#[derive(Clone)]
+pub(crate) struct ExpiryIndex<K> {
+    by_deadline: BTreeMap<Instant, Vec<K>>,
+    len: usize,
+}
+
+impl<K: Clone> ExpiryIndex<K> {
+    pub(crate) fn new() -> Self {
+        ExpiryIndex { by_deadline: BTreeMap::new(), len: 0 }
+    }
+
+    // the number of keys filed, stale ones included
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    pub(crate) fn insert(&mut self, key: &K, deadline: Instant) {
+        self.by_deadline.entry(deadline).or_default().push(key.clone());
+        self.len += 1;
+    }
+
+    // remove and return every key filed under a deadline before now, in deadline order
+    pub(crate) fn take_due(&mut self, now: Instant) -> Vec<K> {
+        let later = self.by_deadline.split_off(&now);
+        let due = std::mem::replace(&mut self.by_deadline, later);
+        let keys: Vec<K> = due.into_values().flatten().collect();
+        self.len -= keys.len();
+        keys
+    }
+
+    // every key filed, earliest deadline first
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (Instant, &K)> + '_ {
+        self.by_deadline.iter().flat_map(|(&deadline, keys)| keys.iter().map(move |key| (deadline, key)))
+    }
+
+    // replace the contents of the index with the given keys and deadlines
+    pub(crate) fn rebuild<'a, I>(&mut self, deadlines: I)
+    where
+        I: IntoIterator<Item = (&'a K, Instant)>,
+        K: 'a,
+    {
+        self.clear();
+        for (key, deadline) in deadlines {
+            self.insert(key, deadline);
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.by_deadline.clear();
+        self.len = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn expiry_index_test() {
+        let start = Instant::now();
+        let mut index = ExpiryIndex::new();
+        index.insert(&"b", start + Duration::from_secs(2));
+        index.insert(&"a", start + Duration::from_secs(1));
+        index.insert(&"c", start + Duration::from_secs(3));
+        index.insert(&"a", start + Duration::from_secs(3));
+        assert_eq!(index.len(), 4);
+        assert_eq!(index.iter().next(), Some((start + Duration::from_secs(1), &"a")));
+
+        // a deadline equal to now hasn't passed yet
+        assert_eq!(index.take_due(start + Duration::from_secs(2)), ["a"]);
+        assert_eq!(index.take_due(start + Duration::from_secs(5)), ["b", "c", "a"]);
+        assert_eq!(index.len(), 0);
+
+        index.rebuild([(&"d", start)]);
+        assert_eq!(index.iter().collect::<Vec<_>>(), [(start, &"d")]);
+    }
+}
@@ -0,0 +1,172 @@
+//! Cache of two-level keys that can be removed a group at a time
+//!
+//! A `GroupedCache` stores entries under `(group, key)` pairs, such as a user
+//! and one of their resources, in a single `Simcache`. Entries are looked up
+//! and inserted by the full pair and share the cache's limits and eviction
+//! policy with every other group, so a group's combined weight counts against
+//! the capacity like any other entries. `remove_group` removes everything in a
+//! group at once, e.g. on logout.
+//!
+//! The members of each group are indexed alongside the cache. Entries the
+//! cache evicts or expires stay in the index until it is rebuilt, which
+//! happens once it holds twice as many keys as the cache, so it never grows
+//! past that.
+
+use std::collections::hash_map::RandomState;
+use std::collections::{HashMap, HashSet};
+use std::hash::{BuildHasher, Hash};
+use std::time::Duration;
+use crate::{Clock, EvictionPolicy, InsertOutcome, Simcache, SystemClock};
+
+/// a cache of (group, key) pairs that can remove a whole group at once
+pub struct GroupedCache<G, K, V, E, S = RandomState, C = SystemClock>
+where
+    E: EvictionPolicy<(G, K)>
+{
+    cache: Simcache<(G, K), V, E, S, C>,
+    // the keys inserted into each group, including some the cache has since dropped
+    members: HashMap<G, HashSet<K>>,
+    indexed: usize,
+}
+
+impl<G, K, V, E, S, C> GroupedCache<G, K, V, E, S, C>
+where
+    G: Eq + Hash + Clone,
+    K: Eq + Hash + Clone,
+    E: EvictionPolicy<(G, K)>,
+    S: BuildHasher,
+    C: Clock,
+    {
+        /// return a grouped cache storing its entries in the given cache, which should be empty
+        pub fn new(cache: Simcache<(G, K), V, E, S, C>) -> Self {
+            GroupedCache { cache, members: HashMap::new(), indexed: 0 }
+        }
+
+        /// insert a value under the group and key, evicting from any group to make room
+        pub fn insert(&mut self, key: (G, K), value: V, ttl: Option<Duration>) -> InsertOutcome {
+            let (group, member) = key.clone();
+            let outcome = self.cache.insert(key, value, ttl);
+            if outcome != InsertOutcome::Rejected && self.members.entry(group).or_default().insert(member) {
+                self.indexed += 1;
+                if self.indexed > 2 * self.cache.len() + 16 {
+                    self.reindex();
+                }
+            }
+            outcome
+        }
+
+        /// return the value under the group and key if it is present and not expired
+        pub fn get(&mut self, key: &(G, K)) -> Option<&V> {
+            self.cache.get(key)
+        }
+
+        /// remove the value under the group and key, returning it if it was present
+        pub fn remove(&mut self, key: &(G, K)) -> Option<V> {
+            let (group, member) = key;
+            if let Some(members) = self.members.get_mut(group) {
+                if members.remove(member) {
+                    self.indexed -= 1;
+                }
+                if members.is_empty() {
+                    self.members.remove(group);
+                }
+            }
+            self.cache.remove(key)
+        }
+
+        /// remove every entry in the group, returning how many were present
+        pub fn remove_group(&mut self, group: &G) -> usize {
+            let Some(members) = self.members.remove(group) else {
+                return 0;
+            };
+            self.indexed -= members.len();
+            members.into_iter().filter(|member| self.cache.remove(&(group.clone(), member.clone())).is_some()).count()
+        }
+
+        /// return the keys of the group's live entries, in no particular order
+        pub fn group_keys(&self, group: &G) -> Vec<&K> {
+            self.members
+                .get(group)
+                .map(|members| members.iter().filter(|member| self.cache.contains_key(&(group.clone(), (*member).clone()))).collect())
+                .unwrap_or_default()
+        }
+
+        /// return the number of live entries in the group
+        pub fn group_len(&self, group: &G) -> usize {
+            self.group_keys(group).len()
+        }
+
+        /// return the combined weight of the group's live entries, as counted against max_weight
+        pub fn group_weight(&self, group: &G) -> u64 {
+            self.group_keys(group)
+                .into_iter()
+                .filter_map(|member| self.cache.entry_info(&(group.clone(), member.clone())))
+                .map(|info| info.weight)
+                .sum()
+        }
+
+        /// remove every entry of every group
+        pub fn clear(&mut self) {
+            self.cache.clear();
+            self.members.clear();
+            self.indexed = 0;
+        }
+
+        /// return the cache holding the entries
+        pub fn cache(&self) -> &Simcache<(G, K), V, E, S, C> {
+            &self.cache
+        }
+
+        /// return the cache holding the entries, without the group index
+        pub fn into_inner(self) -> Simcache<(G, K), V, E, S, C> {
+            self.cache
+        }
+
+        // drop the keys the cache no longer holds from the index
+        fn reindex(&mut self) {
+            self.members.clear();
+            for (group, member) in self.cache.keys() {
+                self.members.entry(group.clone()).or_default().insert(member.clone());
+            }
+            self.indexed = self.cache.len();
+        }
+    }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LRU;
+
+    type Key = (&'static str, u32);
+
+    #[test]
+    fn grouped_test() {
+        let cache: Simcache<Key, Vec<u8>, LRU<Key>> = Simcache::builder()
+            .max_weight(10)
+            .weigher(|_, value: &Vec<u8>| value.len() as u64)
+            .build();
+        let mut cache = GroupedCache::new(cache);
+        cache.insert(("alice", 1), vec![0; 3], None);
+        cache.insert(("alice", 2), vec![0; 2], None);
+        cache.insert(("bob", 1), vec![0; 4], None);
+        assert_eq!(cache.get(&("alice", 2)), Some(&vec![0; 2]));
+        assert_eq!((cache.group_len(&"alice"), cache.group_weight(&"alice")), (2, 5));
+
+        // groups share the limits, so room for bob's second entry is made by evicting alice's least recently used
+        cache.insert(("bob", 2), vec![0; 2], None);
+        assert_eq!(cache.group_keys(&"alice"), [&2]);
+
+        assert_eq!(cache.remove_group(&"bob"), 2);
+        assert_eq!(cache.remove_group(&"bob"), 0);
+        assert_eq!(cache.cache().len(), 1);
+        assert_eq!(cache.remove(&("alice", 2)), Some(vec![0; 2]));
+        assert_eq!(cache.group_len(&"alice"), 0);
+
+        // evicted keys don't pile up in the index
+        for i in 0..1_000 {
+            cache.insert(("carol", i), vec![0; 1], None);
+        }
+        assert_eq!(cache.group_len(&"carol"), 10);
+        assert!(cache.indexed <= 2 * 10 + 16);
+    }
+}
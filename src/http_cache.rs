@@ -0,0 +1,265 @@
+//! Response caching middleware in the shape of tower's `Service` and `Layer`
+//!
+//! The crate has no dependencies, so `Service` and `Layer` here mirror tower's
+//! traits method for method, and requests and responses are reached through
+//! the small `HttpRequest` and `HttpResponse` traits rather than the `http`
+//! crate's types. Implementing those two traits for `http::Request` and
+//! `http::Response`, and forwarding tower's traits to these, is all an axum
+//! stack needs to use the layer.
+//!
+//! `ResponseCacheLayer` caches the responses to GET and HEAD requests in a
+//! `ShardedSimcache` keyed by method and URI. A response is only cached if its
+//! `Cache-Control` header gives a `max-age` (or `s-maxage`, which takes
+//! precedence), which becomes its ttl; `no-store`, `no-cache`, and `private`
+//! responses are passed through without being cached. Concurrent misses on the
+//! same key each call the inner service.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use crate::{ShardedSimcache, LRU};
+
+/// an asynchronous function from a request to a response, as tower::Service
+pub trait Service<Request> {
+    type Response;
+    type Error;
+    type Future: Future<Output = Result<Self::Response, Self::Error>>;
+    /// return Ready once the service can take a request
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>>;
+    /// handle a request, once poll_ready has returned Ready(Ok)
+    fn call(&mut self, request: Request) -> Self::Future;
+}
+
+/// wraps a service in another, as tower::Layer
+pub trait Layer<S> {
+    type Service;
+    fn layer(&self, inner: S) -> Self::Service;
+}
+
+/// the parts of a request the cache keys on
+pub trait HttpRequest {
+    /// the method, such as GET
+    fn method(&self) -> &str;
+    /// the URI, including the query
+    fn uri(&self) -> String;
+}
+
+/// the parts of a response the cache reads, and a way to copy it out of the cache
+pub trait HttpResponse: Clone {
+    /// the value of the named header, matched without regard to case, if the response has it
+    fn header(&self, name: &str) -> Option<&str>;
+}
+
+/// a request's method and URI
+pub type RequestKey = (String, String);
+
+/// the cache shared by every service a ResponseCacheLayer makes
+pub type ResponseCache<R> = ShardedSimcache<RequestKey, R, LRU<RequestKey>>;
+
+/// return how long a response may be cached for by its Cache-Control header, or None if it may not be
+pub fn cacheable_for(cache_control: Option<&str>) -> Option<Duration> {
+    let mut max_age = None;
+    let mut shared_max_age = None;
+    for directive in cache_control?.split(',') {
+        let directive = directive.trim().to_ascii_lowercase();
+        let (name, value) = match directive.split_once('=') {
+            Some((name, value)) => (name.trim().to_string(), Some(value.trim().trim_matches('"').to_string())),
+            None => (directive.clone(), None),
+        };
+        match (name.as_str(), value) {
+            ("no-store" | "no-cache" | "private", _) => return None,
+            ("max-age", Some(value)) => max_age = value.parse().ok(),
+            ("s-maxage", Some(value)) => shared_max_age = value.parse().ok(),
+            _ => {}
+        }
+    }
+    shared_max_age.or(max_age).filter(|seconds| *seconds > 0).map(Duration::from_secs)
+}
+
+/// a layer that caches the responses of the services it wraps in one shared cache
+pub struct ResponseCacheLayer<R> {
+    cache: Arc<ResponseCache<R>>,
+}
+
+impl<R> ResponseCacheLayer<R> {
+    /// return a layer caching responses in the given cache
+    pub fn new(cache: Arc<ResponseCache<R>>) -> Self {
+        ResponseCacheLayer { cache }
+    }
+}
+
+impl<R> Clone for ResponseCacheLayer<R> {
+    fn clone(&self) -> Self {
+        ResponseCacheLayer { cache: Arc::clone(&self.cache) }
+    }
+}
+
+impl<S, R> Layer<S> for ResponseCacheLayer<R> {
+    type Service = ResponseCacheService<S, R>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ResponseCacheService { inner, cache: Arc::clone(&self.cache) }
+    }
+}
+
+/// a service answering cached requests from the cache and passing the rest to the inner service
+pub struct ResponseCacheService<S, R> {
+    inner: S,
+    cache: Arc<ResponseCache<R>>,
+}
+
+impl<S: Clone, R> Clone for ResponseCacheService<S, R> {
+    fn clone(&self) -> Self {
+        ResponseCacheService { inner: self.inner.clone(), cache: Arc::clone(&self.cache) }
+    }
+}
+
+impl<S, Req, R> Service<Req> for ResponseCacheService<S, R>
+where
+    S: Service<Req, Response = R>,
+    Req: HttpRequest,
+    R: HttpResponse,
+{
+    type Response = R;
+    type Error = S::Error;
+    type Future = ResponseFuture<S::Future, R>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Req) -> Self::Future {
+        let method = request.method().to_ascii_uppercase();
+        if method != "GET" && method != "HEAD" {
+            return ResponseFuture(State::Miss { inner: Box::pin(self.inner.call(request)), store: None });
+        }
+        let key = (method, request.uri());
+        if let Some(response) = self.cache.get(&key) {
+            return ResponseFuture(State::Hit(Some(response)));
+        }
+        let store = Some((Arc::clone(&self.cache), key));
+        ResponseFuture(State::Miss { inner: Box::pin(self.inner.call(request)), store })
+    }
+}
+
+/// the response of a ResponseCacheService, from the cache or from the inner service
+pub struct ResponseFuture<F, R>(State<F, R>);
+
+enum State<F, R> {
+    Hit(Option<R>),
+    // the cache and key to store a cacheable response under, or None if the request isn't cached
+    Miss { inner: Pin<Box<F>>, store: Option<(Arc<ResponseCache<R>>, RequestKey)> },
+}
+
+// the inner future is boxed and the response is never pinned, so the future can be moved between polls
+impl<F, R> Unpin for ResponseFuture<F, R> {}
+
+impl<F, R, E> Future for ResponseFuture<F, R>
+where
+    F: Future<Output = Result<R, E>>,
+    R: HttpResponse,
+{
+    type Output = Result<R, E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match &mut self.get_mut().0 {
+            State::Hit(response) => Poll::Ready(Ok(response.take().expect("a response future should not be polled after it finished"))),
+            State::Miss { inner, store } => {
+                let result = std::task::ready!(inner.as_mut().poll(cx));
+                if let (Ok(response), Some((cache, key))) = (&result, store.take()) {
+                    if let Some(ttl) = cacheable_for(response.header("cache-control")) {
+                        cache.insert(key, response.clone(), Some(ttl));
+                    }
+                }
+                Poll::Ready(result)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::future::{ready, Ready};
+    use std::task::Waker;
+
+    struct Request(&'static str, &'static str);
+
+    impl HttpRequest for Request {
+        fn method(&self) -> &str {
+            self.0
+        }
+        fn uri(&self) -> String {
+            self.1.to_string()
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    struct Response {
+        body: String,
+        cache_control: Option<&'static str>,
+    }
+
+    impl HttpResponse for Response {
+        fn header(&self, name: &str) -> Option<&str> {
+            name.eq_ignore_ascii_case("cache-control").then_some(self.cache_control).flatten()
+        }
+    }
+
+    // answers with the URI and a count of its calls, and a Cache-Control header chosen by the path
+    struct Origin(usize);
+
+    impl Service<Request> for Origin {
+        type Response = Response;
+        type Error = String;
+        type Future = Ready<Result<Response, String>>;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), String>> {
+            Poll::Ready(Ok(()))
+        }
+        fn call(&mut self, request: Request) -> Self::Future {
+            self.0 += 1;
+            let cache_control = match request.1 {
+                "/static" => Some("public, max-age=60"),
+                "/account" => Some("private, max-age=60"),
+                _ => Some("no-store"),
+            };
+            ready(Ok(Response { body: format!("{} {}", request.1, self.0), cache_control }))
+        }
+    }
+
+    fn call<S: Service<Request>>(service: &mut S, request: Request) -> Result<S::Response, S::Error> {
+        let mut future = std::pin::pin!(service.call(request));
+        match future.as_mut().poll(&mut Context::from_waker(Waker::noop())) {
+            Poll::Ready(result) => result,
+            Poll::Pending => panic!("the test services should answer straight away"),
+        }
+    }
+
+    #[test]
+    fn http_cache_test() {
+        let cache = Arc::new(ResponseCache::new(2, 100));
+        let mut service = ResponseCacheLayer::new(Arc::clone(&cache)).layer(Origin(0));
+
+        assert_eq!(call(&mut service, Request("GET", "/static")).unwrap().body, "/static 1");
+        assert_eq!(call(&mut service, Request("get", "/static")).unwrap().body, "/static 1");
+        assert!(cache.with_shard(&("GET".to_string(), "/static".to_string()), |shard| {
+            shard.ttl_remaining(&("GET".to_string(), "/static".to_string())).is_some_and(|ttl| ttl <= Duration::from_secs(60))
+        }));
+
+        // no-store and private responses, and requests that aren't reads, go to the origin every time
+        assert_eq!(call(&mut service, Request("GET", "/now")).unwrap().body, "/now 2");
+        assert_eq!(call(&mut service, Request("GET", "/now")).unwrap().body, "/now 3");
+        assert_eq!(call(&mut service, Request("GET", "/account")).unwrap().body, "/account 4");
+        assert_eq!(call(&mut service, Request("POST", "/static")).unwrap().body, "/static 5");
+        assert_eq!(cache.len(), 1);
+
+        assert_eq!(cacheable_for(Some("max-age=10, s-maxage=\"30\"")), Some(Duration::from_secs(30)));
+        assert_eq!(cacheable_for(Some("Max-Age=0")), None);
+        assert_eq!(cacheable_for(Some("public")), None);
+        assert_eq!(cacheable_for(Some("max-age=5, No-Store")), None);
+        assert_eq!(cacheable_for(None), None);
+    }
+}
@@ -0,0 +1,93 @@
+//! Trace events for cache operations
+//!
+//! A cache built with a tracer calls it with a `TraceEvent` for every lookup,
+//! insert, and removal. Events carry a hash of the key rather than the key
+//! itself, so they can be logged without the key type implementing Debug and
+//! without leaking key contents into logs. To forward them to the `tracing` crate:
+//!
+//! ```ignore
+//! let cache: Simcache<u64, String, LRU<u64>> = Simcache::builder()
+//!     .max_capacity(1000)
+//!     .tracer(|event| tracing::trace!(?event, "simcache"))
+//!     .build();
+//! ```
+
+use std::sync::Arc;
+use crate::{InsertOutcome, RemovalCause};
+
+/// what a lookup found
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LookupOutcome {
+    /// the key had a live entry
+    Hit,
+    /// the key had no entry
+    Miss,
+    /// the key's entry had expired, and was removed
+    Expired,
+}
+
+/// something that happened to one key in the cache
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceEvent {
+    /// a lookup such as get, get_mut, or entry
+    Lookup { key_hash: u64, outcome: LookupOutcome },
+    /// a value passed to insert or one of its variants
+    Insert { key_hash: u64, outcome: InsertOutcome },
+    /// an entry left the cache
+    Removal { key_hash: u64, cause: RemovalCause },
+}
+
+/// called with every trace event, shared by clones of the cache
+pub(crate) type Tracer = Arc<dyn Fn(&TraceEvent) + Send + Sync>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MockClock, Simcache, LRU};
+    use std::collections::hash_map::RandomState;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    #[test]
+    fn tracer_test() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&events);
+        let clock = MockClock::new();
+        let mut cache: Simcache<&str, u64, LRU<&str>, RandomState, MockClock> = Simcache::builder()
+            .max_capacity(1)
+            .clock(clock.clone())
+            .tracer(move |event| recorded.lock().unwrap().push(*event))
+            .build();
+
+        cache.insert("a", 1, Some(Duration::from_secs(1)));
+        cache.get(&"a");
+        cache.insert("b", 2, None);
+        cache.get(&"a");
+        clock.advance(Duration::from_secs(2));
+        cache.remove(&"b");
+
+        let kinds: Vec<String> = events
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|event| match event {
+                TraceEvent::Lookup { outcome, .. } => format!("lookup {:?}", outcome),
+                TraceEvent::Insert { outcome, .. } => format!("insert {:?}", outcome),
+                TraceEvent::Removal { cause, .. } => format!("removal {:?}", cause),
+            })
+            .collect();
+        assert_eq!(kinds, ["insert Inserted", "lookup Hit", "removal Evicted", "insert Inserted", "lookup Miss", "removal Removed"]);
+
+        // an entry found expired is reported as such, after its removal
+        events.lock().unwrap().clear();
+        cache.insert("c", 3, Some(Duration::from_secs(1)));
+        clock.advance(Duration::from_secs(2));
+        cache.get(&"c");
+        let events = events.lock().unwrap();
+        let TraceEvent::Insert { key_hash, .. } = events[0] else {
+            panic!("the first event should be the insert");
+        };
+        assert_eq!(events[1], TraceEvent::Removal { key_hash, cause: RemovalCause::Expired });
+        assert_eq!(events[2], TraceEvent::Lookup { key_hash, outcome: LookupOutcome::Expired });
+    }
+}
@@ -0,0 +1,133 @@
+//! Keeping the caches of several processes coherent through a message bus
+//!
+//! Each process wraps its local cache in a `CoherentCache` with an
+//! `Invalidator` that publishes to the bus, such as a Redis or NATS channel.
+//! Every local insert and remove publishes the key, and each message received
+//! from the bus is passed to `apply_invalidation`, which drops the local copy
+//! without publishing again, so invalidations never loop between processes.
+//! Evictions and expiry only concern the local cache and are not published.
+
+use std::borrow::Borrow;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hash};
+use std::time::Duration;
+use crate::{Clock, EvictionPolicy, InsertOutcome, Simcache, SystemClock};
+
+/// sends invalidations to the other processes sharing a bus
+pub trait Invalidator<K> {
+    type Error;
+    /// tell the other processes that the key's cached value is no longer current
+    fn publish(&mut self, key: &K) -> Result<(), Self::Error>;
+}
+
+/// a cache that publishes its changes so that other processes can drop their copies
+pub struct CoherentCache<K, V, E, I, S = RandomState, C = SystemClock>
+where
+    E: EvictionPolicy<K>
+{
+    cache: Simcache<K, V, E, S, C>,
+    invalidator: I,
+}
+
+impl<K, V, E, I, S, C> CoherentCache<K, V, E, I, S, C>
+where
+    K: Eq + Hash + Clone,
+    E: EvictionPolicy<K>,
+    I: Invalidator<K>,
+    S: BuildHasher,
+    C: Clock,
+    {
+        /// return a coherent cache over the given local cache, publishing with the invalidator
+        pub fn new(cache: Simcache<K, V, E, S, C>, invalidator: I) -> Self {
+            CoherentCache { cache, invalidator }
+        }
+
+        /// insert a key value pair into the local cache and publish the key,
+        /// so that other processes drop the value they have for it
+        /// the value is cached even if publishing fails
+        pub fn insert(&mut self, key: K, value: V, ttl: Option<Duration>) -> Result<InsertOutcome, I::Error> {
+            let outcome = self.cache.insert(key.clone(), value, ttl);
+            self.invalidator.publish(&key)?;
+            Ok(outcome)
+        }
+
+        /// return the value of the given key from the local cache if it is not expired
+        pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
+        where
+            K: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+        {
+            self.cache.get(key)
+        }
+
+        /// remove the key from the local cache and publish it, whether or not it was cached here
+        /// the key is removed locally even if publishing fails
+        pub fn remove(&mut self, key: &K) -> Result<Option<V>, I::Error> {
+            let value = self.cache.remove(key);
+            self.invalidator.publish(key)?;
+            Ok(value)
+        }
+
+        /// remove the key from the local cache because another process published it,
+        /// returning true if there was an entry; nothing is published
+        pub fn apply_invalidation<Q>(&mut self, key: &Q) -> bool
+        where
+            K: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+        {
+            self.cache.remove(key).is_some()
+        }
+
+        /// return the local cache
+        pub fn cache(&self) -> &Simcache<K, V, E, S, C> {
+            &self.cache
+        }
+
+        /// return the invalidator
+        pub fn invalidator(&self) -> &I {
+            &self.invalidator
+        }
+    }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eviction::LRU;
+
+    // a bus that only records what was published
+    #[derive(Default)]
+    struct Outbox(Vec<&'static str>);
+
+    impl Invalidator<&'static str> for Outbox {
+        type Error = ();
+        fn publish(&mut self, key: &&'static str) -> Result<(), ()> {
+            self.0.push(key);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn invalidation_test() {
+        let mut first = CoherentCache::new(Simcache::<&str, u64, LRU<&str>>::new(2), Outbox::default());
+        let mut second = CoherentCache::new(Simcache::<&str, u64, LRU<&str>>::new(2), Outbox::default());
+
+        first.insert("a", 1, None).unwrap();
+        second.insert("a", 2, None).unwrap();
+        for key in first.invalidator().0.clone() {
+            second.apply_invalidation(&key);
+        }
+        assert_eq!(second.get(&"a"), None);
+        assert_eq!(second.invalidator().0, ["a"]);
+
+        // applying an invalidation publishes nothing, so it doesn't bounce back
+        assert_eq!(first.remove(&"a"), Ok(Some(1)));
+        assert!(!second.apply_invalidation(&"a"));
+        assert_eq!(first.invalidator().0, ["a", "a"]);
+        assert_eq!(second.invalidator().0, ["a"]);
+        // evictions stay local
+        first.insert("b", 1, None).unwrap();
+        first.insert("c", 1, None).unwrap();
+        first.insert("d", 1, None).unwrap();
+        assert_eq!(first.invalidator().0.len(), 5);
+    }
+}
@@ -1,11 +1,80 @@
 //! Simcache: A flexible caching library with pluggable eviction policies
 
+pub mod arc_cache;
+pub mod backing;
+pub mod bench;
+pub mod builder;
 pub mod cache;
+pub mod capacity;
+#[cfg(feature = "encryption")]
+mod chacha;
+pub mod clock;
+#[cfg_attr(not(feature = "async"), allow(dead_code))]
+mod coalesce;
+pub mod compat;
+#[cfg(feature = "compression")]
+pub mod compression;
+mod doorkeeper;
+pub mod entry;
+pub mod error;
+pub mod events;
 pub mod eviction;
+mod expiry;
+pub mod grouped;
+#[cfg(feature = "tracing")]
+pub mod instrument;
+pub mod invalidation;
+pub mod listener;
+pub mod loading;
+pub mod mem_size;
+#[cfg(feature = "server")]
+pub mod memcached;
+pub mod memoize;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+pub mod namespaced;
+mod ordered;
+#[cfg(feature = "persist")]
+pub mod persist;
+pub mod priority;
+mod rng;
+mod shadow;
+pub mod sharded;
+pub mod simulation;
+pub mod stats;
+mod tags;
+#[cfg(feature = "testing")]
+pub mod testing;
+#[cfg(feature = "tiered")]
+pub mod tiered;
+#[cfg(feature = "sweeper")]
+pub mod sweeper;
+#[cfg(feature = "async")]
+pub mod asynk;
+#[cfg(feature = "tower")]
+pub mod http_cache;
+
+// lets the code #[memoize] generates name the crate as ::simcache from inside it too
+#[cfg(feature = "macros")]
+extern crate self as simcache;
 
 // Re-export main types for convenience
-pub use cache::Simcache;
+pub use arc_cache::ArcSimcache;
+pub use builder::SimcacheBuilder;
+pub use cache::{EntryInfo, InsertOutcome, InsertResult, Lookup, ScanMode, Simcache};
+pub use capacity::CapacityLimit;
+pub use clock::{Clock, MockClock, SystemClock};
+pub use entry::Entry;
+pub use error::SimcacheError;
+pub use events::{CacheEvent, EventCause};
 pub use eviction::{EvictionPolicy, LRU};
+pub use listener::RemovalCause;
+pub use mem_size::MemSize;
+#[cfg(feature = "macros")]
+pub use simcache_macros::memoize;
+pub use priority::Priority;
+pub use sharded::{CacheReader, ShardedSimcache};
+pub use stats::{DurationHistogram, LifetimeStats, Stats};
 
 // Re-export commonly used types
 pub use std::time::Duration;
\ No newline at end of file
@@ -0,0 +1,19 @@
+//! Notifications for entries leaving the cache
+
+use std::sync::Arc;
+
+/// why an entry was removed from the cache
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RemovalCause {
+    /// removed to make room under the capacity or weight limit
+    Evicted,
+    /// removed because its ttl had passed
+    Expired,
+    /// removed by an explicit call to remove
+    Removed,
+    /// overwritten by a new value for the same key
+    Replaced,
+}
+
+/// called with each entry as it leaves the cache, shared by clones of the cache
+pub(crate) type Listener<K, V> = Arc<dyn Fn(&K, &V, RemovalCause) + Send + Sync>;
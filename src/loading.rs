@@ -0,0 +1,247 @@
+//! Read-through cache that loads misses in bulk
+//!
+//! A `LoadingCache` answers misses from a `CacheLoader`. `get_many` gathers
+//! every key it misses on and asks the loader for all of them with one call to
+//! `load_many`, so a page of misses can be one database query rather than one
+//! query per key.
+//!
+//! `get_or_stale` keeps serving a key through a loader outage: if the loader
+//! fails while the key's expired value is still held, that value is returned
+//! flagged as stale instead of the error.
+
+use std::collections::hash_map::RandomState;
+use std::collections::{HashMap, HashSet};
+use std::hash::{BuildHasher, Hash};
+use crate::{Clock, EvictionPolicy, Simcache, SystemClock};
+
+/// where a LoadingCache gets the values it misses on
+pub trait CacheLoader<K, V> {
+    type Error;
+    /// return the value of the key, or None if it has none
+    fn load(&mut self, key: &K) -> Result<Option<V>, Self::Error>;
+    /// return the keys that have values along with their values, in any order
+    /// loads each key in turn unless overridden with something like a single query for them all
+    fn load_many(&mut self, keys: &[K]) -> Result<Vec<(K, V)>, Self::Error>
+    where
+        K: Clone,
+    {
+        let mut loaded = Vec::with_capacity(keys.len());
+        for key in keys {
+            if let Some(value) = self.load(key)? {
+                loaded.push((key.clone(), value));
+            }
+        }
+        Ok(loaded)
+    }
+}
+
+/// a value returned by get_or_stale, flagged with whether it had expired
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fetched<T> {
+    /// a live value, either cached or just loaded
+    Fresh(T),
+    /// an expired value, returned because the loader failed
+    Stale(T),
+}
+
+impl<T> Fetched<T> {
+    /// return the value, fresh or not
+    pub fn value(self) -> T {
+        match self {
+            Fetched::Fresh(value) | Fetched::Stale(value) => value,
+        }
+    }
+
+    /// return whether the value had expired
+    pub fn is_stale(&self) -> bool {
+        matches!(self, Fetched::Stale(_))
+    }
+}
+
+/// a cache that loads the values it misses on
+pub struct LoadingCache<K, V, E, L, S = RandomState, C = SystemClock>
+where
+    E: EvictionPolicy<K>
+{
+    cache: Simcache<K, V, E, S, C>,
+    loader: L,
+    // a loaded value the cache had no room for, kept so get can still return it
+    rejected: Option<V>,
+}
+
+impl<K, V, E, L, S, C> LoadingCache<K, V, E, L, S, C>
+where
+    K: Eq + Hash + Clone,
+    E: EvictionPolicy<K>,
+    L: CacheLoader<K, V>,
+    S: BuildHasher,
+    C: Clock,
+    {
+        /// return a cache that loads its misses with the loader
+        pub fn new(cache: Simcache<K, V, E, S, C>, loader: L) -> Self {
+            LoadingCache { cache, loader, rejected: None }
+        }
+
+        /// return the value of the key, loading it if it isn't cached
+        /// a loaded value the cache refuses to keep is returned this once and then dropped
+        pub fn get(&mut self, key: &K) -> Result<Option<&V>, L::Error> {
+            if self.cache.get(key).is_some() {
+                return Ok(self.cache.peek(key));
+            }
+            let Some(value) = self.loader.load(key)? else {
+                return Ok(None);
+            };
+            Ok(Some(self.keep(key, value)))
+        }
+
+        /// like get, but if the loader fails while an expired value of the key is still held,
+        /// return that value flagged as stale instead of the error
+        /// the stale value stays in the cache, so it is served again on the next failure,
+        /// until a load succeeds or the value is purged or evicted
+        pub fn get_or_stale(&mut self, key: &K) -> Result<Option<Fetched<&V>>, L::Error> {
+            if self.cache.contains_key(key) {
+                return Ok(self.cache.get(key).map(Fetched::Fresh));
+            }
+            match self.loader.load(key) {
+                Err(_) if self.cache.peek_stale(key).is_some() => Ok(self.cache.peek_stale(key).map(Fetched::Stale)),
+                loaded => {
+                    // counts the miss and drops the expired value, if there is one
+                    self.cache.get(key);
+                    let Some(value) = loaded? else {
+                        return Ok(None);
+                    };
+                    Ok(Some(Fetched::Fresh(self.keep(key, value))))
+                }
+            }
+        }
+
+        // cache a loaded value, or hold on to it for this once if the cache refuses it
+        fn keep(&mut self, key: &K, value: V) -> &V {
+            match self.cache.insert_or_return(key.clone(), value, None) {
+                Ok(_) => self.cache.peek(key).expect("inserted value should be live"),
+                Err(value) => self.rejected.insert(value),
+            }
+        }
+
+        /// return the value of each key in the order given, loading every key that isn't cached
+        /// with a single call to load_many; nothing is cached if that call fails
+        /// values are cloned out, as loading one key may evict another that was asked for
+        pub fn get_many(&mut self, keys: &[K]) -> Result<Vec<Option<V>>, L::Error>
+        where
+            V: Clone,
+        {
+            let mut values: Vec<Option<V>> = self.cache.get_many(keys).into_iter().map(|value| value.cloned()).collect();
+            let mut seen = HashSet::new();
+            let missing: Vec<K> = keys
+                .iter()
+                .zip(&values)
+                .filter(|(key, value)| value.is_none() && seen.insert(*key))
+                .map(|(key, _)| key.clone())
+                .collect();
+            if missing.is_empty() {
+                return Ok(values);
+            }
+            let loaded: HashMap<K, V> = self.loader.load_many(&missing)?.into_iter().collect();
+            for (key, value) in keys.iter().zip(&mut values) {
+                if value.is_none() {
+                    *value = loaded.get(key).cloned();
+                }
+            }
+            for (key, value) in loaded {
+                self.cache.insert(key, value, None);
+            }
+            Ok(values)
+        }
+
+        /// drop the cached value of the key, so the next get loads it again
+        pub fn invalidate(&mut self, key: &K) -> Option<V> {
+            self.cache.remove(key)
+        }
+
+        /// return the cache in front of the loader
+        pub fn cache(&self) -> &Simcache<K, V, E, S, C> {
+            &self.cache
+        }
+
+        /// return the loader
+        pub fn loader(&self) -> &L {
+            &self.loader
+        }
+    }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use crate::eviction::LRU;
+    use crate::MockClock;
+
+    // squares the keys below 100, counting how it was called
+    #[derive(Default)]
+    struct Squares {
+        loads: usize,
+        batches: Vec<Vec<u64>>,
+        // fail every load after this many
+        fail_after: Option<usize>,
+    }
+
+    impl CacheLoader<u64, u64> for Squares {
+        type Error = String;
+        fn load(&mut self, key: &u64) -> Result<Option<u64>, String> {
+            self.loads += 1;
+            if self.fail_after.is_some_and(|limit| self.loads > limit) {
+                return Err("upstream down".to_string());
+            }
+            Ok((*key < 100).then(|| key * key))
+        }
+        fn load_many(&mut self, keys: &[u64]) -> Result<Vec<(u64, u64)>, String> {
+            if keys.contains(&666) {
+                return Err("query failed".to_string());
+            }
+            self.batches.push(keys.to_vec());
+            Ok(keys.iter().filter(|key| **key < 100).map(|key| (*key, key * key)).collect())
+        }
+    }
+
+    #[test]
+    fn loading_cache_test() {
+        let cache: Simcache<u64, u64, LRU<u64>> = Simcache::new(10);
+        let mut cache = LoadingCache::new(cache, Squares::default());
+        assert_eq!(cache.get(&3), Ok(Some(&9)));
+        assert_eq!(cache.get(&3), Ok(Some(&9)));
+        assert_eq!(cache.get(&200), Ok(None));
+        assert_eq!(cache.loader().loads, 2);
+
+        // the misses are loaded together, once each, and the hit isn't loaded at all
+        let values = cache.get_many(&[1, 3, 2, 1, 500]).unwrap();
+        assert_eq!(values, [Some(1), Some(9), Some(4), Some(1), None]);
+        assert_eq!(cache.loader().batches, [vec![1, 2, 500]]);
+        assert!(cache.cache().contains_key(&2));
+        assert_eq!(cache.get_many(&[1, 2]).unwrap(), [Some(1), Some(4)]);
+        assert_eq!(cache.loader().batches.len(), 1);
+
+        assert_eq!(cache.get_many(&[4, 666]), Err("query failed".to_string()));
+        assert!(!cache.cache().contains_key(&4));
+        assert_eq!(cache.invalidate(&3), Some(9));
+    }
+
+    #[test]
+    fn stale_fallback_test() {
+        let clock = MockClock::new();
+        let cache: Simcache<u64, u64, LRU<u64>, RandomState, MockClock> =
+            Simcache::builder().clock(clock.clone()).max_capacity(10).default_ttl(Duration::from_secs(1)).build();
+        let mut cache = LoadingCache::new(cache, Squares { fail_after: Some(1), ..Squares::default() });
+        assert_eq!(cache.get_or_stale(&3), Ok(Some(Fetched::Fresh(&9))));
+        assert_eq!(cache.get_or_stale(&3), Ok(Some(Fetched::Fresh(&9))));
+
+        // once the value expires the loader is asked again, and its failure is answered with the old value
+        clock.advance(Duration::from_secs(2));
+        assert_eq!(cache.get_or_stale(&3), Ok(Some(Fetched::Stale(&9))));
+        assert!(cache.get_or_stale(&3).unwrap().unwrap().is_stale());
+        assert_eq!(cache.get_or_stale(&4), Err("upstream down".to_string()));
+
+        // a plain get drops the expired value and returns the error
+        assert_eq!(cache.get(&3), Err("upstream down".to_string()));
+        assert_eq!(cache.get_or_stale(&3), Err("upstream down".to_string()));
+    }
+}
@@ -1,21 +1,49 @@
-use simcache::{Simcache, eviction::LFU};
-use std::time::Duration;
+//! A memcached compatible cache server
+//!
+//! usage: simcache [-l address] [-p port] [-m megabytes] [-t shards] [-I max_item_bytes]
+//!
+//! Listens on 127.0.0.1 unless given another address, as it is meant to sit next to its clients.
 
-fn main() {
-    let mut cache: Simcache::<&'static str, &'static str, LFU<&'static str>> = Simcache::new(2);
-
-    cache.insert("key1", "val1", None);
-    cache.insert("key2", "val2", Some(Duration::from_secs(330)));
+use simcache::memcached::{cache_with_memory, serve, DEFAULT_MAX_ITEM_SIZE};
+use std::net::TcpListener;
+use std::process;
+use std::sync::Arc;
 
-    cache.get(&"key1");
-    cache.get(&"key1");
+fn main() {
+    let mut address = "127.0.0.1".to_string();
+    let mut port: u16 = 11211;
+    let mut megabytes: u64 = 64;
+    let mut shards: usize = 16;
+    let mut max_item_size = DEFAULT_MAX_ITEM_SIZE;
 
-    cache.insert("key3", "val3", None);
-    //cache.insert("key4", "val4", None);
-    println!("key1: {:?}", cache.get(&"key1"));
-    println!("key2: {:?}", cache.get(&"key2"));
-    println!("key3: {:?}", cache.get(&"key3"));
+    let mut args = std::env::args().skip(1);
+    while let Some(flag) = args.next() {
+        let value = args.next();
+        let parsed = match (flag.as_str(), value.as_deref()) {
+            ("-l", Some(value)) => {
+                address = value.to_string();
+                true
+            }
+            ("-p", Some(value)) => value.parse().map(|value| port = value).is_ok(),
+            ("-m", Some(value)) => value.parse().map(|value| megabytes = value).is_ok(),
+            ("-t", Some(value)) => value.parse().map(|value| shards = value).is_ok(),
+            ("-I", Some(value)) => value.parse().map(|value| max_item_size = value).is_ok(),
+            _ => false,
+        };
+        if !parsed {
+            eprintln!("usage: simcache [-l address] [-p port] [-m megabytes] [-t shards] [-I max_item_bytes]");
+            process::exit(2);
+        }
+    }
 
-    // std::thread::sleep(Duration::from_secs(4));
-    //println!("After adding 4 - key1: {:?}", cache.get(&"key1"));
-}
\ No newline at end of file
+    let listener = TcpListener::bind((address.as_str(), port)).unwrap_or_else(|err| {
+        eprintln!("simcache: could not listen on {address} port {port}: {err}");
+        process::exit(1);
+    });
+    let Some(bytes) = megabytes.checked_mul(1024 * 1024) else {
+        eprintln!("simcache: -m {megabytes} is more memory than can be counted");
+        process::exit(2);
+    };
+    let cache = Arc::new(cache_with_memory(bytes, shards));
+    serve(listener, cache, max_item_size);
+}
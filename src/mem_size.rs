@@ -0,0 +1,150 @@
+//! Approximate memory sizes of keys and values
+//!
+//! A cache built with `max_bytes` or `track_memory` adds up the `MemSize` of
+//! every key and value it holds, plus its own bookkeeping for each entry, so
+//! that it can be bounded by memory instead of by entry count. The sizes are
+//! estimates: allocator overhead is ignored, and shared data such as the
+//! value behind an `Arc` is counted in full by every owner.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::mem::size_of;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// reports how much memory a value takes up
+pub trait MemSize {
+    /// return the bytes the value owns on the heap, such as the buffer of a String
+    fn heap_size(&self) -> usize {
+        0
+    }
+
+    /// return the inline size of the value plus the bytes it owns on the heap
+    fn mem_size(&self) -> usize
+    where
+        Self: Sized,
+    {
+        size_of::<Self>() + self.heap_size()
+    }
+}
+
+macro_rules! inline_only {
+    ($($t:ty),*) => {
+        $(impl MemSize for $t {})*
+    };
+}
+
+inline_only!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64, bool, char, (), Duration, Instant);
+
+// borrowed data belongs to someone else
+impl<T: ?Sized> MemSize for &T {}
+
+impl MemSize for String {
+    fn heap_size(&self) -> usize {
+        self.capacity()
+    }
+}
+
+impl MemSize for Box<str> {
+    fn heap_size(&self) -> usize {
+        self.len()
+    }
+}
+
+impl<T: MemSize> MemSize for Box<T> {
+    fn heap_size(&self) -> usize {
+        self.as_ref().mem_size()
+    }
+}
+
+impl<T: MemSize> MemSize for Box<[T]> {
+    fn heap_size(&self) -> usize {
+        self.iter().map(MemSize::mem_size).sum()
+    }
+}
+
+impl<T: MemSize> MemSize for Vec<T> {
+    fn heap_size(&self) -> usize {
+        (self.capacity() - self.len()) * size_of::<T>() + self.iter().map(MemSize::mem_size).sum::<usize>()
+    }
+}
+
+impl<T: MemSize> MemSize for VecDeque<T> {
+    fn heap_size(&self) -> usize {
+        (self.capacity() - self.len()) * size_of::<T>() + self.iter().map(MemSize::mem_size).sum::<usize>()
+    }
+}
+
+// the hash tables also keep a control byte per slot
+impl<K: MemSize, V: MemSize, S> MemSize for HashMap<K, V, S> {
+    fn heap_size(&self) -> usize {
+        let unused = self.capacity() - self.len();
+        unused * (size_of::<(K, V)>() + 1) + self.iter().map(|(k, v)| k.mem_size() + v.mem_size() + 1).sum::<usize>()
+    }
+}
+
+impl<T: MemSize, S> MemSize for HashSet<T, S> {
+    fn heap_size(&self) -> usize {
+        let unused = self.capacity() - self.len();
+        unused * (size_of::<T>() + 1) + self.iter().map(|t| t.mem_size() + 1).sum::<usize>()
+    }
+}
+
+impl<T: MemSize> MemSize for Option<T> {
+    fn heap_size(&self) -> usize {
+        self.as_ref().map_or(0, MemSize::heap_size)
+    }
+}
+
+impl<T: MemSize> MemSize for Arc<T> {
+    fn heap_size(&self) -> usize {
+        // the strong and weak counts sit next to the value
+        2 * size_of::<usize>() + self.as_ref().mem_size()
+    }
+}
+
+impl<T: MemSize> MemSize for Rc<T> {
+    fn heap_size(&self) -> usize {
+        2 * size_of::<usize>() + self.as_ref().mem_size()
+    }
+}
+
+impl<T: MemSize, const N: usize> MemSize for [T; N] {
+    fn heap_size(&self) -> usize {
+        self.iter().map(MemSize::heap_size).sum()
+    }
+}
+
+impl<A: MemSize, B: MemSize> MemSize for (A, B) {
+    fn heap_size(&self) -> usize {
+        self.0.heap_size() + self.1.heap_size()
+    }
+}
+
+impl<A: MemSize, B: MemSize, C: MemSize> MemSize for (A, B, C) {
+    fn heap_size(&self) -> usize {
+        self.0.heap_size() + self.1.heap_size() + self.2.heap_size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mem_size_test() {
+        assert_eq!(7u64.mem_size(), 8);
+        assert_eq!("borrowed".mem_size(), size_of::<&str>());
+
+        let s = String::with_capacity(10);
+        assert_eq!(s.mem_size(), size_of::<String>() + 10);
+
+        let mut v: Vec<String> = Vec::with_capacity(4);
+        v.push("abc".to_string());
+        assert_eq!(v.heap_size(), 3 * size_of::<String>() + size_of::<String>() + 3);
+
+        assert_eq!(Some(Box::new(1u32)).heap_size(), 4);
+        assert_eq!((1u8, "xy".to_string()).heap_size(), 2);
+        assert_eq!(Arc::new(0u64).heap_size(), 2 * size_of::<usize>() + 8);
+    }
+}
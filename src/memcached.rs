@@ -0,0 +1,256 @@
+//! Memcached text protocol server backed by a ShardedSimcache
+//!
+//! Supports the commands a sidecar cache needs: `get` with one or more keys,
+//! `set`, `delete`, `flush_all`, `version`, and `quit`. Each connection is
+//! served by its own thread, and entries are limited by the bytes of their keys
+//! and data, like memcached's `-m`. A `set` of more data than the item size
+//! limit, like memcached's `-I`, is refused before any of it is buffered. An exptime of up to 30 days is a number of
+//! seconds from now, a larger one is a unix time, and a negative one stores
+//! nothing and drops any value the key had.
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use crate::{InsertOutcome, ShardedSimcache, Simcache, LRU};
+
+// the longest key memcached accepts
+const MAX_KEY_LEN: usize = 250;
+// the longest command line read, with room for a get of many keys; a longer one closes the connection
+const MAX_LINE: u64 = 64 * 1024;
+// exptimes above this are unix times rather than seconds from now
+const MAX_RELATIVE_EXPTIME: i64 = 60 * 60 * 24 * 30;
+
+/// the most data a single set may store unless the server is given another limit, as in memcached
+pub const DEFAULT_MAX_ITEM_SIZE: usize = 1 << 20;
+
+/// a stored value with the flags the client set it with
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Item {
+    pub flags: u32,
+    pub data: Vec<u8>,
+}
+
+/// the cache behind a memcached server
+pub type MemcachedCache = ShardedSimcache<String, Item, LRU<String>>;
+
+/// return a cache of the given number of shards whose keys and data take up at most max_bytes between them
+pub fn cache_with_memory(max_bytes: u64, shards: usize) -> MemcachedCache {
+    let shards = shards.max(1);
+    let per_shard = max_bytes.div_ceil(shards as u64);
+    let caches = (0..shards)
+        .map(|_| {
+            Simcache::builder()
+                .weigher(|key: &String, item: &Item| (key.len() + item.data.len()) as u64)
+                .max_weight(per_shard)
+                .build()
+        })
+        .collect();
+    ShardedSimcache::from_shards(caches)
+}
+
+/// accept connections on the listener and serve each on its own thread, refusing sets of more than
+/// max_item_size bytes of data
+/// a failed accept, such as from running out of file descriptors, is logged and skipped, so this never returns
+pub fn serve(listener: TcpListener, cache: Arc<MemcachedCache>, max_item_size: usize) {
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(err) => {
+                eprintln!("simcache: could not accept a connection: {err}");
+                continue;
+            }
+        };
+        let cache = cache.clone();
+        thread::spawn(move || {
+            let reader = BufReader::new(stream.try_clone()?);
+            handle_connection(&cache, max_item_size, reader, stream)
+        });
+    }
+}
+
+/// answer the commands read from reader on writer until the client quits or disconnects,
+/// refusing sets of more than max_item_size bytes of data
+pub fn handle_connection<R: BufRead, W: Write>(
+    cache: &MemcachedCache,
+    max_item_size: usize,
+    mut reader: R,
+    mut writer: W,
+) -> io::Result<()> {
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        if reader.by_ref().take(MAX_LINE).read_until(b'\n', &mut line)? == 0 {
+            return Ok(());
+        }
+        if !line.ends_with(b"\n") && line.len() as u64 == MAX_LINE {
+            writer.write_all(b"CLIENT_ERROR line too long\r\n")?;
+            return writer.flush();
+        }
+        let Ok(command) = std::str::from_utf8(trim_line(&line)) else {
+            writer.write_all(b"CLIENT_ERROR bad command line format\r\n")?;
+            continue;
+        };
+        let words: Vec<&str> = command.split_ascii_whitespace().collect();
+        match words.as_slice() {
+            ["get", keys @ ..] | ["gets", keys @ ..] if !keys.is_empty() => {
+                for key in keys {
+                    if let Some(item) = cache.get(*key) {
+                        write!(writer, "VALUE {} {} {}\r\n", key, item.flags, item.data.len())?;
+                        writer.write_all(&item.data)?;
+                        writer.write_all(b"\r\n")?;
+                    }
+                }
+                writer.write_all(b"END\r\n")?;
+            }
+            ["set", key, flags, exptime, bytes, rest @ ..] if rest.is_empty() || rest == ["noreply"] => {
+                let (Ok(flags), Ok(exptime), Ok(bytes)) = (flags.parse::<u32>(), exptime.parse::<i64>(), bytes.parse::<usize>()) else {
+                    writer.write_all(b"CLIENT_ERROR bad command line format\r\n")?;
+                    continue;
+                };
+                let Some(chunk) = bytes.checked_add(2).filter(|_| bytes <= max_item_size) else {
+                    writer.write_all(b"SERVER_ERROR object too large for cache\r\n")?;
+                    // skip the data so the next command is read from after it, unless its length can't even be counted
+                    match u64::try_from(bytes).ok().and_then(|bytes| bytes.checked_add(2)) {
+                        Some(chunk) => io::copy(&mut reader.by_ref().take(chunk), &mut io::sink())?,
+                        None => return Ok(()),
+                    };
+                    continue;
+                };
+                let mut data = vec![0; chunk];
+                reader.read_exact(&mut data)?;
+                if !data.ends_with(b"\r\n") {
+                    writer.write_all(b"CLIENT_ERROR bad data chunk\r\n")?;
+                    continue;
+                }
+                data.truncate(bytes);
+                let reply: &[u8] = if key.len() > MAX_KEY_LEN {
+                    b"CLIENT_ERROR key too long\r\n"
+                } else {
+                    set(cache, key, Item { flags, data }, exptime)
+                };
+                if rest.is_empty() {
+                    writer.write_all(reply)?;
+                }
+            }
+            ["delete", key, rest @ ..] if rest.is_empty() || rest == ["noreply"] => {
+                let reply: &[u8] = match cache.remove(*key) {
+                    Some(_) => b"DELETED\r\n",
+                    None => b"NOT_FOUND\r\n",
+                };
+                if rest.is_empty() {
+                    writer.write_all(reply)?;
+                }
+            }
+            // a delay isn't supported, so everything is flushed straight away
+            ["flush_all", rest @ ..] => {
+                cache.clear();
+                if rest.last() != Some(&"noreply") {
+                    writer.write_all(b"OK\r\n")?;
+                }
+            }
+            ["version"] => write!(writer, "VERSION simcache {}\r\n", env!("CARGO_PKG_VERSION"))?,
+            ["quit"] => return Ok(()),
+            _ => writer.write_all(b"ERROR\r\n")?,
+        }
+        writer.flush()?;
+    }
+}
+
+fn trim_line(line: &[u8]) -> &[u8] {
+    let line = line.strip_suffix(b"\n").unwrap_or(line);
+    line.strip_suffix(b"\r").unwrap_or(line)
+}
+
+// store the item and return the reply to the set
+fn set(cache: &MemcachedCache, key: &str, item: Item, exptime: i64) -> &'static [u8] {
+    let key = key.to_string();
+    // the cache would keep an item heavier than its shard on its own, flushing the shard and going over the limit
+    let weight = (key.len() + item.data.len()) as u64;
+    if weight > cache.with_shard(&key, |shard| shard.max_weight()) {
+        return b"SERVER_ERROR object too large for cache\r\n";
+    }
+    let outcome = match exptime {
+        0 => cache.insert(key, item, None),
+        exptime if exptime < 0 => {
+            cache.remove(&key);
+            return b"STORED\r\n";
+        }
+        exptime if exptime <= MAX_RELATIVE_EXPTIME => cache.insert(key, item, Some(Duration::from_secs(exptime as u64))),
+        exptime => {
+            let expires_at = UNIX_EPOCH + Duration::from_secs(exptime as u64);
+            if expires_at <= SystemTime::now() {
+                cache.remove(&key);
+                return b"STORED\r\n";
+            }
+            cache.with_shard(&key, |shard| shard.insert_until(key.clone(), item, expires_at))
+        }
+    };
+    match outcome {
+        InsertOutcome::Rejected => b"SERVER_ERROR out of memory storing object\r\n",
+        _ => b"STORED\r\n",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(cache: &MemcachedCache, input: &str) -> String {
+        let mut output = Vec::new();
+        handle_connection(cache, DEFAULT_MAX_ITEM_SIZE, input.as_bytes(), &mut output).expect("writing to a Vec should not fail");
+        String::from_utf8(output).expect("replies should be utf-8")
+    }
+
+    #[test]
+    fn memcached_test() {
+        let cache = cache_with_memory(1 << 20, 4);
+        assert_eq!(run(&cache, "set a 5 0 3\r\nabc\r\nset b 0 60 2 noreply\r\nhi\r\n"), "STORED\r\n");
+        assert_eq!(run(&cache, "get a b c\r\n"), "VALUE a 5 3\r\nabc\r\nVALUE b 0 2\r\nhi\r\nEND\r\n");
+        assert!(cache.with_shard("b", |shard| shard.ttl_remaining("b").is_some()));
+
+        assert_eq!(run(&cache, "delete a\r\ndelete a\r\n"), "DELETED\r\nNOT_FOUND\r\n");
+        assert_eq!(run(&cache, "set c 0 -1 1\r\nx\r\nget c\r\n"), "STORED\r\nEND\r\n");
+        assert_eq!(run(&cache, "set d 0 0 5\r\nabcdefg"), "CLIENT_ERROR bad data chunk\r\n");
+        assert_eq!(run(&cache, "bogus\r\nset e x 0 1\r\n"), "ERROR\r\nCLIENT_ERROR bad command line format\r\n");
+        assert_eq!(run(&cache, "flush_all\r\nget b\r\nquit\r\nget b\r\n"), "OK\r\nEND\r\n");
+        assert!(cache.is_empty());
+
+        // keys and data count against the memory limit
+        let small = cache_with_memory(8, 1);
+        run(&small, "set k1 0 0 2\r\nab\r\nset k2 0 0 2\r\ncd\r\n");
+        assert_eq!(small.len(), 2);
+        run(&small, "set k3 0 0 2\r\nef\r\n");
+        assert_eq!(small.len(), 2);
+        assert!(!small.contains_key("k1"));
+
+        // an item heavier than a shard is refused rather than flushing the shard to keep it
+        assert_eq!(run(&small, "set big 0 0 6\r\nabcdef\r\nget k2\r\n"), "SERVER_ERROR object too large for cache\r\nVALUE k2 0 2\r\ncd\r\nEND\r\n");
+        assert!(!small.contains_key("big"));
+    }
+
+    #[test]
+    fn item_size_test() {
+        let cache = cache_with_memory(1 << 20, 1);
+        let mut output = Vec::new();
+        let input = "set a 0 0 5\r\nabcde\r\nset b 0 0 4\r\nabcd\r\nget a b\r\n";
+        handle_connection(&cache, 4, input.as_bytes(), &mut output).unwrap();
+        // the oversized data is skipped, and the commands after it still run
+        assert_eq!(output, b"SERVER_ERROR object too large for cache\r\nSTORED\r\nVALUE b 0 4\r\nabcd\r\nEND\r\n");
+
+        // a command line with no end in sight closes the connection rather than being buffered
+        let mut output = Vec::new();
+        let endless = vec![b'a'; 2 * MAX_LINE as usize];
+        handle_connection(&cache, 4, &endless[..], &mut output).unwrap();
+        assert_eq!(output, b"CLIENT_ERROR line too long\r\n");
+
+        // a length that would overflow is refused without allocating, and the connection is closed
+        let mut output = Vec::new();
+        handle_connection(&cache, 4, "set c 0 0 18446744073709551615\r\nget b\r\n".as_bytes(), &mut output).unwrap();
+        assert_eq!(output, b"SERVER_ERROR object too large for cache\r\n");
+        let mut output = Vec::new();
+        handle_connection(&cache, 4, "set c 0 0 99999999999\r\nx\r\n".as_bytes(), &mut output).unwrap();
+        assert_eq!(output, b"SERVER_ERROR object too large for cache\r\n");
+    }
+}
@@ -0,0 +1,162 @@
+//! Functions whose results are cached in a Simcache
+//!
+//! A `Memoized` wraps a function of one argument and answers repeated calls
+//! with the same argument from its cache. Functions of several arguments take
+//! them as a tuple. The cache is locked only to look up and insert results, not
+//! while the function runs, so a memoized function may call itself recursively.
+//! Two threads missing on the same argument at once may both compute it.
+//!
+//! With the `macros` feature, `#[memoize(capacity = 100)]` on a function does
+//! the wrapping for you, with an optional `ttl` in seconds and `policy` named
+//! from `simcache::eviction`:
+//!
+//! ```ignore
+//! #[simcache::memoize(capacity = 1000, ttl = 60, policy = LFU)]
+//! fn fib(n: u64) -> u64 {
+//!     if n < 2 { n } else { fib(n - 1) + fib(n - 2) }
+//! }
+//! ```
+
+use std::hash::Hash;
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
+use crate::{EvictionPolicy, Simcache, Stats, LRU};
+
+/// a function whose results are cached by argument
+pub struct Memoized<K, V, F, E = LRU<K>>
+where
+    K: Eq + Hash + Clone,
+    E: EvictionPolicy<K>,
+{
+    cache: Mutex<Simcache<K, V, E>>,
+    f: F,
+}
+
+impl<K, V, F> Memoized<K, V, F>
+where
+    K: Eq + Hash + Clone,
+    F: Fn(K) -> V,
+    {
+        /// cache up to capacity results of f, least recently used first out,
+        /// each for the given ttl if there is one
+        pub fn new(capacity: usize, ttl: Option<Duration>, f: F) -> Self {
+            let mut builder = Simcache::builder().max_capacity(capacity);
+            if let Some(ttl) = ttl {
+                builder = builder.default_ttl(ttl);
+            }
+            Self::with_cache(builder.build(), f)
+        }
+    }
+
+impl<K, V, F, E> Memoized<K, V, F, E>
+where
+    K: Eq + Hash + Clone,
+    F: Fn(K) -> V,
+    E: EvictionPolicy<K>,
+    {
+        /// cache the results of f in the given cache, for another eviction policy or other limits
+        pub fn with_cache(cache: Simcache<K, V, E>, f: F) -> Self {
+            Memoized { cache: Mutex::new(cache), f }
+        }
+
+        fn cache(&self) -> MutexGuard<'_, Simcache<K, V, E>> {
+            self.cache.lock().expect("memoized cache mutex should not be poisoned")
+        }
+
+        /// return f(arg), from the cache if it has been computed before
+        pub fn call(&self, arg: K) -> V
+        where
+            V: Clone,
+        {
+            if let Some(value) = self.cache().get(&arg) {
+                return value.clone();
+            }
+            let value = (self.f)(arg.clone());
+            self.cache().insert(arg, value.clone(), None);
+            value
+        }
+
+        /// forget the result for the argument, returning it
+        pub fn invalidate(&self, arg: &K) -> Option<V> {
+            self.cache().remove(arg)
+        }
+
+        /// forget every result
+        pub fn clear(&self) {
+            self.cache().clear();
+        }
+
+        /// return the number of results cached
+        pub fn len(&self) -> usize {
+            self.cache().len()
+        }
+
+        /// return true if no results are cached
+        pub fn is_empty(&self) -> bool {
+            self.len() == 0
+        }
+
+        /// return the hits and misses of the calls so far
+        pub fn stats(&self) -> Stats {
+            self.cache().stats()
+        }
+
+        /// return the cache of results
+        pub fn into_inner(self) -> Simcache<K, V, E> {
+            self.cache.into_inner().expect("memoized cache mutex should not be poisoned")
+        }
+    }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use crate::eviction::LFU;
+
+    #[test]
+    fn memoize_test() {
+        let calls = AtomicUsize::new(0);
+        let square = Memoized::new(2, None, |n: u64| {
+            calls.fetch_add(1, Ordering::Relaxed);
+            n * n
+        });
+        assert_eq!(square.call(3), 9);
+        assert_eq!(square.call(3), 9);
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+        assert_eq!(square.stats().hits, 1);
+
+        square.call(4);
+        square.call(5);
+        assert_eq!(square.len(), 2);
+        assert_eq!(square.invalidate(&5), Some(25));
+        square.call(3);
+        assert_eq!(calls.load(Ordering::Relaxed), 4);
+
+        let add = Memoized::with_cache(Simcache::<_, _, LFU<_>>::new(10), |(a, b): (u64, u64)| a + b);
+        assert_eq!(add.call((1, 2)), 3);
+        add.clear();
+        assert!(add.is_empty());
+    }
+
+    #[cfg(feature = "macros")]
+    #[test]
+    fn memoize_macro_test() {
+        static CALLS: AtomicUsize = AtomicUsize::new(0);
+
+        #[crate::memoize(capacity = 100, ttl = 60, policy = LFU)]
+        fn fib(n: u64) -> u64 {
+            CALLS.fetch_add(1, Ordering::Relaxed);
+            if n < 2 { n } else { fib(n - 1) + fib(n - 2) }
+        }
+
+        #[crate::memoize(capacity = 10)]
+        fn join(a: String, mut b: String) -> String {
+            b.insert_str(0, &a);
+            b
+        }
+
+        assert_eq!(fib(50), 12586269025);
+        assert_eq!(CALLS.load(Ordering::Relaxed), 51);
+        assert_eq!(join("a".to_string(), "b".to_string()), "ab");
+    }
+}
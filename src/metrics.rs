@@ -0,0 +1,91 @@
+//! Publishing cache statistics to a metrics system
+//!
+//! `publish_metrics` reports a cache's counters and gauges to a `MetricsSink`,
+//! tagging each with a `cache` label so that many caches can share one
+//! dashboard. Call it periodically, e.g. from the same thread that scrapes or
+//! flushes the rest of a service's metrics. The sink is a small trait rather
+//! than a dependency, so any metrics library can sit behind it; for the
+//! `metrics` facade:
+//!
+//! ```ignore
+//! struct Facade;
+//!
+//! impl simcache::metrics::MetricsSink for Facade {
+//!     fn counter(&mut self, name: &'static str, cache: &str, value: u64) {
+//!         metrics::counter!(name, "cache" => cache.to_string()).absolute(value);
+//!     }
+//!     fn gauge(&mut self, name: &'static str, cache: &str, value: f64) {
+//!         metrics::gauge!(name, "cache" => cache.to_string()).set(value);
+//!     }
+//! }
+//! ```
+
+use std::hash::{BuildHasher, Hash};
+use crate::{Clock, EvictionPolicy, Simcache};
+
+/// somewhere to send a cache's metrics, such as an adapter over a metrics library
+pub trait MetricsSink {
+    /// record the running total of a counter for the named cache
+    fn counter(&mut self, name: &'static str, cache: &str, value: u64);
+    /// record the current value of a gauge for the named cache
+    fn gauge(&mut self, name: &'static str, cache: &str, value: f64);
+}
+
+impl<K, V, E, S, C> Simcache<K, V, E, S, C>
+where
+    K: Eq + Hash + Clone,
+    E: EvictionPolicy<K>,
+    S: BuildHasher,
+    C: Clock,
+    {
+        /// report the hit, miss, eviction, and expiry counters, the size and weight gauges, and the fraction
+        /// of entries with a ttl evicted before it ran out to the sink, labelled with the given cache name
+        /// counters are running totals, so they start over from 0 after reset_stats
+        pub fn publish_metrics<M: MetricsSink>(&self, cache: &str, sink: &mut M) {
+            let stats = self.stats();
+            sink.counter("simcache_hits_total", cache, stats.hits);
+            sink.counter("simcache_misses_total", cache, stats.misses);
+            sink.counter("simcache_evictions_total", cache, stats.evictions);
+            sink.counter("simcache_expired_total", cache, stats.expired);
+            sink.gauge("simcache_entries", cache, self.len() as f64);
+            sink.gauge("simcache_weight", cache, self.weight() as f64);
+            sink.gauge("simcache_evicted_before_expiry_ratio", cache, self.lifetime_stats().evicted_before_expiry());
+        }
+    }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LRU;
+
+    #[derive(Default)]
+    struct Recorded(Vec<(&'static str, String, f64)>);
+
+    impl MetricsSink for Recorded {
+        fn counter(&mut self, name: &'static str, cache: &str, value: u64) {
+            self.0.push((name, cache.to_string(), value as f64));
+        }
+        fn gauge(&mut self, name: &'static str, cache: &str, value: f64) {
+            self.0.push((name, cache.to_string(), value));
+        }
+    }
+
+    #[test]
+    fn metrics_test() {
+        let mut cache: Simcache<&str, u64, LRU<&str>> = Simcache::new(1);
+        cache.insert("a", 1, Some(std::time::Duration::from_secs(60)));
+        cache.insert("b", 2, None);
+        cache.get(&"b");
+        cache.get(&"a");
+
+        let mut sink = Recorded::default();
+        cache.publish_metrics("sessions", &mut sink);
+        let value = |name| sink.0.iter().find(|(n, _, _)| *n == name).map(|(_, _, v)| *v);
+        assert_eq!(value("simcache_hits_total"), Some(1.0));
+        assert_eq!(value("simcache_misses_total"), Some(1.0));
+        assert_eq!(value("simcache_evictions_total"), Some(1.0));
+        assert_eq!(value("simcache_entries"), Some(1.0));
+        assert_eq!(value("simcache_evicted_before_expiry_ratio"), Some(1.0));
+        assert!(sink.0.iter().all(|(_, cache, _)| cache == "sessions"));
+    }
+}
@@ -0,0 +1,204 @@
+//! Caches split into namespaces, each with its own quota
+//!
+//! A `NamespacedCache` keeps the entries of each namespace, such as each
+//! tenant of a multi-tenant service, in a cache of its own with its own
+//! limits, eviction policy state, and stats. A namespace that fills its quota
+//! only evicts its own entries, so one busy tenant can't push out everyone
+//! else's. Namespaces are created on first insert with the default quota
+//! unless they were given one with set_quota.
+
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::time::Duration;
+use crate::{CapacityLimit, EvictionPolicy, InsertOutcome, Simcache, SimcacheBuilder, Stats};
+
+/// the limits of one namespace
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quota {
+    pub capacity: CapacityLimit,
+    pub max_weight: u64,
+}
+
+impl Quota {
+    /// return a quota of up to max_entries entries, with no weight limit
+    pub fn entries(max_entries: usize) -> Self {
+        Quota { capacity: CapacityLimit::Bounded(max_entries), max_weight: u64::MAX }
+    }
+
+    /// also limit the combined weight of the namespace's entries
+    pub fn max_weight(mut self, max_weight: u64) -> Self {
+        self.max_weight = max_weight;
+        self
+    }
+}
+
+/// a cache whose entries belong to namespaces that are limited and evicted separately
+pub struct NamespacedCache<N, K, V, E>
+where
+    E: EvictionPolicy<K>
+{
+    namespaces: HashMap<N, Simcache<K, V, E>>,
+    default_quota: Quota,
+    quotas: HashMap<N, Quota>,
+    // applied to the builder of each new namespace before its quota
+    configure: Option<Configure<K, V, E>>,
+}
+
+type Configure<K, V, E> = Box<dyn Fn(SimcacheBuilder<K, V, E>) -> SimcacheBuilder<K, V, E> + Send + Sync>;
+
+impl<N, K, V, E> NamespacedCache<N, K, V, E>
+where
+    N: Eq + Hash + Clone,
+    K: Eq + Hash + Clone,
+    E: EvictionPolicy<K>,
+    {
+        /// return an empty cache whose namespaces get the given quota unless set_quota says otherwise
+        pub fn new(default_quota: Quota) -> Self {
+            NamespacedCache { namespaces: HashMap::new(), default_quota, quotas: HashMap::new(), configure: None }
+        }
+
+        /// set up the cache of each namespace with the given function, e.g. to add a weigher or a default ttl
+        /// the namespace's quota is applied after it, and only namespaces created after this call use it
+        pub fn configure<F>(mut self, configure: F) -> Self
+        where
+            F: Fn(SimcacheBuilder<K, V, E>) -> SimcacheBuilder<K, V, E> + Send + Sync + 'static,
+        {
+            self.configure = Some(Box::new(configure));
+            self
+        }
+
+        /// give the namespace its own quota, evicting from it until it fits if it already exists
+        /// return the number of entries evicted
+        pub fn set_quota(&mut self, namespace: N, quota: Quota) -> usize {
+            self.quotas.insert(namespace.clone(), quota);
+            match self.namespaces.get_mut(&namespace) {
+                Some(cache) => cache.set_capacity_limit(quota.capacity) + cache.set_max_weight(quota.max_weight),
+                None => 0,
+            }
+        }
+
+        /// return the quota the namespace has, or would have once something is inserted into it
+        pub fn quota(&self, namespace: &N) -> Quota {
+            self.quotas.get(namespace).copied().unwrap_or(self.default_quota)
+        }
+
+        /// insert a key value pair into the namespace, evicting only from that namespace to make room
+        pub fn insert(&mut self, namespace: N, key: K, value: V, ttl: Option<Duration>) -> InsertOutcome {
+            if !self.namespaces.contains_key(&namespace) {
+                let cache = self.new_namespace(&namespace);
+                self.namespaces.insert(namespace.clone(), cache);
+            }
+            let cache = self.namespaces.get_mut(&namespace).expect("namespace should have just been created");
+            cache.insert(key, value, ttl)
+        }
+
+        /// return the value of the key in the namespace if it is present and not expired
+        pub fn get<Q>(&mut self, namespace: &N, key: &Q) -> Option<&V>
+        where
+            K: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+        {
+            self.namespaces.get_mut(namespace)?.get(key)
+        }
+
+        /// remove the key from the namespace, returning its value if it was present
+        pub fn remove<Q>(&mut self, namespace: &N, key: &Q) -> Option<V>
+        where
+            K: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+        {
+            self.namespaces.get_mut(namespace)?.remove(key)
+        }
+
+        /// remove every entry of the namespace, returning how many there were
+        /// its quota is kept, but its stats start over
+        pub fn clear_namespace(&mut self, namespace: &N) -> usize {
+            match self.namespaces.remove(namespace) {
+                Some(mut cache) => {
+                    let len = cache.len();
+                    cache.clear();
+                    len
+                }
+                None => 0,
+            }
+        }
+
+        /// return the stats of the namespace, or None if nothing has been inserted into it
+        pub fn stats(&self, namespace: &N) -> Option<Stats> {
+            self.namespaces.get(namespace).map(|cache| cache.stats())
+        }
+
+        /// return the cache holding the namespace's entries
+        pub fn namespace(&self, namespace: &N) -> Option<&Simcache<K, V, E>> {
+            self.namespaces.get(namespace)
+        }
+
+        /// return an iterator over the namespaces that have a cache, in no particular order
+        pub fn namespaces(&self) -> impl Iterator<Item = &N> + '_ {
+            self.namespaces.keys()
+        }
+
+        /// return the number of entries across every namespace
+        pub fn len(&self) -> usize {
+            self.namespaces.values().map(|cache| cache.len()).sum()
+        }
+
+        /// return true if no namespace holds any entries
+        pub fn is_empty(&self) -> bool {
+            self.len() == 0
+        }
+
+        fn new_namespace(&self, namespace: &N) -> Simcache<K, V, E> {
+            let quota = self.quota(namespace);
+            let builder = match &self.configure {
+                Some(configure) => configure(Simcache::builder()),
+                None => Simcache::builder(),
+            };
+            builder.capacity(quota.capacity).max_weight(quota.max_weight).build()
+        }
+    }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eviction::LRU;
+
+    #[test]
+    fn namespaced_test() {
+        let mut cache: NamespacedCache<&str, u64, u64, LRU<u64>> = NamespacedCache::new(Quota::entries(2))
+            .configure(|builder| builder.weigher(|_, value| *value));
+        cache.set_quota("big", Quota::entries(4));
+        cache.set_quota("heavy", Quota::entries(10).max_weight(10));
+
+        cache.insert("quiet", 1, 1, None);
+        cache.insert("quiet", 2, 2, None);
+        // a noisy namespace only evicts its own entries
+        for key in 0..10 {
+            cache.insert("noisy", key, key, None);
+        }
+        assert_eq!(cache.get(&"quiet", &1), Some(&1));
+        assert_eq!(cache.namespace(&"noisy").unwrap().len(), 2);
+        assert_eq!(cache.stats(&"noisy").unwrap().evictions, 8);
+        assert_eq!(cache.stats(&"quiet").unwrap().hits, 1);
+        assert_eq!(cache.get(&"other", &1), None);
+
+        for key in 0..5 {
+            cache.insert("big", key, key, None);
+        }
+        assert_eq!(cache.namespace(&"big").unwrap().len(), 4);
+        assert_eq!(cache.set_quota("big", Quota::entries(3)), 1);
+        assert_eq!(cache.len(), 7);
+
+        // the weigher from configure applies to the weight quota
+        cache.insert("heavy", 1, 6, None);
+        cache.insert("heavy", 2, 6, None);
+        assert_eq!(cache.namespace(&"heavy").unwrap().len(), 1);
+        cache.clear_namespace(&"heavy");
+
+        assert_eq!(cache.clear_namespace(&"noisy"), 2);
+        assert_eq!(cache.stats(&"noisy"), None);
+        assert_eq!(cache.remove(&"quiet", &2), Some(2));
+        assert_eq!(cache.len(), 4);
+    }
+}
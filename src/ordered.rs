@@ -0,0 +1,45 @@
+//! Ordered index of the keys in a cache, for removing ranges of keys
+
+use std::collections::BTreeSet;
+
+// the cache doesn't require Ord of its keys, so the functions that need it are chosen
+// when the index is created, where the builder knows the keys are Ord
+#[derive(Clone)]
+pub(crate) struct OrderedKeys<K> {
+    keys: BTreeSet<K>,
+    insert: fn(&mut BTreeSet<K>, &K),
+    remove: fn(&mut BTreeSet<K>, &K),
+}
+
+impl<K> OrderedKeys<K> {
+    pub(crate) fn new() -> Self
+    where
+        K: Ord + Clone,
+    {
+        OrderedKeys {
+            keys: BTreeSet::new(),
+            insert: |keys, key| {
+                keys.insert(key.clone());
+            },
+            remove: |keys, key| {
+                keys.remove(key);
+            },
+        }
+    }
+
+    pub(crate) fn insert(&mut self, key: &K) {
+        (self.insert)(&mut self.keys, key);
+    }
+
+    pub(crate) fn remove(&mut self, key: &K) {
+        (self.remove)(&mut self.keys, key);
+    }
+
+    pub(crate) fn keys(&self) -> &BTreeSet<K> {
+        &self.keys
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.keys.clear();
+    }
+}
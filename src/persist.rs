@@ -0,0 +1,157 @@
+//! Encoding of keys and values for cache snapshots
+//!
+//! Snapshots are written with a small length-prefixed little-endian format
+//! rather than a general serialization framework, so that the crate stays
+//! dependency free. Keys and values are encoded through the `Persist` trait,
+//! which is implemented for the standard integer types, `bool`, `char`,
+//! `String`, `Vec`, `Option`, and pairs.
+
+use std::io::{self, Read, Write};
+
+/// a type that can be written to and read back from a snapshot
+pub trait Persist: Sized {
+    fn write_to<W: Write>(&self, w: &mut W) -> io::Result<()>;
+    fn read_from<R: Read>(r: &mut R) -> io::Result<Self>;
+}
+
+/// return an InvalidData error with the given message
+pub(crate) fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+macro_rules! persist_number {
+    ($($t:ty),*) => {
+        $(
+            impl Persist for $t {
+                fn write_to<W: Write>(&self, w: &mut W) -> io::Result<()> {
+                    w.write_all(&self.to_le_bytes())
+                }
+                fn read_from<R: Read>(r: &mut R) -> io::Result<Self> {
+                    let mut bytes = [0; std::mem::size_of::<$t>()];
+                    r.read_exact(&mut bytes)?;
+                    Ok(<$t>::from_le_bytes(bytes))
+                }
+            }
+        )*
+    };
+}
+
+persist_number!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128, f32, f64);
+
+// usize and isize are always written as 64 bits, so snapshots move between platforms
+impl Persist for usize {
+    fn write_to<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        (*self as u64).write_to(w)
+    }
+    fn read_from<R: Read>(r: &mut R) -> io::Result<Self> {
+        usize::try_from(u64::read_from(r)?).map_err(|_| invalid("length does not fit in usize"))
+    }
+}
+
+impl Persist for isize {
+    fn write_to<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        (*self as i64).write_to(w)
+    }
+    fn read_from<R: Read>(r: &mut R) -> io::Result<Self> {
+        isize::try_from(i64::read_from(r)?).map_err(|_| invalid("integer does not fit in isize"))
+    }
+}
+
+impl Persist for bool {
+    fn write_to<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        (*self as u8).write_to(w)
+    }
+    fn read_from<R: Read>(r: &mut R) -> io::Result<Self> {
+        match u8::read_from(r)? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(invalid("bool should be 0 or 1")),
+        }
+    }
+}
+
+impl Persist for char {
+    fn write_to<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        (*self as u32).write_to(w)
+    }
+    fn read_from<R: Read>(r: &mut R) -> io::Result<Self> {
+        char::from_u32(u32::read_from(r)?).ok_or_else(|| invalid("char should be a unicode scalar value"))
+    }
+}
+
+impl Persist for String {
+    fn write_to<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        self.len().write_to(w)?;
+        w.write_all(self.as_bytes())
+    }
+    fn read_from<R: Read>(r: &mut R) -> io::Result<Self> {
+        let len = usize::read_from(r)?;
+        let mut bytes = Vec::new();
+        r.take(len as u64).read_to_end(&mut bytes)?;
+        if bytes.len() != len {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        String::from_utf8(bytes).map_err(|_| invalid("string should be utf-8"))
+    }
+}
+
+impl<T: Persist> Persist for Vec<T> {
+    fn write_to<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        self.len().write_to(w)?;
+        self.iter().try_for_each(|item| item.write_to(w))
+    }
+    fn read_from<R: Read>(r: &mut R) -> io::Result<Self> {
+        let len = usize::read_from(r)?;
+        // the length isn't trusted for the allocation, a corrupt one fails on the first missing item
+        let mut items = Vec::with_capacity(len.min(1024));
+        for _ in 0..len {
+            items.push(T::read_from(r)?);
+        }
+        Ok(items)
+    }
+}
+
+impl<T: Persist> Persist for Option<T> {
+    fn write_to<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        match self {
+            Some(value) => {
+                true.write_to(w)?;
+                value.write_to(w)
+            }
+            None => false.write_to(w),
+        }
+    }
+    fn read_from<R: Read>(r: &mut R) -> io::Result<Self> {
+        if bool::read_from(r)? {
+            T::read_from(r).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+impl<A: Persist, B: Persist> Persist for (A, B) {
+    fn write_to<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        self.0.write_to(w)?;
+        self.1.write_to(w)
+    }
+    fn read_from<R: Read>(r: &mut R) -> io::Result<Self> {
+        Ok((A::read_from(r)?, B::read_from(r)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn persist_test() {
+        let value = (vec![Some("a".to_string()), None], (-7i64, 'λ'));
+        let mut bytes = Vec::new();
+        value.write_to(&mut bytes).expect("writing to a Vec should not fail");
+
+        let read: (Vec<Option<String>>, (i64, char)) = Persist::read_from(&mut bytes.as_slice()).expect("snapshot should decode");
+        assert_eq!(read, value);
+        assert!(String::read_from(&mut &bytes[..3]).is_err());
+    }
+}
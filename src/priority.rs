@@ -0,0 +1,104 @@
+//! Priority levels that decide which entries are evicted first
+
+use crate::EvictionPolicy;
+
+/// how reluctant the cache is to evict an entry
+/// every entry of a lower priority is evicted before any entry of a higher one,
+/// and within a level the cache's eviction policy decides
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+impl Priority {
+    pub(crate) const ALL: [Priority; 3] = [Priority::Low, Priority::Normal, Priority::High];
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// one eviction policy per priority level, along with how many entries each one tracks
+/// the configured policy serves Normal, and the other levels get a new policy
+/// like it, from new_like, the first time an entry is given that priority
+#[derive(Clone)]
+pub(crate) struct PolicyLevels<E> {
+    policies: [Option<E>; 3],
+    tracked: [usize; 3],
+    capacity: usize,
+}
+
+impl<E> PolicyLevels<E> {
+    pub(crate) fn new<K>(mut normal: E, capacity: usize) -> Self
+    where
+        E: EvictionPolicy<K>,
+    {
+        normal.set_capacity(capacity);
+        PolicyLevels { policies: [None, Some(normal), None], tracked: [0; 3], capacity }
+    }
+
+    /// return the policy for the given level, creating it if it doesn't exist yet
+    pub(crate) fn get_mut<K>(&mut self, priority: Priority) -> &mut E
+    where
+        E: EvictionPolicy<K>,
+    {
+        if self.policies[priority.index()].is_none() {
+            let normal = self.policies[Priority::Normal.index()].as_ref().expect("the normal level should always have a policy");
+            let mut policy = normal.new_like();
+            policy.set_capacity(self.capacity);
+            self.policies[priority.index()] = Some(policy);
+        }
+        self.policies[priority.index()].as_mut().expect("the level's policy was just created")
+    }
+
+    /// return the policy for the given level, if it exists
+    pub(crate) fn get(&self, priority: Priority) -> Option<&E> {
+        self.policies[priority.index()].as_ref()
+    }
+
+    /// return the policies that exist, lowest priority first
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &E> + '_ {
+        self.policies.iter().flatten()
+    }
+
+    /// record that an entry of the given priority is now tracked by its level's policy
+    pub(crate) fn track(&mut self, priority: Priority) {
+        self.tracked[priority.index()] += 1;
+    }
+
+    /// record that an entry of the given priority is no longer tracked
+    pub(crate) fn untrack(&mut self, priority: Priority) {
+        self.tracked[priority.index()] -= 1;
+    }
+
+    /// return the lowest priority that has a tracked entry to evict
+    pub(crate) fn lowest(&self) -> Option<Priority> {
+        Priority::ALL.into_iter().find(|priority| self.tracked[priority.index()] > 0)
+    }
+
+    /// tell every level's policy the cache's new entry limit
+    pub(crate) fn set_capacity<K>(&mut self, capacity: usize)
+    where
+        E: EvictionPolicy<K>,
+    {
+        self.capacity = capacity;
+        for policy in self.policies.iter_mut().flatten() {
+            policy.set_capacity(capacity);
+        }
+    }
+
+    /// forget every tracked key, in every level
+    pub(crate) fn clear<K>(&mut self)
+    where
+        E: EvictionPolicy<K>,
+    {
+        for policy in self.policies.iter_mut().flatten() {
+            policy.clear();
+            policy.set_capacity(self.capacity);
+        }
+        self.tracked = [0; 3];
+    }
+}
@@ -0,0 +1,58 @@
+//! Small seedable pseudo random number generator
+//!
+//! Randomness here only needs to be cheap and reproducible, not secure.
+
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+
+/// SplitMix64 generator
+#[derive(Debug, Clone)]
+pub(crate) struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    /// return a generator that always produces the same sequence for the same seed
+    pub(crate) fn new(seed: u64) -> Self {
+        Rng { state: seed }
+    }
+
+    /// return a generator seeded from the process's random hasher keys
+    pub(crate) fn from_entropy() -> Self {
+        Rng::new(RandomState::new().hash_one(0u64))
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// return an integer uniformly distributed in [0, n), n must not be 0
+    pub(crate) fn below(&mut self, n: usize) -> usize {
+        // multiply-shift keeps the bias negligible without a division
+        ((self.next_u64() as u128 * n as u128) >> 64) as usize
+    }
+
+    /// return a float uniformly distributed in [0, 1)
+    pub(crate) fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rng_test() {
+        let mut a = Rng::new(7);
+        let mut b = Rng::new(7);
+
+        assert_eq!(a.next_u64(), b.next_u64());
+        assert!((0..1000).all(|_| (0.0..1.0).contains(&b.next_f64())));
+        assert!((0..1000).all(|_| b.below(3) < 3));
+    }
+}
@@ -0,0 +1,153 @@
+//! Shadow caches that estimate the hit ratio at other capacities
+
+use std::borrow::Borrow;
+use std::collections::HashSet;
+use std::hash::Hash;
+use crate::stats::{ShadowCounts, ShadowStats};
+use crate::EvictionPolicy;
+
+// the keys a cache with a different entry limit would hold, and the policy choosing among them
+// values, weights, and expiry are ignored, so a shadow only answers whether a lookup would have hit
+#[derive(Clone)]
+pub(crate) struct Shadow<K, E> {
+    keys: HashSet<K>,
+    policy: E,
+    counts: ShadowCounts,
+}
+
+impl<K, E> Shadow<K, E>
+where
+    K: Eq + Hash + Clone,
+    E: EvictionPolicy<K>,
+    {
+        fn new(mut policy: E, capacity: usize) -> Self {
+            policy.set_capacity(capacity);
+            Shadow { keys: HashSet::new(), policy, counts: ShadowCounts { capacity, ..ShadowCounts::default() } }
+        }
+
+        fn lookup<Q>(&mut self, key: &Q, cached: Option<&K>)
+        where
+            K: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+        {
+            if let Some(stored_key) = self.keys.get(key) {
+                self.counts.hits += 1;
+                self.policy.on_access(stored_key);
+                return;
+            }
+            self.counts.misses += 1;
+            // a miss in the real cache is followed by an insert, but a hit there isn't,
+            // so the shadow loads the key itself
+            if let Some(cached) = cached {
+                self.insert(cached);
+            }
+        }
+
+        fn insert(&mut self, key: &K) {
+            if self.keys.contains(key) {
+                self.policy.on_insert(key, 1);
+                return;
+            }
+            if self.counts.capacity == 0 {
+                return;
+            }
+            self.shrink_to(self.counts.capacity - 1);
+            self.keys.insert(key.clone());
+            self.policy.on_insert(key, 1);
+        }
+
+        fn remove<Q>(&mut self, key: &Q)
+        where
+            K: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+        {
+            if let Some(stored_key) = self.keys.take(key) {
+                self.policy.on_remove(&stored_key);
+            }
+        }
+
+        fn shrink_to(&mut self, len: usize) {
+            while self.keys.len() > len {
+                let evicted = self.policy.evict_next();
+                self.keys.remove(&evicted);
+            }
+        }
+
+        fn set_capacity(&mut self, capacity: usize) {
+            self.counts.capacity = capacity;
+            self.policy.set_capacity(capacity);
+            self.shrink_to(capacity);
+        }
+
+        fn clear(&mut self) {
+            self.keys.clear();
+            self.policy.clear();
+            self.policy.set_capacity(self.counts.capacity);
+        }
+    }
+
+/// a shadow cache at half and one at double the cache's entry limit
+#[derive(Clone)]
+pub(crate) struct Shadows<K, E> {
+    half: Shadow<K, E>,
+    double: Shadow<K, E>,
+}
+
+impl<K, E> Shadows<K, E>
+where
+    K: Eq + Hash + Clone,
+    E: EvictionPolicy<K>,
+    {
+        /// return empty shadows for a cache with the given limit, using policies like the given one
+        pub(crate) fn new(policy: &E, capacity: usize) -> Self {
+            Shadows {
+                half: Shadow::new(policy.new_like(), capacity / 2),
+                double: Shadow::new(policy.new_like(), capacity.saturating_mul(2)),
+            }
+        }
+
+        /// count a lookup, with the stored key if the real cache has a live entry for it
+        pub(crate) fn lookup<Q>(&mut self, key: &Q, cached: Option<&K>)
+        where
+            K: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+        {
+            self.half.lookup(key, cached);
+            self.double.lookup(key, cached);
+        }
+
+        pub(crate) fn insert(&mut self, key: &K) {
+            self.half.insert(key);
+            self.double.insert(key);
+        }
+
+        pub(crate) fn remove<Q>(&mut self, key: &Q)
+        where
+            K: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+        {
+            self.half.remove(key);
+            self.double.remove(key);
+        }
+
+        /// follow a change to the cache's entry limit
+        pub(crate) fn set_capacity(&mut self, capacity: usize) {
+            self.half.set_capacity(capacity / 2);
+            self.double.set_capacity(capacity.saturating_mul(2));
+        }
+
+        pub(crate) fn clear(&mut self) {
+            self.half.clear();
+            self.double.clear();
+        }
+
+        pub(crate) fn stats(&self) -> ShadowStats {
+            ShadowStats { half: self.half.counts, double: self.double.counts }
+        }
+
+        pub(crate) fn reset_stats(&mut self) {
+            for shadow in [&mut self.half, &mut self.double] {
+                shadow.counts = ShadowCounts { capacity: shadow.counts.capacity, ..ShadowCounts::default() };
+            }
+        }
+    }
@@ -0,0 +1,469 @@
+//! Cache partitioned across independently locked shards
+//!
+//! Each key belongs to one of N shards, chosen by its hash, and every shard is
+//! a `Simcache` behind its own mutex. Threads working on keys in different
+//! shards don't wait for each other, and whole-cache operations such as
+//! `retain` and `purge_expired` lock one shard at a time instead of the whole
+//! cache. The entry limit applies to each shard separately, so a shard can
+//! fill and evict while others still have room.
+//!
+//! `get_or_insert_with` runs the loader without holding any lock, and threads
+//! missing on a key that is already being loaded block until that load
+//! finishes instead of loading it again. A loader may look up or load other
+//! keys, but loading its own key again panics rather than waiting for itself,
+//! and two loaders that each load the other's key wait for each other forever.
+
+use std::borrow::Borrow;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hash};
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::Duration;
+use crate::coalesce::{InFlight, Role};
+use crate::{EvictionPolicy, InsertOutcome, Simcache, Stats};
+
+/// a cache split into shards that can be used from several threads at once
+pub struct ShardedSimcache<K, V, E>
+where
+    E: EvictionPolicy<K>
+{
+    shards: Vec<Mutex<Simcache<K, V, E>>>,
+    // a copy of each shard's stats, kept up to date by every use of the shard so they can be read without its lock
+    shard_stats: Vec<SharedStats>,
+    // picks the shard of a key, separately from the hasher each shard uses internally
+    hasher: RandomState,
+    // loads started by get_or_insert_with that haven't finished yet
+    in_flight: InFlight<K, V>,
+}
+
+/// a shard's stats as atomics, each set on its own cache line so that threads on different shards
+/// never write to the same one
+#[derive(Default)]
+#[repr(align(64))]
+struct SharedStats {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: AtomicU64,
+    expired: AtomicU64,
+    inserts: AtomicU64,
+}
+
+impl SharedStats {
+    fn store(&self, stats: Stats) {
+        self.hits.store(stats.hits, Ordering::Relaxed);
+        self.misses.store(stats.misses, Ordering::Relaxed);
+        self.evictions.store(stats.evictions, Ordering::Relaxed);
+        self.expired.store(stats.expired, Ordering::Relaxed);
+        self.inserts.store(stats.inserts, Ordering::Relaxed);
+    }
+
+    fn load(&self) -> Stats {
+        Stats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            evictions: self.evictions.load(Ordering::Relaxed),
+            expired: self.expired.load(Ordering::Relaxed),
+            inserts: self.inserts.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// a locked shard, which copies its stats out for lock-free reads when it is unlocked
+struct ShardGuard<'a, K, V, E>
+where
+    K: Eq + Hash + Clone,
+    E: EvictionPolicy<K>,
+{
+    cache: MutexGuard<'a, Simcache<K, V, E>>,
+    stats: &'a SharedStats,
+}
+
+impl<K, V, E> Deref for ShardGuard<'_, K, V, E>
+where
+    K: Eq + Hash + Clone,
+    E: EvictionPolicy<K>,
+{
+    type Target = Simcache<K, V, E>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.cache
+    }
+}
+
+impl<K, V, E> DerefMut for ShardGuard<'_, K, V, E>
+where
+    K: Eq + Hash + Clone,
+    E: EvictionPolicy<K>,
+{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.cache
+    }
+}
+
+impl<K, V, E> Drop for ShardGuard<'_, K, V, E>
+where
+    K: Eq + Hash + Clone,
+    E: EvictionPolicy<K>,
+{
+    fn drop(&mut self) {
+        self.stats.store(self.cache.stats());
+    }
+}
+
+impl<K, V, E> ShardedSimcache<K, V, E>
+where
+    K: Eq + Hash + Clone,
+    E: EvictionPolicy<K>,
+    {
+        /// return a new, empty cache of the given number of shards, at least 1,
+        /// splitting max_capacity between them and rounding up
+        pub fn new(shards: usize, max_capacity: usize) -> Self {
+            let shards = shards.max(1);
+            let per_shard = max_capacity.div_ceil(shards);
+            Self::from_shards((0..shards).map(|_| Simcache::new(per_shard)).collect())
+        }
+
+        /// return a cache over the given shards, e.g. ones made with the same builder settings
+        /// keys are placed by hash, so the shards should start out empty
+        pub fn from_shards(shards: Vec<Simcache<K, V, E>>) -> Self {
+            assert!(!shards.is_empty(), "a sharded cache should have at least one shard");
+            let shard_stats = shards
+                .iter()
+                .map(|shard| {
+                    let stats = SharedStats::default();
+                    stats.store(shard.stats());
+                    stats
+                })
+                .collect();
+            ShardedSimcache {
+                shards: shards.into_iter().map(Mutex::new).collect(),
+                shard_stats,
+                hasher: RandomState::new(),
+                in_flight: InFlight::new(),
+            }
+        }
+
+        fn shard<Q>(&self, key: &Q) -> ShardGuard<'_, K, V, E>
+        where
+            K: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+        {
+            let index = (self.hasher.hash_one(key) % self.shards.len() as u64) as usize;
+            self.lock_shard(index)
+        }
+
+        fn lock_shard(&self, index: usize) -> ShardGuard<'_, K, V, E> {
+            ShardGuard { cache: lock(&self.shards[index]), stats: &self.shard_stats[index] }
+        }
+
+        // lock each shard in turn
+        fn each_shard(&self) -> impl Iterator<Item = ShardGuard<'_, K, V, E>> + '_ {
+            (0..self.shards.len()).map(|index| self.lock_shard(index))
+        }
+
+        /// insert a key value pair into the key's shard
+        /// option to include a ttl for the item, otherwise the shard's default ttl applies
+        pub fn insert(&self, key: K, value: V, ttl: Option<Duration>) -> InsertOutcome {
+            self.shard(&key).insert(key, value, ttl)
+        }
+
+        /// return a clone of the value of the given key if it is present and not expired
+        pub fn get<Q>(&self, key: &Q) -> Option<V>
+        where
+            K: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+            V: Clone,
+        {
+            self.shard(key).get(key).cloned()
+        }
+
+        /// return a clone of the cached value for the key, or run the loader and cache its result
+        /// callers missing on a key while another thread loads it block until that load finishes,
+        /// and if that loader panics one of them runs its own; no lock is held while a loader runs
+        /// panics if the loader, or anything it calls, loads the key it is loading
+        pub fn get_or_insert_with<F>(&self, key: K, ttl: Option<Duration>, f: F) -> V
+        where
+            V: Clone,
+            F: FnOnce() -> V,
+        {
+            let mut loader = Some(f);
+            loop {
+                if let Some(value) = self.get(&key) {
+                    return value;
+                }
+                match self.in_flight.join(&key, || self.peek(&key)) {
+                    Role::Cached(value) => return value,
+                    Role::Wait(load) => {
+                        assert!(load.leader() != thread::current().id(), "a loader should not load its own key, which would wait for itself forever");
+                        if let Some(value) = load.wait() {
+                            return value;
+                        }
+                    }
+                    Role::Lead(guard) => {
+                        let f = loader.take().expect("loader should only be run by the leading caller");
+                        let value = f();
+
+                        self.insert(key.clone(), value.clone(), ttl);
+                        guard.finish(value.clone());
+                        return value;
+                    }
+                }
+            }
+        }
+
+        /// call f with the key's shard locked, for anything the sharded api doesn't cover
+        pub fn with_shard<Q, F, R>(&self, key: &Q, f: F) -> R
+        where
+            K: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+            F: FnOnce(&mut Simcache<K, V, E>) -> R,
+        {
+            f(&mut self.shard(key))
+        }
+
+        /// remove the key from its shard, returning its value if it was present
+        pub fn remove<Q>(&self, key: &Q) -> Option<V>
+        where
+            K: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+        {
+            self.shard(key).remove(key)
+        }
+
+        /// return a clone of the value of the given key if it is present and not expired, without counting as a use
+        pub fn peek<Q>(&self, key: &Q) -> Option<V>
+        where
+            K: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+            V: Clone,
+        {
+            self.shard(key).peek(key).cloned()
+        }
+
+        /// return true if the key's shard holds an unexpired entry for it
+        pub fn contains_key<Q>(&self, key: &Q) -> bool
+        where
+            K: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+        {
+            self.shard(key).contains_key(key)
+        }
+
+        /// keep only the entries for which the predicate returns true, one shard at a time
+        pub fn retain<F>(&self, mut f: F)
+        where
+            F: FnMut(&K, &mut V) -> bool,
+        {
+            for mut shard in self.each_shard() {
+                shard.retain(&mut f);
+            }
+        }
+
+        /// remove every expired entry, one shard at a time, returning the number removed
+        pub fn purge_expired(&self) -> usize {
+            self.each_shard().map(|mut shard| shard.purge_expired()).sum()
+        }
+
+        /// remove every entry, one shard at a time
+        pub fn clear(&self) {
+            for mut shard in self.each_shard() {
+                shard.clear();
+            }
+        }
+
+        /// return the number of entries across every shard
+        pub fn len(&self) -> usize {
+            self.each_shard().map(|shard| shard.len()).sum()
+        }
+
+        /// return true if no shard holds any entries
+        pub fn is_empty(&self) -> bool {
+            self.each_shard().all(|shard| shard.is_empty())
+        }
+
+        /// return the number of shards
+        pub fn shard_count(&self) -> usize {
+            self.shards.len()
+        }
+
+        /// return the stats of each shard, in shard order, without waiting for any shard's lock
+        /// a shard's stats take in each use of it once that use has finished
+        pub fn shard_stats(&self) -> Vec<Stats> {
+            self.shard_stats.iter().map(SharedStats::load).collect()
+        }
+
+        /// return the stats of every shard added together, without waiting for any shard's lock
+        pub fn stats(&self) -> Stats {
+            self.shard_stats.iter().map(SharedStats::load).sum()
+        }
+
+        /// return a read-only handle to the cache, e.g. to hand to a plugin that may look but not change anything
+        pub fn reader(self: &Arc<Self>) -> CacheReader<K, V, E> {
+            CacheReader { cache: Arc::clone(self) }
+        }
+    }
+
+/// a cheap, cloneable handle that can peek into a sharded cache but not insert or remove
+/// peeks don't count as uses, so a reader never changes what the cache evicts
+pub struct CacheReader<K, V, E>
+where
+    E: EvictionPolicy<K>
+{
+    cache: Arc<ShardedSimcache<K, V, E>>,
+}
+
+impl<K, V, E> Clone for CacheReader<K, V, E>
+where
+    E: EvictionPolicy<K>
+{
+    fn clone(&self) -> Self {
+        CacheReader { cache: Arc::clone(&self.cache) }
+    }
+}
+
+impl<K, V, E> CacheReader<K, V, E>
+where
+    K: Eq + Hash + Clone,
+    E: EvictionPolicy<K>,
+    {
+        /// return a clone of the value of the given key if it is present and not expired
+        pub fn peek<Q>(&self, key: &Q) -> Option<V>
+        where
+            K: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+            V: Clone,
+        {
+            self.cache.peek(key)
+        }
+
+        /// return true if the cache holds an unexpired entry for the key
+        pub fn contains_key<Q>(&self, key: &Q) -> bool
+        where
+            K: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+        {
+            self.cache.contains_key(key)
+        }
+
+        /// return the number of entries across every shard
+        pub fn len(&self) -> usize {
+            self.cache.len()
+        }
+
+        /// return true if no shard holds any entries
+        pub fn is_empty(&self) -> bool {
+            self.cache.is_empty()
+        }
+
+        /// return the stats of every shard added together
+        pub fn stats(&self) -> Stats {
+            self.cache.stats()
+        }
+    }
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().expect("sharded cache mutexes should not be poisoned")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LRU;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn sharded_test() {
+        let cache: ShardedSimcache<u64, u64, LRU<u64>> = ShardedSimcache::new(4, 400);
+        assert_eq!(cache.shard_count(), 4);
+
+        thread::scope(|scope| {
+            for thread in 0..4 {
+                let cache = &cache;
+                scope.spawn(move || {
+                    for key in thread * 50..(thread + 1) * 50 {
+                        cache.insert(key, key * 10, None);
+                    }
+                });
+            }
+        });
+        assert_eq!(cache.len(), 200);
+        assert_eq!(cache.get(&7), Some(70));
+        assert_eq!(cache.get(&1000), None);
+        assert_eq!(cache.with_shard(&7, |shard| shard.peek(&7).copied()), Some(70));
+
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses, stats.inserts), (1, 1, 200));
+        assert_eq!(cache.shard_stats().iter().map(|stats| stats.inserts).sum::<u64>(), 200);
+        // stats are read without the shard locks, so they can be read while a shard is held
+        assert_eq!(cache.with_shard(&7, |_| cache.stats()), stats);
+        let shard_inserts = cache.with_shard(&7, |shard| {
+            let inserts = shard.stats().inserts;
+            shard.reset_stats();
+            inserts
+        });
+        assert_eq!(cache.stats().inserts, 200 - shard_inserts);
+
+        cache.retain(|key, _| key % 2 == 0);
+        assert_eq!(cache.len(), 100);
+        assert_eq!(cache.remove(&8), Some(80));
+        assert!(!cache.contains_key(&8));
+        cache.clear();
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn reader_test() {
+        let cache: Arc<ShardedSimcache<u64, u64, LRU<u64>>> = Arc::new(ShardedSimcache::new(2, 10));
+        cache.insert(1, 10, None);
+        let reader = cache.reader();
+        let plugin = reader.clone();
+        thread::spawn(move || {
+            assert_eq!(plugin.peek(&1), Some(10));
+            assert!(!plugin.contains_key(&2));
+        })
+        .join()
+        .unwrap();
+
+        // peeks aren't lookups, and the reader sees later writes
+        assert_eq!(reader.stats().hits + reader.stats().misses, 0);
+        cache.insert(2, 20, None);
+        assert_eq!((reader.len(), reader.peek(&2)), (2, Some(20)));
+        drop(cache);
+        assert!(!reader.is_empty());
+    }
+
+    #[test]
+    fn get_or_insert_with_test() {
+        let cache: ShardedSimcache<u64, u64, LRU<u64>> = ShardedSimcache::new(4, 100);
+        let loads = AtomicUsize::new(0);
+        thread::scope(|scope| {
+            for _ in 0..8 {
+                scope.spawn(|| {
+                    let value = cache.get_or_insert_with(1, None, || {
+                        loads.fetch_add(1, Ordering::SeqCst);
+                        thread::sleep(Duration::from_millis(20));
+                        10
+                    });
+                    assert_eq!(value, 10);
+                });
+            }
+        });
+        assert_eq!(loads.load(Ordering::SeqCst), 1);
+
+        // a loader that panics hands the load to the next caller
+        let panicked = thread::scope(|scope| scope.spawn(|| cache.get_or_insert_with(2, None, || panic!("load failed"))).join());
+        assert!(panicked.is_err());
+        assert_eq!(cache.get_or_insert_with(2, None, || 20), 20);
+        // loaders can load other keys
+        assert_eq!(cache.get_or_insert_with(3, None, || cache.get_or_insert_with(4, None, || 40) + 1), 41);
+    }
+
+    #[test]
+    #[should_panic(expected = "a loader should not load its own key")]
+    fn reentrant_load_test() {
+        let cache: ShardedSimcache<u64, u64, LRU<u64>> = ShardedSimcache::new(4, 100);
+        cache.get_or_insert_with(1, None, || cache.get_or_insert_with(1, None, || 10));
+    }
+}
@@ -0,0 +1,181 @@
+//! Trace-driven simulation for comparing eviction policies
+//!
+//! A trace is replayed against a fresh cache for each policy: every access
+//! is a lookup, and a miss inserts the key as if its value had just been
+//! fetched. The report for each policy counts hits, misses, evictions, and
+//! the bytes that had to be fetched on a miss.
+//!
+//! ```
+//! use simcache::eviction::PolicyKind;
+//! use simcache::simulation::Simulation;
+//!
+//! let trace = ["a", "b", "a", "c", "a", "b"];
+//! let reports = Simulation::new(2).compare(trace, &[PolicyKind::Lru, PolicyKind::Fifo]);
+//! assert!(reports[0].1.hit_ratio() > reports[1].1.hit_ratio());
+//! ```
+//!
+//! The `workload` module generates synthetic traces to replay, and the
+//! `trace` module reads and writes traces in common file formats. The `mrc`
+//! module estimates miss-rate curves across many capacities at once.
+
+pub mod mrc;
+pub mod trace;
+pub mod workload;
+
+use std::hash::Hash;
+use crate::eviction::{AnyPolicy, PolicyKind};
+use crate::{EvictionPolicy, Simcache};
+
+/// one access in a trace, with the size of the value that would be fetched on a miss
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Access<K> {
+    pub key: K,
+    pub size: u64,
+}
+
+impl<K> Access<K> {
+    /// return an access of size 1
+    pub fn new(key: K) -> Self {
+        Access { key, size: 1 }
+    }
+}
+
+/// what happened when a trace was replayed against one policy
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Report {
+    pub accesses: u64,
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+    /// the combined size of every access that hit
+    pub bytes_hit: u64,
+    /// the combined size of every access that missed, which had to be fetched
+    pub bytes_moved: u64,
+}
+
+impl Report {
+    /// return the fraction of accesses that were hits, or 0 for an empty trace
+    pub fn hit_ratio(&self) -> f64 {
+        if self.accesses == 0 {
+            0.0
+        } else {
+            self.hits as f64 / self.accesses as f64
+        }
+    }
+
+    /// return the fraction of accessed bytes that were hits, or 0 for an empty trace
+    pub fn byte_hit_ratio(&self) -> f64 {
+        let bytes = self.bytes_hit + self.bytes_moved;
+        if bytes == 0 {
+            0.0
+        } else {
+            self.bytes_hit as f64 / bytes as f64
+        }
+    }
+}
+
+/// the cache settings a trace is replayed with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Simulation {
+    capacity: usize,
+    max_bytes: u64,
+}
+
+impl Simulation {
+    /// simulate a cache holding up to the given number of entries
+    pub fn new(capacity: usize) -> Self {
+        Simulation { capacity, max_bytes: u64::MAX }
+    }
+
+    /// also limit the simulated cache to the given combined access size
+    pub fn max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    /// replay the trace against a cache using the given policy
+    pub fn run<K, E>(&self, trace: &[Access<K>], policy: E) -> Report
+    where
+        K: Eq + Hash + Clone,
+        E: EvictionPolicy<K>,
+    {
+        let mut cache: Simcache<K, u64, E> = Simcache::builder()
+            .max_capacity(self.capacity)
+            .max_weight(self.max_bytes)
+            .weigher(|_, size| *size)
+            .policy(policy)
+            .build();
+        let mut report = Report::default();
+        for access in trace {
+            report.accesses += 1;
+            if cache.get(&access.key).is_some() {
+                report.hits += 1;
+                report.bytes_hit += access.size;
+            } else {
+                report.misses += 1;
+                report.bytes_moved += access.size;
+                cache.insert(access.key.clone(), access.size, None);
+            }
+        }
+        report.evictions = cache.stats().evictions;
+        report
+    }
+
+    /// replay the trace against each of the built-in policies, returning a report for each in the same order
+    pub fn compare_trace<K>(&self, trace: &[Access<K>], kinds: &[PolicyKind]) -> Vec<(PolicyKind, Report)>
+    where
+        K: Eq + Hash + Clone,
+    {
+        kinds
+            .iter()
+            .map(|&kind| (kind, self.run(trace, AnyPolicy::from_kind(kind))))
+            .collect()
+    }
+
+    /// like compare_trace, for a trace of keys that all have size 1
+    pub fn compare<K, I>(&self, keys: I, kinds: &[PolicyKind]) -> Vec<(PolicyKind, Report)>
+    where
+        K: Eq + Hash + Clone,
+        I: IntoIterator<Item = K>,
+    {
+        let trace: Vec<Access<K>> = keys.into_iter().map(Access::new).collect();
+        self.compare_trace(&trace, kinds)
+    }
+
+    /// like compare_trace, for a trace of key and size pairs
+    pub fn compare_sized<K, I>(&self, accesses: I, kinds: &[PolicyKind]) -> Vec<(PolicyKind, Report)>
+    where
+        K: Eq + Hash + Clone,
+        I: IntoIterator<Item = (K, u64)>,
+    {
+        let trace: Vec<Access<K>> = accesses.into_iter().map(|(key, size)| Access { key, size }).collect();
+        self.compare_trace(&trace, kinds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eviction::{FIFO, LRU};
+
+    #[test]
+    fn simulation_test() {
+        let trace: Vec<Access<&str>> = ["a", "b", "a", "c", "a", "b"].into_iter().map(Access::new).collect();
+
+        // lru keeps a, which is used again, while fifo evicts it for c
+        let lru = Simulation::new(2).run(&trace, LRU::new());
+        assert_eq!(lru, Report { accesses: 6, hits: 2, misses: 4, evictions: 2, bytes_hit: 2, bytes_moved: 4 });
+        let fifo = Simulation::new(2).run(&trace, FIFO::new());
+        assert_eq!(fifo.hits, 1);
+
+        let reports = Simulation::new(2).compare_trace(&trace, &[PolicyKind::Lru, PolicyKind::Fifo]);
+        assert_eq!(reports, vec![(PolicyKind::Lru, lru), (PolicyKind::Fifo, fifo)]);
+
+        // with sizes, a large value doesn't fit alongside the others
+        let sized = [("a", 5), ("b", 3), ("a", 5), ("c", 4), ("a", 5)];
+        let report = Simulation::new(10).max_bytes(9).compare_sized(sized, &[PolicyKind::Lru])[0].1;
+        assert_eq!(report.hits, 2);
+        assert_eq!(report.bytes_moved, 12);
+        assert_eq!(report.byte_hit_ratio(), 10.0 / 22.0);
+    }
+}
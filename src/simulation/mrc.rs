@@ -0,0 +1,167 @@
+//! Approximate miss-rate curves over a range of cache capacities
+//!
+//! Both methods sample the trace spatially, as in SHARDS: a key is kept when
+//! its hash falls below the sample rate, so every access to a sampled key is
+//! kept and the accesses to the rest are dropped. A curve for a trace of a
+//! billion accesses then costs about as much as one for the sampled share of it.
+//!
+//! `shards` computes LRU stack distances over the sample and scales them up,
+//! giving the LRU miss ratio at every capacity from a single pass. The miss
+//! ratios are taken over the number of accesses expected in the sample rather
+//! than the number that landed in it, which corrects for hot keys that happen
+//! to be sampled or not (SHARDS-adj). `mini_simulation` instead replays the
+//! sample against caches shrunk by the sample rate, which works for any policy
+//! at the cost of one replay per capacity.
+//!
+//! ```
+//! use simcache::simulation::mrc;
+//! use simcache::simulation::workload::Zipf;
+//!
+//! let trace: Vec<u64> = Zipf::with_seed(10_000, 1.0, 1).take(100_000).collect();
+//! let curve = mrc::shards(&trace, 0.1, &[100, 1_000, 5_000]);
+//! assert!(curve.windows(2).all(|pair| pair[0].1 >= pair[1].1));
+//! ```
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use crate::eviction::{AnyPolicy, PolicyKind};
+use super::{Access, Simulation};
+
+// hashes are compared against the sample rate scaled to this modulus
+const MODULUS: u64 = 1 << 24;
+
+// the hash of a sampled key, or None if the key is left out of the sample
+// DefaultHasher::new always uses the same keys, so the sample is the same from run to run
+fn sample<K: Hash + ?Sized>(key: &K, threshold: u64) -> Option<u64> {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    let hash = hasher.finish();
+    (hash % MODULUS < threshold).then_some(hash)
+}
+
+fn threshold(sample_rate: f64) -> u64 {
+    assert!(sample_rate > 0.0 && sample_rate <= 1.0, "a sample rate should be in (0, 1]");
+    ((sample_rate * MODULUS as f64).round() as u64).max(1)
+}
+
+// counts of recorded positions, for the number of distinct keys touched since a key was last accessed
+struct Fenwick(Vec<i64>);
+
+impl Fenwick {
+    fn add(&mut self, mut i: usize, delta: i64) {
+        i += 1;
+        while i < self.0.len() {
+            self.0[i] += delta;
+            i += i & i.wrapping_neg();
+        }
+    }
+
+    // the sum of positions 0 to i - 1
+    fn prefix(&self, mut i: usize) -> i64 {
+        let mut sum = 0;
+        while i > 0 {
+            sum += self.0[i];
+            i -= i & i.wrapping_neg();
+        }
+        sum
+    }
+}
+
+/// return the approximate LRU miss ratio of the trace at each capacity, sampling keys at sample_rate
+/// a sample rate of 1 gives exact miss ratios; 0.01 is usually within a percent or two on large traces
+pub fn shards<K, I>(keys: I, sample_rate: f64, capacities: &[usize]) -> Vec<(usize, f64)>
+where
+    K: Hash,
+    I: IntoIterator<Item = K>,
+{
+    let threshold = threshold(sample_rate);
+    let rate = threshold as f64 / MODULUS as f64;
+    let mut total = 0u64;
+    let sampled: Vec<u64> = keys
+        .into_iter()
+        .filter_map(|key| {
+            total += 1;
+            sample(&key, threshold)
+        })
+        .collect();
+
+    // the stack distance of each reaccess, the number of distinct keys accessed since the last access to it
+    let mut last_access: HashMap<u64, usize> = HashMap::new();
+    let mut positions = Fenwick(vec![0; sampled.len() + 1]);
+    let mut distances = Vec::new();
+    for (t, hash) in sampled.iter().enumerate() {
+        if let Some(last) = last_access.insert(*hash, t) {
+            distances.push((positions.prefix(t) - positions.prefix(last + 1)) as f64 / rate);
+            positions.add(last, -1);
+        }
+        positions.add(t, 1);
+    }
+    distances.sort_by(f64::total_cmp);
+
+    let expected = total as f64 * rate;
+    capacities
+        .iter()
+        .map(|&capacity| {
+            let hits = distances.partition_point(|&d| d < capacity as f64);
+            let misses = sampled.len() - hits;
+            let ratio = if expected == 0.0 { 0.0 } else { (misses as f64 / expected).clamp(0.0, 1.0) };
+            (capacity, ratio)
+        })
+        .collect()
+}
+
+/// return the approximate miss ratio of the trace under the given policy at each capacity,
+/// replaying the keys sampled at sample_rate against a cache of capacity * sample_rate entries
+pub fn mini_simulation<K, I>(keys: I, kind: PolicyKind, sample_rate: f64, capacities: &[usize]) -> Vec<(usize, f64)>
+where
+    K: Eq + Hash + Clone,
+    I: IntoIterator<Item = K>,
+{
+    let threshold = threshold(sample_rate);
+    let rate = threshold as f64 / MODULUS as f64;
+    let trace: Vec<Access<K>> = keys
+        .into_iter()
+        .filter(|key| sample(key, threshold).is_some())
+        .map(Access::new)
+        .collect();
+    capacities
+        .iter()
+        .map(|&capacity| {
+            let scaled = ((capacity as f64 * rate).round() as usize).max(1);
+            let report = Simulation::new(scaled).run(&trace, AnyPolicy::from_kind(kind));
+            let ratio = if report.accesses == 0 { 0.0 } else { report.misses as f64 / report.accesses as f64 };
+            (capacity, ratio)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::workload::Zipf;
+
+    #[test]
+    fn mrc_test() {
+        let trace: Vec<u64> = Zipf::with_seed(5_000, 0.9, 7).take(50_000).collect();
+        let capacities = [10, 100, 1_000];
+
+        // without sampling, stack distances give exactly the LRU miss ratio
+        let exact = shards(&trace, 1.0, &capacities);
+        for (capacity, ratio) in &exact {
+            let report = Simulation::new(*capacity).compare(trace.iter().copied(), &[PolicyKind::Lru])[0].1;
+            assert!((ratio - (1.0 - report.hit_ratio())).abs() < 1e-9);
+        }
+        assert!(exact.windows(2).all(|pair| pair[0].1 > pair[1].1));
+
+        let sampled = shards(&trace, 0.2, &capacities);
+        for ((_, exact), (_, sampled)) in exact.iter().zip(&sampled) {
+            assert!((exact - sampled).abs() < 0.05);
+        }
+        let simulated = mini_simulation(&trace, PolicyKind::Lru, 0.2, &capacities);
+        for ((_, exact), (_, simulated)) in exact.iter().zip(&simulated) {
+            assert!((exact - simulated).abs() < 0.05);
+        }
+        assert_eq!(shards(Vec::<u64>::new(), 0.5, &[10]), vec![(10, 0.0)]);
+    }
+}
@@ -0,0 +1,144 @@
+//! Reading and writing access traces in common file formats
+//!
+//! Keys are read as strings, and taken as written. Every format but CSV
+//! gives each access a size of 1.
+
+use std::fmt::Display;
+use std::io::{self, BufRead, Write};
+use super::Access;
+
+/// the layout of a trace file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TraceFormat {
+    /// one key per line
+    Keys,
+    /// `timestamp,key,size` lines, with an optional header line; the timestamp is ignored on reading
+    Csv,
+    /// the format of the ARC paper's traces, `start count ignored request` lines
+    /// that each access the count blocks numbered from start
+    Arc,
+    /// the format of the LIRS paper's traces, one block number per line
+    Lirs,
+}
+
+fn invalid_line(line: usize, message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {}", line, message))
+}
+
+fn parse_number(line: usize, field: &str) -> io::Result<u64> {
+    field.trim().parse().map_err(|_| invalid_line(line, "expected a number"))
+}
+
+/// read a trace in the given format, skipping blank lines
+pub fn read_trace<R: BufRead>(reader: R, format: TraceFormat) -> io::Result<Vec<Access<String>>> {
+    let mut trace = Vec::new();
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        let number = i + 1;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        match format {
+            TraceFormat::Keys => trace.push(Access::new(line.to_string())),
+            TraceFormat::Csv => {
+                let Some((key, size)) = line.split_once(',').and_then(|(_, rest)| rest.rsplit_once(',')) else {
+                    return Err(invalid_line(number, "expected timestamp,key,size"));
+                };
+                match parse_number(number, size) {
+                    Ok(size) => trace.push(Access { key: key.to_string(), size }),
+                    // a header has a size column that isn't a number
+                    Err(_) if number == 1 => continue,
+                    Err(e) => return Err(e),
+                }
+            }
+            TraceFormat::Arc => {
+                let mut fields = line.split_whitespace();
+                let (Some(start), Some(count)) = (fields.next(), fields.next()) else {
+                    return Err(invalid_line(number, "expected a start block and a block count"));
+                };
+                let start = parse_number(number, start)?;
+                let count = parse_number(number, count)?;
+                trace.extend((start..start.saturating_add(count)).map(|block| Access::new(block.to_string())));
+            }
+            TraceFormat::Lirs => {
+                let block = parse_number(number, line)?;
+                trace.push(Access::new(block.to_string()));
+            }
+        }
+    }
+    Ok(trace)
+}
+
+/// write a trace in the given format
+/// the ARC and LIRS formats expect keys that are block numbers; sizes are dropped by every format but CSV,
+/// which numbers the accesses from 0 in place of a timestamp
+pub fn write_trace<W: Write, K: Display>(mut writer: W, trace: &[Access<K>], format: TraceFormat) -> io::Result<()> {
+    if format == TraceFormat::Csv {
+        writeln!(writer, "timestamp,key,size")?;
+    }
+    for (i, access) in trace.iter().enumerate() {
+        match format {
+            TraceFormat::Keys | TraceFormat::Lirs => writeln!(writer, "{}", access.key)?,
+            TraceFormat::Csv => writeln!(writer, "{},{},{}", i, access.key, access.size)?,
+            TraceFormat::Arc => writeln!(writer, "{} 1 0 {}", access.key, i)?,
+        }
+    }
+    writer.flush()
+}
+
+/// write the lookups recorded by Simcache::record_accesses as a trace
+pub fn write_recorded<W: Write>(writer: W, recorded: &[u64], format: TraceFormat) -> io::Result<()> {
+    let trace: Vec<Access<u64>> = recorded.iter().copied().map(Access::new).collect();
+    write_trace(writer, &trace, format)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eviction::LRU;
+    use crate::Simcache;
+
+    fn keys(trace: &[Access<String>]) -> Vec<&str> {
+        trace.iter().map(|access| access.key.as_str()).collect()
+    }
+
+    #[test]
+    fn trace_test() {
+        let trace = read_trace("a\n\nb\na\n".as_bytes(), TraceFormat::Keys).unwrap();
+        assert_eq!(keys(&trace), vec!["a", "b", "a"]);
+
+        let csv = "timestamp,key,size\n0,a,10\n1,b,c,20\n";
+        let trace = read_trace(csv.as_bytes(), TraceFormat::Csv).unwrap();
+        assert_eq!(trace, vec![Access { key: "a".to_string(), size: 10 }, Access { key: "b,c".to_string(), size: 20 }]);
+        assert!(read_trace("0,a,x\n1,b,2\n2,c,x\n".as_bytes(), TraceFormat::Csv).is_err());
+
+        let trace = read_trace("5 3 0 1\n2 1 0 2\n".as_bytes(), TraceFormat::Arc).unwrap();
+        assert_eq!(keys(&trace), vec!["5", "6", "7", "2"]);
+        assert!(read_trace("7\nx\n".as_bytes(), TraceFormat::Lirs).is_err());
+
+        // every format reads back what it wrote
+        for format in [TraceFormat::Keys, TraceFormat::Csv, TraceFormat::Arc, TraceFormat::Lirs] {
+            let trace: Vec<Access<u64>> = [3, 1, 3].into_iter().map(Access::new).collect();
+            let mut file = Vec::new();
+            write_trace(&mut file, &trace, format).unwrap();
+            assert_eq!(keys(&read_trace(file.as_slice(), format).unwrap()), vec!["3", "1", "3"]);
+        }
+
+        let mut cache: Simcache<&str, u32, LRU<&str>> = Simcache::new(2);
+        cache.get(&"not recorded");
+        cache.record_accesses();
+        cache.insert("a", 1, None);
+        cache.get(&"a");
+        cache.get(&"b");
+        cache.get_or_insert_with("a", None, || 2);
+        let recorded = cache.take_recorded();
+        assert_eq!(recorded.len(), 3);
+        assert_eq!(recorded[0], recorded[2]);
+        assert_ne!(recorded[0], recorded[1]);
+
+        let mut file = Vec::new();
+        write_recorded(&mut file, &recorded, TraceFormat::Lirs).unwrap();
+        assert_eq!(read_trace(file.as_slice(), TraceFormat::Lirs).unwrap().len(), 3);
+    }
+}
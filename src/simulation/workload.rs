@@ -0,0 +1,209 @@
+//! Synthetic access patterns to feed the simulator
+//!
+//! Every generator is an endless iterator of `u64` keys, so take as many
+//! accesses as the simulation needs. The random ones give the same sequence
+//! for the same seed.
+//!
+//! ```
+//! use simcache::eviction::PolicyKind;
+//! use simcache::simulation::Simulation;
+//! use simcache::simulation::workload::{Mixture, Scan, Zipf};
+//!
+//! // a hot set with occasional scans through keys that are never reused
+//! let trace = Mixture::with_seed(1)
+//!     .add(0.9, Zipf::with_seed(1_000, 1.0, 2))
+//!     .add(0.1, Scan::new(1_000_000, 100_000))
+//!     .take(10_000);
+//! let reports = Simulation::new(100).compare(trace, &[PolicyKind::Lru, PolicyKind::TinyLfu]);
+//! assert!(reports.iter().all(|(_, report)| report.accesses == 10_000));
+//! ```
+
+use crate::rng::Rng;
+
+/// keys 0 to n - 1, where the frequency of key k falls off as 1 / (k + 1)^exponent
+/// so key 0 is the most popular, and a larger exponent makes the popular keys hotter
+#[derive(Debug, Clone)]
+pub struct Zipf {
+    // cumulative probability of each key
+    cdf: Vec<f64>,
+    rng: Rng,
+}
+
+impl Zipf {
+    /// return a generator over n keys seeded from entropy
+    pub fn new(n: usize, exponent: f64) -> Self {
+        Zipf::from_rng(n, exponent, Rng::from_entropy())
+    }
+
+    /// return a generator over n keys that gives the same sequence for the same seed
+    pub fn with_seed(n: usize, exponent: f64, seed: u64) -> Self {
+        Zipf::from_rng(n, exponent, Rng::new(seed))
+    }
+
+    fn from_rng(n: usize, exponent: f64, rng: Rng) -> Self {
+        assert!(n > 0, "a zipf workload should have at least one key");
+        let mut total = 0.0;
+        let mut cdf: Vec<f64> = (0..n)
+            .map(|k| {
+                total += 1.0 / ((k + 1) as f64).powf(exponent);
+                total
+            })
+            .collect();
+        for p in &mut cdf {
+            *p /= total;
+        }
+        Zipf { cdf, rng }
+    }
+}
+
+impl Iterator for Zipf {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        let p = self.rng.next_f64();
+        let k = self.cdf.partition_point(|&c| c <= p).min(self.cdf.len() - 1);
+        Some(k as u64)
+    }
+}
+
+/// keys 0 to n - 1, each equally likely
+#[derive(Debug, Clone)]
+pub struct Uniform {
+    n: usize,
+    rng: Rng,
+}
+
+impl Uniform {
+    /// return a generator over n keys seeded from entropy
+    pub fn new(n: usize) -> Self {
+        Uniform::from_rng(n, Rng::from_entropy())
+    }
+
+    /// return a generator over n keys that gives the same sequence for the same seed
+    pub fn with_seed(n: usize, seed: u64) -> Self {
+        Uniform::from_rng(n, Rng::new(seed))
+    }
+
+    fn from_rng(n: usize, rng: Rng) -> Self {
+        assert!(n > 0, "a uniform workload should have at least one key");
+        Uniform { n, rng }
+    }
+}
+
+impl Iterator for Uniform {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        Some(self.rng.below(self.n) as u64)
+    }
+}
+
+/// the keys start to start + len - 1 in order, starting over after the last one
+#[derive(Debug, Clone)]
+pub struct Scan {
+    start: u64,
+    len: u64,
+    offset: u64,
+}
+
+impl Scan {
+    /// return a scan over len keys from start
+    pub fn new(start: u64, len: u64) -> Self {
+        assert!(len > 0, "a scan should cover at least one key");
+        Scan { start, len, offset: 0 }
+    }
+}
+
+impl Iterator for Scan {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        let key = self.start + self.offset;
+        self.offset = (self.offset + 1) % self.len;
+        Some(key)
+    }
+}
+
+/// takes each key from one of several workloads, picked at random by weight
+/// such as a zipf hot set mixed with a scan
+pub struct Mixture {
+    // the running total of the weights up to and including each workload
+    workloads: Vec<(f64, Box<dyn Iterator<Item = u64>>)>,
+    rng: Rng,
+}
+
+impl Mixture {
+    /// return an empty mixture seeded from entropy
+    pub fn new() -> Self {
+        Mixture { workloads: Vec::new(), rng: Rng::from_entropy() }
+    }
+
+    /// return an empty mixture that picks workloads in the same order for the same seed
+    pub fn with_seed(seed: u64) -> Self {
+        Mixture { workloads: Vec::new(), rng: Rng::new(seed) }
+    }
+
+    /// take keys from the workload in proportion to its weight
+    pub fn add<I>(mut self, weight: f64, workload: I) -> Self
+    where
+        I: Iterator<Item = u64> + 'static,
+    {
+        assert!(weight > 0.0, "a workload's weight should be positive");
+        let total = self.workloads.last().map_or(0.0, |(total, _)| *total) + weight;
+        self.workloads.push((total, Box::new(workload)));
+        self
+    }
+}
+
+impl Default for Mixture {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Iterator for Mixture {
+    type Item = u64;
+
+    /// return None once the workload picked has run out, or if there are no workloads
+    fn next(&mut self) -> Option<u64> {
+        let (total, _) = self.workloads.last()?;
+        let p = self.rng.next_f64() * total;
+        let pick = self.workloads.partition_point(|(total, _)| *total <= p).min(self.workloads.len() - 1);
+        self.workloads[pick].1.next()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eviction::PolicyKind;
+    use crate::simulation::Simulation;
+
+    #[test]
+    fn workload_test() {
+        assert!(Zipf::with_seed(100, 1.0, 3).take(50).eq(Zipf::with_seed(100, 1.0, 3).take(50)));
+        let mut counts = [0; 10];
+        for key in Zipf::with_seed(10, 1.0, 3).take(10_000) {
+            counts[key as usize] += 1;
+        }
+        assert!(counts.windows(2).take(3).all(|pair| pair[0] > pair[1]));
+
+        assert!(Uniform::with_seed(5, 3).take(1000).all(|key| key < 5));
+        assert_eq!(Scan::new(10, 3).take(5).collect::<Vec<_>>(), vec![10, 11, 12, 10, 11]);
+
+        let scanned = Mixture::with_seed(3)
+            .add(3.0, Uniform::with_seed(10, 4))
+            .add(1.0, Scan::new(100, 1000))
+            .take(10_000)
+            .filter(|&key| key >= 100)
+            .count();
+        assert!((2000..3000).contains(&scanned));
+        assert_eq!(Mixture::new().next(), None);
+
+        // a skewed workload is easier to cache than a uniform one over the same keys
+        let simulation = Simulation::new(100);
+        let skewed = simulation.compare(Zipf::with_seed(1000, 1.0, 5).take(10_000), &[PolicyKind::Lru])[0].1;
+        let uniform = simulation.compare(Uniform::with_seed(1000, 5).take(10_000), &[PolicyKind::Lru])[0].1;
+        assert!(skewed.hit_ratio() > 2.0 * uniform.hit_ratio());
+    }
+}
@@ -0,0 +1,157 @@
+//! Cache statistics
+
+use std::time::Duration;
+
+/// counters describing how a cache has been used since it was created
+/// or since its stats were last reset
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// lookups that found a live entry
+    pub hits: u64,
+    /// lookups that found no entry, or only an expired one
+    pub misses: u64,
+    /// entries removed to make room for a new one
+    pub evictions: u64,
+    /// entries removed because their ttl had passed
+    pub expired: u64,
+    /// values written to the cache
+    pub inserts: u64,
+}
+
+impl Stats {
+    /// return the fraction of lookups that were hits, or 0 if there were no lookups
+    pub fn hit_ratio(&self) -> f64 {
+        let lookups = self.hits + self.misses;
+        if lookups == 0 {
+            0.0
+        } else {
+            self.hits as f64 / lookups as f64
+        }
+    }
+}
+
+// adding stats together gives the stats of caches used side by side, such as the shards of a sharded cache
+impl std::ops::Add for Stats {
+    type Output = Stats;
+
+    fn add(self, other: Stats) -> Stats {
+        Stats {
+            hits: self.hits + other.hits,
+            misses: self.misses + other.misses,
+            evictions: self.evictions + other.evictions,
+            expired: self.expired + other.expired,
+            inserts: self.inserts + other.inserts,
+        }
+    }
+}
+
+impl std::iter::Sum for Stats {
+    fn sum<I: Iterator<Item = Stats>>(iter: I) -> Stats {
+        iter.fold(Stats::default(), |total, stats| total + stats)
+    }
+}
+
+/// hit counters for a shadow cache, which tracks keys only as if the cache had a different entry limit
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ShadowCounts {
+    /// the entry limit the shadow cache simulates
+    pub capacity: usize,
+    /// lookups that would have been hits
+    pub hits: u64,
+    /// lookups that would have been misses
+    pub misses: u64,
+}
+
+impl ShadowCounts {
+    /// return the fraction of lookups that would have been hits, or 0 if there were no lookups
+    pub fn hit_ratio(&self) -> f64 {
+        let lookups = self.hits + self.misses;
+        if lookups == 0 {
+            0.0
+        } else {
+            self.hits as f64 / lookups as f64
+        }
+    }
+}
+
+/// what the hit ratio would have been at half and at double the cache's entry limit
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ShadowStats {
+    pub half: ShadowCounts,
+    pub double: ShadowCounts,
+}
+
+// the last bucket holds every duration from 2^30 ms, about 12 days, up
+const BUCKETS: usize = 32;
+
+/// counts of durations in power of two millisecond buckets: under 1ms, under 2ms, under 4ms, and so on
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DurationHistogram {
+    counts: [u64; BUCKETS],
+}
+
+impl DurationHistogram {
+    pub(crate) fn record(&mut self, duration: Duration) {
+        let millis = duration.as_millis();
+        let bucket = (u128::BITS - millis.leading_zeros()) as usize;
+        self.counts[bucket.min(BUCKETS - 1)] += 1;
+    }
+
+    /// return the number of durations recorded
+    pub fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// return the upper bound of each bucket with the number of durations below it and at least the bound before,
+    /// smallest first; the last bucket's bound is Duration::MAX
+    pub fn buckets(&self) -> impl Iterator<Item = (Duration, u64)> + '_ {
+        self.counts.iter().enumerate().map(|(bucket, count)| {
+            let bound = if bucket == BUCKETS - 1 { Duration::MAX } else { Duration::from_millis(1 << bucket) };
+            (bound, *count)
+        })
+    }
+
+    /// return the upper bound of the bucket holding the q quantile, e.g. 0.5 for the median,
+    /// or None if nothing has been recorded
+    pub fn quantile(&self, q: f64) -> Option<Duration> {
+        let count = self.count();
+        if count == 0 {
+            return None;
+        }
+        let rank = ((q.clamp(0.0, 1.0) * count as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        self.buckets().find(|(_, n)| {
+            seen += n;
+            seen >= rank
+        }).map(|(bound, _)| bound)
+    }
+}
+
+/// how long entries lasted, and how much time evicted entries had left before they would have expired
+/// a cache that mostly evicts entries with time left is short of capacity for its ttls,
+/// while one whose entries mostly expire could keep them longer
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LifetimeStats {
+    /// the time from insert to leaving the cache, of every entry evicted or expired
+    pub lifetimes: DurationHistogram,
+    /// the time left until expiry of every entry evicted with a ttl or idle timeout
+    pub ttl_left_at_eviction: DurationHistogram,
+    /// entries evicted that would never have expired
+    pub evicted_without_ttl: u64,
+    /// entries that expired
+    pub expired: u64,
+}
+
+impl LifetimeStats {
+    /// return the fraction of entries with a ttl that were evicted before it ran out instead of expiring,
+    /// or 0 if none have left the cache
+    pub fn evicted_before_expiry(&self) -> f64 {
+        let evicted = self.ttl_left_at_eviction.count();
+        let ended = evicted + self.expired;
+        if ended == 0 {
+            0.0
+        } else {
+            evicted as f64 / ended as f64
+        }
+    }
+}
@@ -0,0 +1,114 @@
+//! Background thread that keeps up a cache's maintenance
+//!
+//! Every interval the thread calls `run_pending_tasks`, which removes expired
+//! entries and gives the eviction policy the hits of a cache built with
+//! `buffer_accesses`. Without it, expired entries are only dropped when they
+//! are next looked up or when capacity eviction happens to pick them, and
+//! buffered hits wait for the buffer to fill or for the next insert.
+
+use std::hash::{BuildHasher, Hash};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use crate::{Clock, EvictionPolicy, Simcache};
+
+/// a cache shared between the sweeper and the rest of the program
+pub type SharedSimcache<K, V, E, S, C> = Arc<Mutex<Simcache<K, V, E, S, C>>>;
+
+/// stops the sweeper thread when dropped
+pub struct Sweeper {
+    stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+/// start a thread that calls run_pending_tasks on the cache every interval
+/// the thread exits when the returned handle is dropped or the cache itself is dropped
+pub fn spawn<K, V, E, S, C>(cache: &SharedSimcache<K, V, E, S, C>, interval: Duration) -> Sweeper
+where
+    K: Eq + Hash + Clone + Send + 'static,
+    V: Send + 'static,
+    E: EvictionPolicy<K> + Send + 'static,
+    S: BuildHasher + Send + 'static,
+    C: Clock + Send + 'static,
+{
+    let cache = Arc::downgrade(cache);
+    let (stop, stopped) = mpsc::channel();
+    let thread = thread::spawn(move || {
+        while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+            let Some(cache) = cache.upgrade() else {
+                return;
+            };
+            // a poisoned cache can't be swept safely, so give up on it
+            let Ok(mut cache) = cache.lock() else {
+                return;
+            };
+            cache.run_pending_tasks();
+        }
+    });
+    Sweeper { stop: Some(stop), thread: Some(thread) }
+}
+
+impl Sweeper {
+    /// stop the sweeper and wait for its thread to finish
+    pub fn stop(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        // dropping the sender wakes the thread immediately
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for Sweeper {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+    use crate::LRU;
+
+    type Cache = Simcache<&'static str, &'static str, LRU<&'static str>>;
+    type Shared = Arc<Mutex<Cache>>;
+
+    // poll the cache until the condition holds, giving a slow machine plenty of time for the sweeper to run
+    fn wait_until(cache: &Shared, condition: impl Fn(&Cache) -> bool) -> bool {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while Instant::now() < deadline {
+            if condition(&cache.lock().unwrap()) {
+                return true;
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+        false
+    }
+
+    #[test]
+    fn sweeper_test() {
+        let cache: Shared = Arc::new(Mutex::new(Simcache::new(3)));
+        cache.lock().unwrap().insert("a", "1", Some(Duration::ZERO));
+        cache.lock().unwrap().insert("b", "2", None);
+
+        let sweeper = spawn(&cache, Duration::from_millis(1));
+        assert!(wait_until(&cache, |cache| cache.len() == 1));
+        sweeper.stop();
+
+        // the sweeper also hands buffered hits to the policy
+        let cache: Shared = Arc::new(Mutex::new(Simcache::builder().max_capacity(3).buffer_accesses(100).build()));
+        cache.lock().unwrap().insert("a", "1", None);
+        cache.lock().unwrap().insert("b", "2", None);
+        cache.lock().unwrap().get(&"a");
+        assert_eq!(cache.lock().unwrap().peek_eviction_candidates(2), [&"a", &"b"]);
+        let sweeper = spawn(&cache, Duration::from_millis(1));
+        assert!(wait_until(&cache, |cache| cache.peek_eviction_candidates(2) == [&"b", &"a"]));
+        sweeper.stop();
+    }
+}
@@ -0,0 +1,95 @@
+//! Index of the tags attached to cached entries
+
+use std::any::Any;
+use std::borrow::Borrow;
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+
+/// a tag of any type, kept alongside its hash so that a lookup by hash is confirmed with ==
+pub(crate) trait Tag: Any + Send + Sync {
+    fn equals(&self, other: &dyn Any) -> bool;
+    fn clone_tag(&self) -> Box<dyn Tag>;
+}
+
+impl<T: Any + Eq + Clone + Send + Sync> Tag for T {
+    fn equals(&self, other: &dyn Any) -> bool {
+        other.downcast_ref::<T>() == Some(self)
+    }
+    fn clone_tag(&self) -> Box<dyn Tag> {
+        Box::new(self.clone())
+    }
+}
+
+// a boxed tag, which clones through clone_tag
+pub(crate) struct BoxedTag(pub(crate) Box<dyn Tag>);
+
+impl Clone for BoxedTag {
+    fn clone(&self) -> Self {
+        BoxedTag(self.0.clone_tag())
+    }
+}
+
+// keys are found by the stable hash of a tag, so that any type can be a tag without the cache
+// having a type parameter for it, and then kept only if they carry a tag equal to the one asked for,
+// so two tags sharing a hash never invalidate each other's entries
+#[derive(Clone)]
+pub(crate) struct TagIndex<K> {
+    keys_by_tag: HashMap<u64, HashSet<K>>,
+    tags_by_key: HashMap<K, Vec<(u64, BoxedTag)>>,
+}
+
+impl<K: Eq + Hash + Clone> TagIndex<K> {
+    pub(crate) fn new() -> Self {
+        TagIndex { keys_by_tag: HashMap::new(), tags_by_key: HashMap::new() }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.tags_by_key.is_empty()
+    }
+
+    // attach the tags to the key, which must not have any yet
+    pub(crate) fn tag(&mut self, key: &K, tags: Vec<(u64, BoxedTag)>) {
+        if tags.is_empty() {
+            return;
+        }
+        for (hash, _) in &tags {
+            self.keys_by_tag.entry(*hash).or_default().insert(key.clone());
+        }
+        self.tags_by_key.insert(key.clone(), tags);
+    }
+
+    // detach every tag from the key
+    pub(crate) fn untag<Q>(&mut self, key: &Q)
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let Some(tags) = self.tags_by_key.remove(key) else {
+            return;
+        };
+        for (hash, _) in tags {
+            if let Some(keys) = self.keys_by_tag.get_mut(&hash) {
+                keys.remove(key);
+                if keys.is_empty() {
+                    self.keys_by_tag.remove(&hash);
+                }
+            }
+        }
+    }
+
+    // return the keys carrying a tag equal to the given one, whose stable hash is hash
+    pub(crate) fn keys(&self, hash: u64, tag: &dyn Any) -> Vec<K> {
+        let Some(keys) = self.keys_by_tag.get(&hash) else {
+            return Vec::new();
+        };
+        keys.iter()
+            .filter(|key| self.tags_by_key.get(*key).is_some_and(|tags| tags.iter().any(|(h, t)| *h == hash && t.0.equals(tag))))
+            .cloned()
+            .collect()
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.keys_by_tag.clear();
+        self.tags_by_key.clear();
+    }
+}
@@ -0,0 +1,299 @@
+//! Checking eviction policies and caches against a reference model
+//!
+//! An `Op` sequence, such as one from `random_ops`, is replayed three ways:
+//!
+//! - `check_policy` drives a policy the way the cache does and checks it only
+//!   ever evicts or predicts keys that are resident, so a policy that keeps
+//!   stale keys after `on_remove` is caught.
+//! - `check_cache` replays the ops against a Simcache and a plain map, checking
+//!   the cache only loses keys by evicting them for an insert, never holds more
+//!   than its capacity, and always returns what the map says.
+//! - `check_against` replays the ops against two caches and checks they agree
+//!   on every result, such as an optimized policy and `ReferenceLru`, a linear
+//!   scan LRU that is easy to see is right.
+//!
+//! The random ops are the same for the same seed, so a failure is reproduced
+//! by rerunning with the seed it was found with.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::hash::Hash;
+use crate::rng::Rng;
+use crate::{EvictionPolicy, InsertOutcome, Simcache};
+
+/// one step of a test run
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Op<K> {
+    Insert(K, u64),
+    Get(K),
+    Remove(K),
+    /// evict whatever the policy would evict next
+    PopNext,
+    Clear,
+}
+
+/// the first step at which a run went wrong
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Inconsistency {
+    pub step: usize,
+    pub message: String,
+}
+
+impl fmt::Display for Inconsistency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "step {}: {}", self.step, self.message)
+    }
+}
+
+impl std::error::Error for Inconsistency {}
+
+/// return len ops on keys 0 to keys - 1, the same ones for the same seed
+/// mostly inserts and gets, with some removes, pops, and the odd clear
+pub fn random_ops(seed: u64, len: usize, keys: u64) -> Vec<Op<u64>> {
+    let mut rng = Rng::new(seed);
+    (0..len)
+        .map(|_| {
+            let key = rng.below(keys.max(1) as usize) as u64;
+            match rng.below(200) {
+                0 => Op::Clear,
+                1..=80 => Op::Insert(key, rng.next_u64()),
+                81..=160 => Op::Get(key),
+                161..=185 => Op::Remove(key),
+                _ => Op::PopNext,
+            }
+        })
+        .collect()
+}
+
+/// an LRU that scans a Vec for every operation, slow but plainly right, as a reference for other policies
+#[derive(Debug, Clone)]
+pub struct ReferenceLru<K> {
+    // least recently used first
+    keys: Vec<K>,
+}
+
+impl<K: PartialEq + Clone> EvictionPolicy<K> for ReferenceLru<K> {
+    fn evict_next(&mut self) -> K {
+        self.keys.remove(0)
+    }
+    fn on_insert(&mut self, key: &K, _weight: u64) {
+        self.on_access(key);
+    }
+    fn on_access(&mut self, key: &K) {
+        self.keys.retain(|k| k != key);
+        self.keys.push(key.clone());
+    }
+    fn on_remove(&mut self, key: &K) {
+        self.keys.retain(|k| k != key);
+    }
+    fn next_victim(&self) -> Option<&K> {
+        self.keys.first()
+    }
+    fn eviction_order(&self) -> Vec<&K> {
+        self.keys.iter().collect()
+    }
+    fn new() -> Self {
+        ReferenceLru { keys: Vec::new() }
+    }
+}
+
+fn fail<T>(step: usize, message: String) -> Result<T, Inconsistency> {
+    Err(Inconsistency { step, message })
+}
+
+// check what the policy predicts against the keys that are resident
+fn check_predictions<K, E>(step: usize, policy: &E, resident: &HashSet<K>) -> Result<(), Inconsistency>
+where
+    K: Eq + Hash + fmt::Debug,
+    E: EvictionPolicy<K>,
+{
+    if let Some(victim) = policy.next_victim() {
+        if !resident.contains(victim) {
+            return fail(step, format!("next_victim {victim:?} is not resident"));
+        }
+    }
+    let order = policy.eviction_order();
+    if order.is_empty() {
+        return Ok(());
+    }
+    let distinct: HashSet<&K> = order.iter().copied().collect();
+    if distinct.len() != order.len() {
+        return fail(step, "eviction_order repeats a key".to_string());
+    }
+    if let Some(stale) = order.iter().find(|key| !resident.contains(**key)) {
+        return fail(step, format!("eviction_order has {stale:?}, which is not resident"));
+    }
+    if order.len() != resident.len() {
+        return fail(step, format!("eviction_order has {} keys but {} are resident", order.len(), resident.len()));
+    }
+    Ok(())
+}
+
+/// drive the policy with the ops as a cache of the given capacity would, checking that every key it evicts,
+/// names as its next victim, or lists in its eviction order is resident
+pub fn check_policy<K, E>(mut policy: E, capacity: usize, ops: &[Op<K>]) -> Result<(), Inconsistency>
+where
+    K: Eq + Hash + Clone + fmt::Debug,
+    E: EvictionPolicy<K>,
+{
+    policy.set_capacity(capacity);
+    let mut resident = HashSet::new();
+    for (step, op) in ops.iter().enumerate() {
+        match op {
+            Op::Insert(key, _) => {
+                if !resident.contains(key) && resident.len() >= capacity.max(1) {
+                    let victim = policy.evict_next();
+                    if !resident.remove(&victim) {
+                        return fail(step, format!("evicted {victim:?}, which is not resident"));
+                    }
+                }
+                policy.on_insert(key, 1);
+                resident.insert(key.clone());
+            }
+            Op::Get(key) if resident.contains(key) => policy.on_access(key),
+            Op::Remove(key) if resident.remove(key) => policy.on_remove(key),
+            Op::PopNext if !resident.is_empty() => {
+                let victim = policy.evict_next();
+                if !resident.remove(&victim) {
+                    return fail(step, format!("evicted {victim:?}, which is not resident"));
+                }
+            }
+            Op::Clear => {
+                policy.clear();
+                policy.set_capacity(capacity);
+                resident.clear();
+            }
+            _ => {}
+        }
+        check_predictions(step, &policy, &resident)?;
+    }
+    Ok(())
+}
+
+/// replay the ops against a cache of the given capacity using the policy and against a plain map,
+/// checking that the cache agrees with the map and only drops keys by evicting them for an insert
+pub fn check_cache<E>(policy: E, capacity: usize, ops: &[Op<u64>]) -> Result<(), Inconsistency>
+where
+    E: EvictionPolicy<u64>,
+{
+    let mut cache: Simcache<u64, u64, E> = Simcache::builder().max_capacity(capacity).policy(policy).build();
+    let mut model: HashMap<u64, u64> = HashMap::new();
+    for (step, op) in ops.iter().enumerate() {
+        let mut may_evict = false;
+        match op {
+            Op::Insert(key, value) => {
+                let was_live = model.contains_key(key);
+                let outcome = cache.insert(*key, *value, None);
+                let expected = if was_live { InsertOutcome::Replaced } else { InsertOutcome::Inserted };
+                if outcome != InsertOutcome::Rejected && outcome != expected {
+                    return fail(step, format!("insert of {key} was {outcome:?}, expected {expected:?}"));
+                }
+                if outcome != InsertOutcome::Rejected {
+                    model.insert(*key, *value);
+                }
+                may_evict = !was_live;
+            }
+            Op::Get(key) => {
+                let got = cache.get(key).copied();
+                if got != model.get(key).copied() {
+                    return fail(step, format!("get of {key} was {got:?}, expected {:?}", model.get(key)));
+                }
+            }
+            Op::Remove(key) => {
+                let removed = cache.remove(key);
+                if removed != model.remove(key) {
+                    return fail(step, format!("remove of {key} was {removed:?}"));
+                }
+            }
+            Op::PopNext => match cache.pop_next() {
+                Some((key, value)) if model.remove(&key) == Some(value) => {}
+                Some((key, _)) => return fail(step, format!("popped {key}, which the model doesn't hold")),
+                None if model.is_empty() => {}
+                None => return fail(step, format!("popped nothing with {} keys held", model.len())),
+            },
+            Op::Clear => {
+                cache.clear();
+                model.clear();
+            }
+        }
+        let lost: Vec<u64> = model.keys().filter(|key| !cache.contains_key(*key)).copied().collect();
+        if !lost.is_empty() && !may_evict {
+            return fail(step, format!("keys {lost:?} went missing without an insert to make room for"));
+        }
+        for key in lost {
+            model.remove(&key);
+        }
+        if cache.len() != model.len() || cache.len() > capacity {
+            return fail(step, format!("cache holds {} keys, the model {}, with a capacity of {capacity}", cache.len(), model.len()));
+        }
+        if let Some((key, _)) = cache.iter().find(|(key, value)| model.get(*key) != Some(*value)) {
+            return fail(step, format!("cache holds {key}, which the model doesn't"));
+        }
+    }
+    Ok(())
+}
+
+/// replay the ops against two caches of the given capacity, one using each policy,
+/// checking that every get, remove, and pop gives the same result from both
+pub fn check_against<A, B>(a: A, b: B, capacity: usize, ops: &[Op<u64>]) -> Result<(), Inconsistency>
+where
+    A: EvictionPolicy<u64>,
+    B: EvictionPolicy<u64>,
+{
+    let mut a: Simcache<u64, u64, A> = Simcache::builder().max_capacity(capacity).policy(a).build();
+    let mut b: Simcache<u64, u64, B> = Simcache::builder().max_capacity(capacity).policy(b).build();
+    for (step, op) in ops.iter().enumerate() {
+        let (left, right) = match op {
+            Op::Insert(key, value) => (format!("{:?}", a.insert(*key, *value, None)), format!("{:?}", b.insert(*key, *value, None))),
+            Op::Get(key) => (format!("{:?}", a.get(key)), format!("{:?}", b.get(key))),
+            Op::Remove(key) => (format!("{:?}", a.remove(key)), format!("{:?}", b.remove(key))),
+            Op::PopNext => (format!("{:?}", a.pop_next()), format!("{:?}", b.pop_next())),
+            Op::Clear => {
+                a.clear();
+                b.clear();
+                continue;
+            }
+        };
+        if left != right {
+            return fail(step, format!("{op:?} gave {left} and {right}"));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eviction::{AnyPolicy, PolicyKind, LRU};
+
+    #[test]
+    fn testing_test() {
+        for seed in 0..5 {
+            let ops = random_ops(seed, 2_000, 24);
+            for kind in PolicyKind::all() {
+                let result = check_policy(AnyPolicy::from_kind(kind), 8, &ops);
+                assert_eq!(result, Ok(()), "{kind} with seed {seed}");
+                assert_eq!(check_cache(AnyPolicy::from_kind(kind), 8, &ops), Ok(()), "{kind} with seed {seed}");
+            }
+            assert_eq!(check_against(LRU::new(), ReferenceLru::new(), 8, &ops), Ok(()), "seed {seed}");
+        }
+        assert_eq!(random_ops(3, 50, 10), random_ops(3, 50, 10));
+
+        // a policy that forgets to drop removed keys is caught
+        struct Forgetful(ReferenceLru<u64>);
+        impl EvictionPolicy<u64> for Forgetful {
+            fn evict_next(&mut self) -> u64 {
+                self.0.evict_next()
+            }
+            fn on_access(&mut self, key: &u64) {
+                self.0.on_access(key);
+            }
+            fn on_remove(&mut self, _key: &u64) {}
+            fn new() -> Self {
+                Forgetful(ReferenceLru::new())
+            }
+        }
+        let ops = [Op::Insert(1, 0), Op::Insert(2, 0), Op::Remove(1), Op::Insert(3, 0), Op::Insert(4, 0)];
+        assert_eq!(check_policy(Forgetful::new(), 2, &ops).map_err(|e| e.step), Err(4));
+    }
+}
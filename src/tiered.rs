@@ -0,0 +1,334 @@
+//! Two-tier cache that spills evicted entries to a secondary store
+//!
+//! Hot entries live in an in-memory `Simcache`. Whatever that cache evicts is
+//! written to a `SecondaryStore`, such as the file backed `FileStore`, and a
+//! hit on the secondary tier moves the entry back into memory.
+
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::hash::{BuildHasher, Hash};
+use std::marker::PhantomData;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use crate::persist::Persist;
+use crate::{Clock, EvictionPolicy, InsertOutcome, Simcache, SystemClock};
+
+/// somewhere slower than memory to keep the entries a cache evicts
+pub trait SecondaryStore<K, V> {
+    /// store the value under the key, replacing any value already there
+    fn put(&mut self, key: K, value: V) -> io::Result<()>;
+    /// remove the value of the key and return it
+    fn take(&mut self, key: &K) -> io::Result<Option<V>>;
+    /// remove the value of the key, returning true if there was one
+    fn remove(&mut self, key: &K) -> io::Result<bool>;
+    /// return true if a value is stored under the key
+    fn contains(&self, key: &K) -> bool;
+    /// return the number of values stored
+    fn len(&self) -> usize;
+    /// return true if no values are stored
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+// compaction only runs once this much of the file is dead and the dead bytes outnumber the live ones
+const COMPACT_MIN_GARBAGE: u64 = 64 * 1024;
+
+/// a secondary store that appends values to a log file
+/// the index of where each value is lives in memory, so the file is only
+/// meaningful to the store that wrote it and is truncated when a store is created
+pub struct FileStore<K, V> {
+    path: PathBuf,
+    file: File,
+    // key to the offset and length of its latest value
+    index: HashMap<K, (u64, u64)>,
+    end: u64,
+    // bytes taken up by overwritten and removed values
+    garbage: u64,
+    values: PhantomData<fn() -> V>,
+}
+
+impl<K: Eq + Hash, V> FileStore<K, V> {
+    /// return an empty store writing to the file at path, truncating it if it exists
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let file = OpenOptions::new().read(true).write(true).create(true).truncate(true).open(&path)?;
+        Ok(FileStore { path, file, index: HashMap::new(), end: 0, garbage: 0, values: PhantomData })
+    }
+
+    /// return the size of the log file in bytes
+    pub fn file_len(&self) -> u64 {
+        self.end
+    }
+
+    /// rewrite the log with only the latest value of each key
+    pub fn compact(&mut self) -> io::Result<()> {
+        let compacted_path = self.path.with_extension("compact");
+        let mut compacted = OpenOptions::new().read(true).write(true).create(true).truncate(true).open(&compacted_path)?;
+        let mut end = 0;
+        for (offset, len) in self.index.values_mut() {
+            let bytes = read_at(&mut self.file, *offset, *len)?;
+            compacted.write_all(&bytes)?;
+            *offset = end;
+            end += *len;
+        }
+        compacted.sync_all()?;
+        fs::rename(&compacted_path, &self.path)?;
+        self.file = compacted;
+        self.end = end;
+        self.garbage = 0;
+        Ok(())
+    }
+
+    fn discard(&mut self, len: u64) -> io::Result<()> {
+        self.garbage += len;
+        if self.garbage >= COMPACT_MIN_GARBAGE && self.garbage > self.end - self.garbage {
+            self.compact()?;
+        }
+        Ok(())
+    }
+}
+
+fn read_at(file: &mut File, offset: u64, len: u64) -> io::Result<Vec<u8>> {
+    let mut bytes = vec![0; len as usize];
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+impl<K: Eq + Hash, V: Persist> SecondaryStore<K, V> for FileStore<K, V> {
+    fn put(&mut self, key: K, value: V) -> io::Result<()> {
+        let mut bytes = Vec::new();
+        value.write_to(&mut bytes)?;
+        self.file.seek(SeekFrom::Start(self.end))?;
+        self.file.write_all(&bytes)?;
+        let len = bytes.len() as u64;
+        let replaced = self.index.insert(key, (self.end, len));
+        self.end += len;
+        match replaced {
+            Some((_, old_len)) => self.discard(old_len),
+            None => Ok(()),
+        }
+    }
+    fn take(&mut self, key: &K) -> io::Result<Option<V>> {
+        let Some((offset, len)) = self.index.remove(key) else {
+            return Ok(None);
+        };
+        let bytes = read_at(&mut self.file, offset, len)?;
+        let value = V::read_from(&mut bytes.as_slice())?;
+        self.discard(len)?;
+        Ok(Some(value))
+    }
+    fn remove(&mut self, key: &K) -> io::Result<bool> {
+        match self.index.remove(key) {
+            Some((_, len)) => self.discard(len).map(|_| true),
+            None => Ok(false),
+        }
+    }
+    fn contains(&self, key: &K) -> bool {
+        self.index.contains_key(key)
+    }
+    fn len(&self) -> usize {
+        self.index.len()
+    }
+}
+
+/// a cache whose evicted entries move to a secondary store instead of being dropped
+/// entries keep their expiry time on the secondary tier and are promoted back into memory on a hit
+pub struct TieredCache<K, V, E, D, S = RandomState, C = SystemClock>
+where
+    E: EvictionPolicy<K>
+{
+    memory: Simcache<K, V, E, S, C>,
+    disk: D,
+    // expiry times of the entries on the secondary tier that have one
+    disk_deadlines: HashMap<K, Instant>,
+    // a promoted value the memory tier had no room for, kept so get can still return it
+    rejected: Option<V>,
+}
+
+impl<K, V, E, D, S, C> TieredCache<K, V, E, D, S, C>
+where
+    K: Eq + Hash + Clone,
+    E: EvictionPolicy<K>,
+    D: SecondaryStore<K, V>,
+    S: BuildHasher,
+    C: Clock,
+    {
+        /// return a tiered cache over the given memory tier and secondary store
+        pub fn new(mut memory: Simcache<K, V, E, S, C>, disk: D) -> Self {
+            memory.collect_spilled();
+            TieredCache { memory, disk, disk_deadlines: HashMap::new(), rejected: None }
+        }
+
+        /// insert a key value pair into the memory tier, moving anything it evicts to the secondary store
+        pub fn insert(&mut self, key: K, value: V, ttl: Option<Duration>) -> io::Result<InsertOutcome> {
+            self.forget_on_disk(&key)?;
+            let outcome = self.memory.insert(key, value, ttl);
+            self.spill()?;
+            Ok(outcome)
+        }
+
+        /// return the value of the given key from either tier if it is not expired
+        /// a value found on the secondary store is moved back into memory; if the memory tier
+        /// refuses it, the value is returned this once and then dropped
+        pub fn get(&mut self, key: &K) -> io::Result<Option<&V>> {
+            if self.memory.contains_key(key) {
+                return Ok(self.memory.get(key));
+            }
+            let Some(value) = self.disk.take(key)? else {
+                return Ok(None);
+            };
+            let now = self.memory.now();
+            let ttl = match self.disk_deadlines.remove(key) {
+                Some(deadline) if deadline <= now => return Ok(None),
+                Some(deadline) => Some(deadline - now),
+                None => None,
+            };
+            match self.memory.insert_exact_or_return(key.clone(), value, ttl) {
+                Ok(_) => {
+                    self.spill()?;
+                    Ok(self.memory.peek(key))
+                }
+                Err(value) => Ok(Some(self.rejected.insert(value))),
+            }
+        }
+
+        /// remove the key from both tiers, returning its value if it was present and not expired
+        pub fn remove(&mut self, key: &K) -> io::Result<Option<V>> {
+            if let Some(value) = self.memory.remove(key) {
+                return Ok(Some(value));
+            }
+            let value = self.disk.take(key)?;
+            let now = self.memory.now();
+            match self.disk_deadlines.remove(key) {
+                Some(deadline) if deadline <= now => Ok(None),
+                _ => Ok(value),
+            }
+        }
+
+        /// return true if either tier holds the key, expired entries on the secondary store included
+        pub fn contains_key(&self, key: &K) -> bool {
+            self.memory.contains_key(key) || self.disk.contains(key)
+        }
+
+        /// return the number of entries across both tiers
+        pub fn len(&self) -> usize {
+            self.memory.len() + self.disk.len()
+        }
+
+        /// return true if neither tier holds any entries
+        pub fn is_empty(&self) -> bool {
+            self.len() == 0
+        }
+
+        /// return the in-memory tier
+        pub fn memory(&self) -> &Simcache<K, V, E, S, C> {
+            &self.memory
+        }
+
+        /// return the secondary store
+        pub fn disk(&self) -> &D {
+            &self.disk
+        }
+
+        fn forget_on_disk(&mut self, key: &K) -> io::Result<()> {
+            self.disk_deadlines.remove(key);
+            self.disk.remove(key).map(|_| ())
+        }
+
+        // move everything the memory tier evicted to the secondary store, dropping what has already expired
+        fn spill(&mut self) -> io::Result<()> {
+            let now = self.memory.now();
+            for (key, value, expires_at) in self.memory.take_spilled() {
+                match expires_at {
+                    Some(deadline) if deadline <= now => continue,
+                    Some(deadline) => {
+                        self.disk_deadlines.insert(key.clone(), deadline);
+                    }
+                    None => {
+                        self.disk_deadlines.remove(&key);
+                    }
+                }
+                self.disk.put(key, value)?;
+            }
+            Ok(())
+        }
+    }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eviction::LRU;
+    use crate::MockClock;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("simcache-{}-{}.log", std::process::id(), name))
+    }
+
+    #[test]
+    fn tiered_test() {
+        let clock = MockClock::new();
+        let path = temp_path("tiered");
+        let memory: Simcache<u64, String, LRU<u64>, RandomState, MockClock> = Simcache::builder()
+            .max_capacity(2)
+            .clock(clock.clone())
+            .build();
+        let mut cache = TieredCache::new(memory, FileStore::create(&path).unwrap());
+
+        cache.insert(1, "one".to_string(), None).unwrap();
+        cache.insert(2, "two".to_string(), Some(Duration::from_secs(5))).unwrap();
+        cache.insert(3, "three".to_string(), None).unwrap();
+        cache.insert(4, "four".to_string(), None).unwrap();
+
+        // 1 and 2 were evicted to disk
+        assert_eq!(cache.memory().len(), 2);
+        assert_eq!(cache.disk().len(), 2);
+        assert_eq!(cache.len(), 4);
+
+        // a hit on disk moves 1 back into memory, which pushes 3 out
+        assert_eq!(cache.get(&1).unwrap(), Some(&"one".to_string()));
+        assert!(cache.memory().contains_key(&1));
+        assert!(!cache.memory().contains_key(&3));
+        assert_eq!(cache.get(&3).unwrap(), Some(&"three".to_string()));
+
+        // 2 kept its ttl while on disk
+        clock.advance(Duration::from_secs(6));
+        assert_eq!(cache.get(&2).unwrap(), None);
+        assert_eq!(cache.disk().len(), 1);
+
+        // an insert replaces the copy on disk
+        cache.insert(4, "FOUR".to_string(), None).unwrap();
+        cache.insert(1, "ONE".to_string(), None).unwrap();
+        assert_eq!(cache.get(&1).unwrap(), Some(&"ONE".to_string()));
+        assert_eq!(cache.remove(&3).unwrap(), Some("three".to_string()));
+        assert_eq!(cache.get(&3).unwrap(), None);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn file_store_test() {
+        let path = temp_path("file-store");
+        let mut store = FileStore::create(&path).unwrap();
+
+        store.put("a", 1u64).unwrap();
+        store.put("b", 2u64).unwrap();
+        store.put("a", 3u64).unwrap();
+        assert_eq!(store.len(), 2);
+        assert_eq!(store.file_len(), 24);
+
+        // compaction drops the overwritten value of a
+        store.compact().unwrap();
+        assert_eq!(store.file_len(), 16);
+        assert_eq!(fs::metadata(&path).unwrap().len(), 16);
+        assert_eq!(store.take(&"a").unwrap(), Some(3u64));
+        assert_eq!(store.take(&"a").unwrap(), None);
+        assert!(store.remove(&"b").unwrap());
+        assert!(store.is_empty());
+
+        fs::remove_file(&path).unwrap();
+    }
+}